@@ -118,6 +118,8 @@ contract ArbitrageExecutor is ReentrancyGuard, Pausable {
     address sellRouter;      // target contract for leg 2
     address sellSpender;     // token spender for leg 2 approvals
     bytes   sellCalldata;    // low-level call data for leg 2
+    uint256[] sellAmountOffsets; // byte offsets of leg 2 amountIn words; empty keeps sellCalldata as built
+    uint256[] sellShares;    // share of the leg 1 output each of those swaps spends, 1e18 = all
 
     address tokenBorrowed;   // asset borrowed from pool
     address tokenIntermediate; // asset received after buy (sold in leg 2)
@@ -198,6 +200,7 @@ contract ArbitrageExecutor is ReentrancyGuard, Pausable {
 
     // Snapshot profit token before operations
     uint256 beforeProfit = IERC20(p.profitToken).balanceOf(address(this));
+    uint256 beforeMid = IERC20(p.tokenIntermediate).balanceOf(address(this));
 
     // Approve leg 1 (buy)
     if (p.buySpender != address(0) && amount > 0) {
@@ -208,6 +211,10 @@ contract ArbitrageExecutor is ReentrancyGuard, Pausable {
 
     // Approve leg 2 (sell) for full intermediate balance
     uint256 midBal = IERC20(p.tokenIntermediate).balanceOf(address(this));
+    // Size leg 2's swaps on what leg 1 actually delivered
+    if (p.sellAmountOffsets.length > 0) {
+      _writeShares(p.sellCalldata, p.sellAmountOffsets, p.sellShares, midBal - beforeMid);
+    }
     if (p.sellSpender != address(0) && midBal > 0) {
       IERC20(p.tokenIntermediate).safeApprove(p.sellSpender, 0);
       IERC20(p.tokenIntermediate).safeApprove(p.sellSpender, midBal);
@@ -244,6 +251,30 @@ contract ArbitrageExecutor is ReentrancyGuard, Pausable {
     require(initiator == address(this) || initiator == owner, "BAD_INITIATOR");
   }
 
+  /// @dev Overwrites the uint256 words at `offsets` in `data` with `total` split by `shares`
+  /// (1e18 = all of it); each rounds down and the last takes the remainder, so they sum to `total`.
+  function _writeShares(
+    bytes memory data,
+    uint256[] memory offsets,
+    uint256[] memory shares,
+    uint256 total
+  ) internal pure {
+    require(offsets.length == shares.length, "SHARES_LEN");
+    uint256 left = total;
+    uint256 sum;
+    for (uint256 i = 0; i < offsets.length; i++) {
+      uint256 at = offsets[i];
+      require(at + 32 <= data.length, "OFFSET_OOB");
+      sum += shares[i];
+      uint256 amount = i + 1 == offsets.length ? left : (total * shares[i]) / 1e18;
+      left -= amount;
+      assembly {
+        mstore(add(add(data, 0x20), at), amount)
+      }
+    }
+    require(sum == 1e18, "SHARES_SUM");
+  }
+
   /// @dev Low-level opaque call. Reverts bubbling up the original reason.
   function _rawCall(address target, bytes memory data) internal {
     require(target != address(0), "TARGET_0");
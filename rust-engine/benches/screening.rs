@@ -0,0 +1,124 @@
+//! Per-block latency of the negative-cycle screen.
+//!
+//! Builds a random pool graph, then each block moves a slice of the pools
+//! and times `CycleScreen::update` plus `negative_cycles`, the work the
+//! detection loop does before any exact sizing. Run with
+//! `cargo bench --bench screening`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use hyperliquid_arb_engine::amm::V2Pool;
+use hyperliquid_arb_engine::screen::CycleScreen;
+use hyperliquid_arb_engine::state::{Dex, Token, TrackedPool};
+use hyperliquid_arb_engine::univ3::U256;
+use rand::RngExt;
+use rand_pcg::Pcg64;
+
+const BLOCKS: usize = 200;
+/// Share of pools whose state moves each block.
+const MOVED: f64 = 0.02;
+const MAX_HOPS: usize = 3;
+/// `ln(1 + 10 bps)`, the default `min_spread_bps`.
+const MIN_LOG_EDGE: f64 = 0.000_999_5;
+
+fn main() {
+    println!(
+        "{:>7} {:>7} {:>7} {:>10} {:>10} {:>10} {:>10} {:>7}",
+        "pools", "tokens", "moved", "cold_us", "p50_us", "p99_us", "max_us", "cycles"
+    );
+    for pools in [100, 1_000, 5_000, 20_000] {
+        run(pools, (pools / 4).max(10));
+    }
+}
+
+fn run(pool_count: usize, token_count: usize) {
+    let mut rng = Pcg64::new(u128::from(pool_count as u64), 0xa02b_dbf7_bb3c_0a7a);
+    let tokens: Vec<Token> = (0..token_count)
+        .map(|i| Token {
+            address: format!("0x{i:040x}"),
+            symbol: format!("T{i}"),
+            decimals: 18,
+            ..Token::default()
+        })
+        .collect();
+    // A fair price per token; pools quote it with a few bps of noise.
+    let fair: Vec<f64> = (0..token_count)
+        .map(|_| 10f64.powf(rng.random::<f64>() * 4.0 - 2.0))
+        .collect();
+    let mut pools: Vec<TrackedPool> = (0..pool_count)
+        .map(|i| {
+            let a = rng.random_range(0..token_count);
+            let b = (a + rng.random_range(1..token_count)) % token_count;
+            TrackedPool {
+                address: format!("0x{:040x}", 1 << 32 | i),
+                dex: if i % 2 == 0 {
+                    Dex::Prjx
+                } else {
+                    Dex::HyperSwap
+                },
+                token0: tokens[a].clone(),
+                token1: tokens[b].clone(),
+                state: quote(&mut rng, fair[a] / fair[b]).into(),
+                tvl_usd: None,
+                block: Some(0),
+                updated_at: Utc::now(),
+            }
+        })
+        .collect();
+    let pair = |p: &TrackedPool| {
+        let index = |t: &Token| tokens.iter().position(|x| x.address == t.address).unwrap();
+        (index(&p.token0), index(&p.token1))
+    };
+    let pairs: Vec<(usize, usize)> = pools.iter().map(pair).collect();
+
+    let mut screen = CycleScreen::new();
+    let start = Instant::now();
+    screen.update(&pools);
+    black_box(screen.negative_cycles(MAX_HOPS, MIN_LOG_EDGE, &[]));
+    let cold = start.elapsed();
+
+    let moved = ((pool_count as f64 * MOVED) as usize).max(1);
+    let mut times = Vec::with_capacity(BLOCKS);
+    let mut found = 0;
+    for block in 1..=BLOCKS as u64 {
+        for _ in 0..moved {
+            let i = rng.random_range(0..pool_count);
+            let (a, b) = pairs[i];
+            pools[i].state = quote(&mut rng, fair[a] / fair[b]).into();
+            pools[i].block = Some(block);
+        }
+        let start = Instant::now();
+        screen.update(&pools);
+        let cycles = black_box(screen.negative_cycles(MAX_HOPS, MIN_LOG_EDGE, &[]));
+        times.push(start.elapsed());
+        found += cycles.len();
+    }
+    times.sort();
+    let us = |d: Duration| d.as_secs_f64() * 1e6;
+    println!(
+        "{:>7} {:>7} {:>7} {:>10.0} {:>10.0} {:>10.0} {:>10.0} {:>7.1}",
+        pool_count,
+        token_count,
+        moved,
+        us(cold),
+        us(times[BLOCKS / 2]),
+        us(times[BLOCKS * 99 / 100]),
+        us(times[BLOCKS - 1]),
+        found as f64 / BLOCKS as f64,
+    );
+}
+
+/// 0.05% constant-product pool at `price` token1 per token0, give or take
+/// 30 bps.
+fn quote(rng: &mut Pcg64, price: f64) -> V2Pool {
+    let noisy = price * (1.0 + (rng.random::<f64>() - 0.5) * 0.006);
+    let reserve0 = 1e24;
+    V2Pool::new(
+        U256::from(reserve0 as u128),
+        U256::from((reserve0 * noisy) as u128),
+        500,
+    )
+    .unwrap()
+}
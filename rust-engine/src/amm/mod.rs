@@ -0,0 +1,355 @@
+//! Venue-agnostic pool interface.
+//!
+//! [`Pool`] is what sizing and the strategies quote against: exact-input and
+//! exact-output swaps, the marginal price and the depth behind it, and a
+//! swap applied to the state. It is implemented for concentrated-liquidity
+//! V3 ([`PoolState`]), constant-product V2 ([`V2Pool`]) and Curve-style
+//! stableswap ([`StablePool`]) pools; [`Amm`] holds any of them.
+//!
+//! Prices are token0 in token1, in raw units and before fees, so a price
+//! limit means the same thing on every curve: a swap selling token0 stops
+//! once the price has fallen to it, one buying token0 once it has risen to
+//! it. [`Error`] wraps the V3 math errors, which the other curves share for
+//! overflow, bad fees and empty reserves, and adds the stableswap ones.
+
+pub mod stableswap;
+pub mod v2;
+mod v3;
+
+use std::fmt;
+
+pub use stableswap::StablePool;
+pub use v2::V2Pool;
+
+use crate::univ3::{self, PoolState, FEE_PIPS_DENOMINATOR, U256};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// A V3 math error, or the same failure on another curve.
+    Math(univ3::Error),
+    InvalidAmplification(u64),
+    /// The stableswap invariant did not converge.
+    NoConvergence,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Math(e) => e.fmt(f),
+            Error::InvalidAmplification(amp) => write!(f, "invalid amplification {amp}"),
+            Error::NoConvergence => write!(f, "invariant did not converge"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<univ3::Error> for Error {
+    fn from(e: univ3::Error) -> Self {
+        Error::Math(e)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Result of quoting or applying a swap on a [`Pool`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct SwapQuote {
+    /// Input taken by the pool, fees included.
+    pub amount_in: U256,
+    pub amount_out: U256,
+    /// LP fee, in the input token.
+    pub fee_amount: U256,
+    /// Unfilled part of the specified amount when the price limit was reached.
+    pub amount_remaining: U256,
+    /// Pool price after the swap, as [`Pool::spot_price`].
+    pub price_after: f64,
+}
+
+impl SwapQuote {
+    pub fn is_partial(&self) -> bool {
+        !self.amount_remaining.is_zero()
+    }
+}
+
+pub trait Pool: fmt::Debug {
+    /// Swap fee in hundredths of a bip.
+    fn fee_pips(&self) -> u32;
+
+    /// Marginal price of token0 in token1, raw units, fees excluded.
+    fn spot_price(&self) -> f64;
+
+    /// Raw token amounts that hold the pool's depth at the current price:
+    /// the reserves of a V2 or stableswap pool, the virtual reserves of the
+    /// active range of a V3 pool.
+    fn virtual_reserves(&self) -> (f64, f64);
+
+    /// Output for `amount_in`, or as much of it as fills before the price
+    /// reaches `price_limit`.
+    fn quote_exact_in(
+        &self,
+        zero_for_one: bool,
+        amount_in: U256,
+        price_limit: Option<f64>,
+    ) -> Result<SwapQuote>;
+
+    /// Input for `amount_out`, or as much of it as fills before the price
+    /// reaches `price_limit`.
+    fn quote_exact_out(
+        &self,
+        zero_for_one: bool,
+        amount_out: U256,
+        price_limit: Option<f64>,
+    ) -> Result<SwapQuote>;
+
+    /// Swaps `amount_in` against the state, as [`Self::quote_exact_in`]
+    /// quotes it.
+    fn apply_swap(
+        &mut self,
+        zero_for_one: bool,
+        amount_in: U256,
+        price_limit: Option<f64>,
+    ) -> Result<SwapQuote>;
+
+    fn fee_fraction(&self) -> f64 {
+        self.fee_pips() as f64 / FEE_PIPS_DENOMINATOR as f64
+    }
+}
+
+/// Any supported pool.
+#[derive(Debug, Clone)]
+pub enum Amm {
+    V3(PoolState),
+    V2(V2Pool),
+    Stable(StablePool),
+}
+
+impl Amm {
+    /// The V3 state, for the on-chain readers that only track V3 pools.
+    pub fn as_v3(&self) -> Option<&PoolState> {
+        match self {
+            Amm::V3(state) => Some(state),
+            _ => None,
+        }
+    }
+
+    pub fn as_v3_mut(&mut self) -> Option<&mut PoolState> {
+        match self {
+            Amm::V3(state) => Some(state),
+            _ => None,
+        }
+    }
+
+    fn inner(&self) -> &dyn Pool {
+        match self {
+            Amm::V3(p) => p,
+            Amm::V2(p) => p,
+            Amm::Stable(p) => p,
+        }
+    }
+
+    fn inner_mut(&mut self) -> &mut dyn Pool {
+        match self {
+            Amm::V3(p) => p,
+            Amm::V2(p) => p,
+            Amm::Stable(p) => p,
+        }
+    }
+}
+
+impl From<PoolState> for Amm {
+    fn from(state: PoolState) -> Self {
+        Amm::V3(state)
+    }
+}
+
+impl From<V2Pool> for Amm {
+    fn from(pool: V2Pool) -> Self {
+        Amm::V2(pool)
+    }
+}
+
+impl From<StablePool> for Amm {
+    fn from(pool: StablePool) -> Self {
+        Amm::Stable(pool)
+    }
+}
+
+impl Pool for Amm {
+    fn fee_pips(&self) -> u32 {
+        self.inner().fee_pips()
+    }
+
+    fn spot_price(&self) -> f64 {
+        self.inner().spot_price()
+    }
+
+    fn virtual_reserves(&self) -> (f64, f64) {
+        self.inner().virtual_reserves()
+    }
+
+    fn quote_exact_in(
+        &self,
+        zero_for_one: bool,
+        amount_in: U256,
+        price_limit: Option<f64>,
+    ) -> Result<SwapQuote> {
+        self.inner()
+            .quote_exact_in(zero_for_one, amount_in, price_limit)
+    }
+
+    fn quote_exact_out(
+        &self,
+        zero_for_one: bool,
+        amount_out: U256,
+        price_limit: Option<f64>,
+    ) -> Result<SwapQuote> {
+        self.inner()
+            .quote_exact_out(zero_for_one, amount_out, price_limit)
+    }
+
+    fn apply_swap(
+        &mut self,
+        zero_for_one: bool,
+        amount_in: U256,
+        price_limit: Option<f64>,
+    ) -> Result<SwapQuote> {
+        self.inner_mut()
+            .apply_swap(zero_for_one, amount_in, price_limit)
+    }
+}
+
+/// Exact-input quote on a curve without ticks: `swap` of the whole amount
+/// when the price stays within `price_limit`, otherwise of the largest input
+/// that keeps it there. A failed `swap` counts as crossing the limit.
+pub(crate) fn exact_in_within(
+    zero_for_one: bool,
+    spot: f64,
+    amount_in: U256,
+    price_limit: Option<f64>,
+    swap: impl Fn(U256) -> Result<SwapQuote>,
+) -> Result<SwapQuote> {
+    let Some(limit) = price_limit else {
+        return swap(amount_in);
+    };
+    let valid = if zero_for_one {
+        limit < spot && limit >= 0.0
+    } else {
+        limit > spot
+    };
+    let within = |price: f64| {
+        if zero_for_one {
+            price >= limit
+        } else {
+            price <= limit
+        }
+    };
+    if !valid {
+        return Err(univ3::Error::InvalidPriceLimit.into());
+    }
+    if let Ok(quote) = swap(amount_in) {
+        if within(quote.price_after) {
+            return Ok(quote);
+        }
+    }
+    let fits = |x: u128| swap(U256::from(x)).is_ok_and(|q| within(q.price_after));
+    let (mut lo, mut hi) = (0u128, amount_in.to_u128().unwrap_or(u128::MAX));
+    while hi - lo > 1 {
+        let mid = lo + (hi - lo) / 2;
+        if fits(mid) {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+    let mut quote = swap(U256::from(lo))?;
+    quote.amount_remaining = amount_in - quote.amount_in;
+    Ok(quote)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::univ3::tick_math::get_sqrt_ratio_at_tick;
+
+    const E18: u128 = 10u128.pow(18);
+
+    /// The same 1:1 market on each curve, 1M of each token deep.
+    fn pools() -> Vec<Amm> {
+        let reserve = U256::from(1_000_000 * E18);
+        let mut v3 = PoolState::new(
+            500,
+            10,
+            get_sqrt_ratio_at_tick(0).unwrap(),
+            0,
+            1_000_000 * E18,
+        )
+        .unwrap();
+        v3.set_liquidity_net(-887_270, (1_000_000 * E18) as i128)
+            .unwrap();
+        v3.set_liquidity_net(887_270, -((1_000_000 * E18) as i128))
+            .unwrap();
+        vec![
+            v3.into(),
+            V2Pool::new(reserve, reserve, 500).unwrap().into(),
+            StablePool::new([reserve, reserve], [18, 18], 100, 500)
+                .unwrap()
+                .into(),
+        ]
+    }
+
+    #[test]
+    fn quotes_every_curve_through_one_interface() {
+        let amount = U256::from(1_000 * E18);
+        for pool in pools() {
+            assert!((pool.spot_price() - 1.0).abs() < 1e-9, "{pool:?}");
+            assert_eq!(pool.fee_fraction(), 0.0005);
+            for zero_for_one in [true, false] {
+                let q = pool.quote_exact_in(zero_for_one, amount, None).unwrap();
+                assert!(!q.is_partial());
+                assert_eq!(q.amount_in, amount);
+                // 5 bps of fee and a little impact.
+                let out = q.amount_out.to_f64() / amount.to_f64();
+                assert!(out < 0.9995 && out > 0.9975, "{pool:?}: {out}");
+                assert_eq!(q.price_after < 1.0, zero_for_one);
+
+                let back = pool
+                    .quote_exact_out(zero_for_one, q.amount_out, None)
+                    .unwrap();
+                let diff = back.amount_in.to_f64() / amount.to_f64() - 1.0;
+                assert!(diff.abs() < 1e-9, "{pool:?}: {diff}");
+            }
+        }
+    }
+
+    #[test]
+    fn price_limits_cap_every_curve() {
+        let amount = U256::from(100_000 * E18);
+        for pool in pools() {
+            let limit = pool.spot_price() * (1.0 - 10e-4);
+            let q = pool.quote_exact_in(true, amount, Some(limit)).unwrap();
+            assert!(q.is_partial(), "{pool:?}");
+            assert_eq!(q.amount_in + q.amount_remaining, amount);
+            assert!(q.price_after >= limit * (1.0 - 1e-9), "{pool:?}");
+            assert!(q.price_after < limit * (1.0 + 1e-4), "{pool:?}");
+            assert!(pool
+                .quote_exact_in(true, amount, Some(pool.spot_price() * 1.01))
+                .is_err());
+        }
+    }
+
+    #[test]
+    fn apply_swap_moves_the_state_as_quoted() {
+        let amount = U256::from(5_000 * E18);
+        for mut pool in pools() {
+            let quoted = pool.quote_exact_in(false, amount, None).unwrap();
+            let applied = pool.apply_swap(false, amount, None).unwrap();
+            assert_eq!(quoted, applied);
+            assert!((pool.spot_price() / applied.price_after - 1.0).abs() < 1e-12);
+            // Swapping back returns a little less than went in.
+            let undo = pool.apply_swap(true, applied.amount_out, None).unwrap();
+            assert!(undo.amount_out < amount, "{pool:?}");
+            assert!(undo.amount_out.to_f64() > amount.to_f64() * 0.998);
+        }
+    }
+}
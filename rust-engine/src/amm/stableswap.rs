@@ -0,0 +1,352 @@
+//! Curve-style stableswap pools of two coins.
+//!
+//! The invariant `A·n^n·Σx + D = A·n^n·D + D^(n+1) / (n^n·Πx)` and its
+//! Newton solvers follow `get_D` / `get_y` of the StableSwap contracts, on
+//! balances normalised to 18 decimals by the pool's rates. The fee is taken
+//! from the output and stays in the pool; admin fees are ignored.
+
+use super::{exact_in_within, Error, Pool, Result, SwapQuote};
+use crate::univ3::full_math::{mul_div, mul_div_rounding_up};
+use crate::univ3::{Error as MathError, FEE_PIPS_DENOMINATOR, U256};
+
+const N: u32 = 2;
+/// Newton iterations before giving up, as in the contracts.
+const MAX_ITERATIONS: usize = 255;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StablePool {
+    /// Raw balances of token0 and token1.
+    pub balances: [U256; 2],
+    /// The contract's `A()`, so `Ann = A * n`.
+    pub amp: u64,
+    /// Swap fee in hundredths of a bip (`400` = 4 bps).
+    pub fee: u32,
+    /// `10^(36 - decimals)`: raw balance times rate over 1e18 is 18 decimals.
+    rates: [U256; 2],
+}
+
+impl StablePool {
+    pub fn new(balances: [U256; 2], decimals: [u8; 2], amp: u64, fee: u32) -> Result<Self> {
+        if fee >= FEE_PIPS_DENOMINATOR {
+            return Err(MathError::InvalidFee(fee).into());
+        }
+        if amp == 0 {
+            return Err(Error::InvalidAmplification(amp));
+        }
+        if balances.iter().any(U256::is_zero) {
+            return Err(MathError::ZeroLiquidity.into());
+        }
+        Ok(Self {
+            balances,
+            amp,
+            fee,
+            rates: decimals.map(|d| U256::pow10(36 - u32::from(d.min(36)))),
+        })
+    }
+
+    fn ann(&self) -> U256 {
+        U256::from(self.amp) * U256::from(N)
+    }
+
+    /// Balances in 18 decimals.
+    fn xp(&self, balances: [U256; 2]) -> Result<[U256; 2]> {
+        let scale = U256::pow10(18);
+        Ok([
+            mul_div(balances[0], self.rates[0], scale)?,
+            mul_div(balances[1], self.rates[1], scale)?,
+        ])
+    }
+
+    fn get_d(&self, xp: [U256; 2]) -> Result<U256> {
+        let n = U256::from(N);
+        let ann = self.ann();
+        let sum = xp[0].checked_add(xp[1]).ok_or(MathError::Overflow)?;
+        if sum.is_zero() {
+            return Ok(U256::ZERO);
+        }
+        let mut d = sum;
+        for _ in 0..MAX_ITERATIONS {
+            let mut d_p = d;
+            for x in xp {
+                d_p = mul_div(d_p, d, x.checked_mul(n).ok_or(MathError::Overflow)?)?;
+            }
+            let prev = d;
+            let numerator = checked(ann.checked_mul(sum), d_p.checked_mul(n))?;
+            let denominator = checked(
+                (ann - U256::ONE).checked_mul(d),
+                (n + U256::ONE).checked_mul(d_p),
+            )?;
+            d = mul_div(numerator, d, denominator)?;
+            if abs_diff(d, prev) <= U256::ONE {
+                return Ok(d);
+            }
+        }
+        Err(Error::NoConvergence)
+    }
+
+    /// Normalised balance of one coin that keeps `d` when the other has
+    /// normalised balance `x`.
+    fn get_y(&self, x: U256, d: U256) -> Result<U256> {
+        let n = U256::from(N);
+        let ann = self.ann();
+        let c = mul_div(d, d, x.checked_mul(n).ok_or(MathError::Overflow)?)?;
+        let c = mul_div(c, d, ann.checked_mul(n).ok_or(MathError::Overflow)?)?;
+        let b = x.checked_add(d / ann).ok_or(MathError::Overflow)?;
+        let mut y = d;
+        for _ in 0..MAX_ITERATIONS {
+            let prev = y;
+            let numerator = checked(y.checked_mul(y), Some(c))?;
+            let denominator = y
+                .checked_mul(U256::from(2u32))
+                .and_then(|v| v.checked_add(b))
+                .and_then(|v| v.checked_sub(d))
+                .ok_or(MathError::Overflow)?;
+            if denominator.is_zero() {
+                return Err(MathError::DivisionByZero.into());
+            }
+            y = numerator / denominator;
+            if abs_diff(y, prev) <= U256::ONE {
+                return Ok(y);
+            }
+        }
+        Err(Error::NoConvergence)
+    }
+
+    fn index(zero_for_one: bool) -> (usize, usize) {
+        if zero_for_one {
+            (0, 1)
+        } else {
+            (1, 0)
+        }
+    }
+
+    /// Marginal price of token0 in token1 at `balances`, raw units: the
+    /// ratio of the invariant's partial derivatives.
+    fn price_at(&self, balances: [U256; 2]) -> Result<f64> {
+        let xp = self.xp(balances)?;
+        let d = self.get_d(xp)?.to_f64();
+        let (x, y) = (xp[0].to_f64(), xp[1].to_f64());
+        let ann = self.ann().to_f64();
+        let d3 = d * d * d / 4.0;
+        let normalised = (ann + d3 / (x * x * y)) / (ann + d3 / (x * y * y));
+        Ok(normalised * self.rates[0].to_f64() / self.rates[1].to_f64())
+    }
+
+    fn balances_after(
+        &self,
+        zero_for_one: bool,
+        amount_in: U256,
+        amount_out: U256,
+    ) -> Result<[U256; 2]> {
+        let (i, j) = Self::index(zero_for_one);
+        let mut balances = self.balances;
+        balances[i] = balances[i]
+            .checked_add(amount_in)
+            .ok_or(MathError::Overflow)?;
+        balances[j] = balances[j]
+            .checked_sub(amount_out)
+            .filter(|b| !b.is_zero())
+            .ok_or(MathError::LiquidityUnderflow)?;
+        Ok(balances)
+    }
+
+    fn fill(&self, zero_for_one: bool, amount_in: U256, amount_out: U256) -> Result<SwapQuote> {
+        let balances = self.balances_after(zero_for_one, amount_in, amount_out)?;
+        Ok(SwapQuote {
+            amount_in,
+            amount_out,
+            // Charged on the output; reported as the same share of the input,
+            // which is near enough for the pegged pairs these pools hold.
+            fee_amount: mul_div_rounding_up(
+                amount_in,
+                U256::from(self.fee),
+                U256::from(FEE_PIPS_DENOMINATOR),
+            )?,
+            amount_remaining: U256::ZERO,
+            price_after: self.price_at(balances)?,
+        })
+    }
+
+    /// `get_dy`: output for `amount_in`, net of the fee.
+    fn amount_out(&self, zero_for_one: bool, amount_in: U256) -> Result<U256> {
+        if amount_in.is_zero() {
+            return Ok(U256::ZERO);
+        }
+        let (i, j) = Self::index(zero_for_one);
+        let scale = U256::pow10(18);
+        let xp = self.xp(self.balances)?;
+        let d = self.get_d(xp)?;
+        let x = xp[i]
+            .checked_add(mul_div(amount_in, self.rates[i], scale)?)
+            .ok_or(MathError::Overflow)?;
+        let y = self.get_y(x, d)?;
+        let dy = xp[j].saturating_sub(y).saturating_sub(U256::ONE);
+        let fee = mul_div(dy, U256::from(self.fee), U256::from(FEE_PIPS_DENOMINATOR))?;
+        Ok(mul_div(dy - fee, scale, self.rates[j])?)
+    }
+
+    /// Input that buys `amount_out` net of the fee; the inverse of
+    /// [`Self::amount_out`], rounded against the trader.
+    fn amount_in(&self, zero_for_one: bool, amount_out: U256) -> Result<U256> {
+        if amount_out.is_zero() {
+            return Ok(U256::ZERO);
+        }
+        let (i, j) = Self::index(zero_for_one);
+        let scale = U256::pow10(18);
+        let xp = self.xp(self.balances)?;
+        let d = self.get_d(xp)?;
+        let dy = mul_div_rounding_up(amount_out, self.rates[j], scale)?;
+        let gross = mul_div_rounding_up(
+            dy,
+            U256::from(FEE_PIPS_DENOMINATOR),
+            U256::from(FEE_PIPS_DENOMINATOR - self.fee),
+        )?;
+        let y = xp[j]
+            .checked_sub(gross)
+            .and_then(|y| y.checked_sub(U256::ONE))
+            .filter(|y| !y.is_zero())
+            .ok_or(MathError::LiquidityUnderflow)?;
+        let x = self.get_y(y, d)?;
+        let dx = x.checked_sub(xp[i]).ok_or(MathError::Overflow)?;
+        Ok(mul_div_rounding_up(dx, scale, self.rates[i])?)
+    }
+}
+
+fn checked(a: Option<U256>, b: Option<U256>) -> Result<U256> {
+    Ok(a.zip(b)
+        .and_then(|(a, b)| a.checked_add(b))
+        .ok_or(MathError::Overflow)?)
+}
+
+fn abs_diff(a: U256, b: U256) -> U256 {
+    if a > b {
+        a - b
+    } else {
+        b - a
+    }
+}
+
+impl Pool for StablePool {
+    fn fee_pips(&self) -> u32 {
+        self.fee
+    }
+
+    fn spot_price(&self) -> f64 {
+        self.price_at(self.balances).unwrap_or(f64::NAN)
+    }
+
+    /// The balances; the curve is far deeper than an `x * y = k` pool on
+    /// them near the peg.
+    fn virtual_reserves(&self) -> (f64, f64) {
+        (self.balances[0].to_f64(), self.balances[1].to_f64())
+    }
+
+    fn quote_exact_in(
+        &self,
+        zero_for_one: bool,
+        amount_in: U256,
+        price_limit: Option<f64>,
+    ) -> Result<SwapQuote> {
+        exact_in_within(
+            zero_for_one,
+            self.spot_price(),
+            amount_in,
+            price_limit,
+            |x| self.fill(zero_for_one, x, self.amount_out(zero_for_one, x)?),
+        )
+    }
+
+    fn quote_exact_out(
+        &self,
+        zero_for_one: bool,
+        amount_out: U256,
+        price_limit: Option<f64>,
+    ) -> Result<SwapQuote> {
+        let (_, j) = Self::index(zero_for_one);
+        let mut cap = self.balances[j].saturating_sub(U256::ONE);
+        if price_limit.is_some() {
+            let most = self.quote_exact_in(zero_for_one, U256::from(u128::MAX), price_limit)?;
+            cap = cap.min(most.amount_out);
+        }
+        let filled = amount_out.min(cap);
+        let amount_in = self.amount_in(zero_for_one, filled)?;
+        let mut quote = self.fill(zero_for_one, amount_in, filled)?;
+        quote.amount_remaining = amount_out - filled;
+        Ok(quote)
+    }
+
+    fn apply_swap(
+        &mut self,
+        zero_for_one: bool,
+        amount_in: U256,
+        price_limit: Option<f64>,
+    ) -> Result<SwapQuote> {
+        let quote = self.quote_exact_in(zero_for_one, amount_in, price_limit)?;
+        self.balances = self.balances_after(zero_for_one, quote.amount_in, quote.amount_out)?;
+        Ok(quote)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const E18: u128 = 10u128.pow(18);
+
+    /// 1M USDT0 (6 decimals) against 1M USDe (18 decimals), A = 200, 4 bps.
+    fn pool(usdt: u128, usde: u128) -> StablePool {
+        StablePool::new(
+            [U256::from(usdt * 1_000_000), U256::from(usde * E18)],
+            [6, 18],
+            200,
+            400,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn balanced_pool_trades_near_par() {
+        let p = pool(1_000_000, 1_000_000);
+        // Raw price: 1e18 USDe wei per 1e6 USDT0 wei.
+        assert!((p.spot_price() / 1e12 - 1.0).abs() < 1e-12);
+        let d = p.get_d(p.xp(p.balances).unwrap()).unwrap();
+        assert!(abs_diff(d, U256::from(2_000_000 * E18)) <= U256::ONE);
+
+        // 10k moves a stableswap pool far less than an xyk pool of the
+        // same balances (which would lose ~1% to impact).
+        let q = p
+            .quote_exact_in(true, U256::from(10_000_000_000u128), None)
+            .unwrap();
+        let out = q.amount_out.to_f64() / 1e22;
+        assert!(out > 0.9995 && out < 0.9996, "{out}");
+    }
+
+    #[test]
+    fn imbalance_moves_the_price() {
+        let heavy0 = pool(1_500_000, 500_000);
+        let heavy1 = pool(500_000, 1_500_000);
+        // Abundant token0 is cheap, and the curve is symmetric.
+        let (p0, p1) = (heavy0.spot_price() / 1e12, heavy1.spot_price() / 1e12);
+        assert!(p0 < 1.0 && p1 > 1.0);
+        assert!((p0 * p1 - 1.0).abs() < 1e-9, "{p0} {p1}");
+    }
+
+    #[test]
+    fn rejects_bad_parameters() {
+        let one = U256::from(E18);
+        assert_eq!(
+            StablePool::new([one, one], [18, 18], 0, 400).unwrap_err(),
+            Error::InvalidAmplification(0)
+        );
+        assert_eq!(
+            StablePool::new([one, U256::ZERO], [18, 18], 100, 400).unwrap_err(),
+            Error::Math(MathError::ZeroLiquidity)
+        );
+        let p = pool(1_000_000, 1_000_000);
+        // Unlike an xyk pool, no input drains the output side.
+        let err = p
+            .quote_exact_out(true, U256::from(2_000_000 * E18), None)
+            .unwrap_err();
+        assert_eq!(err, Error::Math(MathError::LiquidityUnderflow));
+    }
+}
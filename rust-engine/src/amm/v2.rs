@@ -0,0 +1,248 @@
+//! Constant-product (`x * y = k`) pools, as `UniswapV2Pair` and its forks.
+//!
+//! Amounts follow `UniswapV2Library.getAmountOut` / `getAmountIn` with the
+//! fee generalised to pips, so quotes match the router to the wei. The fee
+//! is taken from the input and stays in the reserves.
+
+use super::{exact_in_within, Pool, Result, SwapQuote};
+use crate::univ3::full_math::{mul_div, mul_div_rounding_up};
+use crate::univ3::{Error as MathError, FEE_PIPS_DENOMINATOR, U256};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct V2Pool {
+    pub reserve0: U256,
+    pub reserve1: U256,
+    /// Swap fee in hundredths of a bip (`3000` = 0.3%, the V2 default).
+    pub fee: u32,
+}
+
+impl V2Pool {
+    pub fn new(reserve0: U256, reserve1: U256, fee: u32) -> Result<Self> {
+        if fee >= FEE_PIPS_DENOMINATOR {
+            return Err(MathError::InvalidFee(fee).into());
+        }
+        if reserve0.is_zero() || reserve1.is_zero() {
+            return Err(MathError::ZeroLiquidity.into());
+        }
+        Ok(Self {
+            reserve0,
+            reserve1,
+            fee,
+        })
+    }
+
+    /// `(reserve_in, reserve_out)` for a swap in this direction.
+    fn reserves(&self, zero_for_one: bool) -> (U256, U256) {
+        if zero_for_one {
+            (self.reserve0, self.reserve1)
+        } else {
+            (self.reserve1, self.reserve0)
+        }
+    }
+
+    fn fee_complement(&self) -> U256 {
+        U256::from(FEE_PIPS_DENOMINATOR - self.fee)
+    }
+
+    /// `getAmountOut`.
+    fn amount_out(&self, zero_for_one: bool, amount_in: U256) -> Result<U256> {
+        let (reserve_in, reserve_out) = self.reserves(zero_for_one);
+        let in_with_fee = amount_in
+            .checked_mul(self.fee_complement())
+            .ok_or(MathError::Overflow)?;
+        let denominator = reserve_in
+            .checked_mul(U256::from(FEE_PIPS_DENOMINATOR))
+            .and_then(|d| d.checked_add(in_with_fee))
+            .ok_or(MathError::Overflow)?;
+        Ok(mul_div(in_with_fee, reserve_out, denominator)?)
+    }
+
+    /// `getAmountIn`; `amount_out` must be below the output reserve.
+    fn amount_in(&self, zero_for_one: bool, amount_out: U256) -> Result<U256> {
+        if amount_out.is_zero() {
+            return Ok(U256::ZERO);
+        }
+        let (reserve_in, reserve_out) = self.reserves(zero_for_one);
+        let left = reserve_out
+            .checked_sub(amount_out)
+            .filter(|r| !r.is_zero())
+            .ok_or(MathError::LiquidityUnderflow)?;
+        let numerator = reserve_in
+            .checked_mul(U256::from(FEE_PIPS_DENOMINATOR))
+            .ok_or(MathError::Overflow)?;
+        let denominator = left
+            .checked_mul(self.fee_complement())
+            .ok_or(MathError::Overflow)?;
+        Ok(mul_div(numerator, amount_out, denominator)?
+            .checked_add(U256::ONE)
+            .ok_or(MathError::Overflow)?)
+    }
+
+    /// `(reserve0, reserve1)` after `amount_in` goes in and `amount_out` out.
+    fn reserves_after(
+        &self,
+        zero_for_one: bool,
+        amount_in: U256,
+        amount_out: U256,
+    ) -> Result<(U256, U256)> {
+        let (reserve_in, reserve_out) = self.reserves(zero_for_one);
+        let reserve_in = reserve_in
+            .checked_add(amount_in)
+            .ok_or(MathError::Overflow)?;
+        let reserve_out = reserve_out
+            .checked_sub(amount_out)
+            .ok_or(MathError::LiquidityUnderflow)?;
+        Ok(if zero_for_one {
+            (reserve_in, reserve_out)
+        } else {
+            (reserve_out, reserve_in)
+        })
+    }
+
+    fn fill(&self, zero_for_one: bool, amount_in: U256, amount_out: U256) -> Result<SwapQuote> {
+        let (reserve0, reserve1) = self.reserves_after(zero_for_one, amount_in, amount_out)?;
+        Ok(SwapQuote {
+            amount_in,
+            amount_out,
+            fee_amount: mul_div_rounding_up(
+                amount_in,
+                U256::from(self.fee),
+                U256::from(FEE_PIPS_DENOMINATOR),
+            )?,
+            amount_remaining: U256::ZERO,
+            price_after: reserve1.to_f64() / reserve0.to_f64(),
+        })
+    }
+}
+
+impl Pool for V2Pool {
+    fn fee_pips(&self) -> u32 {
+        self.fee
+    }
+
+    fn spot_price(&self) -> f64 {
+        self.reserve1.to_f64() / self.reserve0.to_f64()
+    }
+
+    fn virtual_reserves(&self) -> (f64, f64) {
+        (self.reserve0.to_f64(), self.reserve1.to_f64())
+    }
+
+    fn quote_exact_in(
+        &self,
+        zero_for_one: bool,
+        amount_in: U256,
+        price_limit: Option<f64>,
+    ) -> Result<SwapQuote> {
+        exact_in_within(
+            zero_for_one,
+            self.spot_price(),
+            amount_in,
+            price_limit,
+            |x| self.fill(zero_for_one, x, self.amount_out(zero_for_one, x)?),
+        )
+    }
+
+    fn quote_exact_out(
+        &self,
+        zero_for_one: bool,
+        amount_out: U256,
+        price_limit: Option<f64>,
+    ) -> Result<SwapQuote> {
+        let (_, reserve_out) = self.reserves(zero_for_one);
+        let mut cap = reserve_out.saturating_sub(U256::ONE);
+        if price_limit.is_some() {
+            let most = self.quote_exact_in(zero_for_one, U256::from(u128::MAX), price_limit)?;
+            cap = cap.min(most.amount_out);
+        }
+        let filled = amount_out.min(cap);
+        let amount_in = self.amount_in(zero_for_one, filled)?;
+        let mut quote = self.fill(zero_for_one, amount_in, filled)?;
+        quote.amount_remaining = amount_out - filled;
+        Ok(quote)
+    }
+
+    fn apply_swap(
+        &mut self,
+        zero_for_one: bool,
+        amount_in: U256,
+        price_limit: Option<f64>,
+    ) -> Result<SwapQuote> {
+        let quote = self.quote_exact_in(zero_for_one, amount_in, price_limit)?;
+        (self.reserve0, self.reserve1) =
+            self.reserves_after(zero_for_one, quote.amount_in, quote.amount_out)?;
+        Ok(quote)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amm::Error;
+
+    const E18: u128 = 10u128.pow(18);
+
+    fn pool() -> V2Pool {
+        // 1,000 WHYPE (token0) against 40,000 USDC (6 decimals) at 0.3%.
+        V2Pool::new(
+            U256::from(1_000 * E18),
+            U256::from(40_000_000_000u128),
+            3000,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn matches_the_router_formulas() {
+        let p = pool();
+        // getAmountOut(1e18, 1000e18, 40000e6) with 997/1000.
+        let x = E18;
+        let expected = x * 997 * 40_000_000_000 / (1_000 * E18 * 1000 + x * 997);
+        let q = p.quote_exact_in(true, U256::from(x), None).unwrap();
+        assert_eq!(q.amount_out, U256::from(expected));
+        assert_eq!(q.fee_amount, U256::from(3 * E18 / 1000));
+        assert!((p.spot_price() * 1e12 - 40.0).abs() < 1e-9);
+
+        // getAmountIn(40e6): floor(r_in * out * 1000 / ((r_out - out) * 997)) + 1.
+        let out = 40_000_000u128;
+        let q = p.quote_exact_out(false, U256::from(out), None).unwrap();
+        let r_in = U256::from(40_000_000_000u128);
+        let r_out = U256::from(1_000 * E18);
+        let want = r_in * U256::from(out) * U256::from(1000u32)
+            / ((r_out - U256::from(out)) * U256::from(997u32))
+            + U256::ONE;
+        assert_eq!(q.amount_in, want);
+        assert_eq!(q.amount_out, U256::from(out));
+    }
+
+    #[test]
+    fn exact_out_cannot_drain_the_pool() {
+        let p = pool();
+        let q = p
+            .quote_exact_out(true, U256::from(50_000_000_000u128), None)
+            .unwrap();
+        assert_eq!(q.amount_out, U256::from(40_000_000_000u128 - 1));
+        assert_eq!(q.amount_remaining, U256::from(10_000_000_001u128));
+        assert!(V2Pool::new(U256::ZERO, U256::ONE, 3000).is_err());
+        assert_eq!(
+            V2Pool::new(U256::ONE, U256::ONE, 1_000_000).unwrap_err(),
+            Error::Math(MathError::InvalidFee(1_000_000))
+        );
+    }
+
+    #[test]
+    fn price_limit_bounds_exact_out() {
+        let p = pool();
+        let limit = p.spot_price() * 1.01;
+        let q = p
+            .quote_exact_out(false, U256::from(100 * E18), Some(limit))
+            .unwrap();
+        assert!(q.is_partial());
+        // Buying token0 pushes the price up to the limit and no further.
+        assert!(
+            (q.price_after / limit - 1.0).abs() < 1e-9,
+            "{}",
+            q.price_after
+        );
+    }
+}
@@ -0,0 +1,113 @@
+//! [`Pool`] for concentrated-liquidity V3 pools, over the exact swap loop of
+//! [`PoolState`].
+
+use super::{Pool, Result, SwapQuote};
+use crate::univ3::full_math::mul_div;
+use crate::univ3::tick_math::{MAX_SQRT_RATIO, MIN_SQRT_RATIO};
+use crate::univ3::{self, Error, PoolState, SwapResult, Q96, U256};
+
+fn price(sqrt_price_x96: U256) -> f64 {
+    let sqrt = sqrt_price_x96.to_f64() / Q96.to_f64();
+    sqrt * sqrt
+}
+
+impl PoolState {
+    /// `sqrtPriceX96` for a [`Pool`] price limit, clamped to the range the
+    /// swap loop accepts.
+    fn sqrt_price_limit(
+        &self,
+        zero_for_one: bool,
+        limit: Option<f64>,
+    ) -> univ3::Result<Option<U256>> {
+        let Some(limit) = limit else {
+            return Ok(None);
+        };
+        let spot = self.spot_price();
+        let valid = if zero_for_one {
+            limit < spot && limit >= 0.0
+        } else {
+            limit > spot
+        };
+        if !valid {
+            return Err(Error::InvalidPriceLimit);
+        }
+        let factor = (limit / spot).sqrt();
+        let scale = U256::pow10(18);
+        let sqrt = mul_div(
+            self.sqrt_price_x96,
+            U256::from((factor * 1e18) as u128),
+            scale,
+        )?;
+        Ok(Some(sqrt.clamp(
+            MIN_SQRT_RATIO + U256::ONE,
+            MAX_SQRT_RATIO - U256::ONE,
+        )))
+    }
+}
+
+impl From<SwapResult> for SwapQuote {
+    fn from(r: SwapResult) -> Self {
+        SwapQuote {
+            amount_in: r.amount_in,
+            amount_out: r.amount_out,
+            fee_amount: r.fee_amount,
+            amount_remaining: r.amount_remaining,
+            price_after: price(r.sqrt_price_x96),
+        }
+    }
+}
+
+impl Pool for PoolState {
+    fn fee_pips(&self) -> u32 {
+        self.fee
+    }
+
+    fn spot_price(&self) -> f64 {
+        price(self.sqrt_price_x96)
+    }
+
+    /// `(L / sqrtP, L * sqrtP)` of the active range.
+    fn virtual_reserves(&self) -> (f64, f64) {
+        let sqrt = self.sqrt_price_x96.to_f64() / Q96.to_f64();
+        let liquidity = self.liquidity as f64;
+        (liquidity / sqrt, liquidity * sqrt)
+    }
+
+    fn quote_exact_in(
+        &self,
+        zero_for_one: bool,
+        amount_in: U256,
+        price_limit: Option<f64>,
+    ) -> Result<SwapQuote> {
+        let limit = self.sqrt_price_limit(zero_for_one, price_limit)?;
+        Ok(self
+            .quote_exact_input(zero_for_one, amount_in, limit)?
+            .into())
+    }
+
+    fn quote_exact_out(
+        &self,
+        zero_for_one: bool,
+        amount_out: U256,
+        price_limit: Option<f64>,
+    ) -> Result<SwapQuote> {
+        let limit = self.sqrt_price_limit(zero_for_one, price_limit)?;
+        Ok(self
+            .quote_exact_output(zero_for_one, amount_out, limit)?
+            .into())
+    }
+
+    fn apply_swap(
+        &mut self,
+        zero_for_one: bool,
+        amount_in: U256,
+        price_limit: Option<f64>,
+    ) -> Result<SwapQuote> {
+        let limit = self.sqrt_price_limit(zero_for_one, price_limit)?;
+        let result = self.quote_exact_input(zero_for_one, amount_in, limit)?;
+        self.sqrt_price_x96 = result.sqrt_price_x96;
+        self.tick = result.tick;
+        self.liquidity = result.liquidity;
+        Ok(result.into())
+    }
+}
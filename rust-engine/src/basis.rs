@@ -0,0 +1,546 @@
+//! Basis detector: a HyperEVM pool against the Hyperliquid L1 book of the
+//! same asset.
+//!
+//! Where the pool is below the book's best bid, the token is bought on the
+//! pool and sold on L1; where it is above the best ask, bought on L1 and sold
+//! on the pool. The size maximises the profit of both legs together: the
+//! pool leg through its exact swap math, the L1 leg walked through
+//! the mirrored levels, so whichever of the pool curve and the book depth
+//! runs out first caps it.
+//!
+//! Neither leg can be flash-borrowed against the other, so the route is
+//! costed as trading from inventory on both venues. A perp leg leaves a
+//! position that is closed out separately; the edge is the price difference
+//! captured at entry.
+
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::amm::{Pool, SwapQuote};
+use crate::detector::{pool_liquidity_usd, price_error, score, DetectorConfig};
+use crate::hyperliquid::{BookMirror, Fill, L1Market, OrderBook, Side};
+use crate::opportunity::{L1Order, Opportunity, OpportunityKind, Route, RouteLeg, TokenPair};
+use crate::pricing::UsdPrices;
+use crate::profit_gate::{cost_breakdown, Costs, Quote};
+use crate::sizing::Leg;
+use crate::state::TrackedPool;
+
+/// An L1 coin and the HyperEVM token that trades as the same asset.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BasisMarket {
+    pub coin: String,
+    /// Lowercase address.
+    pub token: String,
+}
+
+impl BasisMarket {
+    pub fn market(&self) -> L1Market {
+        L1Market::of(&self.coin)
+    }
+}
+
+impl FromStr for BasisMarket {
+    type Err = String;
+
+    /// `COIN=0xtoken`, e.g. `HYPE=0x5555555555555555555555555555555555555555`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (coin, token) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected COIN=0xTOKEN, got {s:?}"))?;
+        let (coin, token) = (coin.trim(), token.trim());
+        let hex = token.strip_prefix("0x").unwrap_or_default();
+        if coin.is_empty() || hex.len() != 40 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format!("expected COIN=0xTOKEN, got {s:?}"));
+        }
+        Ok(Self {
+            coin: coin.to_string(),
+            token: token.to_lowercase(),
+        })
+    }
+}
+
+impl fmt::Display for BasisMarket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.coin, self.token)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BasisConfig {
+    /// Coins compared with their pools; none disables the strategy.
+    pub markets: Vec<BasisMarket>,
+    pub perp_taker_fee_bps: f64,
+    pub spot_taker_fee_bps: f64,
+    /// Books whose last snapshot is older than this are ignored.
+    pub max_book_age: Duration,
+}
+
+impl Default for BasisConfig {
+    fn default() -> Self {
+        Self {
+            markets: Vec::new(),
+            perp_taker_fee_bps: 4.5,
+            spot_taker_fee_bps: 7.0,
+            max_book_age: Duration::from_secs(5),
+        }
+    }
+}
+
+impl BasisConfig {
+    pub fn taker_fee_bps(&self, market: L1Market) -> f64 {
+        match market {
+            L1Market::Perp => self.perp_taker_fee_bps,
+            L1Market::Spot => self.spot_taker_fee_bps,
+        }
+    }
+
+    pub fn coins(&self) -> impl Iterator<Item = &str> {
+        self.markets.iter().map(|m| m.coin.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BasisDetector {
+    /// Thresholds, sizing caps and costs shared with the cross-DEX detector.
+    pub config: DetectorConfig,
+    pub basis: BasisConfig,
+}
+
+/// Both legs at one pool input.
+struct Trade {
+    swap: SwapQuote,
+    fill: Fill,
+    /// Pool input, whole units of its token.
+    input: f64,
+    /// Pool output, whole units of its token.
+    output: f64,
+    l1_fee_usd: f64,
+    profit_usd: f64,
+}
+
+impl BasisDetector {
+    pub fn new(config: DetectorConfig, basis: BasisConfig) -> Self {
+        Self { config, basis }
+    }
+
+    /// Every pool of a configured market's token whose price is through the
+    /// L1 book by more than both legs' fees, highest net profit first.
+    pub fn detect<'a>(
+        &self,
+        pools: impl IntoIterator<Item = &'a TrackedPool>,
+        books: &BookMirror,
+        prices: &UsdPrices,
+        now: DateTime<Utc>,
+    ) -> Vec<Opportunity> {
+        let pools: Vec<&TrackedPool> = pools
+            .into_iter()
+            .filter(|p| {
+                age(p.updated_at, now) <= self.config.max_state_age
+                    && p.token0.is_standard()
+                    && p.token1.is_standard()
+            })
+            .collect();
+
+        let mut out = Vec::new();
+        for market in &self.basis.markets {
+            let Some(book) = books.book(&market.coin) else {
+                continue;
+            };
+            if book_age(book, now) > self.basis.max_book_age {
+                continue;
+            }
+            for pool in &pools {
+                if pool.token0.address == market.token || pool.token1.address == market.token {
+                    out.extend(self.evaluate(pool, market, book, prices, now));
+                }
+            }
+        }
+        out.sort_by(|a, b| b.costs.net_usd.total_cmp(&a.costs.net_usd));
+        out
+    }
+
+    fn evaluate(
+        &self,
+        pool: &TrackedPool,
+        market: &BasisMarket,
+        book: &OrderBook,
+        prices: &UsdPrices,
+        now: DateTime<Utc>,
+    ) -> Option<Opportunity> {
+        let base_is_0 = pool.token0.address == market.token;
+        let (base, quote) = if base_is_0 {
+            (&pool.token0, &pool.token1)
+        } else {
+            (&pool.token1, &pool.token0)
+        };
+        let quote_usd = prices.get(&quote.address)?;
+        let mid = pool.mid_price();
+        if mid <= 0.0 || quote_usd <= 0.0 {
+            return None;
+        }
+        // USD per whole base token on the pool.
+        let pool_px = if base_is_0 { mid } else { 1.0 / mid } * quote_usd;
+        let (bid, ask, book_mid) = (book.best_bid()?.px, book.best_ask()?.px, book.mid()?);
+
+        let l1_market = market.market();
+        let fee_bps = self.basis.taker_fee_bps(l1_market);
+        let keep = (1.0 - pool.fee_fraction()) * (1.0 - fee_bps / 1e4);
+        let buy_pool_bps = (bid * keep / pool_px - 1.0) * 1e4;
+        let sell_pool_bps = (pool_px * keep / ask - 1.0) * 1e4;
+        let buy_on_pool = buy_pool_bps >= sell_pool_bps;
+        let spread_bps = buy_pool_bps.max(sell_pool_bps);
+        if spread_bps < self.config.min_spread_bps {
+            return None;
+        }
+
+        // Taking from the book: its bids when the pool leg buys the token.
+        let side = if buy_on_pool { Side::Sell } else { Side::Buy };
+        let (usd0, usd1) = if base_is_0 {
+            (pool_px, quote_usd)
+        } else {
+            (quote_usd, pool_px)
+        };
+        let liquidity_usd = pool_liquidity_usd(pool, usd0, usd1).min(book.notional(side));
+        if liquidity_usd < self.config.min_liquidity_usd {
+            return None;
+        }
+
+        let (token_in, token_out, usd_in) = if buy_on_pool {
+            (quote, base, quote_usd)
+        } else {
+            (base, quote, pool_px)
+        };
+        let leg = Leg {
+            pool: &pool.state,
+            zero_for_one: token_in.address == pool.token0.address,
+        };
+        let limit = leg.price_limit(self.config.max_slippage_bps);
+        let fee_fraction = fee_bps / 1e4;
+        let trade = |input: f64| -> Option<Trade> {
+            let swap = leg.quote(token_in.from_units(input), limit).ok()?;
+            if swap.is_partial() || swap.amount_out.is_zero() {
+                return None;
+            }
+            let output = token_out.to_units(swap.amount_out);
+            let base_size = if buy_on_pool { output } else { input };
+            let fill = book.depth_for_size(side, base_size)?;
+            let l1_fee_usd = fill.notional * fee_fraction;
+            let profit_usd = if buy_on_pool {
+                fill.notional - l1_fee_usd - input * quote_usd
+            } else {
+                output * quote_usd - fill.notional - l1_fee_usd
+            };
+            Some(Trade {
+                swap,
+                fill,
+                input,
+                output,
+                l1_fee_usd,
+                profit_usd,
+            })
+        };
+
+        // As in the cross-DEX detector, the notional cap holds at the top of
+        // the input token's price interval.
+        let price_error = price_error(pool, prices);
+        let max_input = self.config.max_notional_usd / (usd_in * (1.0 + price_error));
+        let t = trade(best_input(max_input, |x| trade(x).map(|t| t.profit_usd))?)?;
+
+        let size_usd = if buy_on_pool {
+            t.input * quote_usd
+        } else {
+            t.fill.notional
+        };
+        let pool_fee_usd = token_in.to_units(t.swap.fee_amount) * usd_in;
+        let lp_fees_usd = pool_fee_usd + t.l1_fee_usd;
+        let gross_usd = t.profit_usd + lp_fees_usd;
+        let edge_at_mid = if buy_on_pool {
+            book_mid / pool_px - 1.0
+        } else {
+            pool_px / book_mid - 1.0
+        };
+        let slip_bps = if size_usd > 0.0 {
+            ((size_usd * edge_at_mid - gross_usd) / size_usd * 1e4).max(0.0)
+        } else {
+            0.0
+        };
+        let costs = inventory_costs(&self.config.costs);
+        let native_usd = prices.get(&self.config.native_token).unwrap_or(0.0);
+        let breakdown = cost_breakdown(
+            &Quote {
+                size_usd,
+                gross_usd,
+                lp_fees_usd,
+                slip_bps,
+            },
+            &costs,
+            native_usd,
+        );
+
+        // Pool leg's marginal rate in whole units, carried through the
+        // book's last level into USD out per USD in.
+        let scale = 10f64.powi(i32::from(token_in.decimals) - i32::from(token_out.decimals));
+        let pool_rate = leg.marginal_rate(t.swap.price_after) * scale;
+        let marginal_price = if buy_on_pool {
+            pool_rate * t.fill.worst_px * (1.0 - fee_fraction) / quote_usd
+        } else {
+            pool_rate * quote_usd / (t.fill.worst_px * (1.0 + fee_fraction))
+        };
+
+        let max_pool_age = self.config.max_state_age.as_secs_f64().max(1e-9);
+        let max_book_age = self.basis.max_book_age.as_secs_f64().max(1e-9);
+        let staleness = (age(pool.updated_at, now).as_secs_f64() / max_pool_age)
+            .max(book_age(book, now).as_secs_f64() / max_book_age);
+        let confidence = ((1.0 - staleness) * (1.0 - price_error.min(1.0))).clamp(0.0, 1.0);
+
+        let mut opp = Opportunity {
+            kind: OpportunityKind::Arbitrage,
+            pair: TokenPair {
+                base: pool.token0.clone(),
+                quote: pool.token1.clone(),
+            },
+            spread_bps,
+            est_gas_usd: breakdown.gas_usd,
+            est_profit_usd: t.profit_usd,
+            liquidity_usd,
+            confidence,
+            size_usd,
+            amount_in: t.swap.amount_in,
+            expected_out: t.swap.amount_out,
+            marginal_price,
+            slip_bps,
+            costs: breakdown,
+            viable: true,
+            rejections: Vec::new(),
+            ev_per_sec: 0.0,
+            p_success: 0.0,
+            ev_size_usd: 0.0,
+            tail_risk: Vec::new(),
+            route: Route {
+                legs: vec![RouteLeg {
+                    dex: pool.dex,
+                    pool: pool.address.clone(),
+                    token_in: token_in.address.clone(),
+                    token_out: token_out.address.clone(),
+                    fee: pool.state.fee_pips(),
+                    fee_usd: pool_fee_usd,
+                    split: Vec::new(),
+                }],
+                l1_order: Some(L1Order {
+                    market: l1_market,
+                    coin: market.coin.clone(),
+                    side,
+                    size: if buy_on_pool { t.output } else { t.input },
+                    avg_px: t.fill.avg_px,
+                    worst_px: t.fill.worst_px,
+                    fee_bps,
+                    fee_usd: t.l1_fee_usd,
+                }),
+            },
+            carry: None,
+        };
+        score(&mut opp, &self.config, &costs);
+        Some(opp)
+    }
+}
+
+/// `costs` for a route funded from inventory: no flash loan and none of the
+/// fees that come with one.
+pub(crate) fn inventory_costs(costs: &Costs) -> Costs {
+    Costs {
+        flash_enabled: false,
+        flash_fee_bps: 0.0,
+        flash_fixed_usd: 0.0,
+        referral_bps: 0.0,
+        ..costs.clone()
+    }
+}
+
+/// Input in `(0, max]` that maximises `profit`, or `None` when no input is
+/// profitable. `profit` is concave where it is defined; `None` (the pool's
+/// price limit or the book's depth exceeded) ranks below any value.
+pub(crate) fn best_input(max: f64, profit: impl Fn(f64) -> Option<f64>) -> Option<f64> {
+    if !(max.is_finite() && max > 0.0) {
+        return None;
+    }
+    let worse = |a: f64, b: f64| match (profit(a), profit(b)) {
+        (Some(pa), Some(pb)) => pa < pb,
+        (None, Some(_)) => true,
+        _ => false,
+    };
+    let (mut lo, mut hi) = (0.0, max);
+    for _ in 0..100 {
+        let third = (hi - lo) / 3.0;
+        let (m1, m2) = (lo + third, hi - third);
+        if worse(m1, m2) {
+            lo = m1;
+        } else {
+            hi = m2;
+        }
+    }
+    let x = (lo + hi) / 2.0;
+    profit(x).filter(|&p| p > 0.0).map(|_| x)
+}
+
+pub(crate) fn age(updated_at: DateTime<Utc>, now: DateTime<Utc>) -> Duration {
+    (now - updated_at).to_std().unwrap_or_default()
+}
+
+pub(crate) fn book_age(book: &OrderBook, now: DateTime<Utc>) -> Duration {
+    let ms = now.timestamp_millis().max(0) as u64;
+    Duration::from_millis(ms.saturating_sub(book.time))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::detector::WHYPE;
+    use crate::hyperliquid::{L2Book, Level};
+    use crate::state::Dex;
+    use crate::testing::{tick_for_price, token, v3_pool};
+
+    fn prices() -> UsdPrices {
+        let mut p = UsdPrices::new();
+        p.set(&token("USDC").address, 1.0);
+        p.set(WHYPE, 40.0);
+        p
+    }
+
+    fn hype_usdc(price: f64) -> TrackedPool {
+        let (t0, t1) = (token("WHYPE"), token("USDC"));
+        let tick = tick_for_price(price, &t0, &t1);
+        v3_pool("0xa", Dex::Prjx, t0, t1, 500, tick, 10u128.pow(17))
+    }
+
+    fn mirror(coin: &str, time: u64, bids: &[(f64, f64)], asks: &[(f64, f64)]) -> BookMirror {
+        let levels = |side: &[(f64, f64)]| {
+            side.iter()
+                .map(|&(px, sz)| Level { px, sz, n: 1 })
+                .collect()
+        };
+        let mut m = BookMirror::new();
+        m.apply_book(L2Book {
+            coin: coin.into(),
+            time,
+            levels: [levels(bids), levels(asks)],
+        })
+        .unwrap();
+        m
+    }
+
+    fn detector(coin: &str) -> BasisDetector {
+        BasisDetector::new(
+            DetectorConfig::default(),
+            BasisConfig {
+                markets: vec![format!("{coin}={WHYPE}").parse().unwrap()],
+                ..BasisConfig::default()
+            },
+        )
+    }
+
+    fn now_ms(now: DateTime<Utc>) -> u64 {
+        now.timestamp_millis() as u64
+    }
+
+    #[test]
+    fn buys_on_the_pool_and_sells_into_the_perp_bids() {
+        let now = Utc::now();
+        let books = mirror(
+            "HYPE",
+            now_ms(now),
+            &[(40.4, 300.0), (40.38, 300.0), (40.3, 1000.0)],
+            &[(40.42, 500.0)],
+        );
+        let opps = detector("HYPE").detect([&hype_usdc(40.0)], &books, &prices(), now);
+        assert_eq!(opps.len(), 1);
+        let o = &opps[0];
+        assert_eq!(o.route.to_string(), "PRJX->HL-perp");
+        assert_eq!(o.route.legs[0].token_in, token("USDC").address);
+        let l1 = o.route.l1_order.as_ref().unwrap();
+        assert_eq!(
+            (l1.market, l1.side, l1.fee_bps),
+            (L1Market::Perp, Side::Sell, 4.5)
+        );
+        // The L1 order sells exactly what the pool leg bought.
+        assert!((l1.size - token("WHYPE").to_units(o.expected_out)).abs() < 1e-9);
+        assert!(l1.avg_px <= 40.4 && l1.worst_px <= l1.avg_px);
+        // ~100 bps gross less 5 bps of LP fee and 4.5 of taker fee.
+        assert!((o.spread_bps - 90.5).abs() < 1.0, "{}", o.spread_bps);
+        assert!(o.est_profit_usd > 0.0 && o.size_usd <= 25_000.0);
+        let fees = o.route.legs[0].fee_usd + l1.fee_usd;
+        assert!((o.costs.lp_fees_usd - fees).abs() < 1e-9);
+        assert!((l1.fee_usd - l1.size * l1.avg_px * 4.5e-4).abs() < 1e-9);
+        assert_eq!(o.costs.flash_fee_usd, 0.0);
+        assert!(o.marginal_price >= 1.0 - 1e-6, "{}", o.marginal_price);
+        assert!(o.confidence > 0.5 && o.ev_per_sec > 0.0);
+    }
+
+    #[test]
+    fn buys_from_the_spot_asks_and_sells_on_the_pool() {
+        let now = Utc::now();
+        let books = mirror(
+            "@107",
+            now_ms(now),
+            &[(39.5, 500.0)],
+            &[(39.6, 400.0), (39.65, 1000.0)],
+        );
+        let opps = detector("@107").detect([&hype_usdc(40.0)], &books, &prices(), now);
+        assert_eq!(opps.len(), 1);
+        let o = &opps[0];
+        assert_eq!(o.route.to_string(), "HL-spot->PRJX");
+        let l1 = o.route.l1_order.as_ref().unwrap();
+        assert_eq!(
+            (l1.market, l1.side, l1.fee_bps),
+            (L1Market::Spot, Side::Buy, 7.0)
+        );
+        assert_eq!(o.route.legs[0].token_in, WHYPE);
+        assert!((l1.size - token("WHYPE").to_units(o.amount_in)).abs() < 1e-9);
+        assert!(l1.avg_px >= 39.6);
+        assert!((o.size_usd - l1.size * l1.avg_px).abs() < 1e-6);
+        assert!(o.est_profit_usd > 0.0);
+    }
+
+    #[test]
+    fn book_depth_caps_the_size() {
+        let now = Utc::now();
+        // 20 HYPE bid well above the pool, nothing behind it.
+        let books = mirror("HYPE", now_ms(now), &[(40.4, 20.0)], &[(40.5, 20.0)]);
+        let mut basis = detector("HYPE");
+        basis.config.min_liquidity_usd = 0.0;
+        let o = basis
+            .detect([&hype_usdc(40.0)], &books, &prices(), now)
+            .remove(0);
+        let l1 = o.route.l1_order.unwrap();
+        assert!(l1.size <= 20.0 && l1.size > 19.9, "{}", l1.size);
+        assert_eq!(l1.worst_px, 40.4);
+        assert!(o.size_usd < 20.0 * 40.1);
+
+        // The same bid is too shallow for the default liquidity floor.
+        assert!(detector("HYPE")
+            .detect([&hype_usdc(40.0)], &books, &prices(), now)
+            .is_empty());
+    }
+
+    #[test]
+    fn skips_stale_books_and_prices_inside_the_spread() {
+        let now = Utc::now();
+        let deep = |time| mirror("HYPE", time, &[(40.4, 1000.0)], &[(40.45, 1000.0)]);
+        let pool = hype_usdc(40.0);
+        let stale = deep(now_ms(now) - 60_000);
+        assert!(detector("HYPE")
+            .detect([&pool], &stale, &prices(), now)
+            .is_empty());
+        // No book mirrored for the coin.
+        assert!(detector("PURR")
+            .detect([&pool], &deep(now_ms(now)), &prices(), now)
+            .is_empty());
+        let tight = mirror("HYPE", now_ms(now), &[(39.99, 1000.0)], &[(40.01, 1000.0)]);
+        assert!(detector("HYPE")
+            .detect([&pool], &tight, &prices(), now)
+            .is_empty());
+    }
+}
@@ -0,0 +1,487 @@
+//! Funding carry: spot bought on a HyperEVM pool against a Hyperliquid perp
+//! short of the same size.
+//!
+//! While perp funding is positive the short collects it every hour, and the
+//! two legs cancel each other's price exposure. Over the configured holding
+//! horizon that has to pay for entering and exiting both legs: the pool swap
+//! in and back out, and the perp sold into the bids and bought back from the
+//! asks, each with its fee. Both exits are quoted from the venues as they
+//! stand now. The size maximises funding net of that round trip, and the
+//! result is published as an [`OpportunityKind::Carry`] with its horizon and
+//! yearly rate.
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::amm::{Pool, SwapQuote};
+use crate::basis::{age, best_input, book_age, inventory_costs, BasisConfig, BasisMarket};
+use crate::detector::{pool_liquidity_usd, price_error, score, DetectorConfig};
+use crate::hyperliquid::{AssetCtx, BookMirror, Fill, L1Market, OrderBook, Side};
+use crate::opportunity::{
+    Carry, L1Order, Opportunity, OpportunityKind, Route, RouteLeg, TokenPair,
+};
+use crate::pricing::UsdPrices;
+use crate::profit_gate::{cost_breakdown, Costs, Quote};
+use crate::sizing::Leg;
+use crate::state::TrackedPool;
+
+const HOURS_PER_YEAR: f64 = 24.0 * 365.0;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CarryConfig {
+    /// Perps carried against their token's pools; none disables the
+    /// strategy.
+    pub markets: Vec<BasisMarket>,
+    /// How long a position is held before it is unwound.
+    pub horizon_hours: f64,
+    /// Net yearly rate below which a carry is not reported.
+    pub min_apr: f64,
+    /// Funding rates fetched longer ago than this are ignored.
+    pub max_funding_age: Duration,
+}
+
+impl Default for CarryConfig {
+    fn default() -> Self {
+        Self {
+            markets: Vec::new(),
+            horizon_hours: 168.0,
+            min_apr: 0.1,
+            max_funding_age: Duration::from_secs(120),
+        }
+    }
+}
+
+impl CarryConfig {
+    pub fn coins(&self) -> impl Iterator<Item = &str> {
+        self.markets.iter().map(|m| m.coin.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CarryDetector {
+    /// Thresholds, sizing caps and costs shared with the cross-DEX detector.
+    pub config: DetectorConfig,
+    /// L1 taker fees and book freshness, shared with the basis detector.
+    pub basis: BasisConfig,
+    pub carry: CarryConfig,
+}
+
+/// Entry and exit of both legs at one pool input.
+struct Position {
+    entry: SwapQuote,
+    exit: SwapQuote,
+    short: Fill,
+    /// Pool input, whole units of the quote token.
+    input: f64,
+    /// Spot held and perp shorted, whole units.
+    size: f64,
+    taker_fees_usd: f64,
+    funding_usd: f64,
+    round_trip_usd: f64,
+}
+
+impl Position {
+    fn profit_usd(&self) -> f64 {
+        self.funding_usd - self.round_trip_usd
+    }
+}
+
+impl CarryDetector {
+    pub fn new(config: DetectorConfig, basis: BasisConfig, carry: CarryConfig) -> Self {
+        Self {
+            config,
+            basis,
+            carry,
+        }
+    }
+
+    /// Every pool of a configured perp's token that carries above
+    /// `min_apr` over the horizon, highest net profit first.
+    pub fn detect<'a>(
+        &self,
+        pools: impl IntoIterator<Item = &'a TrackedPool>,
+        books: &BookMirror,
+        prices: &UsdPrices,
+        now: DateTime<Utc>,
+    ) -> Vec<Opportunity> {
+        let pools: Vec<&TrackedPool> = pools
+            .into_iter()
+            .filter(|p| {
+                age(p.updated_at, now) <= self.config.max_state_age
+                    && p.token0.is_standard()
+                    && p.token1.is_standard()
+            })
+            .collect();
+
+        let mut out = Vec::new();
+        for market in &self.carry.markets {
+            if market.market() != L1Market::Perp {
+                continue;
+            }
+            let (Some(book), Some(ctx)) = (books.book(&market.coin), books.asset_ctx(&market.coin))
+            else {
+                continue;
+            };
+            if book_age(book, now) > self.basis.max_book_age
+                || ctx_age(ctx, now) > self.carry.max_funding_age
+            {
+                continue;
+            }
+            for pool in &pools {
+                if pool.token0.address == market.token || pool.token1.address == market.token {
+                    out.extend(self.evaluate(pool, market, book, ctx, prices, now));
+                }
+            }
+        }
+        out.sort_by(|a, b| b.costs.net_usd.total_cmp(&a.costs.net_usd));
+        out
+    }
+
+    fn evaluate(
+        &self,
+        pool: &TrackedPool,
+        market: &BasisMarket,
+        book: &OrderBook,
+        ctx: &AssetCtx,
+        prices: &UsdPrices,
+        now: DateTime<Utc>,
+    ) -> Option<Opportunity> {
+        let horizon = self.carry.horizon_hours;
+        if ctx.funding <= 0.0 || ctx.oracle_px <= 0.0 {
+            return None;
+        }
+        let fee_bps = self.basis.perp_taker_fee_bps;
+        // Funding over the horizon less every leg's fee, in and out.
+        let spread_bps = ctx.funding * horizon * 1e4 - 2.0 * (pool.fee_fraction() * 1e4 + fee_bps);
+        if spread_bps < self.config.min_spread_bps {
+            return None;
+        }
+
+        let base_is_0 = pool.token0.address == market.token;
+        let (base, quote) = if base_is_0 {
+            (&pool.token0, &pool.token1)
+        } else {
+            (&pool.token1, &pool.token0)
+        };
+        let quote_usd = prices.get(&quote.address)?;
+        let mid = pool.mid_price();
+        if mid <= 0.0 || quote_usd <= 0.0 {
+            return None;
+        }
+        let pool_px = if base_is_0 { mid } else { 1.0 / mid } * quote_usd;
+        let (usd0, usd1) = if base_is_0 {
+            (pool_px, quote_usd)
+        } else {
+            (quote_usd, pool_px)
+        };
+        let liquidity_usd = pool_liquidity_usd(pool, usd0, usd1)
+            .min(book.notional(Side::Sell))
+            .min(book.notional(Side::Buy));
+        if liquidity_usd < self.config.min_liquidity_usd {
+            return None;
+        }
+
+        let buy = Leg {
+            pool: &pool.state,
+            zero_for_one: !base_is_0,
+        };
+        let sell = Leg {
+            pool: &pool.state,
+            zero_for_one: base_is_0,
+        };
+        let buy_limit = buy.price_limit(self.config.max_slippage_bps);
+        let sell_limit = sell.price_limit(self.config.max_slippage_bps);
+        let fee_fraction = fee_bps / 1e4;
+        let position = |input: f64| -> Option<Position> {
+            let entry = buy.quote(quote.from_units(input), buy_limit).ok()?;
+            if entry.is_partial() || entry.amount_out.is_zero() {
+                return None;
+            }
+            let exit = sell.quote(entry.amount_out, sell_limit).ok()?;
+            if exit.is_partial() {
+                return None;
+            }
+            let size = base.to_units(entry.amount_out);
+            let short = book.depth_for_size(Side::Sell, size)?;
+            let cover = book.depth_for_size(Side::Buy, size)?;
+            let taker_fees_usd = (short.notional + cover.notional) * fee_fraction;
+            let pool_loss_usd = (input - quote.to_units(exit.amount_out)) * quote_usd;
+            Some(Position {
+                entry,
+                exit,
+                short,
+                input,
+                size,
+                taker_fees_usd,
+                funding_usd: ctx.funding * horizon * size * ctx.oracle_px,
+                round_trip_usd: pool_loss_usd + cover.notional - short.notional + taker_fees_usd,
+            })
+        };
+
+        let price_error = price_error(pool, prices);
+        let max_input = self.config.max_notional_usd / (quote_usd * (1.0 + price_error));
+        let best = best_input(max_input, |x| position(x).map(|p| p.profit_usd()))?;
+        let p = position(best)?;
+
+        let size_usd = p.input * quote_usd;
+        let pool_fees_usd = quote.to_units(p.entry.fee_amount) * quote_usd
+            + base.to_units(p.exit.fee_amount) * pool_px;
+        let lp_fees_usd = pool_fees_usd + p.taker_fees_usd;
+        let est_profit_usd = p.profit_usd();
+        let gross_usd = est_profit_usd + lp_fees_usd;
+        let slip_bps = ((p.round_trip_usd - lp_fees_usd) / size_usd * 1e4).max(0.0);
+        // Entry and exit are a transaction each.
+        let costs = Costs {
+            gas_limit: self.config.costs.gas_limit * 2,
+            ..inventory_costs(&self.config.costs)
+        };
+        let native_usd = prices.get(&self.config.native_token).unwrap_or(0.0);
+        let breakdown = cost_breakdown(
+            &Quote {
+                size_usd,
+                gross_usd,
+                lp_fees_usd,
+                slip_bps,
+            },
+            &costs,
+            native_usd,
+        );
+        let apr = breakdown.net_usd / size_usd * HOURS_PER_YEAR / horizon;
+        if apr < self.carry.min_apr {
+            return None;
+        }
+
+        // Profit on the last sliver of input, as output per unit of it.
+        let step = best * 1e-3;
+        let marginal_price = position(best - step).map_or(1.0, |q| {
+            1.0 + (est_profit_usd - q.profit_usd()) / (step * quote_usd)
+        });
+
+        let staleness = [
+            (age(pool.updated_at, now), self.config.max_state_age),
+            (book_age(book, now), self.basis.max_book_age),
+            (ctx_age(ctx, now), self.carry.max_funding_age),
+        ]
+        .into_iter()
+        .map(|(age, max)| age.as_secs_f64() / max.as_secs_f64().max(1e-9))
+        .fold(0.0, f64::max);
+        let confidence = ((1.0 - staleness) * (1.0 - price_error.min(1.0))).clamp(0.0, 1.0);
+
+        let mut opp = Opportunity {
+            kind: OpportunityKind::Carry,
+            pair: TokenPair {
+                base: pool.token0.clone(),
+                quote: pool.token1.clone(),
+            },
+            spread_bps,
+            est_gas_usd: breakdown.gas_usd,
+            est_profit_usd,
+            liquidity_usd,
+            confidence,
+            size_usd,
+            amount_in: p.entry.amount_in,
+            expected_out: p.entry.amount_out,
+            marginal_price,
+            slip_bps,
+            costs: breakdown,
+            viable: true,
+            rejections: Vec::new(),
+            ev_per_sec: 0.0,
+            p_success: 0.0,
+            ev_size_usd: 0.0,
+            tail_risk: Vec::new(),
+            route: Route {
+                legs: vec![RouteLeg {
+                    dex: pool.dex,
+                    pool: pool.address.clone(),
+                    token_in: quote.address.clone(),
+                    token_out: base.address.clone(),
+                    fee: pool.state.fee_pips(),
+                    fee_usd: quote.to_units(p.entry.fee_amount) * quote_usd,
+                    split: Vec::new(),
+                }],
+                l1_order: Some(L1Order {
+                    market: L1Market::Perp,
+                    coin: market.coin.clone(),
+                    side: Side::Sell,
+                    size: p.size,
+                    avg_px: p.short.avg_px,
+                    worst_px: p.short.worst_px,
+                    fee_bps,
+                    fee_usd: p.short.notional * fee_fraction,
+                }),
+            },
+            carry: Some(Carry {
+                horizon_hours: horizon,
+                funding_rate: ctx.funding,
+                funding_usd: p.funding_usd,
+                round_trip_usd: p.round_trip_usd,
+                apr,
+            }),
+        };
+        score(&mut opp, &self.config, &costs);
+        Some(opp)
+    }
+}
+
+fn ctx_age(ctx: &AssetCtx, now: DateTime<Utc>) -> Duration {
+    let ms = now.timestamp_millis().max(0) as u64;
+    Duration::from_millis(ms.saturating_sub(ctx.time))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::detector::WHYPE;
+    use crate::hyperliquid::{L2Book, Level};
+    use crate::state::Dex;
+    use crate::testing::{tick_for_price, token, v3_pool};
+
+    fn prices() -> UsdPrices {
+        let mut p = UsdPrices::new();
+        p.set(&token("USDC").address, 1.0);
+        p.set(WHYPE, 40.0);
+        p
+    }
+
+    fn hype_usdc() -> TrackedPool {
+        let (t0, t1) = (token("WHYPE"), token("USDC"));
+        let tick = tick_for_price(40.0, &t0, &t1);
+        v3_pool("0xa", Dex::Prjx, t0, t1, 500, tick, 10u128.pow(17))
+    }
+
+    /// HYPE book a cent either side of 40 with `funding` fetched at `time`.
+    fn books(funding: f64, time: u64) -> BookMirror {
+        let levels = |side: &[(f64, f64)]| {
+            side.iter()
+                .map(|&(px, sz)| Level { px, sz, n: 1 })
+                .collect()
+        };
+        let mut m = BookMirror::new();
+        m.apply_book(L2Book {
+            coin: "HYPE".into(),
+            time,
+            levels: [
+                levels(&[(39.99, 400.0), (39.97, 400.0)]),
+                levels(&[(40.01, 400.0), (40.03, 400.0)]),
+            ],
+        })
+        .unwrap();
+        m.apply_asset_ctxs(vec![AssetCtx {
+            coin: "HYPE".into(),
+            funding,
+            mark_px: 40.0,
+            oracle_px: 40.0,
+            open_interest: 1e6,
+            time,
+        }]);
+        m
+    }
+
+    fn detector() -> CarryDetector {
+        CarryDetector::new(
+            DetectorConfig::default(),
+            BasisConfig::default(),
+            CarryConfig {
+                markets: vec![format!("HYPE={WHYPE}").parse().unwrap()],
+                ..CarryConfig::default()
+            },
+        )
+    }
+
+    fn now_ms(now: DateTime<Utc>) -> u64 {
+        now.timestamp_millis() as u64
+    }
+
+    #[test]
+    fn shorts_the_perp_against_pool_spot_for_funding() {
+        let now = Utc::now();
+        // 1 bp an hour, 168 bps over the week.
+        let opps = detector().detect([&hype_usdc()], &books(1e-4, now_ms(now)), &prices(), now);
+        assert_eq!(opps.len(), 1);
+        let o = &opps[0];
+        assert_eq!(o.kind, OpportunityKind::Carry);
+        assert_eq!(o.route.to_string(), "PRJX->HL-perp");
+        assert_eq!(o.route.legs[0].token_in, token("USDC").address);
+        let l1 = o.route.l1_order.as_ref().unwrap();
+        assert_eq!((l1.market, l1.side), (L1Market::Perp, Side::Sell));
+        assert!((l1.size - token("WHYPE").to_units(o.expected_out)).abs() < 1e-9);
+        // Both legs of the 800 HYPE on each side of the book.
+        assert!(l1.size <= 800.0);
+
+        let c = o.carry.as_ref().unwrap();
+        assert_eq!((c.horizon_hours, c.funding_rate), (168.0, 1e-4));
+        assert!((c.funding_usd - 1e-4 * 168.0 * l1.size * 40.0).abs() < 1e-6);
+        // Fees alone are 2 x (5 + 4.5) bps.
+        assert!(c.round_trip_usd > o.size_usd * 19e-4 * 0.99);
+        assert!((o.est_profit_usd - (c.funding_usd - c.round_trip_usd)).abs() < 1e-9);
+        assert!((c.apr - o.costs.net_usd / o.size_usd * 8760.0 / 168.0).abs() < 1e-9);
+        assert!(c.apr > 0.1 && c.apr < 1e-4 * 8760.0, "{}", c.apr);
+        assert!((o.spread_bps - (168.0 - 19.0)).abs() < 1e-6);
+        // Two transactions of gas, no flash loan.
+        assert!((o.costs.gas_usd - 0.02).abs() < 1e-9);
+        assert_eq!(o.costs.flash_fee_usd, 0.0);
+        assert!(o.confidence > 0.5);
+    }
+
+    #[test]
+    fn skips_negative_stale_and_missing_funding() {
+        let now = Utc::now();
+        let pool = hype_usdc();
+        let carry = detector();
+        assert!(carry
+            .detect([&pool], &books(-1e-4, now_ms(now)), &prices(), now)
+            .is_empty());
+        let stale = books(1e-4, now_ms(now) - 300_000);
+        assert!(carry.detect([&pool], &stale, &prices(), now).is_empty());
+
+        let mut no_ctx = BookMirror::new();
+        no_ctx
+            .apply_book(L2Book {
+                coin: "HYPE".into(),
+                time: now_ms(now),
+                levels: [
+                    vec![Level {
+                        px: 39.99,
+                        sz: 1e4,
+                        n: 1,
+                    }],
+                    vec![Level {
+                        px: 40.01,
+                        sz: 1e4,
+                        n: 1,
+                    }],
+                ],
+            })
+            .unwrap();
+        assert!(carry.detect([&pool], &no_ctx, &prices(), now).is_empty());
+    }
+
+    #[test]
+    fn funding_must_pay_for_the_round_trip_within_the_horizon() {
+        let now = Utc::now();
+        let pool = hype_usdc();
+        // 11% a year gross, but a day of it is 3 bps against 19 of fees.
+        let mut short = detector();
+        short.carry.horizon_hours = 24.0;
+        assert!(short
+            .detect([&pool], &books(1.25e-5, now_ms(now)), &prices(), now)
+            .is_empty());
+
+        // A week clears the fees but not a 60% hurdle.
+        let mut strict = detector();
+        strict.config.min_spread_bps = 0.0;
+        assert_eq!(
+            strict
+                .detect([&pool], &books(1e-4, now_ms(now)), &prices(), now)
+                .len(),
+            1
+        );
+        strict.carry.min_apr = 0.6;
+        assert!(strict
+            .detect([&pool], &books(1e-4, now_ms(now)), &prices(), now)
+            .is_empty());
+    }
+}
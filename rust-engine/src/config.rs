@@ -0,0 +1,1344 @@
+//! Engine configuration: `config/runtime_config.json` and
+//! `config/strategies.json` (shared with the TS and Python services), plus
+//! the engine's own environment settings, with env overrides on top.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::basis::BasisConfig;
+use crate::carry::CarryConfig;
+use crate::cycles::{CycleConfig, MAX_HOPS, MIN_HOPS};
+use crate::detector::DetectorConfig;
+use crate::discovery::DiscoveryFilter;
+use crate::ev::EvParams;
+use crate::hyperliquid::L1Market;
+use crate::montecarlo::MonteCarloConfig;
+use crate::opportunity::Opportunity;
+use crate::pricing::PricingConfig;
+use crate::profit_gate::{rejections, Costs, Thresholds};
+use crate::rpc::abi;
+use crate::univ3::FEE_PIPS_DENOMINATOR;
+
+pub const RUNTIME_CONFIG_FILE: &str = "runtime_config.json";
+pub const STRATEGIES_FILE: &str = "strategies.json";
+
+/// Repo `config/` directory, used when `CONFIG_DIR` is unset.
+pub const DEFAULT_CONFIG_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../config");
+
+const DEFAULT_PRJX_SUBGRAPH: &str = "https://api.goldsky.com/api/public/project_cmbbm2iwckb1b01t39xed236t/subgraphs/uniswap-v3-hyperevm-position/prod/gn";
+
+/// `CONFIG_DIR`, or [`DEFAULT_CONFIG_DIR`] when unset.
+pub fn config_dir() -> PathBuf {
+    std::env::var_os("CONFIG_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_CONFIG_DIR))
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    Parse {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+    Env {
+        var: String,
+        value: String,
+        reason: String,
+    },
+    /// Every failed check, as `field: reason`.
+    Invalid(Vec<String>),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io { path, source } => write!(f, "reading {}: {source}", path.display()),
+            ConfigError::Parse { path, source } => {
+                write!(f, "parsing {}: {source}", path.display())
+            }
+            ConfigError::Env { var, value, reason } => write!(f, "{var}={value:?}: {reason}"),
+            ConfigError::Invalid(issues) => write!(f, "invalid config: {}", issues.join("; ")),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConfigError::Io { source, .. } => Some(source),
+            ConfigError::Parse { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// `runtime_config.json`. Fields the engine does not use are still carried
+/// so the file round-trips.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RuntimeConfig {
+    pub min_profit_usd: f64,
+    pub min_spread_bps: f64,
+    pub min_liquidity_usd: f64,
+    pub max_position_eth: f64,
+    pub risk_level: u8,
+    pub slippage_bps: f64,
+    pub gas_multiplier: f64,
+    pub fees_bps: f64,
+    pub max_trade_usd: f64,
+    pub chain_name: String,
+    pub default_gas_limit: u64,
+    pub assets: Vec<String>,
+    pub run_duration_sec: u64,
+    pub target_profit_bps_min: f64,
+    pub target_profit_bps_max: f64,
+}
+
+impl Default for RuntimeConfig {
+    fn default() -> Self {
+        Self {
+            min_profit_usd: 5.0,
+            min_spread_bps: 10.0,
+            min_liquidity_usd: 10_000.0,
+            max_position_eth: 1.0,
+            risk_level: 5,
+            slippage_bps: 30.0,
+            gas_multiplier: 1.0,
+            fees_bps: 5.0,
+            max_trade_usd: 50_000.0,
+            chain_name: "hyperevm-mainnet".to_string(),
+            default_gas_limit: 250_000,
+            assets: vec!["ETH".to_string(), "HYPE".to_string()],
+            run_duration_sec: 3600,
+            target_profit_bps_min: 50.0,
+            target_profit_bps_max: 500.0,
+        }
+    }
+}
+
+/// One entry of `strategies.json`. Unset thresholds fall back to the runtime
+/// config; empty include lists match everything.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Strategy {
+    #[serde(skip)]
+    pub name: String,
+    /// `false` keeps the entry in the file but out of filtering.
+    pub enabled: bool,
+    pub min_profit_usd: Option<f64>,
+    pub min_spread_bps: Option<f64>,
+    pub min_liquidity_usd: Option<f64>,
+    pub slippage_bps: Option<f64>,
+    /// Gas multiplier the gate charges in place of the runtime one.
+    pub gas_multiplier: Option<f64>,
+    /// Fees on notional the gate charges on top of the itemised costs, as
+    /// the research backtests do. Unset charges none, since the engine
+    /// itemises fees rather than assuming the runtime `fees_bps`.
+    pub fees_bps: Option<f64>,
+    /// Symbols or addresses, either side of the pair.
+    pub include_assets: Vec<String>,
+    /// Substrings of the route summary or pair.
+    pub include_routes_contains: Vec<String>,
+    /// Chains the strategy runs on; compared with the runtime `chain_name`.
+    pub include_chain_names: Vec<String>,
+    pub note: Option<String>,
+}
+
+impl Default for Strategy {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            enabled: true,
+            min_profit_usd: None,
+            min_spread_bps: None,
+            min_liquidity_usd: None,
+            slippage_bps: None,
+            gas_multiplier: None,
+            fees_bps: None,
+            include_assets: Vec::new(),
+            include_routes_contains: Vec::new(),
+            include_chain_names: Vec::new(),
+            note: None,
+        }
+    }
+}
+
+impl Strategy {
+    /// Whether `opp` passes this strategy's filters and profit gate.
+    pub fn accepts(&self, opp: &Opportunity, runtime: &RuntimeConfig) -> bool {
+        self.in_scope(opp, runtime) && rejections(opp, &self.thresholds(runtime)).is_empty()
+    }
+
+    /// Limits of the profit gate, runtime values filling the gaps.
+    pub fn thresholds(&self, runtime: &RuntimeConfig) -> Thresholds {
+        Thresholds {
+            min_profit_usd: self.min_profit_usd.unwrap_or(runtime.min_profit_usd),
+            max_slip_bps: self.slippage_bps.unwrap_or(runtime.slippage_bps),
+            min_spread_bps: self.min_spread_bps.unwrap_or(runtime.min_spread_bps),
+            min_liquidity_usd: self.min_liquidity_usd.unwrap_or(runtime.min_liquidity_usd),
+            gas_scale: self
+                .gas_multiplier
+                .map_or(1.0, |m| m / runtime.gas_multiplier),
+            fees_bps: self.fees_bps.unwrap_or(0.0),
+        }
+    }
+
+    /// Whether `opp` matches the chain, asset and route filters.
+    pub fn in_scope(&self, opp: &Opportunity, runtime: &RuntimeConfig) -> bool {
+        if !self.include_chain_names.is_empty()
+            && !self
+                .include_chain_names
+                .iter()
+                .any(|c| c.eq_ignore_ascii_case(&runtime.chain_name))
+        {
+            return false;
+        }
+        if !self.include_assets.is_empty()
+            && !opp.pair.tokens().iter().any(|t| {
+                self.include_assets.iter().any(|a| {
+                    a.eq_ignore_ascii_case(&t.symbol) || a.eq_ignore_ascii_case(&t.address)
+                })
+            })
+        {
+            return false;
+        }
+        if !self.include_routes_contains.is_empty() {
+            let haystack = format!("{} {}", opp.route, opp.pair).to_lowercase();
+            if !self
+                .include_routes_contains
+                .iter()
+                .any(|s| haystack.contains(&s.to_lowercase()))
+            {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Settings only the engine reads, all from the environment.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EngineSettings {
+    pub prjx_subgraph: String,
+    pub hyperswap_subgraph: Option<String>,
+    pub redis_url: Option<String>,
+    pub redis_channel: String,
+    pub pool_refresh: Duration,
+    /// HyperEVM JSON-RPC endpoint.
+    pub rpc_url: Option<String>,
+    /// Further endpoints reads fail over to and sends also go to.
+    pub rpc_fallback_urls: Vec<String>,
+    /// Providers that must have reached a head block before it is used.
+    pub rpc_head_quorum: usize,
+    /// HyperEVM WebSocket endpoint; streams pool logs when set.
+    pub ws_url: Option<String>,
+    /// Period of the RPC re-read of tracked pools at the head block; `None`
+    /// leaves pool state to the subgraphs.
+    pub rpc_pool_refresh: Option<Duration>,
+    /// Blocks of history the log stream keeps to roll back reorgs.
+    pub reorg_window: u64,
+    /// V3 factory whose `PoolCreated` logs supply the venue's pools in
+    /// place of its subgraph.
+    pub prjx_factory: Option<String>,
+    pub hyperswap_factory: Option<String>,
+    /// First block scanned for a factory's pools, e.g. its deployment.
+    pub factory_start_block: u64,
+    /// Where discovered pools and scan progress are persisted.
+    pub pool_registry_path: PathBuf,
+    /// Period of the factory log scan for new pools.
+    pub discovery_poll: Duration,
+    /// Which discovered pools get tracked.
+    pub discovery: DiscoveryFilter,
+    /// How token prices are derived from the pools.
+    pub pricing: PricingConfig,
+    /// Multi-hop cycles searched through every tracked pool.
+    pub cycles: CycleConfig,
+    /// Hyperliquid info WebSocket.
+    pub hl_ws_url: String,
+    /// L1 coins whose order books are mirrored; none disables the feed.
+    pub hl_coins: Vec<String>,
+    /// Pool versus L1 book strategy; its coins are mirrored as well.
+    pub basis: BasisConfig,
+    /// Hyperliquid info API, polled for perp funding when carry is enabled.
+    pub hl_info_url: String,
+    /// Pool spot versus perp short funding strategy; its coins are mirrored
+    /// as well.
+    pub carry: CarryConfig,
+    pub status_top_n: usize,
+    /// Redis channel the engine takes `reload`/`pause`/`resume` commands on.
+    pub control_channel: String,
+}
+
+impl Default for EngineSettings {
+    fn default() -> Self {
+        Self {
+            prjx_subgraph: DEFAULT_PRJX_SUBGRAPH.to_string(),
+            hyperswap_subgraph: None,
+            redis_url: None,
+            redis_channel: "arb:realtime".to_string(),
+            pool_refresh: Duration::from_secs(30),
+            rpc_url: None,
+            rpc_fallback_urls: Vec::new(),
+            rpc_head_quorum: 1,
+            ws_url: None,
+            rpc_pool_refresh: None,
+            reorg_window: crate::rpc::reorg::DEFAULT_REORG_WINDOW,
+            prjx_factory: None,
+            hyperswap_factory: None,
+            factory_start_block: 0,
+            pool_registry_path: PathBuf::from("pool_registry.json"),
+            discovery_poll: Duration::from_secs(10),
+            discovery: DiscoveryFilter::default(),
+            pricing: PricingConfig::default(),
+            hl_ws_url: crate::hyperliquid::MAINNET_WS_URL.to_string(),
+            hl_coins: Vec::new(),
+            cycles: CycleConfig::default(),
+            basis: BasisConfig::default(),
+            hl_info_url: crate::hyperliquid::info::MAINNET_INFO_URL.to_string(),
+            carry: CarryConfig::default(),
+            status_top_n: 20,
+            control_channel: "arb:control".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EngineConfig {
+    pub runtime: RuntimeConfig,
+    /// Sorted by name. An opportunity is kept if any strategy accepts it.
+    pub strategies: Vec<Strategy>,
+    pub engine: EngineSettings,
+    /// Execution costs from the environment; gas limit and multiplier follow
+    /// the runtime config.
+    pub costs: Costs,
+    /// Latency, failure and risk parameters of the EV model, from the
+    /// environment.
+    pub ev: EvParams,
+    /// Tail-risk simulation settings, from the environment.
+    pub monte_carlo: MonteCarloConfig,
+}
+
+impl EngineConfig {
+    /// Loads from [`config_dir`] and the process environment.
+    pub fn from_env() -> Result<Self, ConfigError> {
+        Self::load(&config_dir(), |key| std::env::var(key).ok())
+    }
+
+    /// Reads both files from `dir`, applies overrides from `env` and validates.
+    /// A missing `strategies.json` means no per-strategy filtering.
+    pub fn load(dir: &Path, env: impl Fn(&str) -> Option<String>) -> Result<Self, ConfigError> {
+        let runtime: RuntimeConfig = read_json(&dir.join(RUNTIME_CONFIG_FILE))?;
+        let strategies_path = dir.join(STRATEGIES_FILE);
+        let strategies: BTreeMap<String, Strategy> = if strategies_path.exists() {
+            read_json(&strategies_path)?
+        } else {
+            BTreeMap::new()
+        };
+        let mut config = Self {
+            runtime,
+            strategies: strategies
+                .into_iter()
+                .map(|(name, s)| Strategy { name, ..s })
+                .collect(),
+            engine: EngineSettings::default(),
+            costs: Costs::default(),
+            ev: EvParams::default(),
+            monte_carlo: MonteCarloConfig::default(),
+        };
+        config.apply_env(env)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn apply_env(&mut self, env: impl Fn(&str) -> Option<String>) -> Result<(), ConfigError> {
+        let rt = &mut self.runtime;
+        override_var(&env, "MIN_PROFIT_USD", &mut rt.min_profit_usd)?;
+        override_var(&env, "MIN_SPREAD_BPS", &mut rt.min_spread_bps)?;
+        override_var(&env, "MIN_LIQUIDITY_USD", &mut rt.min_liquidity_usd)?;
+        override_var(&env, "SLIPPAGE_BPS", &mut rt.slippage_bps)?;
+        override_var(&env, "GAS_MULTIPLIER", &mut rt.gas_multiplier)?;
+        override_var(&env, "MAX_TRADE_USD", &mut rt.max_trade_usd)?;
+        override_var(&env, "DEFAULT_GAS_LIMIT", &mut rt.default_gas_limit)?;
+        override_var(&env, "CHAIN_NAME", &mut rt.chain_name)?;
+
+        let engine = &mut self.engine;
+        override_var(&env, "PRJX_SUBGRAPH", &mut engine.prjx_subgraph)?;
+        engine.hyperswap_subgraph = env("HYPERSWAP_SUBGRAPH").filter(|v| !v.is_empty());
+        engine.redis_url = env("REDIS_URL").filter(|v| !v.is_empty());
+        override_var(&env, "REDIS_CHANNEL", &mut engine.redis_channel)?;
+        let mut refresh_secs = engine.pool_refresh.as_secs();
+        override_var(&env, "POOL_REFRESH_SECS", &mut refresh_secs)?;
+        engine.pool_refresh = Duration::from_secs(refresh_secs);
+        engine.rpc_url = env("HYPEREVM_RPC").filter(|v| !v.is_empty());
+        engine.rpc_fallback_urls = env("HYPEREVM_RPC_FALLBACK")
+            .map(|v| {
+                v.split(',')
+                    .map(|u| u.trim().to_string())
+                    .filter(|u| !u.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default();
+        override_var(&env, "RPC_HEAD_QUORUM", &mut engine.rpc_head_quorum)?;
+        engine.ws_url = env("HYPEREVM_WS").filter(|v| !v.is_empty());
+        if env("RPC_REFRESH_MS").is_some_and(|v| !v.trim().is_empty()) {
+            let mut ms = 0u64;
+            override_var(&env, "RPC_REFRESH_MS", &mut ms)?;
+            engine.rpc_pool_refresh = Some(Duration::from_millis(ms));
+        }
+        override_var(&env, "REORG_WINDOW", &mut engine.reorg_window)?;
+        engine.prjx_factory = env("PRJX_FACTORY")
+            .map(|v| v.trim().to_lowercase())
+            .filter(|v| !v.is_empty());
+        engine.hyperswap_factory = env("HYPERSWAP_FACTORY")
+            .map(|v| v.trim().to_lowercase())
+            .filter(|v| !v.is_empty());
+        override_var(&env, "FACTORY_START_BLOCK", &mut engine.factory_start_block)?;
+        override_var(&env, "POOL_REGISTRY_PATH", &mut engine.pool_registry_path)?;
+        let mut poll_secs = engine.discovery_poll.as_secs();
+        override_var(&env, "DISCOVERY_POLL_SECS", &mut poll_secs)?;
+        engine.discovery_poll = Duration::from_secs(poll_secs);
+        let filter = &mut engine.discovery;
+        filter.tokens = list_var(&env, "DISCOVERY_TOKENS")?
+            .into_iter()
+            .map(|t: String| t.to_lowercase())
+            .collect();
+        filter.fee_tiers = list_var(&env, "DISCOVERY_FEE_TIERS")?;
+        override_var(
+            &env,
+            "DISCOVERY_MIN_LIQUIDITY_USD",
+            &mut filter.min_liquidity_usd,
+        )?;
+        let pricing = &mut engine.pricing;
+        override_var(&env, "PRICING_MIN_DEPTH_USD", &mut pricing.min_depth_usd)?;
+        override_var(&env, "PRICING_MAX_HOPS", &mut pricing.max_hops)?;
+        let cycles = &mut engine.cycles;
+        if env("CYCLE_BASE_TOKENS").is_some() {
+            cycles.base_tokens = list_var(&env, "CYCLE_BASE_TOKENS")?
+                .into_iter()
+                .map(|t: String| t.to_lowercase())
+                .collect();
+        }
+        override_var(&env, "CYCLE_MAX_HOPS", &mut cycles.max_hops)?;
+        override_var(&env, "HL_WS_URL", &mut engine.hl_ws_url)?;
+        engine.hl_coins = list_var(&env, "HL_COINS")?;
+        let basis = &mut engine.basis;
+        basis.markets = list_var(&env, "BASIS_MARKETS")?;
+        override_var(&env, "HL_PERP_TAKER_FEE_BPS", &mut basis.perp_taker_fee_bps)?;
+        override_var(&env, "HL_SPOT_TAKER_FEE_BPS", &mut basis.spot_taker_fee_bps)?;
+        let mut book_age_ms = basis.max_book_age.as_millis() as u64;
+        override_var(&env, "BASIS_MAX_BOOK_AGE_MS", &mut book_age_ms)?;
+        basis.max_book_age = Duration::from_millis(book_age_ms);
+        override_var(&env, "HL_INFO_URL", &mut engine.hl_info_url)?;
+        let carry = &mut engine.carry;
+        carry.markets = list_var(&env, "CARRY_MARKETS")?;
+        override_var(&env, "CARRY_HORIZON_HOURS", &mut carry.horizon_hours)?;
+        override_var(&env, "CARRY_MIN_APR", &mut carry.min_apr)?;
+        override_var(&env, "STATUS_TOP_N", &mut engine.status_top_n)?;
+        override_var(&env, "REDIS_CONTROL_CHANNEL", &mut engine.control_channel)?;
+
+        let costs = &mut self.costs;
+        costs.gas_limit = self.runtime.default_gas_limit;
+        costs.gas_multiplier = self.runtime.gas_multiplier;
+        override_var(&env, "GAS_PRICE_GWEI", &mut costs.gas_price_gwei)?;
+        override_var(&env, "ROUTER_FEE_BPS", &mut costs.router_fee_bps)?;
+        override_var(&env, "FLASH_ENABLED", &mut costs.flash_enabled)?;
+        override_var(&env, "FLASH_FEE_BPS", &mut costs.flash_fee_bps)?;
+        override_var(&env, "FLASH_FIXED_USD", &mut costs.flash_fixed_usd)?;
+        override_var(&env, "REFERRAL_BPS", &mut costs.referral_bps)?;
+        override_var(&env, "EXECUTOR_FEE_USD", &mut costs.executor_fee_usd)?;
+        override_var(&env, "MEV_TIP_USD", &mut costs.mev_tip_usd)?;
+
+        let ev = &mut self.ev;
+        override_var(&env, "EXEC_LATENCY_SEC", &mut ev.latency_sec)?;
+        override_var(
+            &env,
+            "EDGE_DECAY_BPS_PER_SEC",
+            &mut ev.edge_decay_bps_per_sec,
+        )?;
+        override_var(&env, "BASE_FILL_PROB", &mut ev.base_fill_prob)?;
+        override_var(&env, "FILL_THETA", &mut ev.fill_theta)?;
+        override_var(&env, "SLIP_ALPHA", &mut ev.slip_alpha)?;
+        override_var(&env, "GAS_USD_STD", &mut ev.gas_usd_std)?;
+        override_var(&env, "ADVERSE_USD_MEAN", &mut ev.adverse_usd_mean)?;
+        override_var(&env, "ADVERSE_USD_STD", &mut ev.adverse_usd_std)?;
+        override_var(&env, "MEV_PENALTY_USD", &mut ev.mev_penalty_usd)?;
+        override_var(&env, "FAIL_BEFORE_FILL_PROB", &mut ev.fail_before_fill_prob)?;
+        override_var(
+            &env,
+            "FAIL_BETWEEN_LEGS_PROB",
+            &mut ev.fail_between_legs_prob,
+        )?;
+        override_var(&env, "REORG_MEV_PROB", &mut ev.reorg_or_mev_prob)?;
+        override_var(&env, "RISK_AVERSION_LAMBDA", &mut ev.risk_aversion)?;
+
+        let mc = &mut self.monte_carlo;
+        override_var(&env, "MC_SAMPLES", &mut mc.samples)?;
+        override_var(&env, "MC_SEED", &mut mc.seed)?;
+        override_var(&env, "MC_THREADS", &mut mc.threads)?;
+        if let Some(value) = env("MC_ALPHAS").filter(|v| !v.trim().is_empty()) {
+            mc.alphas = value
+                .split(',')
+                .map(|a| a.trim().parse())
+                .collect::<Result<_, _>>()
+                .map_err(|e: std::num::ParseFloatError| ConfigError::Env {
+                    var: "MC_ALPHAS".to_string(),
+                    value: value.clone(),
+                    reason: e.to_string(),
+                })?;
+        }
+        Ok(())
+    }
+
+    /// Checks every field and reports all problems at once.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut issues = Vec::new();
+        let rt = &self.runtime;
+        non_negative(&mut issues, "min_profit_usd", rt.min_profit_usd);
+        non_negative(&mut issues, "min_spread_bps", rt.min_spread_bps);
+        non_negative(&mut issues, "min_liquidity_usd", rt.min_liquidity_usd);
+        bps(&mut issues, "slippage_bps", rt.slippage_bps);
+        bps(&mut issues, "fees_bps", rt.fees_bps);
+        positive(&mut issues, "gas_multiplier", rt.gas_multiplier);
+        positive(&mut issues, "max_trade_usd", rt.max_trade_usd);
+        non_negative(&mut issues, "max_position_eth", rt.max_position_eth);
+        if rt.risk_level > 10 {
+            issues.push(format!(
+                "risk_level: must be 0..=10 (got {})",
+                rt.risk_level
+            ));
+        }
+        if rt.default_gas_limit == 0 {
+            issues.push("default_gas_limit: must be > 0".to_string());
+        }
+        if rt.chain_name.trim().is_empty() {
+            issues.push("chain_name: must not be empty".to_string());
+        }
+        if rt.target_profit_bps_min > rt.target_profit_bps_max {
+            issues.push(format!(
+                "target_profit_bps_min: must not exceed target_profit_bps_max ({} > {})",
+                rt.target_profit_bps_min, rt.target_profit_bps_max
+            ));
+        }
+
+        for s in &self.strategies {
+            let field = |f: &str| format!("strategies.{}.{f}", s.name);
+            for (name, value) in [
+                ("min_profit_usd", s.min_profit_usd),
+                ("min_spread_bps", s.min_spread_bps),
+                ("min_liquidity_usd", s.min_liquidity_usd),
+            ] {
+                if let Some(v) = value {
+                    non_negative(&mut issues, &field(name), v);
+                }
+            }
+            if let Some(v) = s.slippage_bps {
+                bps(&mut issues, &field("slippage_bps"), v);
+            }
+            if let Some(v) = s.fees_bps {
+                bps(&mut issues, &field("fees_bps"), v);
+            }
+            if let Some(v) = s.gas_multiplier {
+                positive(&mut issues, &field("gas_multiplier"), v);
+            }
+            for (name, list) in [
+                ("include_assets", &s.include_assets),
+                ("include_routes_contains", &s.include_routes_contains),
+                ("include_chain_names", &s.include_chain_names),
+            ] {
+                if list.iter().any(|v| v.trim().is_empty()) {
+                    issues.push(format!("{}: entries must not be empty", field(name)));
+                }
+            }
+        }
+
+        let engine = &self.engine;
+        if engine.redis_channel.is_empty() {
+            issues.push("REDIS_CHANNEL: must not be empty".to_string());
+        }
+        if engine.control_channel.is_empty() {
+            issues.push("REDIS_CONTROL_CHANNEL: must not be empty".to_string());
+        }
+        if engine.pool_refresh.is_zero() {
+            issues.push("POOL_REFRESH_SECS: must be > 0".to_string());
+        }
+        if engine.rpc_pool_refresh.is_some_and(|d| d.is_zero()) {
+            issues.push("RPC_REFRESH_MS: must be > 0".to_string());
+        }
+        if engine.rpc_pool_refresh.is_some() && engine.rpc_url.is_none() {
+            issues.push("RPC_REFRESH_MS: requires HYPEREVM_RPC".to_string());
+        }
+        if engine.ws_url.is_some() && engine.rpc_url.is_none() {
+            issues.push("HYPEREVM_WS: requires HYPEREVM_RPC".to_string());
+        }
+        if !engine.rpc_fallback_urls.is_empty() && engine.rpc_url.is_none() {
+            issues.push("HYPEREVM_RPC_FALLBACK: requires HYPEREVM_RPC".to_string());
+        }
+        let providers = usize::from(engine.rpc_url.is_some()) + engine.rpc_fallback_urls.len();
+        if engine.rpc_head_quorum == 0 || engine.rpc_head_quorum > providers.max(1) {
+            issues.push(format!(
+                "RPC_HEAD_QUORUM: must be 1..={} (got {})",
+                providers.max(1),
+                engine.rpc_head_quorum
+            ));
+        }
+        if engine.reorg_window == 0 {
+            issues.push("REORG_WINDOW: must be > 0".to_string());
+        }
+        for (var, factory) in [
+            ("PRJX_FACTORY", &engine.prjx_factory),
+            ("HYPERSWAP_FACTORY", &engine.hyperswap_factory),
+        ] {
+            let Some(factory) = factory else { continue };
+            if engine.rpc_url.is_none() {
+                issues.push(format!("{var}: requires HYPEREVM_RPC"));
+            }
+            if abi::address_word(factory).is_err() {
+                issues.push(format!(
+                    "{var}: must be a 20-byte hex address (got {factory:?})"
+                ));
+            }
+        }
+        if engine.discovery_poll.is_zero() {
+            issues.push("DISCOVERY_POLL_SECS: must be > 0".to_string());
+        }
+        let filter = &engine.discovery;
+        if let Some(t) = filter.tokens.iter().find(|t| abi::address_word(t).is_err()) {
+            issues.push(format!(
+                "DISCOVERY_TOKENS: each must be a 20-byte hex address (got {t:?})"
+            ));
+        }
+        if let Some(fee) = filter
+            .fee_tiers
+            .iter()
+            .find(|&&f| f == 0 || f >= FEE_PIPS_DENOMINATOR)
+        {
+            issues.push(format!(
+                "DISCOVERY_FEE_TIERS: each must be in pips, 1 to 999999 (got {fee})"
+            ));
+        }
+        non_negative(
+            &mut issues,
+            "DISCOVERY_MIN_LIQUIDITY_USD",
+            filter.min_liquidity_usd,
+        );
+        non_negative(
+            &mut issues,
+            "PRICING_MIN_DEPTH_USD",
+            engine.pricing.min_depth_usd,
+        );
+        if engine.pricing.max_hops == 0 {
+            issues.push("PRICING_MAX_HOPS: must be > 0".to_string());
+        }
+        let cycles = &engine.cycles;
+        if let Some(t) = cycles
+            .base_tokens
+            .iter()
+            .find(|t| abi::address_word(t).is_err())
+        {
+            issues.push(format!(
+                "CYCLE_BASE_TOKENS: each must be a 20-byte hex address (got {t:?})"
+            ));
+        }
+        if !(MIN_HOPS..=MAX_HOPS).contains(&cycles.max_hops) {
+            issues.push(format!(
+                "CYCLE_MAX_HOPS: must be {MIN_HOPS}..={MAX_HOPS} (got {})",
+                cycles.max_hops
+            ));
+        }
+        let carry = &engine.carry;
+        let hl_feed = !(engine.hl_coins.is_empty()
+            && engine.basis.markets.is_empty()
+            && carry.markets.is_empty());
+        if hl_feed
+            && !["ws://", "wss://"]
+                .iter()
+                .any(|scheme| engine.hl_ws_url.starts_with(scheme))
+        {
+            issues.push(format!(
+                "HL_WS_URL: must be a ws:// or wss:// URL (got {:?})",
+                engine.hl_ws_url
+            ));
+        }
+        let basis = &engine.basis;
+        bps(
+            &mut issues,
+            "HL_PERP_TAKER_FEE_BPS",
+            basis.perp_taker_fee_bps,
+        );
+        bps(
+            &mut issues,
+            "HL_SPOT_TAKER_FEE_BPS",
+            basis.spot_taker_fee_bps,
+        );
+        if !(basis.markets.is_empty() && carry.markets.is_empty()) && basis.max_book_age.is_zero() {
+            issues.push("BASIS_MAX_BOOK_AGE_MS: must be > 0".to_string());
+        }
+        if !carry.markets.is_empty() {
+            for market in &carry.markets {
+                if market.market() != L1Market::Perp {
+                    issues.push(format!("CARRY_MARKETS: {} is not a perp", market.coin));
+                }
+            }
+            if !["http://", "https://"]
+                .iter()
+                .any(|scheme| engine.hl_info_url.starts_with(scheme))
+            {
+                issues.push(format!(
+                    "HL_INFO_URL: must be an http:// or https:// URL (got {:?})",
+                    engine.hl_info_url
+                ));
+            }
+        }
+        positive(&mut issues, "CARRY_HORIZON_HOURS", carry.horizon_hours);
+        if !carry.min_apr.is_finite() {
+            issues.push(format!(
+                "CARRY_MIN_APR: must be a finite number (got {})",
+                carry.min_apr
+            ));
+        }
+
+        let costs = &self.costs;
+        positive(&mut issues, "GAS_PRICE_GWEI", costs.gas_price_gwei);
+        bps(&mut issues, "ROUTER_FEE_BPS", costs.router_fee_bps);
+        bps(&mut issues, "FLASH_FEE_BPS", costs.flash_fee_bps);
+        bps(&mut issues, "REFERRAL_BPS", costs.referral_bps);
+        non_negative(&mut issues, "FLASH_FIXED_USD", costs.flash_fixed_usd);
+        non_negative(&mut issues, "EXECUTOR_FEE_USD", costs.executor_fee_usd);
+        non_negative(&mut issues, "MEV_TIP_USD", costs.mev_tip_usd);
+
+        let ev = &self.ev;
+        for (name, v) in [
+            ("EXEC_LATENCY_SEC", ev.latency_sec),
+            ("EDGE_DECAY_BPS_PER_SEC", ev.edge_decay_bps_per_sec),
+            ("FILL_THETA", ev.fill_theta),
+            ("GAS_USD_STD", ev.gas_usd_std),
+            ("ADVERSE_USD_MEAN", ev.adverse_usd_mean),
+            ("ADVERSE_USD_STD", ev.adverse_usd_std),
+            ("MEV_PENALTY_USD", ev.mev_penalty_usd),
+            ("RISK_AVERSION_LAMBDA", ev.risk_aversion),
+        ] {
+            non_negative(&mut issues, name, v);
+        }
+        if !(ev.slip_alpha.is_finite() && ev.slip_alpha >= 1.0) {
+            issues.push(format!("SLIP_ALPHA: must be >= 1 (got {})", ev.slip_alpha));
+        }
+        for (name, v) in [
+            ("BASE_FILL_PROB", ev.base_fill_prob),
+            ("FAIL_BEFORE_FILL_PROB", ev.fail_before_fill_prob),
+            ("FAIL_BETWEEN_LEGS_PROB", ev.fail_between_legs_prob),
+            ("REORG_MEV_PROB", ev.reorg_or_mev_prob),
+        ] {
+            probability(&mut issues, name, v);
+        }
+        if ev.base_fill_prob
+            + ev.fail_before_fill_prob
+            + ev.fail_between_legs_prob
+            + ev.reorg_or_mev_prob
+            > 1.0 + 1e-9
+        {
+            issues.push(
+                "BASE_FILL_PROB: fill and failure probabilities must not sum above 1".to_string(),
+            );
+        }
+
+        let mc = &self.monte_carlo;
+        if mc.samples == 0 {
+            issues.push("MC_SAMPLES: must be > 0".to_string());
+        }
+        for &alpha in &mc.alphas {
+            if !(alpha > 0.0 && alpha < 1.0) {
+                issues.push(format!("MC_ALPHAS: each must be in (0, 1) (got {alpha})"));
+            }
+        }
+
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Invalid(issues))
+        }
+    }
+
+    /// Detector thresholds and sizing caps from the runtime config.
+    pub fn detector_config(&self) -> DetectorConfig {
+        let rt = &self.runtime;
+        DetectorConfig {
+            min_spread_bps: rt.min_spread_bps,
+            min_liquidity_usd: rt.min_liquidity_usd,
+            max_notional_usd: rt.max_trade_usd,
+            max_slippage_bps: rt.slippage_bps,
+            costs: self.costs.clone(),
+            ev: self.ev.clone(),
+            monte_carlo: self.monte_carlo.clone(),
+            pricing: self.engine.pricing.clone(),
+            ..DetectorConfig::default()
+        }
+    }
+
+    /// Runs the profit gate of every enabled strategy whose filters cover
+    /// `opp`, or of the runtime thresholds alone when no strategies are
+    /// configured. Returns `false` when nothing covers it. Otherwise `opp` is
+    /// marked viable if any gate passes, or gets the rejections of the
+    /// strategy it came closest to passing.
+    pub fn judge(&self, opp: &mut Opportunity) -> bool {
+        let fallback = [Strategy::default()];
+        let strategies: &[Strategy] = if self.strategies.is_empty() {
+            &fallback
+        } else {
+            &self.strategies
+        };
+        let mut closest: Option<(&Strategy, Vec<String>)> = None;
+        for s in strategies
+            .iter()
+            .filter(|s| s.enabled && s.in_scope(opp, &self.runtime))
+        {
+            let failed = rejections(opp, &s.thresholds(&self.runtime));
+            if failed.is_empty() {
+                opp.viable = true;
+                opp.rejections.clear();
+                return true;
+            }
+            if closest.as_ref().is_none_or(|(_, c)| failed.len() < c.len()) {
+                closest = Some((s, failed));
+            }
+        }
+        let Some((strategy, failed)) = closest else {
+            return false;
+        };
+        opp.viable = false;
+        opp.rejections = if strategy.name.is_empty() {
+            failed
+        } else {
+            failed
+                .into_iter()
+                .map(|r| format!("{}: {r}", strategy.name))
+                .collect()
+        };
+        true
+    }
+}
+
+fn read_json<T: serde::de::DeserializeOwned>(path: &Path) -> Result<T, ConfigError> {
+    let text = std::fs::read_to_string(path).map_err(|source| ConfigError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    serde_json::from_str(&text).map_err(|source| ConfigError::Parse {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+/// Comma-separated `var`, empty entries skipped; empty when unset.
+fn list_var<T>(env: &impl Fn(&str) -> Option<String>, var: &str) -> Result<Vec<T>, ConfigError>
+where
+    T: std::str::FromStr,
+    T::Err: fmt::Display,
+{
+    let Some(value) = env(var) else {
+        return Ok(Vec::new());
+    };
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(|v| {
+            v.parse().map_err(|e: T::Err| ConfigError::Env {
+                var: var.to_string(),
+                value: value.clone(),
+                reason: e.to_string(),
+            })
+        })
+        .collect()
+}
+
+fn override_var<T>(
+    env: &impl Fn(&str) -> Option<String>,
+    var: &str,
+    target: &mut T,
+) -> Result<(), ConfigError>
+where
+    T: std::str::FromStr,
+    T::Err: fmt::Display,
+{
+    let Some(value) = env(var).filter(|v| !v.trim().is_empty()) else {
+        return Ok(());
+    };
+    *target = value.trim().parse().map_err(|e: T::Err| ConfigError::Env {
+        var: var.to_string(),
+        value,
+        reason: e.to_string(),
+    })?;
+    Ok(())
+}
+
+fn non_negative(issues: &mut Vec<String>, field: &str, v: f64) {
+    if !(v.is_finite() && v >= 0.0) {
+        issues.push(format!("{field}: must be a finite number >= 0 (got {v})"));
+    }
+}
+
+fn positive(issues: &mut Vec<String>, field: &str, v: f64) {
+    if !(v.is_finite() && v > 0.0) {
+        issues.push(format!("{field}: must be a finite number > 0 (got {v})"));
+    }
+}
+
+fn probability(issues: &mut Vec<String>, field: &str, v: f64) {
+    if !(v.is_finite() && (0.0..=1.0).contains(&v)) {
+        issues.push(format!("{field}: must be a probability, 0 to 1 (got {v})"));
+    }
+}
+
+fn bps(issues: &mut Vec<String>, field: &str, v: f64) {
+    if !(v.is_finite() && (0.0..10_000.0).contains(&v)) {
+        issues.push(format!("{field}: must be in bps, 0 to 10000 (got {v})"));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::opportunity::{OpportunityKind, Route, RouteLeg, TokenPair};
+    use crate::profit_gate::CostBreakdown;
+    use crate::state::Dex;
+    use crate::testing::{token, TempDir};
+    use crate::univ3::U256;
+    use std::collections::HashMap;
+
+    fn repo_config_dir() -> PathBuf {
+        PathBuf::from(DEFAULT_CONFIG_DIR)
+    }
+
+    fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        move |k| vars.get(k).cloned()
+    }
+
+    fn write_dir(runtime: &str, strategies: Option<&str>) -> TempDir {
+        let dir = TempDir::new("config");
+        std::fs::write(dir.path().join(RUNTIME_CONFIG_FILE), runtime).unwrap();
+        if let Some(s) = strategies {
+            std::fs::write(dir.path().join(STRATEGIES_FILE), s).unwrap();
+        }
+        dir
+    }
+
+    fn opp(pair: &str, spread_bps: f64, profit: f64, liquidity: f64) -> Opportunity {
+        let (base, quote) = pair.split_once('/').unwrap();
+        Opportunity {
+            kind: OpportunityKind::Arbitrage,
+            pair: TokenPair {
+                base: token(base),
+                quote: token(quote),
+            },
+            spread_bps,
+            est_gas_usd: 0.5,
+            est_profit_usd: profit,
+            liquidity_usd: liquidity,
+            confidence: 1.0,
+            size_usd: 1000.0,
+            amount_in: U256::ONE,
+            expected_out: U256::ONE,
+            marginal_price: 1.0,
+            slip_bps: 5.0,
+            costs: CostBreakdown {
+                gross_usd: profit,
+                gas_usd: 0.5,
+                total_costs_usd: 0.5,
+                net_usd: profit - 0.5,
+                ..CostBreakdown::default()
+            },
+            viable: false,
+            rejections: Vec::new(),
+            ev_per_sec: 0.0,
+            p_success: 0.9,
+            ev_size_usd: 1000.0,
+            tail_risk: Vec::new(),
+            route: Route {
+                legs: vec![RouteLeg {
+                    dex: Dex::Prjx,
+                    pool: "0x1".into(),
+                    token_in: "0x2".into(),
+                    token_out: "0x3".into(),
+                    fee: 500,
+                    fee_usd: 0.0,
+                    split: Vec::new(),
+                }],
+                l1_order: None,
+            },
+            carry: None,
+        }
+    }
+
+    #[test]
+    fn loads_repo_config() {
+        let config = EngineConfig::load(&repo_config_dir(), env(&[])).unwrap();
+        assert_eq!(config.runtime.chain_name, "hyperevm-mainnet");
+        assert_eq!(config.runtime.default_gas_limit, 250_000);
+        let khype = config
+            .strategies
+            .iter()
+            .find(|s| s.name == "HYPE_to_KHYPE")
+            .unwrap();
+        assert_eq!(khype.min_profit_usd, Some(8.0));
+        assert_eq!(khype.include_routes_contains, vec!["KHYPE"]);
+        assert_eq!(config.engine, EngineSettings::default());
+    }
+
+    #[test]
+    fn env_overrides_files() {
+        let config = EngineConfig::load(
+            &repo_config_dir(),
+            env(&[
+                ("MIN_PROFIT_USD", "7.5"),
+                ("SLIPPAGE_BPS", " 12 "),
+                ("REDIS_URL", "redis://localhost"),
+                ("POOL_REFRESH_SECS", "5"),
+                ("HYPERSWAP_SUBGRAPH", ""),
+                ("DEFAULT_GAS_LIMIT", "400000"),
+                ("FLASH_ENABLED", "false"),
+                ("MEV_TIP_USD", "0.3"),
+                ("HYPEREVM_RPC", "http://127.0.0.1:8545"),
+                ("RPC_REFRESH_MS", "500"),
+                ("REORG_WINDOW", "16"),
+                (
+                    "HYPEREVM_RPC_FALLBACK",
+                    "http://127.0.0.1:8546, http://127.0.0.1:8547",
+                ),
+                ("RPC_HEAD_QUORUM", "2"),
+                ("PRJX_FACTORY", "0xFF0000000000000000000000000000000000000F"),
+                ("POOL_REGISTRY_PATH", "/var/lib/arb/pools.json"),
+                (
+                    "DISCOVERY_TOKENS",
+                    "0x5555555555555555555555555555555555555555,,0xB88339CB7199b77E23DB6E890353E22632Ba630f",
+                ),
+                ("DISCOVERY_FEE_TIERS", "500, 3000"),
+                ("DISCOVERY_MIN_LIQUIDITY_USD", "25000"),
+                ("PRICING_MIN_DEPTH_USD", "50000"),
+                ("PRICING_MAX_HOPS", "2"),
+                (
+                    "CYCLE_BASE_TOKENS",
+                    "0x5555555555555555555555555555555555555555, 0xB88339CB7199b77E23DB6E890353E22632Ba630f",
+                ),
+                ("CYCLE_MAX_HOPS", "4"),
+                ("HL_COINS", "HYPE, BTC"),
+                (
+                    "BASIS_MARKETS",
+                    "HYPE=0x5555555555555555555555555555555555555555",
+                ),
+                ("HL_SPOT_TAKER_FEE_BPS", "3.5"),
+                ("BASIS_MAX_BOOK_AGE_MS", "2500"),
+                (
+                    "CARRY_MARKETS",
+                    "HYPE=0x5555555555555555555555555555555555555555",
+                ),
+                ("CARRY_HORIZON_HOURS", "72"),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(config.runtime.min_profit_usd, 7.5);
+        assert_eq!(config.runtime.slippage_bps, 12.0);
+        assert_eq!(
+            config.engine.redis_url.as_deref(),
+            Some("redis://localhost")
+        );
+        assert_eq!(config.engine.pool_refresh, Duration::from_secs(5));
+        assert_eq!(config.engine.hyperswap_subgraph, None);
+        assert_eq!(
+            config.engine.rpc_pool_refresh,
+            Some(Duration::from_millis(500))
+        );
+        assert_eq!(config.engine.reorg_window, 16);
+        assert_eq!(
+            config.engine.rpc_fallback_urls,
+            vec!["http://127.0.0.1:8546", "http://127.0.0.1:8547"]
+        );
+        assert_eq!(config.engine.rpc_head_quorum, 2);
+        assert_eq!(
+            config.engine.prjx_factory.as_deref(),
+            Some("0xff0000000000000000000000000000000000000f")
+        );
+        assert_eq!(config.engine.hyperswap_factory, None);
+        assert_eq!(
+            config.engine.pool_registry_path,
+            PathBuf::from("/var/lib/arb/pools.json")
+        );
+        assert_eq!(
+            config.engine.discovery,
+            DiscoveryFilter {
+                tokens: vec![
+                    "0x5555555555555555555555555555555555555555".into(),
+                    "0xb88339cb7199b77e23db6e890353e22632ba630f".into(),
+                ],
+                fee_tiers: vec![500, 3000],
+                min_liquidity_usd: 25_000.0,
+            }
+        );
+        assert_eq!(
+            config.engine.cycles,
+            CycleConfig {
+                base_tokens: vec![
+                    "0x5555555555555555555555555555555555555555".into(),
+                    "0xb88339cb7199b77e23db6e890353e22632ba630f".into(),
+                ],
+                max_hops: 4,
+            }
+        );
+        assert_eq!(config.engine.hl_coins, vec!["HYPE", "BTC"]);
+        assert_eq!(config.engine.hl_ws_url, "wss://api.hyperliquid.xyz/ws");
+        let basis = &config.engine.basis;
+        assert_eq!(basis.coins().collect::<Vec<_>>(), vec!["HYPE"]);
+        assert_eq!(basis.markets[0].token, crate::detector::WHYPE);
+        assert_eq!(
+            (basis.perp_taker_fee_bps, basis.spot_taker_fee_bps),
+            (4.5, 3.5)
+        );
+        assert_eq!(basis.max_book_age, Duration::from_millis(2500));
+        let carry = &config.engine.carry;
+        assert_eq!(carry.coins().collect::<Vec<_>>(), vec!["HYPE"]);
+        assert_eq!((carry.horizon_hours, carry.min_apr), (72.0, 0.1));
+        assert_eq!(
+            config.engine.hl_info_url,
+            "https://api.hyperliquid.xyz/info"
+        );
+        assert_eq!(
+            config.detector_config().pricing,
+            PricingConfig {
+                min_depth_usd: 50_000.0,
+                max_hops: 2,
+            }
+        );
+        assert_eq!(config.detector_config().max_slippage_bps, 12.0);
+        let costs = config.detector_config().costs;
+        assert_eq!(costs.gas_limit, 400_000);
+        assert!(!costs.flash_enabled);
+        assert_eq!(costs.mev_tip_usd, 0.3);
+    }
+
+    #[test]
+    fn reports_descriptive_errors() {
+        let err =
+            EngineConfig::load(&repo_config_dir(), env(&[("MIN_SPREAD_BPS", "ten")])).unwrap_err();
+        assert!(
+            err.to_string().starts_with("MIN_SPREAD_BPS=\"ten\""),
+            "{err}"
+        );
+
+        let dir = write_dir(
+            r#"{"slippage_bps": -1, "gas_multiplier": 0, "chain_name": ""}"#,
+            Some(r#"{"s": {"min_spread_bps": -2, "include_assets": [""]}}"#),
+        );
+        let ConfigError::Invalid(issues) = EngineConfig::load(dir.path(), env(&[])).unwrap_err()
+        else {
+            panic!("expected validation error");
+        };
+        assert_eq!(
+            issues,
+            vec![
+                "slippage_bps: must be in bps, 0 to 10000 (got -1)",
+                "gas_multiplier: must be a finite number > 0 (got 0)",
+                "chain_name: must not be empty",
+                "strategies.s.min_spread_bps: must be a finite number >= 0 (got -2)",
+                "strategies.s.include_assets: entries must not be empty",
+            ]
+        );
+
+        let err = EngineConfig::load(
+            &repo_config_dir(),
+            env(&[
+                ("HYPEREVM_RPC", "http://127.0.0.1:8545"),
+                ("RPC_HEAD_QUORUM", "2"),
+            ]),
+        )
+        .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("RPC_HEAD_QUORUM: must be 1..=1 (got 2)"),
+            "{err}"
+        );
+
+        let ConfigError::Invalid(issues) = EngineConfig::load(
+            &repo_config_dir(),
+            env(&[
+                ("HYPERSWAP_FACTORY", "0x1234"),
+                ("DISCOVERY_TOKENS", "WHYPE"),
+                ("DISCOVERY_FEE_TIERS", "0"),
+                ("PRICING_MIN_DEPTH_USD", "-1"),
+                ("PRICING_MAX_HOPS", "0"),
+                ("CYCLE_MAX_HOPS", "2"),
+                ("HL_COINS", "HYPE"),
+                ("HL_WS_URL", "https://api.hyperliquid.xyz/ws"),
+                ("HL_PERP_TAKER_FEE_BPS", "-1"),
+                (
+                    "CARRY_MARKETS",
+                    "@107=0x5555555555555555555555555555555555555555",
+                ),
+                ("CARRY_HORIZON_HOURS", "0"),
+            ]),
+        )
+        .unwrap_err() else {
+            panic!("expected validation error");
+        };
+        assert_eq!(
+            issues,
+            vec![
+                "HYPERSWAP_FACTORY: requires HYPEREVM_RPC",
+                "HYPERSWAP_FACTORY: must be a 20-byte hex address (got \"0x1234\")",
+                "DISCOVERY_TOKENS: each must be a 20-byte hex address (got \"whype\")",
+                "DISCOVERY_FEE_TIERS: each must be in pips, 1 to 999999 (got 0)",
+                "PRICING_MIN_DEPTH_USD: must be a finite number >= 0 (got -1)",
+                "PRICING_MAX_HOPS: must be > 0",
+                "CYCLE_MAX_HOPS: must be 3..=5 (got 2)",
+                "HL_WS_URL: must be a ws:// or wss:// URL (got \"https://api.hyperliquid.xyz/ws\")",
+                "HL_PERP_TAKER_FEE_BPS: must be in bps, 0 to 10000 (got -1)",
+                "CARRY_MARKETS: @107 is not a perp",
+                "CARRY_HORIZON_HOURS: must be a finite number > 0 (got 0)",
+            ]
+        );
+        let err =
+            EngineConfig::load(&repo_config_dir(), env(&[("BASIS_MARKETS", "HYPE")])).unwrap_err();
+        assert!(err.to_string().contains("expected COIN=0xTOKEN"), "{err}");
+        let err = EngineConfig::load(&repo_config_dir(), env(&[("DISCOVERY_FEE_TIERS", "5%")]))
+            .unwrap_err();
+        assert!(
+            err.to_string().starts_with("DISCOVERY_FEE_TIERS=\"5%\""),
+            "{err}"
+        );
+
+        let dir = write_dir("{not json", None);
+        let err = EngineConfig::load(dir.path(), env(&[])).unwrap_err();
+        assert!(matches!(err, ConfigError::Parse { .. }), "{err}");
+        assert!(err.to_string().contains(RUNTIME_CONFIG_FILE));
+    }
+
+    #[test]
+    fn strategy_filters() {
+        let dir = write_dir(
+            r#"{"min_profit_usd": 1, "min_spread_bps": 5, "min_liquidity_usd": 100}"#,
+            Some(
+                r#"{
+                    "khype": {"min_profit_usd": 8, "include_assets": ["KHYPE"],
+                              "include_routes_contains": ["hyperswap"]},
+                    "other_chain": {"include_chain_names": ["eth-mainnet"]}
+                }"#,
+            ),
+        );
+        let config = EngineConfig::load(dir.path(), env(&[])).unwrap();
+        let khype = &config.strategies[0];
+
+        assert!(!khype.accepts(&opp("KHYPE/WHYPE", 20.0, 5.0, 1e6), &config.runtime));
+        assert!(!khype.accepts(&opp("WHYPE/USDC", 20.0, 50.0, 1e6), &config.runtime));
+        // Route summary is "PRJX" only.
+        assert!(!khype.accepts(&opp("KHYPE/WHYPE", 20.0, 50.0, 1e6), &config.runtime));
+        let mut o = opp("KHYPE/WHYPE", 20.0, 50.0, 1e6);
+        o.route.legs[0].dex = Dex::HyperSwap;
+        assert!(khype.accepts(&o, &config.runtime));
+        let by_address = Strategy {
+            include_assets: vec![token("KHYPE").address.to_uppercase()],
+            ..khype.clone()
+        };
+        assert!(by_address.accepts(&o, &config.runtime));
+        assert!(config.judge(&mut o) && o.viable);
+
+        // The other strategy never applies on hyperevm-mainnet.
+        assert!(!config.judge(&mut opp("WHYPE/USDC", 20.0, 50.0, 1e6)));
+
+        let mut disabled = config.clone();
+        disabled.strategies[0].enabled = false;
+        assert!(!disabled.judge(&mut o));
+
+        // Without strategies the runtime thresholds decide.
+        let plain = EngineConfig {
+            strategies: Vec::new(),
+            ..config
+        };
+        assert!(plain.judge(&mut opp("WHYPE/USDC", 20.0, 50.0, 1e6)));
+        let mut narrow = opp("WHYPE/USDC", 2.0, 50.0, 1e6);
+        assert!(plain.judge(&mut narrow) && !narrow.viable);
+        assert_eq!(narrow.rejections, ["spread 2.0 bps < min 5.0 bps"]);
+    }
+
+    #[test]
+    fn judge_explains_closest_strategy() {
+        let dir = write_dir(
+            r#"{"min_profit_usd": 1, "min_spread_bps": 5, "min_liquidity_usd": 100,
+                "slippage_bps": 30}"#,
+            Some(
+                r#"{
+                    "big": {"min_profit_usd": 100, "min_liquidity_usd": 1e9},
+                    "tight": {"min_profit_usd": 20, "slippage_bps": 2}
+                }"#,
+            ),
+        );
+        let config = EngineConfig::load(dir.path(), env(&[])).unwrap();
+
+        // Net $9.50 fails both; "tight" misses on two checks, "big" on two,
+        // so the first in name order wins the tie.
+        let mut o = opp("WHYPE/USDC", 20.0, 10.0, 1e6);
+        assert!(config.judge(&mut o));
+        assert!(!o.viable);
+        assert_eq!(
+            o.rejections,
+            [
+                "big: net $9.50 < min $100.00 (gross $10.00, costs $0.50)",
+                "big: liquidity $1000000 < min $1000000000",
+            ]
+        );
+
+        // Fixing slippage leaves "tight" one check short.
+        o.slip_bps = 1.0;
+        assert!(config.judge(&mut o));
+        assert_eq!(
+            o.rejections,
+            ["tight: net $9.50 < min $20.00 (gross $10.00, costs $0.50)"]
+        );
+
+        o.costs.net_usd = 25.0;
+        assert!(config.judge(&mut o) && o.viable && o.rejections.is_empty());
+    }
+
+    #[test]
+    fn strategy_gas_and_fees_move_the_gate() {
+        let dir = write_dir(
+            r#"{"min_profit_usd": 40, "min_spread_bps": 5, "min_liquidity_usd": 100,
+                "gas_multiplier": 2}"#,
+            Some(
+                r#"{
+                    "gassy": {"gas_multiplier": 42},
+                    "costly": {"fees_bps": 100},
+                    "plain": {"enabled": false}
+                }"#,
+            ),
+        );
+        let mut config = EngineConfig::load(dir.path(), env(&[])).unwrap();
+        // Net $49.50 on $1000 after $0.50 of gas costed at 2x.
+        let o = opp("WHYPE/USDC", 20.0, 50.0, 1e6);
+        assert!(config.strategies[2].accepts(&o, &config.runtime));
+
+        // 42x gas is 21 times the $0.50, and 100 bps of $1000 is $10.
+        let gassy = &config.strategies[1];
+        assert_eq!(
+            rejections(&o, &gassy.thresholds(&config.runtime)),
+            ["net $39.50 < min $40.00 (gross $50.00, costs $10.50)"]
+        );
+        let costly = &config.strategies[0];
+        assert!(!costly.accepts(&o, &config.runtime));
+
+        config.strategies.retain(|s| s.name != "plain");
+        let mut judged = o.clone();
+        assert!(config.judge(&mut judged) && !judged.viable);
+        config.strategies[0].fees_bps = Some(50.0);
+        assert!(config.judge(&mut judged) && judged.viable);
+    }
+}
@@ -0,0 +1,540 @@
+//! Multi-hop detector: cycles of swaps through the tracked pools that start
+//! and end in a base asset, e.g. USDC->WHYPE->KHYPE->USDC.
+//!
+//! The pools form a token graph with an edge each way per pool. Every simple
+//! cycle of three to [`CycleConfig::max_hops`] swaps through a base token is
+//! screened on mid prices net of pool fees, and those clearing
+//! `min_spread_bps` are sized with exact quotes along the whole route.
+//! With many pools, [`CycleDetector::detect_screened`] sizes only the cycles
+//! a [`CycleScreen`] flags instead. Two-pool routes are the cross-DEX
+//! detector's.
+
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::amm::Pool;
+use crate::basis::age;
+use crate::detector::{pool_liquidity_usd, price_error, score, DetectorConfig, WHYPE};
+use crate::opportunity::{Opportunity, OpportunityKind, Route, RouteLeg, TokenPair};
+use crate::pricing::UsdPrices;
+use crate::profit_gate::{cost_breakdown, Costs, Quote};
+use crate::screen::CycleScreen;
+use crate::sizing::{optimal_route, Leg, SizingLimits};
+use crate::state::{Token, TrackedPool};
+
+/// Fewest swaps in a cycle.
+pub const MIN_HOPS: usize = 3;
+/// Most swaps a cycle may be configured to have; the number of cycles grows
+/// exponentially with it.
+pub const MAX_HOPS: usize = 5;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CycleConfig {
+    /// Lowercase addresses of the tokens cycles start and end in; none
+    /// disables the search.
+    pub base_tokens: Vec<String>,
+    /// Most swaps in a cycle.
+    pub max_hops: usize,
+}
+
+impl Default for CycleConfig {
+    fn default() -> Self {
+        Self {
+            base_tokens: vec![WHYPE.to_string()],
+            max_hops: 3,
+        }
+    }
+}
+
+/// A pool swapped in one direction.
+#[derive(Debug, Clone, Copy)]
+pub struct Hop<'a> {
+    pub pool: &'a TrackedPool,
+    pub zero_for_one: bool,
+}
+
+impl<'a> Hop<'a> {
+    pub fn token_in(&self) -> &'a Token {
+        if self.zero_for_one {
+            &self.pool.token0
+        } else {
+            &self.pool.token1
+        }
+    }
+
+    pub fn token_out(&self) -> &'a Token {
+        if self.zero_for_one {
+            &self.pool.token1
+        } else {
+            &self.pool.token0
+        }
+    }
+
+    /// Raw output per raw input at the pool's mid, before fees.
+    pub fn mid_rate(&self) -> f64 {
+        let price = self.pool.state.spot_price();
+        if self.zero_for_one {
+            price
+        } else {
+            1.0 / price
+        }
+    }
+
+    /// [`Self::mid_rate`] net of the pool fee.
+    pub fn rate(&self) -> f64 {
+        self.mid_rate() * (1.0 - self.pool.fee_fraction())
+    }
+
+    pub fn leg(&self) -> Leg<'a> {
+        Leg {
+            pool: &self.pool.state,
+            zero_for_one: self.zero_for_one,
+        }
+    }
+}
+
+/// Tracked pools as a graph over their tokens.
+#[derive(Debug, Default)]
+pub struct TokenGraph<'a> {
+    /// Hops out of each token, by lowercase address.
+    hops: HashMap<&'a str, Vec<Hop<'a>>>,
+}
+
+impl<'a> TokenGraph<'a> {
+    pub fn new(pools: impl IntoIterator<Item = &'a TrackedPool>) -> Self {
+        let mut hops: HashMap<&str, Vec<Hop>> = HashMap::new();
+        for pool in pools {
+            for zero_for_one in [true, false] {
+                let hop = Hop { pool, zero_for_one };
+                hops.entry(hop.token_in().address.as_str())
+                    .or_default()
+                    .push(hop);
+            }
+        }
+        Self { hops }
+    }
+
+    /// Every cycle of `MIN_HOPS..=max_hops` swaps from `start` back to it
+    /// that passes through no other token twice.
+    pub fn cycles(&self, start: &str, max_hops: usize) -> Vec<Vec<Hop<'a>>> {
+        let mut out = Vec::new();
+        let mut path = Vec::new();
+        let mut seen = HashSet::from([start]);
+        self.extend(start, start, max_hops, &mut path, &mut seen, &mut out);
+        out
+    }
+
+    fn extend<'s>(
+        &self,
+        start: &'s str,
+        at: &'s str,
+        max_hops: usize,
+        path: &mut Vec<Hop<'a>>,
+        seen: &mut HashSet<&'s str>,
+        out: &mut Vec<Vec<Hop<'a>>>,
+    ) where
+        'a: 's,
+    {
+        if path.len() == max_hops {
+            return;
+        }
+        for hop in self.hops.get(at).into_iter().flatten() {
+            let next = hop.token_out().address.as_str();
+            if next == start {
+                if path.len() + 1 >= MIN_HOPS {
+                    let mut cycle = path.clone();
+                    cycle.push(*hop);
+                    out.push(cycle);
+                }
+            } else if seen.insert(next) {
+                path.push(*hop);
+                self.extend(start, next, max_hops, path, seen, out);
+                path.pop();
+                seen.remove(next);
+            }
+        }
+    }
+}
+
+/// The pools and directions of a cycle, from its lowest swap on, so the same
+/// cycle found from two base tokens is recognised.
+fn cycle_key(cycle: &[Hop]) -> Vec<(String, bool)> {
+    let mut key: Vec<(String, bool)> = cycle
+        .iter()
+        .map(|h| (h.pool.address.clone(), h.zero_for_one))
+        .collect();
+    if let Some(first) = (0..key.len()).min_by(|&a, &b| key[a].cmp(&key[b])) {
+        key.rotate_left(first);
+    }
+    key
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CycleDetector {
+    /// Thresholds, sizing caps and costs shared with the cross-DEX detector.
+    pub config: DetectorConfig,
+    pub cycles: CycleConfig,
+}
+
+impl CycleDetector {
+    pub fn new(config: DetectorConfig, cycles: CycleConfig) -> Self {
+        Self { config, cycles }
+    }
+
+    /// Every cycle through a base token with a positive edge, highest net
+    /// profit first. A cycle through several base tokens is reported once,
+    /// from the first of them.
+    pub fn detect<'a>(
+        &self,
+        pools: impl IntoIterator<Item = &'a TrackedPool>,
+        prices: &UsdPrices,
+        now: DateTime<Utc>,
+    ) -> Vec<Opportunity> {
+        let graph = TokenGraph::new(self.fresh(pools, now));
+        let mut seen = HashSet::new();
+        let mut out = Vec::new();
+        for base in &self.cycles.base_tokens {
+            for cycle in graph.cycles(base, self.cycles.max_hops) {
+                if !seen.insert(cycle_key(&cycle)) {
+                    continue;
+                }
+                if let Some(opp) = self.evaluate(&cycle, prices, now) {
+                    out.push(opp);
+                }
+            }
+        }
+        out.sort_by(|a, b| b.costs.net_usd.total_cmp(&a.costs.net_usd));
+        out
+    }
+
+    /// As [`Self::detect`], but only sizes the cycles `screen` flags on log
+    /// prices after bringing it up to date with `pools`. Faster with many
+    /// pools, at the cost of finding one cycle per group of tokens rather
+    /// than all of them.
+    pub fn detect_screened<'a>(
+        &self,
+        screen: &mut CycleScreen,
+        pools: impl IntoIterator<Item = &'a TrackedPool>,
+        prices: &UsdPrices,
+        now: DateTime<Utc>,
+    ) -> Vec<Opportunity> {
+        let fresh = self.fresh(pools, now);
+        screen.update(fresh.iter().copied());
+        let by_address: HashMap<&str, &TrackedPool> =
+            fresh.iter().map(|p| (p.address.as_str(), *p)).collect();
+        let min_log_edge = (self.config.min_spread_bps / 1e4).ln_1p();
+        let mut out = Vec::new();
+        for screened in
+            screen.negative_cycles(self.cycles.max_hops, min_log_edge, &self.cycles.base_tokens)
+        {
+            let Some(mut cycle) = screened
+                .hops
+                .iter()
+                .map(|h| {
+                    Some(Hop {
+                        pool: by_address.get(h.pool.as_str())?,
+                        zero_for_one: h.zero_for_one,
+                    })
+                })
+                .collect::<Option<Vec<_>>>()
+            else {
+                continue;
+            };
+            // Start from the first configured base token on the cycle.
+            let Some(start) = self
+                .cycles
+                .base_tokens
+                .iter()
+                .find_map(|base| cycle.iter().position(|h| h.token_in().address == *base))
+            else {
+                continue;
+            };
+            cycle.rotate_left(start);
+            if let Some(opp) = self.evaluate(&cycle, prices, now) {
+                out.push(opp);
+            }
+        }
+        out.sort_by(|a, b| b.costs.net_usd.total_cmp(&a.costs.net_usd));
+        out
+    }
+
+    /// Pools fresh enough to trade, with standard tokens and a price.
+    fn fresh<'a>(
+        &self,
+        pools: impl IntoIterator<Item = &'a TrackedPool>,
+        now: DateTime<Utc>,
+    ) -> Vec<&'a TrackedPool> {
+        pools
+            .into_iter()
+            .filter(|p| {
+                age(p.updated_at, now) <= self.config.max_state_age
+                    && p.token0.is_standard()
+                    && p.token1.is_standard()
+                    && p.mid_price() > 0.0
+            })
+            .collect()
+    }
+
+    fn evaluate(
+        &self,
+        cycle: &[Hop],
+        prices: &UsdPrices,
+        now: DateTime<Utc>,
+    ) -> Option<Opportunity> {
+        let rate: f64 = cycle.iter().map(Hop::rate).product();
+        let spread_bps = (rate - 1.0) * 1e4;
+        if spread_bps < self.config.min_spread_bps {
+            return None;
+        }
+
+        let usd = |t: &Token| prices.get(&t.address);
+        let mut liquidity_usd = f64::INFINITY;
+        for hop in cycle {
+            let (usd0, usd1) = (usd(&hop.pool.token0)?, usd(&hop.pool.token1)?);
+            liquidity_usd = liquidity_usd.min(pool_liquidity_usd(hop.pool, usd0, usd1));
+        }
+        if liquidity_usd < self.config.min_liquidity_usd {
+            return None;
+        }
+
+        let price_error = cycle
+            .iter()
+            .map(|h| price_error(h.pool, prices))
+            .fold(0.0, f64::max);
+        let base = cycle[0].token_in();
+        let base_usd = usd(base)?;
+        let limits = SizingLimits {
+            max_amount_in: base
+                .from_units(self.config.max_notional_usd / (base_usd * (1.0 + price_error))),
+            max_slippage_bps: self.config.max_slippage_bps,
+        };
+        let legs: Vec<Leg> = cycle.iter().map(Hop::leg).collect();
+        let sizing = optimal_route(&legs, &limits).ok()??;
+        let size_usd = base.to_units(sizing.amount_in) * base_usd;
+        let est_profit_usd = base.to_units(sizing.profit()) * base_usd;
+
+        let fees_usd: Vec<f64> = cycle
+            .iter()
+            .zip(&sizing.fees)
+            .map(|(hop, &fee)| Some(hop.token_in().to_units(fee) * usd(hop.token_in())?))
+            .collect::<Option<_>>()?;
+        let lp_fees_usd: f64 = fees_usd.iter().sum();
+        let gross_usd = est_profit_usd + lp_fees_usd;
+        let mid: f64 = cycle.iter().map(Hop::mid_rate).product();
+        let at_mid_usd = size_usd * (mid - 1.0);
+        let slip_bps = if size_usd > 0.0 {
+            ((at_mid_usd - gross_usd) / size_usd * 1e4).max(0.0)
+        } else {
+            0.0
+        };
+        // The configured gas limit is for a two-swap route.
+        let costs = Costs {
+            gas_limit: self.config.costs.gas_limit * cycle.len() as u64 / 2,
+            ..self.config.costs.clone()
+        };
+        let native_usd = prices.get(&self.config.native_token).unwrap_or(0.0);
+        let breakdown = cost_breakdown(
+            &Quote {
+                size_usd,
+                gross_usd,
+                lp_fees_usd,
+                slip_bps,
+            },
+            &costs,
+            native_usd,
+        );
+
+        let max_age = self.config.max_state_age.as_secs_f64().max(1e-9);
+        let oldest = cycle
+            .iter()
+            .map(|h| age(h.pool.updated_at, now).as_secs_f64())
+            .fold(0.0, f64::max);
+        let confidence = ((1.0 - oldest / max_age) * (1.0 - price_error.min(1.0))).clamp(0.0, 1.0);
+
+        let mut opp = Opportunity {
+            kind: OpportunityKind::Arbitrage,
+            // The first token bought, priced in the one the cycle starts in.
+            pair: TokenPair {
+                base: cycle[0].token_out().clone(),
+                quote: base.clone(),
+            },
+            spread_bps,
+            est_gas_usd: breakdown.gas_usd,
+            est_profit_usd,
+            liquidity_usd,
+            confidence,
+            size_usd,
+            amount_in: sizing.amount_in,
+            expected_out: sizing.amount_out(),
+            marginal_price: sizing.marginal_price,
+            slip_bps,
+            costs: breakdown,
+            viable: true,
+            rejections: Vec::new(),
+            ev_per_sec: 0.0,
+            p_success: 0.0,
+            ev_size_usd: 0.0,
+            tail_risk: Vec::new(),
+            route: Route {
+                legs: cycle
+                    .iter()
+                    .zip(fees_usd)
+                    .map(|(hop, fee_usd)| RouteLeg {
+                        dex: hop.pool.dex,
+                        pool: hop.pool.address.clone(),
+                        token_in: hop.token_in().address.clone(),
+                        token_out: hop.token_out().address.clone(),
+                        fee: hop.pool.state.fee_pips(),
+                        fee_usd,
+                        split: Vec::new(),
+                    })
+                    .collect(),
+                l1_order: None,
+            },
+            carry: None,
+        };
+        score(&mut opp, &self.config, &costs);
+        Some(opp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::Dex;
+    use crate::testing::{priced_pool, token, triangle};
+
+    fn prices() -> UsdPrices {
+        let mut p = UsdPrices::new();
+        p.set(&token("USDC").address, 1.0);
+        p.set(WHYPE, 40.0);
+        p.set(&token("KHYPE").address, 40.0);
+        p
+    }
+
+    fn detector(base: &[&str]) -> CycleDetector {
+        CycleDetector::new(
+            DetectorConfig::default(),
+            CycleConfig {
+                base_tokens: base.iter().map(|t| token(t).address).collect(),
+                max_hops: 3,
+            },
+        )
+    }
+
+    #[test]
+    fn finds_triangular_cycle() {
+        let pools = triangle(40.8);
+        let opps = detector(&["USDC"]).detect(&pools, &prices(), Utc::now());
+        assert_eq!(opps.len(), 1);
+        let o = &opps[0];
+        // USDC buys WHYPE, WHYPE buys KHYPE, KHYPE sells for USDC at 2% more.
+        let path: Vec<(&str, &str)> = o
+            .route
+            .legs
+            .iter()
+            .map(|l| (l.pool.as_str(), l.token_out.as_str()))
+            .collect();
+        let (usdc, khype) = (token("USDC").address, token("KHYPE").address);
+        assert_eq!(
+            path,
+            vec![
+                ("0xa", WHYPE),
+                ("0xb", khype.as_str()),
+                ("0xc", usdc.as_str())
+            ]
+        );
+        assert_eq!(o.route.legs[0].token_in, usdc);
+        assert_eq!(o.route.to_string(), "PRJX->HyperSwap->PRJX");
+        assert_eq!(o.pair.to_string(), "WHYPE/USDC");
+        // ~200 bps less three 5 bps fees.
+        assert!((o.spread_bps - 185.0).abs() < 3.0, "{}", o.spread_bps);
+        assert!(o.est_profit_usd > 0.0 && o.size_usd <= 25_000.0);
+        assert!(o.expected_out > o.amount_in);
+        let leg_fees: f64 = o.route.legs.iter().map(|l| l.fee_usd).sum();
+        assert!((leg_fees - o.costs.lp_fees_usd).abs() < 1e-9);
+        // 375k gas for three swaps, at 1 gwei and $40 per HYPE.
+        assert!((o.costs.gas_usd - 0.015).abs() < 1e-9);
+        assert!(o.p_success > 0.0);
+    }
+
+    #[test]
+    fn no_cycle_without_an_edge() {
+        let pools = triangle(40.0);
+        assert!(detector(&["USDC"])
+            .detect(&pools, &prices(), Utc::now())
+            .is_empty());
+    }
+
+    #[test]
+    fn reports_a_cycle_once_across_base_tokens() {
+        let pools = triangle(40.8);
+        let opps = detector(&["USDC", "WHYPE"]).detect(&pools, &prices(), Utc::now());
+        assert_eq!(opps.len(), 1);
+        assert_eq!(opps[0].route.legs[0].token_in, token("USDC").address);
+    }
+
+    #[test]
+    fn screened_detection_sizes_the_flagged_cycle() {
+        let pools = triangle(40.8);
+        let usdc = detector(&["USDC"]);
+        let mut screen = CycleScreen::new();
+        let screened = usdc.detect_screened(&mut screen, &pools, &prices(), Utc::now());
+        let full = usdc.detect(&pools, &prices(), Utc::now());
+        assert_eq!(screened.len(), 1);
+        assert_eq!(screened[0].route.legs, full[0].route.legs);
+        assert_eq!(screened[0].amount_in, full[0].amount_in);
+        assert_eq!(screen.pool_count(), 3);
+
+        // No base token on the cycle, nothing to start it from.
+        assert!(detector(&["USDT0"])
+            .detect_screened(&mut screen, &pools, &prices(), Utc::now())
+            .is_empty());
+    }
+
+    #[test]
+    fn enumerates_simple_cycles_up_to_max_hops() {
+        // A square USDC-WHYPE-KHYPE-USDT0 with a second WHYPE/USDC pool.
+        let mut pools = triangle(40.0);
+        pools.pop();
+        pools.push(priced_pool(
+            "0xd",
+            Dex::HyperSwap,
+            ("WHYPE", "USDC"),
+            40.0,
+            10u128.pow(17),
+        ));
+        pools.push(priced_pool(
+            "0xe",
+            Dex::Prjx,
+            ("KHYPE", "USDT0"),
+            40.0,
+            10u128.pow(17),
+        ));
+        pools.push(priced_pool(
+            "0xf",
+            Dex::Prjx,
+            ("USDT0", "USDC"),
+            1.0,
+            10u128.pow(17),
+        ));
+        let graph = TokenGraph::new(&pools);
+        let usdc = token("USDC").address;
+        assert!(graph.cycles(&usdc, 3).is_empty());
+        // Two ways into WHYPE, each cycle walked both ways round.
+        let cycles = graph.cycles(&usdc, 4);
+        assert_eq!(cycles.len(), 4);
+        for cycle in &cycles {
+            assert_eq!(cycle.len(), 4);
+            assert_eq!(cycle[0].token_in().address, usdc);
+            assert_eq!(cycle[3].token_out().address, usdc);
+            for pair in cycle.windows(2) {
+                assert_eq!(pair[0].token_out(), pair[1].token_in());
+            }
+        }
+        // The two WHYPE/USDC pools alone make a two-pool route, not a cycle.
+        assert!(graph.cycles(&usdc, 2).is_empty());
+    }
+}
@@ -0,0 +1,731 @@
+//! Cross-DEX detector: the same V3 pair on PRJX and HyperSwap, each side
+//! split across its venue's fee tiers when that pays.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+use crate::amm::Pool;
+use crate::basis::BasisDetector;
+use crate::carry::CarryDetector;
+use crate::config::EngineConfig;
+use crate::cycles::CycleDetector;
+use crate::ev::{self, EvInputs, EvParams};
+use crate::hyperliquid::{BookMirror, SharedBookMirror};
+use crate::montecarlo::{self, MonteCarloConfig};
+use crate::opportunity::{Opportunity, OpportunityKind, Route, RouteLeg, SplitFill, TokenPair};
+use crate::pricing::{PricingConfig, UsdPrices};
+use crate::profit_gate::{cost_breakdown, Costs, Quote};
+use crate::screen::CycleScreen;
+use crate::sizing::{optimal_split_route, Fill, Leg, SizingLimits};
+use crate::state::{SharedPoolStore, Token, TrackedPool};
+use crate::univ3::U256;
+
+/// WHYPE, the wrapped gas token on HyperEVM.
+pub const WHYPE: &str = "0x5555555555555555555555555555555555555555";
+
+#[derive(Debug, Clone)]
+pub struct DetectorConfig {
+    pub min_spread_bps: f64,
+    pub min_liquidity_usd: f64,
+    /// Upper bound on trade size.
+    pub max_notional_usd: f64,
+    /// How far the trade may move either pool's price.
+    pub max_slippage_bps: f64,
+    /// Gas and fees charged against the gross edge.
+    pub costs: Costs,
+    /// Latency, failure and risk parameters for the EV estimate.
+    pub ev: EvParams,
+    /// Payout simulation behind each opportunity's tail risk.
+    pub monte_carlo: MonteCarloConfig,
+    /// Address of the token gas is paid in (wrapped).
+    pub native_token: String,
+    /// Pool snapshots older than this are ignored.
+    pub max_state_age: Duration,
+    /// How token prices are derived from the pools.
+    pub pricing: PricingConfig,
+}
+
+impl Default for DetectorConfig {
+    fn default() -> Self {
+        Self {
+            min_spread_bps: 10.0,
+            min_liquidity_usd: 10_000.0,
+            max_notional_usd: 25_000.0,
+            max_slippage_bps: 50.0,
+            costs: Costs::default(),
+            ev: EvParams::default(),
+            monte_carlo: MonteCarloConfig::default(),
+            native_token: WHYPE.to_string(),
+            max_state_age: Duration::from_secs(90),
+            pricing: PricingConfig::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Detector {
+    pub config: DetectorConfig,
+}
+
+impl Detector {
+    pub fn new(config: DetectorConfig) -> Self {
+        Self { config }
+    }
+
+    /// Every cross-venue pair with a positive edge, highest net profit first.
+    /// Pools of fee-on-transfer or rebasing tokens are left out: no pool
+    /// quote holds for them.
+    pub fn detect<'a>(
+        &self,
+        pools: impl IntoIterator<Item = &'a TrackedPool>,
+        prices: &UsdPrices,
+        now: DateTime<Utc>,
+    ) -> Vec<Opportunity> {
+        let mut by_pair: HashMap<(&str, &str), Vec<&TrackedPool>> = HashMap::new();
+        for pool in pools {
+            if self.age(pool, now) > self.config.max_state_age
+                || !(pool.token0.is_standard() && pool.token1.is_standard())
+            {
+                continue;
+            }
+            by_pair
+                .entry((&pool.token0.address, &pool.token1.address))
+                .or_default()
+                .push(pool);
+        }
+
+        let mut out = Vec::new();
+        let mut seen = HashSet::new();
+        for group in by_pair.values() {
+            for (i, a) in group.iter().enumerate() {
+                for b in &group[i + 1..] {
+                    if a.dex == b.dex {
+                        continue;
+                    }
+                    let (cheap, rich) = if a.mid_price() <= b.mid_price() {
+                        (*a, *b)
+                    } else {
+                        (*b, *a)
+                    };
+                    let (buys, sells) = sides(cheap, rich, group);
+                    let key = |side: &[&'a TrackedPool]| -> Vec<&'a str> {
+                        let mut addresses: Vec<&str> =
+                            side.iter().map(|p| p.address.as_str()).collect();
+                        addresses.sort_unstable();
+                        addresses
+                    };
+                    if !seen.insert((key(&buys), key(&sells))) {
+                        continue;
+                    }
+                    if let Some(opp) = self.evaluate(&buys, &sells, prices, now) {
+                        out.push(opp);
+                    }
+                }
+            }
+        }
+        out.sort_by(|a, b| b.costs.net_usd.total_cmp(&a.costs.net_usd));
+        out
+    }
+
+    fn age(&self, pool: &TrackedPool, now: DateTime<Utc>) -> Duration {
+        (now - pool.updated_at).to_std().unwrap_or_default()
+    }
+
+    /// Buys token0 with token1 on `buys`, where it is cheap, and sells it on
+    /// `sells`, where it is rich. The first pool of each side sets the
+    /// spread; the input of each side is split across all of its pools.
+    fn evaluate<'a>(
+        &self,
+        buys: &[&'a TrackedPool],
+        sells: &[&'a TrackedPool],
+        prices: &UsdPrices,
+        now: DateTime<Utc>,
+    ) -> Option<Opportunity> {
+        let (cheap, rich) = (buys[0], sells[0]);
+        let (p_cheap, p_rich) = (cheap.mid_price(), rich.mid_price());
+        if p_cheap <= 0.0 {
+            return None;
+        }
+        let spread_bps =
+            (p_rich * (1.0 - rich.fee_fraction()) * (1.0 - cheap.fee_fraction()) / p_cheap - 1.0)
+                * 1e4;
+        if spread_bps < self.config.min_spread_bps {
+            return None;
+        }
+
+        let (usd0, usd1) = token_usd(cheap, prices)?;
+        let side_liquidity = |side: &[&TrackedPool]| -> f64 {
+            side.iter().map(|p| pool_liquidity_usd(p, usd0, usd1)).sum()
+        };
+        let liquidity_usd = side_liquidity(buys).min(side_liquidity(sells));
+        if liquidity_usd < self.config.min_liquidity_usd {
+            return None;
+        }
+
+        // Size against the top of the quote token's price interval so the
+        // notional cap holds whatever its true price.
+        let price_error = price_error(cheap, prices);
+        let (token0, token1) = (&cheap.token0, &cheap.token1);
+        let limits = SizingLimits {
+            max_amount_in: token1
+                .from_units(self.config.max_notional_usd / (usd1 * (1.0 + price_error))),
+            max_slippage_bps: self.config.max_slippage_bps,
+        };
+        let legs = |side: &[&'a TrackedPool], zero_for_one| -> Vec<Leg<'a>> {
+            side.iter()
+                .map(|p| Leg {
+                    pool: &p.state,
+                    zero_for_one,
+                })
+                .collect()
+        };
+        let (buy, sell) = (legs(buys, false), legs(sells, true));
+        let sizing = optimal_split_route(&[&buy, &sell], &limits).ok()??;
+        let size_usd = token1.to_units(sizing.amount_in) * usd1;
+        let est_profit_usd = token1.to_units(sizing.profit()) * usd1;
+
+        // Gross edge adds the LP fees back; whatever the mid spread promised
+        // beyond that was lost to price impact.
+        let buy_fee_usd = token1.to_units(sizing.fees[0]) * usd1;
+        let sell_fee_usd = token0.to_units(sizing.fees[1]) * usd0;
+        let lp_fees_usd = buy_fee_usd + sell_fee_usd;
+        let gross_usd = est_profit_usd + lp_fees_usd;
+        let at_mid_usd = size_usd * (p_rich / p_cheap - 1.0);
+        let slip_bps = if size_usd > 0.0 {
+            ((at_mid_usd - gross_usd) / size_usd * 1e4).max(0.0)
+        } else {
+            0.0
+        };
+        let buy_leg = route_leg(buys, &sizing.fills[0], token1, token0, buy_fee_usd);
+        let sell_leg = route_leg(sells, &sizing.fills[1], token0, token1, sell_fee_usd);
+        // The configured gas limit is for one swap each way.
+        let swaps = buy_leg.split.len().max(1) + sell_leg.split.len().max(1);
+        let route_costs = Costs {
+            gas_limit: self.config.costs.gas_limit * swaps as u64 / 2,
+            ..self.config.costs.clone()
+        };
+        let native_usd = prices.get(&self.config.native_token).unwrap_or(0.0);
+        let costs = cost_breakdown(
+            &Quote {
+                size_usd,
+                gross_usd,
+                lp_fees_usd,
+                slip_bps,
+            },
+            &route_costs,
+            native_usd,
+        );
+
+        let max_age = self.config.max_state_age.as_secs_f64().max(1e-9);
+        let oldest = buys
+            .iter()
+            .chain(sells)
+            .map(|p| self.age(p, now))
+            .max()
+            .unwrap_or_default()
+            .as_secs_f64();
+        let confidence = ((1.0 - oldest / max_age) * (1.0 - price_error.min(1.0))).clamp(0.0, 1.0);
+
+        let mut opp = Opportunity {
+            kind: OpportunityKind::Arbitrage,
+            pair: TokenPair {
+                base: token0.clone(),
+                quote: token1.clone(),
+            },
+            spread_bps,
+            est_gas_usd: costs.gas_usd,
+            est_profit_usd,
+            liquidity_usd,
+            confidence,
+            size_usd,
+            amount_in: sizing.amount_in,
+            expected_out: sizing.amount_out(),
+            marginal_price: sizing.marginal_price,
+            slip_bps,
+            costs,
+            viable: true,
+            rejections: Vec::new(),
+            ev_per_sec: 0.0,
+            p_success: 0.0,
+            ev_size_usd: 0.0,
+            tail_risk: Vec::new(),
+            route: Route {
+                legs: vec![buy_leg, sell_leg],
+                l1_order: None,
+            },
+            carry: None,
+        };
+
+        score(&mut opp, &self.config, &route_costs);
+        Some(opp)
+    }
+}
+
+/// The pools of a pair to buy token0 on and to sell it on, given its
+/// cheapest and richest pools of two venues: each side is led by that pool
+/// and takes its venue's other fee tiers priced on its side of the two's
+/// geometric mean. Keeping a side to one venue keeps its leg a single router
+/// call, one swap per fee tier.
+fn sides<'a>(
+    cheap: &'a TrackedPool,
+    rich: &'a TrackedPool,
+    group: &[&'a TrackedPool],
+) -> (Vec<&'a TrackedPool>, Vec<&'a TrackedPool>) {
+    let mid = (cheap.mid_price() * rich.mid_price()).sqrt();
+    let (mut buys, mut sells) = (vec![cheap], vec![rich]);
+    for &p in group {
+        if std::ptr::eq(p, cheap) || std::ptr::eq(p, rich) {
+            continue;
+        }
+        let price = p.mid_price();
+        if p.dex == cheap.dex && price > 0.0 && price < mid {
+            buys.push(p);
+        } else if p.dex == rich.dex && price > mid {
+            sells.push(p);
+        }
+    }
+    (buys, sells)
+}
+
+/// The leg swapping `token_in` for `token_out` through `side`, given each
+/// pool's fill: named for the pool filling the most, with the per-pool
+/// split when more than one trades.
+fn route_leg(
+    side: &[&TrackedPool],
+    fills: &[Fill],
+    token_in: &Token,
+    token_out: &Token,
+    fee_usd: f64,
+) -> RouteLeg {
+    let mut split: Vec<SplitFill> = side
+        .iter()
+        .zip(fills)
+        .filter(|(_, f)| !f.amount_in.is_zero())
+        .map(|(p, f)| SplitFill {
+            dex: p.dex,
+            pool: p.address.clone(),
+            fee: p.state.fee_pips(),
+            amount_in: f.amount_in,
+            amount_out: f.amount_out,
+        })
+        .collect();
+    split.sort_by_key(|f| std::cmp::Reverse(f.amount_in));
+    let main = split.first().cloned().unwrap_or_else(|| SplitFill {
+        dex: side[0].dex,
+        pool: side[0].address.clone(),
+        fee: side[0].state.fee_pips(),
+        amount_in: U256::ZERO,
+        amount_out: U256::ZERO,
+    });
+    if split.len() < 2 {
+        split.clear();
+    }
+    RouteLeg {
+        dex: main.dex,
+        pool: main.pool,
+        token_in: token_in.address.clone(),
+        token_out: token_out.address.clone(),
+        fee: main.fee,
+        fee_usd,
+        split,
+    }
+}
+
+/// Fills in `opp`'s EV model results and the tail risk of one attempt at the
+/// size the model settles on, `costs` being what the route is charged.
+pub(crate) fn score(opp: &mut Opportunity, config: &DetectorConfig, costs: &Costs) {
+    let inputs = EvInputs::for_opportunity(opp, &config.ev, costs);
+    let ev = ev::evaluate(&inputs);
+    opp.ev_per_sec = ev.ev_per_sec;
+    opp.p_success = ev.p_success;
+    opp.ev_size_usd = ev.size_opt_usd;
+    opp.tail_risk = montecarlo::tail_risk(
+        &EvInputs {
+            notional_usd: ev.size_opt_usd,
+            ..inputs
+        },
+        &config.monte_carlo,
+    );
+}
+
+/// Re-runs detection over the pool store every `every`, multi-hop cycles
+/// through the configured base tokens (screened on a log-price graph kept
+/// across runs), and the basis and carry strategies
+/// against `books` when they have markets, and publishes the
+/// opportunities in scope of the live config on the returned channel, viable
+/// ones first, each judged by the profit gate. Each run works on snapshots
+/// of the pools and books on the blocking pool.
+pub fn spawn_detection(
+    config: watch::Receiver<Arc<EngineConfig>>,
+    store: SharedPoolStore,
+    books: SharedBookMirror,
+    every: Duration,
+) -> (JoinHandle<()>, watch::Receiver<Vec<Opportunity>>) {
+    let (tx, rx) = watch::channel(Vec::new());
+    let handle = tokio::spawn(async move {
+        let mut interval = tokio::time::interval(every);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        let mut screen = CycleScreen::new();
+        loop {
+            interval.tick().await;
+            let live = config.borrow().clone();
+            // Snapshots, so no lock is held through pricing, sizing and the
+            // Monte Carlo scoring, which run off the async workers.
+            let pools: Vec<TrackedPool> = store.read().await.iter().cloned().collect();
+            let (basis, carry) = (&live.engine.basis, &live.engine.carry);
+            let book_snapshot = if basis.markets.is_empty() && carry.markets.is_empty() {
+                None
+            } else {
+                Some(books.read().await.clone())
+            };
+            let mut taken = std::mem::take(&mut screen);
+            let run = tokio::task::spawn_blocking(move || {
+                let found = detect_all(&live, &pools, book_snapshot.as_ref(), &mut taken);
+                (found, taken)
+            });
+            // A panicked run loses the screen; the next one rebuilds it.
+            let opportunities = match run.await {
+                Ok((found, kept)) => {
+                    screen = kept;
+                    found
+                }
+                Err(e) => {
+                    warn!(error = %e, "detection run failed");
+                    continue;
+                }
+            };
+            if let Some(best) = opportunities.first() {
+                debug!(count = opportunities.len(), pair = %best.pair, net_usd = best.costs.net_usd, viable = best.viable, "opportunities");
+            }
+            if tx.send(opportunities).is_err() {
+                return;
+            }
+        }
+    });
+    (handle, rx)
+}
+
+/// One detection pass over snapshots of the pools and, when basis or carry
+/// have markets, the L1 books: in scope and judged, viable first.
+fn detect_all(
+    live: &EngineConfig,
+    pools: &[TrackedPool],
+    books: Option<&BookMirror>,
+    screen: &mut CycleScreen,
+) -> Vec<Opportunity> {
+    let detector = Detector::new(live.detector_config());
+    let prices = UsdPrices::from_pools_with(pools.iter(), &detector.config.pricing);
+    let now = Utc::now();
+    let mut found = detector.detect(pools.iter(), &prices, now);
+    let cycles = &live.engine.cycles;
+    if !cycles.base_tokens.is_empty() {
+        let cycles = CycleDetector::new(detector.config.clone(), cycles.clone());
+        found.extend(cycles.detect_screened(screen, pools.iter(), &prices, now));
+    }
+    if let Some(books) = books {
+        let (basis, carry) = (&live.engine.basis, &live.engine.carry);
+        if !basis.markets.is_empty() {
+            let basis = BasisDetector::new(detector.config.clone(), basis.clone());
+            found.extend(basis.detect(pools.iter(), books, &prices, now));
+        }
+        if !carry.markets.is_empty() {
+            let carry = CarryDetector::new(detector.config, basis.clone(), carry.clone());
+            found.extend(carry.detect(pools.iter(), books, &prices, now));
+        }
+    }
+    found.retain_mut(|o| live.judge(o));
+    found.sort_by(|a, b| {
+        b.viable
+            .cmp(&a.viable)
+            .then(b.costs.net_usd.total_cmp(&a.costs.net_usd))
+    });
+    found
+}
+
+/// USD prices of both tokens; one known price is enough given the pool's mid.
+pub(crate) fn token_usd(pool: &TrackedPool, prices: &UsdPrices) -> Option<(f64, f64)> {
+    let mid = pool.mid_price();
+    match (
+        prices.get(&pool.token0.address),
+        prices.get(&pool.token1.address),
+    ) {
+        (Some(p0), Some(p1)) => Some((p0, p1)),
+        (None, Some(p1)) => Some((p1 * mid, p1)),
+        (Some(p0), None) if mid > 0.0 => Some((p0, p0 / mid)),
+        _ => None,
+    }
+}
+
+/// Largest relative error of the USD prices known for `pool`'s tokens.
+pub(crate) fn price_error(pool: &TrackedPool, prices: &UsdPrices) -> f64 {
+    [&pool.token0, &pool.token1]
+        .into_iter()
+        .filter_map(|t| prices.quote(&t.address))
+        .map(|q| q.rel_error())
+        .fold(0.0, f64::max)
+}
+
+pub(crate) fn pool_liquidity_usd(pool: &TrackedPool, usd0: f64, usd1: f64) -> f64 {
+    let (reserve0, reserve1) = pool.virtual_reserves();
+    reserve0 * usd0 + reserve1 * usd1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{Dex, PoolStore, Token};
+    use crate::testing::{stable_tokens, tick_for_price, token, v3_pool};
+
+    fn prices() -> UsdPrices {
+        let mut p = UsdPrices::new();
+        p.set(&token("USDC").address, 1.0);
+        p.set(WHYPE, 40.0);
+        p
+    }
+
+    fn hype_usdc(dex: Dex, address: &str, price: f64, fee: u32) -> TrackedPool {
+        let (t0, t1) = (token("WHYPE"), token("USDC"));
+        let tick = tick_for_price(price, &t0, &t1);
+        v3_pool(address, dex, t0, t1, fee, tick, 10u128.pow(17))
+    }
+
+    #[test]
+    fn finds_cross_venue_spread() {
+        let pools = [
+            hype_usdc(Dex::Prjx, "0xa", 40.0, 500),
+            hype_usdc(Dex::HyperSwap, "0xb", 40.4, 3000),
+        ];
+        let opps = Detector::default().detect(&pools, &prices(), Utc::now());
+        assert_eq!(opps.len(), 1);
+        let o = &opps[0];
+        assert_eq!(o.pair.to_string(), "WHYPE/USDC");
+        assert_eq!(o.pair.base, token("WHYPE"));
+        assert_eq!(o.pair.quote.decimals, 6);
+        assert_eq!(o.route.to_string(), "PRJX->HyperSwap");
+        assert_eq!(o.route.legs[0].pool, "0xa");
+        assert_eq!(o.route.legs[0].token_in, token("USDC").address);
+        assert_eq!(o.route.legs[1].token_out, token("USDC").address);
+        assert!(o.route.l1_order.is_none());
+        // ~100 bps gross less 5 + 30 bps of fees.
+        assert!((o.spread_bps - 65.0).abs() < 2.0, "{}", o.spread_bps);
+        assert!(o.est_profit_usd > 0.0);
+        assert!(o.size_usd > 0.0 && o.size_usd <= 25_000.0);
+        assert!(o.expected_out > o.amount_in);
+        assert!(o.marginal_price >= 1.0);
+        assert!(o.liquidity_usd > 10_000.0);
+        assert!(o.confidence > 0.9 && o.confidence <= 1.0);
+        // 250k gas at 1 gwei and $40 per HYPE.
+        assert!((o.est_gas_usd - 0.01).abs() < 1e-9);
+        assert_eq!(o.costs.gas_usd, o.est_gas_usd);
+        // 5 bps on the buy leg plus 30 bps on the sell leg.
+        let c = &o.costs;
+        assert!(
+            c.lp_fees_usd > o.size_usd * 35e-4 * 0.9 && c.lp_fees_usd < o.size_usd * 35e-4 * 1.1
+        );
+        let leg_fees: f64 = o.route.legs.iter().map(|l| l.fee_usd).sum();
+        assert!((leg_fees - c.lp_fees_usd).abs() < 1e-9);
+        assert!(o.route.legs[1].fee_usd > o.route.legs[0].fee_usd);
+        assert!((c.gross_usd - c.lp_fees_usd - o.est_profit_usd).abs() < 1e-6);
+        assert!((c.net_usd - (o.est_profit_usd - c.gas_usd)).abs() < 1e-6);
+        assert!(o.slip_bps > 0.0 && o.slip_bps <= 2.0 * 50.0);
+        // 0.9 base fill decayed over 1s of latency.
+        assert!((o.p_success - 0.9 * (-0.15f64).exp()).abs() < 1e-12);
+        assert!(o.ev_size_usd > 0.0 && o.ev_size_usd <= o.size_usd + 1e-9);
+        assert!(o.ev_per_sec > 0.0);
+        assert_eq!(o.tail_risk.len(), 2);
+        assert!(o.tail_risk[1].cvar_usd <= o.tail_risk[0].var_usd);
+    }
+
+    #[test]
+    fn fees_can_eat_the_spread() {
+        let pools = [
+            hype_usdc(Dex::Prjx, "0xa", 40.0, 3000),
+            hype_usdc(Dex::HyperSwap, "0xb", 40.2, 3000),
+        ];
+        assert!(Detector::default()
+            .detect(&pools, &prices(), Utc::now())
+            .is_empty());
+    }
+
+    #[test]
+    fn ignores_same_venue_and_stale_pools() {
+        let same_venue = [
+            hype_usdc(Dex::Prjx, "0xa", 40.0, 500),
+            hype_usdc(Dex::Prjx, "0xb", 41.0, 500),
+        ];
+        assert!(Detector::default()
+            .detect(&same_venue, &prices(), Utc::now())
+            .is_empty());
+
+        let mut stale = hype_usdc(Dex::HyperSwap, "0xb", 41.0, 500);
+        stale.updated_at = Utc::now() - chrono::Duration::seconds(600);
+        let pools = [hype_usdc(Dex::Prjx, "0xa", 40.0, 500), stale];
+        assert!(Detector::default()
+            .detect(&pools, &prices(), Utc::now())
+            .is_empty());
+    }
+
+    #[test]
+    fn discounts_uncertain_prices() {
+        let pools = [
+            hype_usdc(Dex::Prjx, "0xa", 40.0, 500),
+            hype_usdc(Dex::HyperSwap, "0xb", 40.4, 3000),
+        ];
+        let exact = Detector::default().detect(&pools, &prices(), Utc::now());
+        // WHYPE priced off the two disagreeing pools themselves.
+        let derived = UsdPrices::from_pools(&pools);
+        let error = derived.quote(WHYPE).unwrap().rel_error();
+        assert!(error > 0.001, "{error}");
+        let loose = Detector::default().detect(&pools, &derived, Utc::now());
+        assert!(loose[0].confidence < exact[0].confidence * (1.0 - error * 0.99));
+        assert!(loose[0].amount_in <= exact[0].amount_in);
+    }
+
+    #[test]
+    fn skips_fee_on_transfer_and_rebasing_tokens() {
+        let pools = [
+            hype_usdc(Dex::Prjx, "0xa", 40.0, 500),
+            hype_usdc(Dex::HyperSwap, "0xb", 40.4, 3000),
+        ];
+        for flag in [
+            |t: &mut Token| t.fee_on_transfer = true,
+            |t: &mut Token| t.rebasing = true,
+        ] {
+            let mut quirky = pools.clone();
+            flag(&mut quirky[1].token0);
+            assert!(Detector::default()
+                .detect(&quirky, &prices(), Utc::now())
+                .is_empty());
+        }
+    }
+
+    #[test]
+    fn scales_amounts_by_each_tokens_decimals() {
+        // The same market quoted against an 18- and a 6-decimal dollar.
+        let run = |decimals: u8| {
+            let usd = Token {
+                decimals,
+                ..token("USDC")
+            };
+            let mut prices = prices();
+            prices.set(&usd.address, 1.0);
+            let pool = |dex, address, price, fee| {
+                let t0 = token("WHYPE");
+                let tick = tick_for_price(price, &t0, &usd);
+                let liquidity = 10u128.pow(17 + u32::from(decimals - 6) / 2);
+                v3_pool(address, dex, t0, usd.clone(), fee, tick, liquidity)
+            };
+            let pools = [
+                pool(Dex::Prjx, "0xa", 40.0, 500),
+                pool(Dex::HyperSwap, "0xb", 40.4, 3000),
+            ];
+            Detector::default()
+                .detect(&pools, &prices, Utc::now())
+                .remove(0)
+        };
+        let (six, eighteen) = (run(6), run(18));
+        assert_eq!(eighteen.pair.quote.decimals, 18);
+        for (a, b) in [
+            (six.size_usd, eighteen.size_usd),
+            (six.est_profit_usd, eighteen.est_profit_usd),
+            (six.costs.lp_fees_usd, eighteen.costs.lp_fees_usd),
+            (six.liquidity_usd, eighteen.liquidity_usd),
+        ] {
+            assert!((a / b - 1.0).abs() < 1e-3, "{a} vs {b}");
+        }
+        let raw = eighteen.amount_in.to_f64() / six.amount_in.to_f64();
+        assert!((raw / 1e12 - 1.0).abs() < 1e-3, "{raw}");
+    }
+
+    #[test]
+    fn splits_across_fee_tiers_of_a_venue() {
+        let pair = [
+            hype_usdc(Dex::Prjx, "0xa", 40.0, 500),
+            hype_usdc(Dex::HyperSwap, "0xb", 40.4, 500),
+        ];
+        let single = Detector::default()
+            .detect(&pair, &prices(), Utc::now())
+            .remove(0);
+        assert!(single.route.legs.iter().all(|l| l.split.is_empty()));
+
+        // A second PRJX tier just above the first, and a HyperSwap tier too
+        // cheap to sell into.
+        let mut pools = pair.to_vec();
+        pools.push(hype_usdc(Dex::Prjx, "0xc", 40.02, 100));
+        pools.push(hype_usdc(Dex::HyperSwap, "0xd", 40.0, 3000));
+        let opps = Detector::default().detect(&pools, &prices(), Utc::now());
+        assert_eq!(opps.len(), 1);
+        let o = &opps[0];
+        assert_eq!(o.route.to_string(), "PRJX->HyperSwap");
+        let (buy, sell) = (&o.route.legs[0], &o.route.legs[1]);
+        assert!(sell.split.is_empty());
+        assert_eq!(sell.pool, "0xb");
+        let mut split_pools: Vec<&str> = buy.split.iter().map(|f| f.pool.as_str()).collect();
+        assert_eq!(buy.pool, split_pools[0]);
+        assert_eq!(buy.fee, buy.split[0].fee);
+        split_pools.sort();
+        assert_eq!(split_pools, ["0xa", "0xc"]);
+        assert!(buy.split[0].amount_in >= buy.split[1].amount_in);
+        let total_in = buy.split.iter().fold(U256::ZERO, |a, f| a + f.amount_in);
+        assert_eq!(total_in, o.amount_in);
+        assert!(o.est_profit_usd > single.est_profit_usd);
+        // One more swap than the plain route.
+        assert!((o.costs.gas_usd - 0.015).abs() < 1e-9);
+
+        let json = serde_json::to_value(o).unwrap();
+        assert!(json["route"]["legs"][1].get("split").is_none());
+        assert!(json["route"]["legs"][0]["split"][0]["amount_in"].is_string());
+    }
+
+    #[test]
+    fn skips_thin_pools() {
+        let (t0, t1) = (token("WHYPE"), token("USDC"));
+        let thin = v3_pool(
+            "0xb",
+            Dex::HyperSwap,
+            t0.clone(),
+            t1.clone(),
+            500,
+            tick_for_price(41.0, &t0, &t1),
+            10u128.pow(12),
+        );
+        let pools = [hype_usdc(Dex::Prjx, "0xa", 40.0, 500), thin];
+        assert!(Detector::default()
+            .detect(&pools, &prices(), Utc::now())
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn spawned_detection_publishes_from_snapshots() {
+        let store = PoolStore::new().with_tokens(stable_tokens()).into_shared();
+        store
+            .write()
+            .await
+            .upsert(hype_usdc(Dex::Prjx, "0xa", 40.0, 500));
+        let (_tx, config) = watch::channel(Arc::new(EngineConfig::default()));
+        let (task, mut rx) = spawn_detection(
+            config,
+            store.clone(),
+            BookMirror::shared(),
+            Duration::from_millis(5),
+        );
+        // A pool added while detection runs shows up in a later pass.
+        store
+            .write()
+            .await
+            .upsert(hype_usdc(Dex::HyperSwap, "0xb", 40.4, 3000));
+        let opps = tokio::time::timeout(Duration::from_secs(5), rx.wait_for(|o| !o.is_empty()))
+            .await
+            .unwrap()
+            .unwrap()
+            .clone();
+        assert_eq!(opps[0].route.to_string(), "PRJX->HyperSwap");
+        task.abort();
+    }
+}
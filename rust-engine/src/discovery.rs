@@ -0,0 +1,675 @@
+//! Pool discovery from the factories' `PoolCreated` logs.
+//!
+//! Every pool a factory ever created goes into a [`PoolRegistry`], persisted
+//! as JSON along with the last block scanned, so a restart only scans the
+//! blocks it missed. The registry keeps pools the filter rejects too: a
+//! filter change or new liquidity needs no rescan, only the next refresh.
+//!
+//! Each poll scans the blocks since the last one and tracks the new pools
+//! that pass; each refresh re-reads every registered pool the static filter
+//! admits and replaces the venue's pool set with those that pass.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+use crate::detector::{pool_liquidity_usd, token_usd};
+use crate::pricing::UsdPrices;
+use crate::rpc::logs::{PoolCreated, POOL_CREATED_TOPIC};
+use crate::rpc::PoolReader;
+use crate::state::{Dex, SharedPoolStore, TrackedPool};
+
+/// Blocks per `eth_getLogs` request.
+pub const DEFAULT_LOG_RANGE: u64 = 1_000;
+
+/// A venue's V3 factory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Factory {
+    pub dex: Dex,
+    /// Lowercase hex address.
+    pub address: String,
+}
+
+/// Which discovered pools get tracked.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct DiscoveryFilter {
+    /// Lowercase token addresses both tokens must be among; empty admits any.
+    pub tokens: Vec<String>,
+    /// Fee tiers in pips; empty admits any.
+    pub fee_tiers: Vec<u32>,
+    /// Minimum USD value of the active range's virtual reserves.
+    pub min_liquidity_usd: f64,
+}
+
+impl DiscoveryFilter {
+    /// The checks that need no chain state.
+    pub fn admits(&self, pool: &PoolCreated) -> bool {
+        let allowed = |t: &String| self.tokens.is_empty() || self.tokens.contains(t);
+        allowed(&pool.token0)
+            && allowed(&pool.token1)
+            && (self.fee_tiers.is_empty() || self.fee_tiers.contains(&pool.fee))
+    }
+
+    /// The liquidity floor. A pool neither of whose tokens has a USD price
+    /// passes only when there is no floor.
+    pub fn deep_enough(&self, pool: &TrackedPool, prices: &UsdPrices) -> bool {
+        if self.min_liquidity_usd <= 0.0 {
+            return true;
+        }
+        token_usd(pool, prices).is_some_and(|(usd0, usd1)| {
+            pool_liquidity_usd(pool, usd0, usd1) >= self.min_liquidity_usd
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RegisteredPool {
+    pub dex: Dex,
+    /// Block of the `PoolCreated` log.
+    pub block: u64,
+    #[serde(flatten)]
+    pub created: PoolCreated,
+}
+
+/// Every pool the factories created, and how far each factory was scanned.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PoolRegistry {
+    /// Last block scanned, by factory address.
+    scanned: BTreeMap<String, u64>,
+    /// By pool address.
+    pools: BTreeMap<String, RegisteredPool>,
+}
+
+impl PoolRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The registry saved at `path`, or an empty one if there is none yet.
+    pub fn load(path: &Path) -> Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => {
+                serde_json::from_str(&text).with_context(|| format!("parsing {}", path.display()))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::new()),
+            Err(e) => Err(e).with_context(|| format!("reading {}", path.display())),
+        }
+    }
+
+    /// Writes a temporary file next to `path` and renames it over, so a
+    /// crash never leaves half a registry.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+        std::fs::write(&tmp, serde_json::to_vec_pretty(self)?)
+            .with_context(|| format!("writing {}", tmp.display()))?;
+        std::fs::rename(&tmp, path).with_context(|| format!("renaming to {}", path.display()))
+    }
+
+    pub fn scanned(&self, factory: &str) -> Option<u64> {
+        self.scanned.get(factory).copied()
+    }
+
+    pub fn set_scanned(&mut self, factory: &str, block: u64) {
+        self.scanned.insert(factory.to_string(), block);
+    }
+
+    /// Returns `false` if the pool was already registered.
+    pub fn insert(&mut self, pool: RegisteredPool) -> bool {
+        if self.pools.contains_key(&pool.created.pool) {
+            return false;
+        }
+        self.pools.insert(pool.created.pool.clone(), pool);
+        true
+    }
+
+    pub fn get(&self, address: &str) -> Option<&RegisteredPool> {
+        self.pools.get(address)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &RegisteredPool> {
+        self.pools.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.pools.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pools.is_empty()
+    }
+}
+
+/// Scans the factories and keeps the store's pools of their venues in line
+/// with the registry and the filter.
+#[derive(Debug)]
+pub struct Discovery {
+    reader: PoolReader,
+    factories: Vec<Factory>,
+    filter: DiscoveryFilter,
+    registry: PoolRegistry,
+    path: Option<PathBuf>,
+    start_block: u64,
+    log_range: u64,
+}
+
+impl Discovery {
+    pub fn new(reader: PoolReader, factories: Vec<Factory>, filter: DiscoveryFilter) -> Self {
+        Self {
+            reader,
+            factories,
+            filter,
+            registry: PoolRegistry::new(),
+            path: None,
+            start_block: 0,
+            log_range: DEFAULT_LOG_RANGE,
+        }
+    }
+
+    /// Loads the registry from `path` and saves it there after each change.
+    pub fn with_registry_path(mut self, path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        self.registry = PoolRegistry::load(&path)?;
+        self.path = Some(path);
+        Ok(self)
+    }
+
+    /// First block scanned for a factory never scanned before, e.g. its
+    /// deployment block.
+    pub fn with_start_block(mut self, block: u64) -> Self {
+        self.start_block = block;
+        self
+    }
+
+    pub fn with_log_range(mut self, blocks: u64) -> Self {
+        self.log_range = blocks.max(1);
+        self
+    }
+
+    pub fn registry(&self) -> &PoolRegistry {
+        &self.registry
+    }
+
+    pub fn dexes(&self) -> Vec<Dex> {
+        self.factories.iter().map(|f| f.dex).collect()
+    }
+
+    /// Registers the pools created up to `head`; returns the new ones.
+    /// Progress up to a failed request is kept.
+    pub async fn scan(&mut self, head: u64) -> Result<Vec<RegisteredPool>> {
+        let mut found = Vec::new();
+        let mut scanned = Ok(());
+        for factory in self.factories.clone() {
+            scanned = self.scan_factory(&factory, head, &mut found).await;
+            if scanned.is_err() {
+                break;
+            }
+        }
+        self.persist();
+        scanned.map(|()| found)
+    }
+
+    async fn scan_factory(
+        &mut self,
+        factory: &Factory,
+        head: u64,
+        found: &mut Vec<RegisteredPool>,
+    ) -> Result<()> {
+        let mut from = self
+            .registry
+            .scanned(&factory.address)
+            .map_or(self.start_block, |b| b + 1);
+        while from <= head {
+            let to = head.min(from + self.log_range - 1);
+            let logs = self
+                .reader
+                .rpc()
+                .get_logs(&factory.address, POOL_CREATED_TOPIC, from, to)
+                .await
+                .with_context(|| format!("{} factory logs {from}..={to}", factory.dex))?;
+            for log in logs.iter().filter(|l| !l.removed) {
+                let created = match PoolCreated::decode(log) {
+                    Ok(Some(created)) => created,
+                    Ok(None) => continue,
+                    Err(e) => {
+                        warn!(dex = %factory.dex, block = log.block_number, error = %e, "bad PoolCreated log");
+                        continue;
+                    }
+                };
+                let pool = RegisteredPool {
+                    dex: factory.dex,
+                    block: log.block_number,
+                    created,
+                };
+                if self.registry.insert(pool.clone()) {
+                    found.push(pool);
+                }
+            }
+            self.registry.set_scanned(&factory.address, to);
+            from = to + 1;
+        }
+        Ok(())
+    }
+
+    fn persist(&self) {
+        if let Some(path) = &self.path {
+            if let Err(e) = self.registry.save(path) {
+                warn!(error = %e, "saving pool registry failed");
+            }
+        }
+    }
+
+    /// Reads `pools` at `block`, and into the store's registry any of their
+    /// tokens it lacks. Pools that fail to read, e.g. ones not yet
+    /// initialized, are left out.
+    async fn read(
+        &self,
+        pools: &[RegisteredPool],
+        block: u64,
+        store: &SharedPoolStore,
+    ) -> Result<Vec<TrackedPool>> {
+        let addresses: Vec<String> = pools.iter().map(|p| p.created.pool.clone()).collect();
+        let missing = store.read().await.tokens().missing(
+            pools
+                .iter()
+                .flat_map(|p| [&p.created.token0, &p.created.token1]),
+        );
+        let (states, tokens) = tokio::try_join!(
+            self.reader.read_states(&addresses, block),
+            self.reader.read_tokens(&missing, block),
+        )?;
+        let mut store = store.write().await;
+        let registry = store.tokens_mut();
+        for (address, token) in missing.iter().zip(tokens) {
+            match token {
+                Ok(token) => {
+                    registry.insert(token);
+                }
+                Err(e) => debug!(token = %address, error = %e, "token read failed"),
+            }
+        }
+        let mut out = Vec::new();
+        for (pool, state) in pools.iter().zip(states) {
+            let (Some(token0), Some(token1)) = (
+                registry.get(&pool.created.token0),
+                registry.get(&pool.created.token1),
+            ) else {
+                continue;
+            };
+            match state {
+                Ok(state) => out.push(TrackedPool {
+                    address: pool.created.pool.clone(),
+                    dex: pool.dex,
+                    token0: token0.clone(),
+                    token1: token1.clone(),
+                    state: state.into(),
+                    tvl_usd: None,
+                    block: Some(block),
+                    updated_at: Utc::now(),
+                }),
+                Err(e) => debug!(pool = %pool.created.pool, error = %e, "skipping pool"),
+            }
+        }
+        Ok(out)
+    }
+
+    /// Pools that pass the liquidity floor, priced against `known` and
+    /// each other.
+    fn deep_enough(&self, pools: Vec<TrackedPool>, known: &[TrackedPool]) -> Vec<TrackedPool> {
+        let prices = UsdPrices::from_pools(known.iter().chain(&pools));
+        pools
+            .into_iter()
+            .filter(|p| self.filter.deep_enough(p, &prices))
+            .collect()
+    }
+
+    /// Scans to the head and starts tracking the new pools that pass.
+    /// Returns how many did.
+    pub async fn poll(&mut self, store: &SharedPoolStore) -> Result<usize> {
+        let head = self.reader.rpc().block_number().await?;
+        let found = self.scan(head).await?;
+        let admitted: Vec<RegisteredPool> = found
+            .into_iter()
+            .filter(|p| self.filter.admits(&p.created))
+            .collect();
+        if admitted.is_empty() {
+            return Ok(0);
+        }
+        let pools = self.read(&admitted, head, store).await?;
+        let known: Vec<TrackedPool> = store.read().await.iter().cloned().collect();
+        let pools = self.deep_enough(pools, &known);
+        let count = pools.len();
+        let mut store = store.write().await;
+        for pool in pools {
+            info!(
+                dex = %pool.dex,
+                pool = %pool.address,
+                token0 = %pool.token0.symbol,
+                token1 = %pool.token1.symbol,
+                "tracking new pool"
+            );
+            store.upsert(pool);
+        }
+        Ok(count)
+    }
+
+    /// Scans to the head, re-reads every admitted pool and replaces each
+    /// venue's pools with the ones that pass.
+    pub async fn refresh(&mut self, store: &SharedPoolStore) -> Result<()> {
+        let head = self.reader.rpc().block_number().await?;
+        self.scan(head).await?;
+        let admitted: Vec<RegisteredPool> = self
+            .registry
+            .iter()
+            .filter(|p| self.filter.admits(&p.created))
+            .cloned()
+            .collect();
+        let pools = self.read(&admitted, head, store).await?;
+        let known: Vec<TrackedPool> = store.read().await.iter().cloned().collect();
+        let pools = self.deep_enough(pools, &known);
+        let mut store = store.write().await;
+        for dex in self.dexes() {
+            let venue: Vec<TrackedPool> = pools.iter().filter(|p| p.dex == dex).cloned().collect();
+            info!(dex = %dex, registered = self.registry.len(), pools = venue.len(), "refreshed discovered pools");
+            store.replace_dex(dex, venue);
+        }
+        Ok(())
+    }
+}
+
+/// Polls the factories every `poll` and refreshes the whole pool set every
+/// `refresh`, starting with a refresh immediately.
+pub fn spawn_discovery(
+    mut discovery: Discovery,
+    store: SharedPoolStore,
+    poll: Duration,
+    refresh: Duration,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(poll);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        let mut refreshed: Option<Instant> = None;
+        loop {
+            interval.tick().await;
+            if refreshed.is_none_or(|at| at.elapsed() >= refresh) {
+                match discovery.refresh(&store).await {
+                    Ok(()) => refreshed = Some(Instant::now()),
+                    Err(e) => warn!(error = %e, "pool discovery refresh failed"),
+                }
+                continue;
+            }
+            if let Err(e) = discovery.poll(&store).await {
+                warn!(error = %e, "pool discovery poll failed");
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::Client;
+
+    use super::*;
+    use crate::detector::WHYPE;
+    use crate::rpc::abi;
+    use crate::rpc::logs::Log;
+    use crate::rpc::RpcClient;
+    use crate::state::PoolStore;
+    use crate::testing::{
+        stable_tokens, tick_for_price, token, uint_word, v3_pool, MockChain, TempDir,
+    };
+
+    const PRJX_FACTORY: &str = "0x00000000000000000000000000000000000000f1";
+    const HYPERSWAP_FACTORY: &str = "0x00000000000000000000000000000000000000f2";
+
+    fn pool(
+        address: &str,
+        symbols: (&str, &str),
+        price: f64,
+        fee: u32,
+        liquidity: u128,
+    ) -> TrackedPool {
+        let (t0, t1) = (token(symbols.0), token(symbols.1));
+        let tick = tick_for_price(price, &t0, &t1);
+        v3_pool(address, Dex::Prjx, t0, t1, fee, tick, liquidity)
+    }
+
+    fn created(factory: &str, pool: &TrackedPool, block: u64) -> Log {
+        let address = |a: &str| abi::address_word(a).unwrap();
+        Log {
+            address: factory.to_string(),
+            topics: vec![
+                POOL_CREATED_TOPIC.to_string(),
+                abi::to_hex(&address(&pool.token0.address)),
+                abi::to_hex(&address(&pool.token1.address)),
+                abi::to_hex(&uint_word(pool.state.as_v3().unwrap().fee.into())),
+            ],
+            data: abi::to_hex(
+                &[
+                    abi::int_word(pool.state.as_v3().unwrap().tick_spacing.into()),
+                    address(&pool.address),
+                ]
+                .concat(),
+            ),
+            block_number: block,
+            block_hash: format!("0x{block:064x}"),
+            log_index: 0,
+            removed: false,
+        }
+    }
+
+    fn discovery(url: &str) -> Discovery {
+        let factories = vec![
+            Factory {
+                dex: Dex::Prjx,
+                address: PRJX_FACTORY.into(),
+            },
+            Factory {
+                dex: Dex::HyperSwap,
+                address: HYPERSWAP_FACTORY.into(),
+            },
+        ];
+        let filter = DiscoveryFilter {
+            tokens: ["WHYPE", "USDC", "UETH"]
+                .iter()
+                .map(|s| token(s).address)
+                .collect(),
+            fee_tiers: vec![500, 3000, 10000],
+            min_liquidity_usd: 10_000.0,
+        };
+        let reader = PoolReader::new(RpcClient::new(Client::new(), url));
+        Discovery::new(reader, factories, filter)
+            .with_start_block(5)
+            .with_log_range(40)
+    }
+
+    fn addresses(store: &PoolStore) -> Vec<(String, Dex)> {
+        let mut out: Vec<_> = store.iter().map(|p| (p.address.clone(), p.dex)).collect();
+        out.sort_by(|a, b| a.0.cmp(&b.0));
+        out
+    }
+
+    #[test]
+    fn filter_checks_tokens_fees_and_depth() {
+        let deep = pool(
+            "0x00000000000000000000000000000000000000a1",
+            ("WHYPE", "USDC"),
+            40.0,
+            3000,
+            10u128.pow(17),
+        );
+        let created = |p: &TrackedPool| PoolCreated {
+            pool: p.address.clone(),
+            token0: p.token0.address.clone(),
+            token1: p.token1.address.clone(),
+            fee: p.state.as_v3().unwrap().fee,
+            tick_spacing: p.state.as_v3().unwrap().tick_spacing,
+        };
+        let filter = DiscoveryFilter {
+            tokens: vec![WHYPE.into(), token("USDC").address],
+            fee_tiers: vec![3000],
+            min_liquidity_usd: 1_000_000.0,
+        };
+        assert!(filter.admits(&created(&deep)));
+        let mut other = created(&deep);
+        other.fee = 500;
+        assert!(!filter.admits(&other));
+        other.fee = 3000;
+        other.token1 = token("UETH").address;
+        assert!(!filter.admits(&other));
+        assert!(DiscoveryFilter::default().admits(&other));
+
+        // About $1.26M of virtual reserves at $40.
+        let prices = UsdPrices::from_pools([&deep]);
+        assert!(filter.deep_enough(&deep, &prices));
+        let thin = DiscoveryFilter {
+            min_liquidity_usd: 2_000_000.0,
+            ..filter.clone()
+        };
+        assert!(!thin.deep_enough(&deep, &prices));
+        // Unpriced tokens only pass without a floor.
+        assert!(!filter.deep_enough(&deep, &UsdPrices::new()));
+        assert!(DiscoveryFilter::default().deep_enough(&deep, &UsdPrices::new()));
+    }
+
+    #[test]
+    fn registry_persists_atomically() {
+        let dir = TempDir::new("registry");
+        let path = dir.path().join("pools.json");
+        assert!(PoolRegistry::load(&path).unwrap().is_empty());
+
+        let mut registry = PoolRegistry::new();
+        let pool = RegisteredPool {
+            dex: Dex::HyperSwap,
+            block: 12,
+            created: PoolCreated {
+                pool: "0x00000000000000000000000000000000000000a1".into(),
+                token0: WHYPE.into(),
+                token1: token("USDC").address,
+                fee: 500,
+                tick_spacing: 10,
+            },
+        };
+        assert!(registry.insert(pool.clone()));
+        assert!(!registry.insert(pool.clone()));
+        registry.set_scanned(HYPERSWAP_FACTORY, 99);
+        registry.save(&path).unwrap();
+
+        let loaded = PoolRegistry::load(&path).unwrap();
+        assert_eq!(loaded, registry);
+        assert_eq!(loaded.get(&pool.created.pool), Some(&pool));
+        assert_eq!(loaded.scanned(HYPERSWAP_FACTORY), Some(99));
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+
+        std::fs::write(&path, "{ truncated").unwrap();
+        assert!(PoolRegistry::load(&path).is_err());
+    }
+
+    #[tokio::test]
+    async fn discovers_filters_and_picks_up_new_pools() {
+        let deep = pool(
+            "0x00000000000000000000000000000000000000a1",
+            ("WHYPE", "USDC"),
+            40.0,
+            3000,
+            10u128.pow(17),
+        );
+        let thin = pool(
+            "0x00000000000000000000000000000000000000a2",
+            ("WHYPE", "USDC"),
+            40.0,
+            10000,
+            10u128.pow(12),
+        );
+        let unlisted = pool(
+            "0x00000000000000000000000000000000000000a3",
+            ("WHYPE", "FOO"),
+            2.0,
+            3000,
+            10u128.pow(20),
+        );
+        let off_tier = pool(
+            "0x00000000000000000000000000000000000000a4",
+            ("WHYPE", "USDC"),
+            40.0,
+            100,
+            10u128.pow(17),
+        );
+        let chain = MockChain::new(
+            vec![
+                deep.clone(),
+                thin.clone(),
+                unlisted.clone(),
+                off_tier.clone(),
+            ],
+            100,
+        );
+        for (i, p) in [&deep, &thin, &unlisted, &off_tier].into_iter().enumerate() {
+            chain.push_log(created(PRJX_FACTORY, p, 10 + 20 * i as u64));
+        }
+        let server = chain.serve().await;
+        let dir = TempDir::new("discovery");
+        let path = dir.path().join("pools.json");
+        let store = PoolStore::new().with_tokens(stable_tokens()).into_shared();
+
+        let mut d = discovery(&server.url).with_registry_path(&path).unwrap();
+        d.refresh(&store).await.unwrap();
+        assert_eq!(d.registry().len(), 4);
+        assert_eq!(
+            addresses(&*store.read().await),
+            vec![(deep.address.clone(), Dex::Prjx)]
+        );
+        // Blocks 5..=100 in ranges of 40, for each factory.
+        let get_logs = |s: &crate::testing::MockJsonServer| {
+            s.requests()
+                .iter()
+                .filter(|r| r["method"] == "eth_getLogs")
+                .count()
+        };
+        assert_eq!(get_logs(&server), 6);
+
+        // A pool created on HyperSwap after the refresh, priced off WHYPE.
+        let mut fresh = pool(
+            "0x00000000000000000000000000000000000000a5",
+            ("UETH", "WHYPE"),
+            100.0,
+            3000,
+            10u128.pow(20),
+        );
+        fresh.dex = Dex::HyperSwap;
+        chain.add_pool(fresh.clone());
+        chain.push_log(created(HYPERSWAP_FACTORY, &fresh, 120));
+        chain.set_head(130);
+        assert_eq!(d.poll(&store).await.unwrap(), 1);
+        {
+            let store = store.read().await;
+            assert_eq!(
+                addresses(&store),
+                vec![
+                    (deep.address.clone(), Dex::Prjx),
+                    (fresh.address.clone(), Dex::HyperSwap)
+                ]
+            );
+            let tracked = store.get(&fresh.address).unwrap();
+            assert_eq!(
+                (tracked.block, tracked.token0.symbol.as_str()),
+                (Some(130), "UETH")
+            );
+        }
+
+        // A restart resumes from the saved registry without rescanning.
+        assert_eq!(&PoolRegistry::load(&path).unwrap(), d.registry());
+        let before = get_logs(&server);
+        let mut restarted = discovery(&server.url).with_registry_path(&path).unwrap();
+        assert!(restarted.scan(130).await.unwrap().is_empty());
+        assert_eq!(get_logs(&server), before);
+        assert_eq!(restarted.registry().len(), 5);
+    }
+}
@@ -0,0 +1,525 @@
+//! Expected-value and risk model: port of `evaluateArb` (`src/eval/model.ts`).
+//!
+//! An attempt ends in one of five states: both legs fill, it fails before
+//! the first fill, it fails between legs (and the first leg is unwound), it
+//! is reorged or sandwiched, or nothing happens. Edge decays with latency,
+//! and the size is picked by a coarse line search over `EV - λ·Var` per
+//! second of latency.
+
+use serde::{Deserialize, Serialize};
+
+use crate::opportunity::Opportunity;
+use crate::profit_gate::Costs;
+
+/// Steps of the size line search, as in the TS model.
+const SIZE_STEPS: u32 = 12;
+/// Share of the slippage cost paid again to unwind a stranded first leg.
+const UNWIND_SLIP_FRACTION: f64 = 0.7;
+
+/// Fees charged on notional, and the flash-loan costs.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Fees {
+    /// Router plus LP fees.
+    pub total_fees_bps: f64,
+    pub flash_fee_bps: f64,
+    pub referral_bps: f64,
+    pub executor_fee_usd: f64,
+    pub flash_fixed_usd: f64,
+}
+
+/// Per-attempt costs that do not scale with size.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Frictions {
+    pub gas_usd_mean: f64,
+    pub gas_usd_std: f64,
+    /// Expected adverse-selection cost.
+    pub adverse_usd_mean: f64,
+    pub adverse_usd_std: f64,
+    pub extra_usd: f64,
+    /// Extra loss when the attempt is reorged or sandwiched.
+    pub mev_penalty_usd: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Latency {
+    /// Signal to inclusion.
+    pub latency_sec: f64,
+    pub edge_decay_bps_per_sec: f64,
+    /// Probability both legs fill with no latency.
+    pub base_fill_prob: f64,
+    /// Exponential decay of the fill probability per second.
+    pub theta: f64,
+}
+
+/// `slip_bps(size) = k · (size / liquidity_ref_usd)^alpha`, alpha at least 1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EmpiricalSlippage {
+    pub k: f64,
+    pub alpha: f64,
+    pub liquidity_ref_usd: f64,
+}
+
+impl EmpiricalSlippage {
+    /// Curve through an exactly quoted `slip_bps` at `size_usd`.
+    pub fn calibrated(size_usd: f64, slip_bps: f64, alpha: f64, liquidity_ref_usd: f64) -> Self {
+        let mut curve = Self {
+            k: 1.0,
+            alpha,
+            liquidity_ref_usd,
+        };
+        let unit = curve.bps(size_usd);
+        curve.k = if unit > 0.0 {
+            slip_bps.max(0.0) / unit
+        } else {
+            0.0
+        };
+        curve
+    }
+
+    pub fn bps(&self, size_usd: f64) -> f64 {
+        let ratio = size_usd.max(0.0) / self.liquidity_ref_usd.max(1e-9);
+        self.k.max(0.0) * ratio.powf(self.alpha.max(1.0))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Failures {
+    /// Transaction reverts or is replaced before anything fills.
+    pub fail_before_fill_prob: f64,
+    /// First leg fills, second does not.
+    pub fail_between_legs_prob: f64,
+    pub reorg_or_mev_prob: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EvInputs {
+    /// Edge at signal time, before fees and slippage.
+    pub edge_bps: f64,
+    /// Proposed size; the search always reaches at least this far.
+    pub notional_usd: f64,
+    pub fees: Fees,
+    pub frictions: Frictions,
+    pub latency: Latency,
+    pub slippage: EmpiricalSlippage,
+    pub failures: Failures,
+    pub flash_enabled: bool,
+    /// λ of the mean-variance penalty.
+    pub risk_aversion: f64,
+    /// Upper end of the size search; `notional_usd` when unset.
+    pub capital_usd: Option<f64>,
+}
+
+impl EvInputs {
+    /// Inputs for an exactly quoted opportunity. The size search only scales
+    /// down from the quoted size, along an empirical slippage curve through
+    /// the quoted point.
+    pub fn for_opportunity(opp: &Opportunity, params: &EvParams, costs: &Costs) -> Self {
+        let size = opp.size_usd.max(1e-9);
+        Self {
+            edge_bps: opp.costs.gross_usd / size * 1e4 + opp.slip_bps,
+            notional_usd: opp.size_usd,
+            fees: Fees {
+                total_fees_bps: opp.costs.lp_fees_usd / size * 1e4 + costs.router_fee_bps,
+                flash_fee_bps: costs.flash_fee_bps,
+                referral_bps: costs.referral_bps,
+                executor_fee_usd: costs.executor_fee_usd,
+                flash_fixed_usd: costs.flash_fixed_usd,
+            },
+            frictions: Frictions {
+                gas_usd_mean: opp.costs.gas_usd,
+                gas_usd_std: params.gas_usd_std,
+                adverse_usd_mean: params.adverse_usd_mean,
+                adverse_usd_std: params.adverse_usd_std,
+                extra_usd: costs.mev_tip_usd,
+                mev_penalty_usd: params.mev_penalty_usd,
+            },
+            latency: Latency {
+                latency_sec: params.latency_sec,
+                edge_decay_bps_per_sec: params.edge_decay_bps_per_sec,
+                base_fill_prob: params.base_fill_prob,
+                theta: params.fill_theta,
+            },
+            slippage: EmpiricalSlippage::calibrated(
+                opp.size_usd,
+                opp.slip_bps,
+                params.slip_alpha,
+                opp.liquidity_usd,
+            ),
+            failures: Failures {
+                fail_before_fill_prob: params.fail_before_fill_prob,
+                fail_between_legs_prob: params.fail_between_legs_prob,
+                reorg_or_mev_prob: params.reorg_or_mev_prob,
+            },
+            flash_enabled: costs.flash_enabled,
+            risk_aversion: params.risk_aversion,
+            capital_usd: None,
+        }
+    }
+}
+
+/// The best size found and its expected outcome.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct EvResult {
+    /// Expected PnL of one attempt.
+    pub net_usd_est: f64,
+    /// Risk-adjusted EV per second of latency; the ranking score.
+    pub ev_per_sec: f64,
+    pub size_opt_usd: f64,
+    /// Probability both legs fill.
+    pub p_success: f64,
+    pub slip_bps_eff: f64,
+    /// All-in cost at `size_opt_usd`, in bps of size.
+    pub breakeven_bps: f64,
+    pub gas_usd: f64,
+    pub seconds: f64,
+    pub flash_cost_usd: f64,
+    pub edge_eff_bps: f64,
+    /// Gross edge less router and LP fees.
+    pub after_router_lp_usd: f64,
+    pub slip_cost_usd: f64,
+}
+
+/// Env-tunable model parameters; variable names are shared with the TS
+/// eval service.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EvParams {
+    pub latency_sec: f64,
+    pub edge_decay_bps_per_sec: f64,
+    pub base_fill_prob: f64,
+    pub fill_theta: f64,
+    pub slip_alpha: f64,
+    pub gas_usd_std: f64,
+    pub adverse_usd_mean: f64,
+    pub adverse_usd_std: f64,
+    pub mev_penalty_usd: f64,
+    pub fail_before_fill_prob: f64,
+    pub fail_between_legs_prob: f64,
+    pub reorg_or_mev_prob: f64,
+    pub risk_aversion: f64,
+}
+
+impl Default for EvParams {
+    fn default() -> Self {
+        Self {
+            latency_sec: 1.0,
+            edge_decay_bps_per_sec: 3.0,
+            base_fill_prob: 0.9,
+            fill_theta: 0.15,
+            slip_alpha: 1.25,
+            gas_usd_std: 0.3,
+            adverse_usd_mean: 0.0,
+            adverse_usd_std: 0.3,
+            mev_penalty_usd: 0.0,
+            fail_before_fill_prob: 0.02,
+            fail_between_legs_prob: 0.01,
+            reorg_or_mev_prob: 0.0,
+            risk_aversion: 0.0,
+        }
+    }
+}
+
+pub fn decay_edge(edge_bps: f64, latency_sec: f64, decay_bps_per_sec: f64) -> f64 {
+    (edge_bps - latency_sec.max(0.0) * decay_bps_per_sec.max(0.0)).max(0.0)
+}
+
+pub fn fill_prob(base_fill_prob: f64, latency_sec: f64, theta: f64) -> f64 {
+    (base_fill_prob * (-theta.max(0.0) * latency_sec.max(0.0)).exp()).clamp(0.0, 1.0)
+}
+
+fn flash_cost_usd(size_usd: f64, fees: &Fees) -> f64 {
+    size_usd * (fees.flash_fee_bps + fees.referral_bps) / 1e4
+        + fees.executor_fee_usd
+        + fees.flash_fixed_usd
+}
+
+/// Size-dependent costs of one attempt.
+pub(crate) struct Attempt {
+    pub slip_bps: f64,
+    pub gross_usd: f64,
+    pub fees_usd: f64,
+    pub slip_cost_usd: f64,
+    pub flash_usd: f64,
+}
+
+impl Attempt {
+    pub fn at(inputs: &EvInputs, size_usd: f64) -> Self {
+        let slip_bps = inputs.slippage.bps(size_usd);
+        Self {
+            slip_bps,
+            gross_usd: edge_eff_bps(inputs) / 1e4 * size_usd,
+            fees_usd: inputs.fees.total_fees_bps / 1e4 * size_usd,
+            slip_cost_usd: slip_bps / 1e4 * size_usd,
+            flash_usd: if inputs.flash_enabled {
+                flash_cost_usd(size_usd, &inputs.fees)
+            } else {
+                0.0
+            },
+        }
+    }
+
+    /// Payoff of success, fail before fill, fail between legs and reorg/MEV,
+    /// for the given gas and adverse-selection costs.
+    pub fn payoffs(&self, frictions: &Frictions, gas_usd: f64, adverse_usd: f64) -> [f64; 4] {
+        [
+            self.gross_usd
+                - self.fees_usd
+                - self.slip_cost_usd
+                - gas_usd
+                - adverse_usd
+                - self.flash_usd
+                - frictions.extra_usd.max(0.0),
+            -gas_usd,
+            -self.slip_cost_usd * UNWIND_SLIP_FRACTION - gas_usd - adverse_usd,
+            -gas_usd - frictions.mev_penalty_usd.max(0.0),
+        ]
+    }
+}
+
+fn edge_eff_bps(inputs: &EvInputs) -> f64 {
+    decay_edge(
+        inputs.edge_bps,
+        inputs.latency.latency_sec,
+        inputs.latency.edge_decay_bps_per_sec,
+    )
+}
+
+/// Probabilities of the states [`Attempt::payoffs`] covers; the rest is
+/// the no-op outcome.
+pub(crate) fn state_probs(inputs: &EvInputs) -> [f64; 4] {
+    [
+        fill_prob(
+            inputs.latency.base_fill_prob,
+            inputs.latency.latency_sec,
+            inputs.latency.theta,
+        ),
+        inputs.failures.fail_before_fill_prob.clamp(0.0, 1.0),
+        inputs.failures.fail_between_legs_prob.clamp(0.0, 1.0),
+        inputs.failures.reorg_or_mev_prob.clamp(0.0, 1.0),
+    ]
+}
+
+/// Scans sizes up to the capital and returns the one with the highest
+/// risk-adjusted EV per second.
+pub fn evaluate(inputs: &EvInputs) -> EvResult {
+    let secs = inputs.latency.latency_sec.max(1e-3);
+    let f = &inputs.frictions;
+    let gas_mean = f.gas_usd_mean.max(0.0);
+    let adv_mean = f.adverse_usd_mean.max(0.0);
+    let exogenous_var = f.gas_usd_std.powi(2) + f.adverse_usd_std.powi(2);
+    let p = state_probs(inputs);
+    let lambda = inputs.risk_aversion.max(0.0);
+
+    let size0 = inputs.notional_usd.max(0.0);
+    let cap = size0.max(inputs.capital_usd.filter(|&c| c != 0.0).unwrap_or(size0));
+
+    let mut best: Option<EvResult> = None;
+    for i in 1..=SIZE_STEPS {
+        let size = f64::from(i) / f64::from(SIZE_STEPS) * cap;
+        let a = Attempt::at(inputs, size);
+        let payoff = a.payoffs(f, gas_mean, adv_mean);
+        let ev: f64 = p.iter().zip(&payoff).map(|(p, x)| p * x).sum();
+        let mix_var: f64 = p
+            .iter()
+            .zip(&payoff)
+            .map(|(p, x)| p * (x - ev).powi(2))
+            .sum();
+        let ev_per_sec = (ev - lambda * (mix_var + exogenous_var)) / secs;
+
+        if best.is_some_and(|b| ev_per_sec <= b.ev_per_sec) {
+            continue;
+        }
+        best = Some(EvResult {
+            net_usd_est: ev,
+            ev_per_sec,
+            size_opt_usd: size,
+            p_success: p[0],
+            slip_bps_eff: a.slip_bps,
+            breakeven_bps: a.slip_bps
+                + inputs.fees.total_fees_bps
+                + (gas_mean + adv_mean + a.flash_usd + f.extra_usd.max(0.0)) / size.max(1e-9) * 1e4,
+            gas_usd: gas_mean,
+            seconds: secs,
+            flash_cost_usd: a.flash_usd,
+            edge_eff_bps: edge_eff_bps(inputs),
+            after_router_lp_usd: a.gross_usd - a.fees_usd,
+            slip_cost_usd: a.slip_cost_usd,
+        });
+    }
+    best.expect("size search has at least one step")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `baseInputs()` of `tests/model.spec.ts`.
+    fn base_inputs() -> EvInputs {
+        EvInputs {
+            edge_bps: 25.0,
+            notional_usd: 10_000.0,
+            fees: Fees {
+                total_fees_bps: 8.0,
+                ..Fees::default()
+            },
+            frictions: Frictions {
+                gas_usd_mean: 0.2,
+                adverse_usd_mean: 0.5,
+                ..Frictions::default()
+            },
+            latency: Latency {
+                latency_sec: 0.5,
+                edge_decay_bps_per_sec: 1.5,
+                base_fill_prob: 0.85,
+                theta: 0.15,
+            },
+            slippage: EmpiricalSlippage {
+                k: 0.9,
+                alpha: 1.2,
+                liquidity_ref_usd: 1_500_000.0,
+            },
+            failures: Failures {
+                fail_before_fill_prob: 0.02,
+                fail_between_legs_prob: 0.01,
+                reorg_or_mev_prob: 0.0,
+            },
+            flash_enabled: false,
+            risk_aversion: 0.00005,
+            capital_usd: Some(20_000.0),
+        }
+    }
+
+    /// `makeInputs()` of `tests/model.flash.spec.ts`.
+    fn flash_spec_inputs() -> EvInputs {
+        let mut inp = base_inputs();
+        inp.edge_bps = 20.0;
+        inp.frictions.adverse_usd_mean = 0.8;
+        inp.latency.latency_sec = 0.6;
+        inp.latency.edge_decay_bps_per_sec = 1.0;
+        inp.slippage.k = 1.0;
+        inp.slippage.liquidity_ref_usd = 1_200_000.0;
+        inp.capital_usd = Some(25_000.0);
+        inp
+    }
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() <= 1e-9 * b.abs().max(1.0)
+    }
+
+    #[test]
+    fn breakeven_edge_gives_zero_ev() {
+        let mut inp = base_inputs();
+        let size = inp.notional_usd;
+        let slip_usd = inp.slippage.bps(size) / 1e4 * size;
+        let fee_usd = inp.fees.total_fees_bps / 1e4 * size;
+        let f = inp.frictions;
+        let p_s = fill_prob(
+            inp.latency.base_fill_prob,
+            inp.latency.latency_sec,
+            inp.latency.theta,
+        );
+        let fails = inp.failures.fail_between_legs_prob * slip_usd * 0.7
+            + inp.failures.fail_before_fill_prob * f.gas_usd_mean
+            + inp.failures.reorg_or_mev_prob * (f.gas_usd_mean + f.mev_penalty_usd);
+        let numerator = fee_usd
+            + slip_usd
+            + f.gas_usd_mean
+            + f.adverse_usd_mean
+            + f.extra_usd
+            + fails / p_s.max(1e-9);
+        // The TS case leaves out the latency decay, so its edge falls short
+        // of breakeven by `latency_sec * edge_decay_bps_per_sec`.
+        inp.edge_bps =
+            numerator / size * 1e4 + inp.latency.latency_sec * inp.latency.edge_decay_bps_per_sec;
+        inp.capital_usd = Some(size);
+        assert!(evaluate(&inp).ev_per_sec.abs() < 0.05);
+    }
+
+    #[test]
+    fn latency_lowers_fill_and_ev() {
+        let mut a = base_inputs();
+        a.latency.latency_sec = 0.2;
+        let mut b = base_inputs();
+        b.latency.latency_sec = 2.0;
+        let (ra, rb) = (evaluate(&a), evaluate(&b));
+        assert!(ra.p_success > rb.p_success);
+        assert!(ra.net_usd_est > rb.net_usd_est);
+    }
+
+    #[test]
+    fn size_search_is_bounded_by_capital() {
+        let mut inp = base_inputs();
+        inp.capital_usd = Some(50_000.0);
+        let r = evaluate(&inp);
+        assert!(r.size_opt_usd > 0.0 && r.size_opt_usd <= 50_000.0);
+    }
+
+    #[test]
+    fn flash_without_fees_matches_own_funds() {
+        let off = evaluate(&flash_spec_inputs());
+        let mut on = flash_spec_inputs();
+        on.flash_enabled = true;
+        let on = evaluate(&on);
+        assert!((off.ev_per_sec - on.ev_per_sec).abs() < 1e-6);
+        assert!((off.size_opt_usd - on.size_opt_usd).abs() < 1e-6);
+
+        let mut low = flash_spec_inputs();
+        low.risk_aversion = 0.0;
+        let mut high = flash_spec_inputs();
+        high.risk_aversion = 0.005;
+        assert!(evaluate(&high).size_opt_usd <= evaluate(&low).size_opt_usd);
+    }
+
+    #[test]
+    fn matches_ts_model_outputs() {
+        // Reference values from evaluateArb on the same inputs.
+        let r = evaluate(&base_inputs());
+        assert!(close(r.net_usd_est, 25.057854803451793));
+        assert!(close(r.ev_per_sec, 50.11019599048371));
+        assert!(close(r.size_opt_usd, 20_000.0));
+        assert!(close(r.p_success, 0.7885819633792699));
+        assert!(close(r.slip_bps_eff, 0.005060215276113));
+        assert!(close(r.breakeven_bps, 8.355060215276113));
+        assert!(close(r.after_router_lp_usd, 32.5));
+
+        let mut flash = base_inputs();
+        flash.flash_enabled = true;
+        flash.fees = Fees {
+            total_fees_bps: 8.0,
+            flash_fee_bps: 9.0,
+            referral_bps: 2.0,
+            executor_fee_usd: 0.3,
+            flash_fixed_usd: 0.1,
+        };
+        flash.frictions = Frictions {
+            gas_usd_mean: 0.2,
+            gas_usd_std: 0.3,
+            adverse_usd_mean: 0.5,
+            adverse_usd_std: 0.3,
+            extra_usd: 0.05,
+            mev_penalty_usd: 2.0,
+        };
+        flash.failures.reorg_or_mev_prob = 0.03;
+        flash.risk_aversion = 0.01;
+        let r = evaluate(&flash);
+        assert!(close(r.net_usd_est, 7.288189725587185));
+        assert!(close(r.ev_per_sec, 14.417160298462278));
+        assert!(close(r.flash_cost_usd, 22.4));
+        assert!(close(r.breakeven_bps, 19.580060215276113));
+
+        // Strong risk aversion settles on an interior size.
+        let mut averse = flash_spec_inputs();
+        averse.risk_aversion = 1.0;
+        let r = evaluate(&averse);
+        assert!(close(r.size_opt_usd, 6_250.0));
+        assert!(close(r.net_usd_est, 4.7432626810685825));
+        assert!(close(r.ev_per_sec, 4.07316691166991));
+    }
+
+    #[test]
+    fn calibrated_slippage_hits_quote() {
+        let curve = EmpiricalSlippage::calibrated(5_000.0, 12.0, 1.25, 200_000.0);
+        assert!(close(curve.bps(5_000.0), 12.0));
+        assert!(curve.bps(10_000.0) > 24.0);
+        assert_eq!(EmpiricalSlippage::calibrated(0.0, 12.0, 1.25, 1e6).k, 0.0);
+    }
+}
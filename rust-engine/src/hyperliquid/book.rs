@@ -0,0 +1,390 @@
+//! Local mirror of Hyperliquid L1 order books.
+//!
+//! Every `l2Book` message is a full snapshot of a coin's top levels. One is
+//! only taken if it is newer than the book it replaces, each side is sorted
+//! best first with positive sizes, and the best bid is below the best ask;
+//! anything else is dropped and counted. Trades are sequenced by trade id,
+//! so the ones a reconnect replays are not counted twice.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use super::info::AssetCtx;
+use super::{L2Book, Level, Trade};
+
+/// Taker direction: a buy walks the asks, a sell the bids.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
+/// Average result of taking `size` from one side of a book.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fill {
+    pub size: f64,
+    pub avg_px: f64,
+    /// Price of the last level reached.
+    pub worst_px: f64,
+    pub notional: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderBook {
+    pub coin: String,
+    pub time: u64,
+    pub bids: Vec<Level>,
+    pub asks: Vec<Level>,
+}
+
+impl OrderBook {
+    /// The book of `snapshot`, if it is well formed.
+    pub fn from_snapshot(snapshot: L2Book) -> Result<Self> {
+        let [bids, asks] = snapshot.levels;
+        for (side, levels, descending) in [("bid", &bids, true), ("ask", &asks, false)] {
+            if let Some(l) = levels
+                .iter()
+                .find(|l| !(l.px.is_finite() && l.px > 0.0 && l.sz.is_finite() && l.sz > 0.0))
+            {
+                bail!("{side} level {} x {} is not positive", l.px, l.sz);
+            }
+            if let Some(w) = levels.windows(2).find(|w| {
+                if descending {
+                    w[0].px <= w[1].px
+                } else {
+                    w[0].px >= w[1].px
+                }
+            }) {
+                bail!("{side}s out of order at {} then {}", w[0].px, w[1].px);
+            }
+        }
+        if let (Some(bid), Some(ask)) = (bids.first(), asks.first()) {
+            if bid.px >= ask.px {
+                bail!("crossed book: bid {} >= ask {}", bid.px, ask.px);
+            }
+        }
+        Ok(Self {
+            coin: snapshot.coin,
+            time: snapshot.time,
+            bids,
+            asks,
+        })
+    }
+
+    pub fn best_bid(&self) -> Option<&Level> {
+        self.bids.first()
+    }
+
+    pub fn best_ask(&self) -> Option<&Level> {
+        self.asks.first()
+    }
+
+    pub fn mid(&self) -> Option<f64> {
+        Some((self.best_bid()?.px + self.best_ask()?.px) / 2.0)
+    }
+
+    pub fn spread_bps(&self) -> Option<f64> {
+        let (bid, ask) = (self.best_bid()?.px, self.best_ask()?.px);
+        Some((ask - bid) / self.mid()? * 1e4)
+    }
+
+    /// Taking `size` on `side`, or `None` if the mirrored levels hold less.
+    pub fn depth_for_size(&self, side: Side, size: f64) -> Option<Fill> {
+        if size.is_nan() || size <= 0.0 {
+            return None;
+        }
+        let (mut left, mut notional) = (size, 0.0);
+        for level in self.side(side) {
+            let take = left.min(level.sz);
+            notional += take * level.px;
+            left -= take;
+            if left <= 0.0 {
+                return Some(Fill {
+                    size,
+                    avg_px: notional / size,
+                    worst_px: level.px,
+                    notional,
+                });
+            }
+        }
+        None
+    }
+
+    /// Value of every mirrored level on `side`.
+    pub fn notional(&self, side: Side) -> f64 {
+        self.side(side).iter().map(|l| l.px * l.sz).sum()
+    }
+
+    /// Size available on `side` within `bps` of the best price.
+    pub fn size_within_bps(&self, side: Side, bps: f64) -> f64 {
+        let levels = self.side(side);
+        let Some(best) = levels.first() else {
+            return 0.0;
+        };
+        levels
+            .iter()
+            .take_while(|l| (l.px - best.px).abs() <= best.px * bps / 1e4)
+            .map(|l| l.sz)
+            .sum()
+    }
+
+    /// The levels a taker on `side` walks, best first.
+    fn side(&self, side: Side) -> &[Level] {
+        match side {
+            Side::Buy => &self.asks,
+            Side::Sell => &self.bids,
+        }
+    }
+}
+
+/// Message counts since start.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct FeedStats {
+    pub snapshots: u64,
+    /// Malformed or out-of-order snapshots dropped.
+    pub rejected: u64,
+    pub trades: u64,
+    /// Trades seen before, e.g. replayed after a reconnect.
+    pub duplicate_trades: u64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BookMirror {
+    books: HashMap<String, OrderBook>,
+    mids: HashMap<String, f64>,
+    last_trade: HashMap<String, Trade>,
+    asset_ctxs: HashMap<String, AssetCtx>,
+    stats: FeedStats,
+}
+
+pub type SharedBookMirror = Arc<RwLock<BookMirror>>;
+
+impl BookMirror {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn shared() -> SharedBookMirror {
+        Arc::new(RwLock::new(Self::new()))
+    }
+
+    /// Replaces the coin's book with `snapshot` if it is valid and newer.
+    pub fn apply_book(&mut self, snapshot: L2Book) -> Result<()> {
+        let applied = match self.books.get(&snapshot.coin) {
+            Some(book) if snapshot.time <= book.time => Err(anyhow::anyhow!(
+                "{} snapshot at {} is not after {}",
+                snapshot.coin,
+                snapshot.time,
+                book.time
+            )),
+            _ => OrderBook::from_snapshot(snapshot),
+        };
+        match applied {
+            Ok(book) => {
+                self.stats.snapshots += 1;
+                self.books.insert(book.coin.clone(), book);
+                Ok(())
+            }
+            Err(e) => {
+                self.stats.rejected += 1;
+                Err(e)
+            }
+        }
+    }
+
+    /// Records the trades newer than the last one seen for their coin and
+    /// returns how many that was.
+    pub fn apply_trades(&mut self, trades: Vec<Trade>) -> usize {
+        let mut fresh = 0;
+        for trade in trades {
+            if self
+                .last_trade
+                .get(&trade.coin)
+                .is_some_and(|last| trade.tid <= last.tid)
+            {
+                self.stats.duplicate_trades += 1;
+                continue;
+            }
+            fresh += 1;
+            self.last_trade.insert(trade.coin.clone(), trade);
+        }
+        self.stats.trades += fresh as u64;
+        fresh
+    }
+
+    pub fn apply_mids(&mut self, mids: HashMap<String, f64>) {
+        self.mids.extend(mids);
+    }
+
+    /// Replaces the funding and prices of each coin in `ctxs`.
+    pub fn apply_asset_ctxs(&mut self, ctxs: Vec<AssetCtx>) {
+        self.asset_ctxs
+            .extend(ctxs.into_iter().map(|c| (c.coin.clone(), c)));
+    }
+
+    /// Drops every book, e.g. while the feed is disconnected; mids, trade
+    /// sequence, asset contexts and stats stay.
+    pub fn clear_books(&mut self) {
+        self.books.clear();
+    }
+
+    pub fn book(&self, coin: &str) -> Option<&OrderBook> {
+        self.books.get(coin)
+    }
+
+    /// The coin's book mid, else its latest `allMids` entry.
+    pub fn mid(&self, coin: &str) -> Option<f64> {
+        self.book(coin)
+            .and_then(OrderBook::mid)
+            .or_else(|| self.mids.get(coin).copied())
+    }
+
+    pub fn best_bid(&self, coin: &str) -> Option<&Level> {
+        self.book(coin)?.best_bid()
+    }
+
+    pub fn best_ask(&self, coin: &str) -> Option<&Level> {
+        self.book(coin)?.best_ask()
+    }
+
+    pub fn depth_for_size(&self, coin: &str, side: Side, size: f64) -> Option<Fill> {
+        self.book(coin)?.depth_for_size(side, size)
+    }
+
+    pub fn last_trade(&self, coin: &str) -> Option<&Trade> {
+        self.last_trade.get(coin)
+    }
+
+    pub fn asset_ctx(&self, coin: &str) -> Option<&AssetCtx> {
+        self.asset_ctxs.get(coin)
+    }
+
+    pub fn stats(&self) -> FeedStats {
+        self.stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn level(px: f64, sz: f64) -> Level {
+        Level { px, sz, n: 1 }
+    }
+
+    fn snapshot(time: u64, bids: &[(f64, f64)], asks: &[(f64, f64)]) -> L2Book {
+        let side = |levels: &[(f64, f64)]| levels.iter().map(|&(p, s)| level(p, s)).collect();
+        L2Book {
+            coin: "HYPE".into(),
+            time,
+            levels: [side(bids), side(asks)],
+        }
+    }
+
+    fn trade(tid: u64) -> Trade {
+        Trade {
+            coin: "HYPE".into(),
+            side: "B".into(),
+            px: 40.0,
+            sz: 1.0,
+            time: tid,
+            tid,
+        }
+    }
+
+    #[test]
+    fn validates_snapshots() {
+        let book = OrderBook::from_snapshot(snapshot(
+            1,
+            &[(39.9, 10.0), (39.8, 20.0)],
+            &[(40.1, 5.0), (40.3, 50.0)],
+        ))
+        .unwrap();
+        assert_eq!(book.best_bid().unwrap().px, 39.9);
+        assert_eq!(book.best_ask().unwrap().px, 40.1);
+        assert!((book.mid().unwrap() - 40.0).abs() < 1e-12);
+        assert!((book.spread_bps().unwrap() - 50.0).abs() < 1e-9);
+
+        for (bad, reason) in [
+            (
+                snapshot(1, &[(39.8, 1.0), (39.9, 1.0)], &[]),
+                "bids out of order",
+            ),
+            (
+                snapshot(1, &[], &[(40.3, 1.0), (40.1, 1.0)]),
+                "asks out of order",
+            ),
+            (snapshot(1, &[(40.2, 1.0)], &[(40.1, 1.0)]), "crossed"),
+            (snapshot(1, &[(39.9, 0.0)], &[(40.1, 1.0)]), "not positive"),
+        ] {
+            let e = OrderBook::from_snapshot(bad).unwrap_err();
+            assert!(e.to_string().contains(reason), "{e}");
+        }
+    }
+
+    #[test]
+    fn walks_levels_for_size() {
+        let book = OrderBook::from_snapshot(snapshot(
+            1,
+            &[(39.9, 10.0), (39.8, 20.0)],
+            &[(40.1, 5.0), (40.3, 50.0)],
+        ))
+        .unwrap();
+        let fill = book.depth_for_size(Side::Buy, 10.0).unwrap();
+        assert!((fill.notional - (5.0 * 40.1 + 5.0 * 40.3)).abs() < 1e-9);
+        assert!((fill.avg_px - 40.2).abs() < 1e-9);
+        assert_eq!(fill.worst_px, 40.3);
+        let fill = book.depth_for_size(Side::Sell, 10.0).unwrap();
+        assert_eq!((fill.avg_px, fill.worst_px), (39.9, 39.9));
+        assert!(book.depth_for_size(Side::Sell, 30.5).is_none());
+        assert!(book.depth_for_size(Side::Buy, 0.0).is_none());
+
+        assert_eq!(book.size_within_bps(Side::Buy, 10.0), 5.0);
+        assert_eq!(book.size_within_bps(Side::Buy, 60.0), 55.0);
+        assert_eq!(book.size_within_bps(Side::Sell, 30.0), 30.0);
+    }
+
+    #[test]
+    fn mirror_sequences_books_and_trades() {
+        let mut mirror = BookMirror::new();
+        mirror
+            .apply_book(snapshot(10, &[(39.9, 1.0)], &[(40.1, 1.0)]))
+            .unwrap();
+        // Older or repeated snapshots, and broken ones, leave the book be.
+        for stale in [5, 10] {
+            let e = mirror
+                .apply_book(snapshot(stale, &[(30.0, 1.0)], &[(31.0, 1.0)]))
+                .unwrap_err();
+            assert!(e.to_string().contains("is not after 10"), "{e}");
+        }
+        assert!(mirror
+            .apply_book(snapshot(11, &[(41.0, 1.0)], &[(40.0, 1.0)]))
+            .is_err());
+        assert_eq!(mirror.best_bid("HYPE").unwrap().px, 39.9);
+        assert_eq!(
+            mirror.stats(),
+            FeedStats {
+                snapshots: 1,
+                rejected: 3,
+                ..FeedStats::default()
+            }
+        );
+
+        assert_eq!(mirror.apply_trades(vec![trade(1), trade(2)]), 2);
+        assert_eq!(mirror.apply_trades(vec![trade(2), trade(3)]), 1);
+        assert_eq!(mirror.last_trade("HYPE").unwrap().tid, 3);
+        assert_eq!(mirror.stats().duplicate_trades, 1);
+
+        mirror.apply_mids(HashMap::from([("HYPE".into(), 41.0), ("BTC".into(), 1e5)]));
+        assert!((mirror.mid("HYPE").unwrap() - 40.0).abs() < 1e-12);
+        mirror.clear_books();
+        assert_eq!(mirror.mid("HYPE"), Some(41.0));
+        assert_eq!(mirror.mid("BTC"), Some(1e5));
+        assert!(mirror.depth_for_size("HYPE", Side::Buy, 1.0).is_none());
+    }
+}
@@ -0,0 +1,282 @@
+//! Hyperliquid info WebSocket client.
+//!
+//! One connection subscribes to `allMids` and to `l2Book` and `trades` for
+//! each configured coin, and feeds every message into the [`BookMirror`].
+//! The server drops idle connections, so a `ping` goes out every
+//! [`L1Feed::with_ping_interval`]. While disconnected the mirror holds no
+//! books: the first snapshot after a reconnect replaces them.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::Message;
+use tracing::{debug, info, warn};
+
+use super::book::SharedBookMirror;
+use super::{L2Book, Trade};
+
+const RECONNECT_MIN: Duration = Duration::from_millis(500);
+const RECONNECT_MAX: Duration = Duration::from_secs(30);
+const PING_INTERVAL: Duration = Duration::from_secs(30);
+
+pub struct L1Feed {
+    url: String,
+    coins: Vec<String>,
+    mirror: SharedBookMirror,
+    ping_every: Duration,
+    backoff: Duration,
+}
+
+impl L1Feed {
+    /// Mirrors the books of `coins` from the info WebSocket at `url`.
+    pub fn new(url: impl Into<String>, coins: Vec<String>, mirror: SharedBookMirror) -> Self {
+        Self {
+            url: url.into(),
+            coins,
+            mirror,
+            ping_every: PING_INTERVAL,
+            backoff: RECONNECT_MIN,
+        }
+    }
+
+    pub fn with_ping_interval(mut self, every: Duration) -> Self {
+        self.ping_every = every;
+        self
+    }
+
+    fn subscriptions(&self) -> Vec<Value> {
+        let mut subs = vec![json!({ "type": "allMids" })];
+        for coin in &self.coins {
+            subs.push(json!({ "type": "l2Book", "coin": coin }));
+            subs.push(json!({ "type": "trades", "coin": coin }));
+        }
+        subs
+    }
+
+    /// One connection, until it fails.
+    async fn session(&mut self) -> Result<()> {
+        let (ws, _) = tokio_tungstenite::connect_async(self.url.as_str())
+            .await
+            .with_context(|| format!("connect {}", self.url))?;
+        let (mut sink, mut source) = ws.split();
+
+        let subs = self.subscriptions();
+        let mut pending = subs.len();
+        for sub in subs {
+            let request = json!({ "method": "subscribe", "subscription": sub });
+            sink.send(Message::Text(request.to_string())).await?;
+        }
+
+        let start = tokio::time::Instant::now() + self.ping_every;
+        let mut ping = tokio::time::interval_at(start, self.ping_every);
+        loop {
+            let msg = tokio::select! {
+                msg = source.next() => msg,
+                _ = ping.tick() => {
+                    let request = json!({ "method": "ping" });
+                    sink.send(Message::Text(request.to_string())).await?;
+                    continue;
+                }
+            };
+            let Some(msg) = msg else {
+                bail!("connection closed");
+            };
+            let text = match msg? {
+                Message::Text(text) => text,
+                Message::Ping(payload) => {
+                    sink.send(Message::Pong(payload)).await?;
+                    continue;
+                }
+                Message::Close(_) => bail!("closed by server"),
+                _ => continue,
+            };
+            let msg: Value = serde_json::from_str(&text).context("decode WebSocket message")?;
+            let data = &msg["data"];
+            match msg["channel"].as_str().unwrap_or_default() {
+                "subscriptionResponse" => {
+                    pending = pending.saturating_sub(1);
+                    if pending == 0 {
+                        info!(url = %self.url, coins = ?self.coins, "subscribed to Hyperliquid L1 feed");
+                        self.backoff = RECONNECT_MIN;
+                    }
+                }
+                "l2Book" => {
+                    let book: L2Book =
+                        serde_json::from_value(data.clone()).context("decode l2Book message")?;
+                    if let Err(e) = self.mirror.write().await.apply_book(book) {
+                        warn!(error = %e, "dropped L1 book snapshot");
+                    }
+                }
+                "trades" => {
+                    let trades: Vec<Trade> =
+                        serde_json::from_value(data.clone()).context("decode trades message")?;
+                    self.mirror.write().await.apply_trades(trades);
+                }
+                "allMids" => {
+                    let mids: HashMap<String, String> =
+                        serde_json::from_value(data["mids"].clone())
+                            .context("decode allMids message")?;
+                    let mids = mids
+                        .into_iter()
+                        .filter_map(|(coin, mid)| Some((coin, mid.parse().ok()?)))
+                        .collect();
+                    self.mirror.write().await.apply_mids(mids);
+                }
+                "pong" => {}
+                "error" => bail!("server error: {data}"),
+                _ => debug!(%text, "unexpected WebSocket message"),
+            }
+        }
+    }
+}
+
+/// Runs `feed` forever, reconnecting with exponential backoff.
+pub fn spawn_l1_feed(mut feed: L1Feed) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            if let Err(e) = feed.session().await {
+                warn!(url = %feed.url, error = %e, "Hyperliquid L1 feed failed");
+            }
+            feed.mirror.write().await.clear_books();
+            tokio::time::sleep(feed.backoff).await;
+            feed.backoff = (feed.backoff * 2).min(RECONNECT_MAX);
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hyperliquid::book::{BookMirror, FeedStats};
+    use crate::hyperliquid::Side;
+    use crate::testing::MockWsServer;
+
+    /// Info WebSocket frames as the server sends them, one per line.
+    fn recorded(name: &str) -> Vec<Value> {
+        let path = format!(
+            "{}/tests/fixtures/hyperliquid/{name}",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        std::fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .filter(|l| !l.trim().is_empty())
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect()
+    }
+
+    async fn info_server() -> MockWsServer {
+        MockWsServer::start(|req| match req["method"].as_str() {
+            Some("ping") => json!({ "channel": "pong" }),
+            _ => json!({ "channel": "subscriptionResponse", "data": req }),
+        })
+        .await
+    }
+
+    fn count(server: &MockWsServer, method: &str) -> usize {
+        server
+            .requests()
+            .iter()
+            .filter(|r| r["method"] == method)
+            .count()
+    }
+
+    async fn wait_for(mirror: &SharedBookMirror, done: impl Fn(&BookMirror) -> bool) {
+        for _ in 0..200 {
+            if done(&*mirror.read().await) {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("timed out: {:?}", mirror.read().await.stats());
+    }
+
+    async fn wait_for_subscribes(server: &MockWsServer, n: usize) {
+        for _ in 0..200 {
+            if count(server, "subscribe") == n {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("timed out: {:?}", server.requests());
+    }
+
+    #[tokio::test]
+    async fn replays_recorded_session() {
+        let server = info_server().await;
+        let mirror = BookMirror::shared();
+        let feed = L1Feed::new(&server.url, vec!["HYPE".into()], mirror.clone())
+            .with_ping_interval(Duration::from_millis(20));
+        let handle = spawn_l1_feed(feed);
+        wait_for_subscribes(&server, 3).await;
+        let subs: Vec<Value> = server
+            .requests()
+            .into_iter()
+            .filter(|r| r["method"] == "subscribe")
+            .map(|r| r["subscription"].clone())
+            .collect();
+        assert_eq!(
+            subs,
+            vec![
+                json!({ "type": "allMids" }),
+                json!({ "type": "l2Book", "coin": "HYPE" }),
+                json!({ "type": "trades", "coin": "HYPE" }),
+            ]
+        );
+
+        let frames = recorded("hype_session.jsonl");
+        for frame in &frames {
+            server.push(frame.clone());
+        }
+        wait_for(&mirror, |m| m.mid("ETH").is_some()).await;
+
+        let m = mirror.read().await;
+        // Two good snapshots; one older than the book, one crossed.
+        assert_eq!(
+            m.stats(),
+            FeedStats {
+                snapshots: 2,
+                rejected: 2,
+                trades: 3,
+                duplicate_trades: 1,
+            }
+        );
+        let book = m.book("HYPE").unwrap();
+        assert_eq!(book.time, 1_717_000_000_500);
+        assert_eq!(m.best_bid("HYPE").unwrap().px, 39.914);
+        assert_eq!(m.best_ask("HYPE").unwrap().sz, 92.4);
+        let fill = m.depth_for_size("HYPE", Side::Buy, 600.0).unwrap();
+        assert_eq!(fill.worst_px, 39.922);
+        assert!(fill.avg_px > 39.919 && fill.avg_px < 39.922);
+        assert!(m.depth_for_size("HYPE", Side::Sell, 1e6).is_none());
+        assert_eq!(m.last_trade("HYPE").unwrap().tid, 958_112_003);
+        assert_eq!(m.mid("BTC"), Some(67_012.5));
+        drop(m);
+
+        assert!(count(&server, "ping") > 0);
+        handle.abort();
+    }
+
+    #[tokio::test]
+    async fn drops_books_and_resubscribes_after_errors() {
+        let server = info_server().await;
+        let mirror = BookMirror::shared();
+        let handle = spawn_l1_feed(L1Feed::new(
+            &server.url,
+            vec!["HYPE".into()],
+            mirror.clone(),
+        ));
+        wait_for_subscribes(&server, 3).await;
+        server.push(recorded("hype_session.jsonl")[0].clone());
+        wait_for(&mirror, |m| m.book("HYPE").is_some()).await;
+
+        server.push(json!({ "channel": "error", "data": "Invalid subscription" }));
+        wait_for(&mirror, |m| m.book("HYPE").is_none()).await;
+        wait_for_subscribes(&server, 6).await;
+        handle.abort();
+    }
+}
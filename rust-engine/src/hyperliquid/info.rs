@@ -0,0 +1,202 @@
+//! Hyperliquid info API over HTTP: perp funding and mark prices.
+//!
+//! `metaAndAssetCtxs` returns the perp universe and, index for index, each
+//! asset's context: the current hourly funding rate, mark and oracle prices
+//! and open interest. Funding only changes on the hour, so a slow poll into
+//! the [`BookMirror`](super::BookMirror) keeps it current.
+
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use chrono::Utc;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::json;
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+use super::book::SharedBookMirror;
+use super::deserialize_decimal;
+
+/// Mainnet info endpoint.
+pub const MAINNET_INFO_URL: &str = "https://api.hyperliquid.xyz/info";
+
+/// One perp's funding and prices.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssetCtx {
+    pub coin: String,
+    /// Funding paid by longs to shorts per hour, as a fraction of notional at
+    /// the oracle price; negative when shorts pay.
+    pub funding: f64,
+    pub mark_px: f64,
+    pub oracle_px: f64,
+    /// Coins.
+    pub open_interest: f64,
+    /// When the context was fetched, in milliseconds.
+    pub time: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct Meta {
+    universe: Vec<Asset>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Asset {
+    name: String,
+    #[serde(default)]
+    is_delisted: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawCtx {
+    #[serde(deserialize_with = "deserialize_decimal")]
+    funding: f64,
+    #[serde(deserialize_with = "deserialize_decimal")]
+    mark_px: f64,
+    #[serde(deserialize_with = "deserialize_decimal")]
+    oracle_px: f64,
+    #[serde(deserialize_with = "deserialize_decimal")]
+    open_interest: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct InfoClient {
+    http: Client,
+    url: String,
+}
+
+impl InfoClient {
+    pub fn new(http: Client, url: impl Into<String>) -> Self {
+        Self {
+            http,
+            url: url.into(),
+        }
+    }
+
+    /// Context of every listed perp.
+    pub async fn perp_asset_ctxs(&self) -> Result<Vec<AssetCtx>> {
+        let resp = self
+            .http
+            .post(&self.url)
+            .json(&json!({ "type": "metaAndAssetCtxs" }))
+            .send()
+            .await
+            .with_context(|| format!("POST {}", self.url))?;
+        let status = resp.status();
+        if !status.is_success() {
+            bail!("info API returned HTTP {status}");
+        }
+        let (meta, ctxs): (Meta, Vec<RawCtx>) =
+            resp.json().await.context("decode metaAndAssetCtxs")?;
+        if meta.universe.len() != ctxs.len() {
+            bail!("{} assets but {} contexts", meta.universe.len(), ctxs.len());
+        }
+        let time = Utc::now().timestamp_millis().max(0) as u64;
+        Ok(meta
+            .universe
+            .into_iter()
+            .zip(ctxs)
+            .filter(|(asset, _)| !asset.is_delisted)
+            .map(|(asset, ctx)| AssetCtx {
+                coin: asset.name,
+                funding: ctx.funding,
+                mark_px: ctx.mark_px,
+                oracle_px: ctx.oracle_px,
+                open_interest: ctx.open_interest,
+                time,
+            })
+            .collect())
+    }
+}
+
+/// Refreshes the perp contexts in `mirror` from `client` every `every`,
+/// starting immediately.
+pub fn spawn_funding_poll(
+    client: InfoClient,
+    mirror: SharedBookMirror,
+    every: Duration,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(every);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            match client.perp_asset_ctxs().await {
+                Ok(ctxs) => {
+                    debug!(assets = ctxs.len(), "refreshed Hyperliquid funding");
+                    mirror.write().await.apply_asset_ctxs(ctxs);
+                }
+                Err(e) => warn!(url = %client.url, error = %e, "Hyperliquid funding poll failed"),
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hyperliquid::BookMirror;
+    use crate::testing::{Fault, MockJsonServer};
+    use serde_json::Value;
+
+    fn fixture() -> Value {
+        let path = format!(
+            "{}/tests/fixtures/hyperliquid/meta_and_asset_ctxs.json",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn reads_listed_perp_contexts() {
+        let body = fixture();
+        let server = MockJsonServer::start(move |_| body.clone()).await;
+        let client = InfoClient::new(Client::new(), &server.url);
+        let ctxs = client.perp_asset_ctxs().await.unwrap();
+        assert_eq!(
+            server.requests(),
+            vec![json!({ "type": "metaAndAssetCtxs" })]
+        );
+
+        // The delisted asset between BTC and HYPE is dropped.
+        let coins: Vec<&str> = ctxs.iter().map(|c| c.coin.as_str()).collect();
+        assert_eq!(coins, vec!["BTC", "HYPE"]);
+        let hype = &ctxs[1];
+        assert_eq!(hype.funding, 0.0000125);
+        assert_eq!(hype.mark_px, 40.012);
+        assert_eq!(hype.oracle_px, 40.005);
+        assert_eq!(hype.open_interest, 25_431_012.45);
+        assert!(hype.time > 0);
+
+        let mirror = BookMirror::shared();
+        let handle = spawn_funding_poll(client, mirror.clone(), Duration::from_secs(60));
+        for _ in 0..200 {
+            if mirror.read().await.asset_ctx("HYPE").is_some() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(
+            mirror.read().await.asset_ctx("BTC").unwrap().funding,
+            -0.0000031
+        );
+        handle.abort();
+    }
+
+    #[tokio::test]
+    async fn rejects_mismatched_and_failed_responses() {
+        let mut body = fixture();
+        body[1].as_array_mut().unwrap().pop();
+        let server = MockJsonServer::start(move |_| body.clone()).await;
+        let client = InfoClient::new(Client::new(), &server.url);
+        let err = client.perp_asset_ctxs().await.unwrap_err();
+        assert_eq!(err.to_string(), "3 assets but 2 contexts");
+
+        server.set_fault(Fault::Status(429));
+        let err = client.perp_asset_ctxs().await.unwrap_err();
+        assert!(err.to_string().contains("HTTP 429"), "{err}");
+    }
+}
@@ -0,0 +1,91 @@
+//! Hyperliquid L1 market data.
+//!
+//! [`feed`] subscribes to the info WebSocket's `l2Book`, `trades` and
+//! `allMids` channels and keeps a [`BookMirror`] current: a validated order
+//! book per coin, the latest trades and every mid. [`info`] polls perp
+//! funding and mark prices into the same mirror.
+
+pub mod book;
+pub mod feed;
+pub mod info;
+
+pub use book::{BookMirror, Fill, OrderBook, SharedBookMirror, Side};
+pub use feed::{spawn_l1_feed, L1Feed};
+pub use info::{spawn_funding_poll, AssetCtx, InfoClient};
+
+use std::fmt;
+
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// Mainnet info WebSocket.
+pub const MAINNET_WS_URL: &str = "wss://api.hyperliquid.xyz/ws";
+
+/// The two kinds of L1 book a coin name can refer to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum L1Market {
+    Spot,
+    Perp,
+}
+
+impl L1Market {
+    /// Spot books are named `@<index>` or `BASE/QUOTE`; anything else is a
+    /// perp.
+    pub fn of(coin: &str) -> Self {
+        if coin.starts_with('@') || coin.contains('/') {
+            Self::Spot
+        } else {
+            Self::Perp
+        }
+    }
+}
+
+impl fmt::Display for L1Market {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Spot => "spot",
+            Self::Perp => "perp",
+        })
+    }
+}
+
+/// One price level of an `l2Book` message.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct Level {
+    #[serde(deserialize_with = "deserialize_decimal")]
+    pub px: f64,
+    #[serde(deserialize_with = "deserialize_decimal")]
+    pub sz: f64,
+    /// Orders resting at the level.
+    pub n: u32,
+}
+
+/// Payload of an `l2Book` message: the top levels of one coin, bids best
+/// first then asks best first.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct L2Book {
+    pub coin: String,
+    /// Exchange time in milliseconds.
+    pub time: u64,
+    pub levels: [Vec<Level>; 2],
+}
+
+/// One fill of a `trades` message.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Trade {
+    pub coin: String,
+    /// Taker side: `B` bought, `A` sold.
+    pub side: String,
+    #[serde(deserialize_with = "deserialize_decimal")]
+    pub px: f64,
+    #[serde(deserialize_with = "deserialize_decimal")]
+    pub sz: f64,
+    pub time: u64,
+    pub tid: u64,
+}
+
+/// Serde adapter for the decimal strings Hyperliquid sends prices and sizes as.
+pub fn deserialize_decimal<'de, D: Deserializer<'de>>(d: D) -> Result<f64, D::Error> {
+    let s = String::deserialize(d)?;
+    s.parse().map_err(serde::de::Error::custom)
+}
@@ -0,0 +1,28 @@
+//! Off-chain arbitrage engine for PRJX and HyperSwap on HyperEVM.
+
+pub mod amm;
+pub mod basis;
+pub mod carry;
+pub mod config;
+pub mod cycles;
+pub mod detector;
+pub mod discovery;
+pub mod ev;
+pub mod hyperliquid;
+pub mod montecarlo;
+pub mod opportunity;
+pub mod pricing;
+pub mod profit_gate;
+pub mod reload;
+pub mod router;
+pub mod rpc;
+pub mod screen;
+pub mod sizing;
+pub mod state;
+pub mod status;
+pub mod subgraph;
+pub mod tokens;
+pub mod univ3;
+
+#[cfg(test)]
+pub(crate) mod testing;
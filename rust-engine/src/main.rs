@@ -1,26 +1,23 @@
 use anyhow::Result;
 use dotenvy::dotenv;
 use reqwest::Client;
-use serde::{Deserialize, Serialize};
-use serde_json::json;
-use std::env;
+use std::time::Duration;
 use tracing::{info, Level};
 use tracing_subscriber::FmtSubscriber;
-use std::time::Duration;
+
+use hyperliquid_arb_engine::config::{self, EngineConfig};
+use hyperliquid_arb_engine::detector;
+use hyperliquid_arb_engine::discovery::{self, Discovery, Factory};
+use hyperliquid_arb_engine::hyperliquid::{self, BookMirror, InfoClient, L1Feed};
+use hyperliquid_arb_engine::reload::{self, ConfigHandle};
+use hyperliquid_arb_engine::rpc::stream::{self, LogStream};
+use hyperliquid_arb_engine::rpc::{pool_reader, provider, PoolReader, RpcClient};
+use hyperliquid_arb_engine::state::{Dex, PoolStore};
+use hyperliquid_arb_engine::status::{self, StatusConfig, StatusReporter};
+use hyperliquid_arb_engine::subgraph::{self, SubgraphClient};
+use hyperliquid_arb_engine::tokens::{self, TokenRegistry};
 
 use redis::aio::ConnectionManager;
-use redis::AsyncCommands;
-
-#[derive(Debug, Serialize, Deserialize, Clone)]
-struct Opportunity {
-    pair: String,
-    spread_bps: f64,
-    est_gas_usd: f64,
-    est_profit_usd: f64,
-    liquidity_usd: f64,
-    confidence: f64,
-    route: String,
-}
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -31,22 +28,25 @@ async fn main() -> Result<()> {
         .finish();
     tracing::subscriber::set_global_default(subscriber).ok();
 
-    info!("starting hyperliquid arbitrage engine (stub)");
+    info!("starting hyperliquid arbitrage engine");
 
-    let subgraph = env::var("PRJX_SUBGRAPH").unwrap_or_else(|_|
-        "https://api.goldsky.com/api/public/project_cmbbm2iwckb1b01t39xed236t/subgraphs/uniswap-v3-hyperevm-position/prod/gn".to_string()
+    let config = EngineConfig::from_env()?;
+    info!(
+        strategies = config.strategies.len(),
+        chain = %config.runtime.chain_name,
+        "loaded config"
     );
 
     // Optional Redis wiring
-    let redis_url = env::var("REDIS_URL").ok();
-    let redis_channel = env::var("REDIS_CHANNEL").unwrap_or_else(|_| "arb:realtime".to_string());
     let mut redis_mgr: Option<ConnectionManager> = None;
-    if let Some(url) = redis_url.clone() {
+    let mut redis_client: Option<redis::Client> = None;
+    if let Some(url) = config.engine.redis_url.clone() {
         match redis::Client::open(url) {
             Ok(client) => match client.get_tokio_connection_manager().await {
                 Ok(conn) => {
                     info!("connected to redis");
                     redis_mgr = Some(conn);
+                    redis_client = Some(client);
                 }
                 Err(e) => info!(error = %e, "failed to connect redis"),
             },
@@ -58,31 +58,161 @@ async fn main() -> Result<()> {
 
     let client = Client::new();
 
-    let query = r#"query Pools($first: Int!) { pools(first: $first, orderBy: volumeUSD, orderDirection: desc) { id liquidity sqrtPrice tick feeTier } }"#;
-    let body = json!({"query": query, "variables": {"first": 1}});
-
-    let resp = client.post(&subgraph).json(&body).send().await?;
-    let status = resp.status();
-    let text = resp.text().await.unwrap_or_default();
-    info!(%status, %text, "sample PRJX GraphQL response");
-
-    // TODO: connect to HyperSwap SDK/Router via RPC and price checks
-    // TODO: opportunity detection + signaling to backend
-
-    // Heartbeat publishing loop (if Redis connected)
-    if let Some(mut conn) = redis_mgr {
-        loop {
-            let payload = json!({
-                "ts": chrono::Utc::now().to_rfc3339(),
-                "pnl": 12.34,
-                "opportunities": [],
-                "engine": {"status": "running"}
-            })
-            .to_string();
-            let _: () = conn.publish::<_, _, ()>(&redis_channel, payload).await.unwrap_or(());
-            tokio::time::sleep(Duration::from_millis(800)).await;
+    // HyperEVM JSON-RPC, when configured
+    let rpc = config.engine.rpc_url.clone().map(|rpc_url| {
+        let mut urls = vec![rpc_url];
+        urls.extend(config.engine.rpc_fallback_urls.iter().cloned());
+        let rpc = RpcClient::with_providers(client.clone(), urls)
+            .with_head_quorum(config.engine.rpc_head_quorum);
+        if !config.engine.rpc_fallback_urls.is_empty() {
+            provider::spawn_health_probe(rpc.clone(), Duration::from_secs(5));
         }
+        rpc
+    });
+
+    // Pool discovery: factory logs for venues with a factory configured,
+    // subgraphs for the rest (PRJX always, HyperSwap when configured)
+    let tokens = TokenRegistry::load(&config::config_dir().join(tokens::TOKENS_FILE))?;
+    info!(known = tokens.len(), "loaded token registry");
+    let store = PoolStore::new().with_tokens(tokens).into_shared();
+    let refresh_every = config.engine.pool_refresh;
+    let mut factories = Vec::new();
+    for (dex, factory) in [
+        (Dex::Prjx, &config.engine.prjx_factory),
+        (Dex::HyperSwap, &config.engine.hyperswap_factory),
+    ] {
+        if let Some(address) = factory.clone() {
+            factories.push(Factory { dex, address });
+        }
+    }
+    if let (Some(rpc), false) = (&rpc, factories.is_empty()) {
+        let discovery = Discovery::new(
+            PoolReader::new(rpc.clone()),
+            factories.clone(),
+            config.engine.discovery.clone(),
+        )
+        .with_registry_path(&config.engine.pool_registry_path)?
+        .with_start_block(config.engine.factory_start_block);
+        info!(
+            registered = discovery.registry().len(),
+            path = %config.engine.pool_registry_path.display(),
+            "loaded pool registry"
+        );
+        discovery::spawn_discovery(
+            discovery,
+            store.clone(),
+            config.engine.discovery_poll,
+            refresh_every,
+        );
+    }
+    let discovered = |dex: Dex| factories.iter().any(|f| f.dex == dex);
+    if !discovered(Dex::Prjx) {
+        subgraph::spawn_refresh(
+            SubgraphClient::new(
+                client.clone(),
+                config.engine.prjx_subgraph.clone(),
+                Dex::Prjx,
+            ),
+            store.clone(),
+            refresh_every,
+        );
+    }
+    if let (Some(url), false) = (
+        config.engine.hyperswap_subgraph.clone(),
+        discovered(Dex::HyperSwap),
+    ) {
+        subgraph::spawn_refresh(
+            SubgraphClient::new(client.clone(), url, Dex::HyperSwap),
+            store.clone(),
+            refresh_every,
+        );
+    }
+
+    // Engine status, published below when Redis is connected
+    let mut venues = vec![Dex::Prjx];
+    if config.engine.hyperswap_subgraph.is_some() || discovered(Dex::HyperSwap) {
+        venues.push(Dex::HyperSwap);
+    }
+    let reporter = StatusReporter::shared(StatusConfig {
+        top_n: config.engine.status_top_n,
+        venues,
+        max_refresh_lag: refresh_every * 4,
+        pricing: config.engine.pricing.clone(),
+    });
+
+    // Head-block pool state straight from the chain, when enabled: streamed
+    // logs over WebSocket, else periodic re-reads over HTTP
+    if let Some(rpc) = rpc {
+        let reader = PoolReader::new(rpc);
+        if let Some(ws_url) = config.engine.ws_url.clone() {
+            let stream = LogStream::new(ws_url, reader, store.clone())
+                .with_reorg_window(config.engine.reorg_window)
+                .with_status(reporter.clone());
+            stream::spawn_log_stream(stream);
+        } else if let Some(every) = config.engine.rpc_pool_refresh {
+            pool_reader::spawn_refresh(reader, store.clone(), every);
+        }
+    }
+
+    // Hyperliquid L1 order books, for the configured coins and the basis and
+    // carry markets, plus perp funding for carry
+    let books = BookMirror::shared();
+    let mut hl_coins = config.engine.hl_coins.clone();
+    let engine = &config.engine;
+    for coin in engine.basis.coins().chain(engine.carry.coins()) {
+        if !hl_coins.iter().any(|c| c == coin) {
+            hl_coins.push(coin.to_string());
+        }
+    }
+    if !hl_coins.is_empty() {
+        hyperliquid::spawn_l1_feed(L1Feed::new(
+            config.engine.hl_ws_url.clone(),
+            hl_coins,
+            books.clone(),
+        ));
+    }
+    if !engine.carry.markets.is_empty() {
+        hyperliquid::spawn_funding_poll(
+            InfoClient::new(client.clone(), engine.hl_info_url.clone()),
+            books.clone(),
+            Duration::from_secs(30),
+        );
+    }
+
+    // Live config: file changes and control-channel commands swap it in place
+    let config_handle = ConfigHandle::new(config.clone(), config::config_dir(), |key| {
+        std::env::var(key).ok()
+    });
+    reload::spawn_file_watch(config_handle.clone(), Duration::from_secs(2));
+    if let Some(client) = redis_client {
+        reload::spawn_control_listener(
+            client,
+            config.engine.control_channel.clone(),
+            config_handle.clone(),
+            reporter.clone(),
+        );
+    }
+
+    // Opportunity detection over the latest pool snapshots
+    let (_, opportunities) = detector::spawn_detection(
+        config_handle.subscribe(),
+        store.clone(),
+        books.clone(),
+        Duration::from_millis(400),
+    );
+
+    // Status snapshot publishing (if Redis connected)
+    if let Some(conn) = redis_mgr {
+        status::spawn_publisher(
+            conn,
+            config.engine.redis_channel.clone(),
+            reporter.clone(),
+            store.clone(),
+            opportunities,
+            Duration::from_millis(800),
+        );
     }
 
+    tokio::signal::ctrl_c().await?;
     Ok(())
 }
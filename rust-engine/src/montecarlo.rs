@@ -0,0 +1,259 @@
+//! Seeded Monte Carlo of one attempt's payout, and its tail risk.
+//!
+//! Port of `simulatePayouts` and `varCvar` (`src/eval/montecarlo.ts`), with
+//! the payoffs of [`ev::evaluate`] so the sample mean converges to its
+//! `net_usd_est` at the same size. Samples are drawn in fixed-size blocks,
+//! each from its own PCG stream keyed by the seed and block index, so a seed
+//! gives the same draws whatever the thread count.
+
+use rand::{Rng, RngExt};
+use rand_pcg::Pcg64;
+use serde::{Deserialize, Serialize};
+
+use crate::ev::{self, Attempt, EvInputs};
+
+/// Samples per PCG stream.
+const BLOCK: usize = 1024;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MonteCarloConfig {
+    pub samples: usize,
+    pub seed: u64,
+    /// Confidence levels to report VaR/CVaR at, e.g. 0.95.
+    pub alphas: Vec<f64>,
+    /// Worker threads; 0 uses every core.
+    pub threads: usize,
+}
+
+impl Default for MonteCarloConfig {
+    fn default() -> Self {
+        Self {
+            samples: 2000,
+            seed: 42,
+            alphas: vec![0.95, 0.99],
+            threads: 0,
+        }
+    }
+}
+
+/// Loss tail of the payout distribution at one confidence level.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TailRisk {
+    pub alpha: f64,
+    /// Payout at the `1 - alpha` quantile; negative is a loss.
+    pub var_usd: f64,
+    /// Mean payout at or below `var_usd`.
+    pub cvar_usd: f64,
+}
+
+/// Payout of `config.samples` independent attempts at `inputs.notional_usd`.
+pub fn simulate_payouts(inputs: &EvInputs, config: &MonteCarloConfig) -> Vec<f64> {
+    let mut out = vec![0.0; config.samples];
+    if out.is_empty() {
+        return out;
+    }
+    let attempt = Attempt::at(inputs, inputs.notional_usd.max(0.0));
+    let probs = state_cdf(inputs);
+
+    let blocks = config.samples.div_ceil(BLOCK);
+    let threads = match config.threads {
+        0 => std::thread::available_parallelism().map_or(1, |n| n.get()),
+        n => n,
+    }
+    .clamp(1, blocks);
+    let per_thread = blocks.div_ceil(threads);
+
+    std::thread::scope(|scope| {
+        for (t, span) in out.chunks_mut(per_thread * BLOCK).enumerate() {
+            let (attempt, probs) = (&attempt, &probs);
+            scope.spawn(move || {
+                for (j, block) in span.chunks_mut(BLOCK).enumerate() {
+                    let stream = (t * per_thread + j) as u128;
+                    let mut rng = Pcg64::new(u128::from(config.seed), stream);
+                    for x in block {
+                        *x = draw(&mut rng, inputs, attempt, probs);
+                    }
+                }
+            });
+        }
+    });
+    out
+}
+
+/// VaR and CVaR at `alpha`, as `varCvar` computes them.
+pub fn var_cvar(values: &[f64], alpha: f64) -> TailRisk {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    tail_of_sorted(&sorted, alpha)
+}
+
+/// Simulates `inputs` and reports the tail at every configured alpha.
+pub fn tail_risk(inputs: &EvInputs, config: &MonteCarloConfig) -> Vec<TailRisk> {
+    let mut payouts = simulate_payouts(inputs, config);
+    payouts.sort_by(f64::total_cmp);
+    config
+        .alphas
+        .iter()
+        .map(|&alpha| tail_of_sorted(&payouts, alpha))
+        .collect()
+}
+
+fn tail_of_sorted(sorted: &[f64], alpha: f64) -> TailRisk {
+    if sorted.is_empty() {
+        return TailRisk {
+            alpha,
+            var_usd: 0.0,
+            cvar_usd: 0.0,
+        };
+    }
+    let idx =
+        (((1.0 - alpha) * sorted.len() as f64).floor().max(0.0) as usize).min(sorted.len() - 1);
+    let tail = &sorted[..=idx];
+    TailRisk {
+        alpha,
+        var_usd: sorted[idx],
+        cvar_usd: tail.iter().sum::<f64>() / tail.len() as f64,
+    }
+}
+
+/// Cumulative state probabilities, normalised when they exceed one.
+fn state_cdf(inputs: &EvInputs) -> [f64; 4] {
+    let p = ev::state_probs(inputs);
+    let total: f64 = p.iter().sum();
+    let scale = if total > 1.0 { 1.0 / total } else { 1.0 };
+    let mut acc = 0.0;
+    p.map(|p| {
+        acc += p * scale;
+        acc
+    })
+}
+
+fn draw(rng: &mut impl Rng, inputs: &EvInputs, attempt: &Attempt, cdf: &[f64; 4]) -> f64 {
+    let f = &inputs.frictions;
+    let gas = (f.gas_usd_mean + f.gas_usd_std * randn(rng)).max(0.0);
+    let adverse = (f.adverse_usd_mean + f.adverse_usd_std * randn(rng)).max(0.0);
+    let r: f64 = rng.random();
+    let payoffs = attempt.payoffs(f, gas, adverse);
+    cdf.iter()
+        .position(|&c| r < c)
+        .map_or(0.0, |state| payoffs[state])
+}
+
+/// Standard normal via Box-Muller.
+fn randn(rng: &mut impl Rng) -> f64 {
+    let u = 1.0 - rng.random::<f64>();
+    let v: f64 = rng.random();
+    (-2.0 * u.ln()).sqrt() * (2.0 * std::f64::consts::PI * v).cos()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ev::{EmpiricalSlippage, Failures, Fees, Frictions, Latency};
+
+    fn inputs() -> EvInputs {
+        EvInputs {
+            edge_bps: 25.0,
+            notional_usd: 10_000.0,
+            fees: Fees {
+                total_fees_bps: 8.0,
+                ..Fees::default()
+            },
+            frictions: Frictions {
+                gas_usd_mean: 0.2,
+                gas_usd_std: 0.3,
+                adverse_usd_mean: 0.5,
+                adverse_usd_std: 0.3,
+                extra_usd: 0.0,
+                mev_penalty_usd: 1.0,
+            },
+            latency: Latency {
+                latency_sec: 0.5,
+                edge_decay_bps_per_sec: 1.5,
+                base_fill_prob: 0.85,
+                theta: 0.15,
+            },
+            slippage: EmpiricalSlippage {
+                k: 0.9,
+                alpha: 1.2,
+                liquidity_ref_usd: 1_500_000.0,
+            },
+            failures: Failures {
+                fail_before_fill_prob: 0.05,
+                fail_between_legs_prob: 0.03,
+                reorg_or_mev_prob: 0.02,
+            },
+            flash_enabled: false,
+            risk_aversion: 0.0,
+            capital_usd: None,
+        }
+    }
+
+    fn config(threads: usize) -> MonteCarloConfig {
+        MonteCarloConfig {
+            samples: 5000,
+            seed: 7,
+            alphas: vec![0.95, 0.99],
+            threads,
+        }
+    }
+
+    #[test]
+    fn seed_fixes_draws_across_thread_counts() {
+        let one = simulate_payouts(&inputs(), &config(1));
+        assert_eq!(one, simulate_payouts(&inputs(), &config(4)));
+        assert_eq!(one, simulate_payouts(&inputs(), &config(0)));
+        let other = MonteCarloConfig {
+            seed: 8,
+            ..config(1)
+        };
+        assert_ne!(one, simulate_payouts(&inputs(), &other));
+    }
+
+    #[test]
+    fn seeded_quantiles_are_exact() {
+        let tails = tail_risk(&inputs(), &config(3));
+        assert_eq!(tails.len(), 2);
+        assert_eq!(tails[0].alpha, 0.95);
+        // Failed attempts make up the tail; the 99% level reaches the MEV losses.
+        assert_eq!(tails[0].var_usd, -0.4859908590050323);
+        assert_eq!(tails[0].cvar_usd, -1.014135003192758);
+        assert_eq!(tails[1].var_usd, -1.2988931179763021);
+        assert_eq!(tails[1].cvar_usd, -1.4932714288060178);
+        assert!(tails[1].cvar_usd <= tails[0].cvar_usd);
+        assert!(tails[0].cvar_usd <= tails[0].var_usd);
+    }
+
+    #[test]
+    fn mean_converges_to_ev() {
+        let mut quiet = inputs();
+        quiet.frictions.gas_usd_std = 0.0;
+        quiet.frictions.adverse_usd_std = 0.0;
+        quiet.capital_usd = Some(quiet.notional_usd);
+        let cfg = MonteCarloConfig {
+            samples: 200_000,
+            ..config(0)
+        };
+        let payouts = simulate_payouts(&quiet, &cfg);
+        let mean = payouts.iter().sum::<f64>() / payouts.len() as f64;
+        // At full capital the search lands on `notional_usd`.
+        let ev = ev::evaluate(&quiet);
+        assert_eq!(ev.size_opt_usd, quiet.notional_usd);
+        assert!(
+            (mean - ev.net_usd_est).abs() < 0.05,
+            "{mean} vs {}",
+            ev.net_usd_est
+        );
+    }
+
+    #[test]
+    fn var_cvar_matches_ts() {
+        let values: Vec<f64> = (1..=20).map(f64::from).rev().collect();
+        // floor(0.05 * 20) = 1: VaR is the second-worst value.
+        let t = var_cvar(&values, 0.95);
+        assert_eq!((t.var_usd, t.cvar_usd), (2.0, 1.5));
+        let t = var_cvar(&values, 0.5);
+        assert_eq!((t.var_usd, t.cvar_usd), (11.0, 6.0));
+        assert_eq!(var_cvar(&[], 0.95).var_usd, 0.0);
+    }
+}
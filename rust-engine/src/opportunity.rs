@@ -0,0 +1,182 @@
+//! The `Opportunity` record the engine publishes.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::hyperliquid::{L1Market, Side};
+use crate::montecarlo::TailRisk;
+use crate::profit_gate::CostBreakdown;
+use crate::state::{Dex, Token};
+use crate::univ3::U256;
+
+/// The tokens an opportunity trades: `base` priced in `quote`, as token0
+/// and token1 of the pools.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct TokenPair {
+    pub base: Token,
+    pub quote: Token,
+}
+
+impl TokenPair {
+    pub fn tokens(&self) -> [&Token; 2] {
+        [&self.base, &self.quote]
+    }
+}
+
+impl fmt::Display for TokenPair {
+    /// `WHYPE/USDC` style symbols.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.base.symbol, self.quote.symbol)
+    }
+}
+
+/// One swap of a route. A leg split across parallel pools of its pair
+/// names the pool taking the largest share and lists every share in `split`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct RouteLeg {
+    pub dex: Dex,
+    pub pool: String,
+    pub token_in: String,
+    pub token_out: String,
+    /// Pool fee in hundredths of a bip.
+    pub fee: u32,
+    /// LP fee this leg pays at the quoted size, across every pool of a split.
+    #[serde(default)]
+    pub fee_usd: f64,
+    /// Per-pool amounts when the leg is split, largest share first; empty
+    /// when it all goes through `pool`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub split: Vec<SplitFill>,
+}
+
+/// One pool's share of a split leg, quoted for `amount_in`. Executed, it
+/// spends the same share of whatever input the leg receives; see
+/// [`crate::router::leg_calldata`].
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SplitFill {
+    pub dex: Dex,
+    pub pool: String,
+    /// Pool fee in hundredths of a bip.
+    pub fee: u32,
+    /// Raw units of the leg's `token_in`.
+    pub amount_in: U256,
+    /// Quoted output, raw units of the leg's `token_out`.
+    pub amount_out: U256,
+}
+
+/// Taker order on a Hyperliquid L1 book that closes out the pool legs.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct L1Order {
+    pub market: L1Market,
+    pub coin: String,
+    pub side: Side,
+    /// Whole units of the coin.
+    pub size: f64,
+    pub avg_px: f64,
+    /// Price of the deepest level the order reaches.
+    pub worst_px: f64,
+    pub fee_bps: f64,
+    /// Taker fee at `avg_px`.
+    pub fee_usd: f64,
+}
+
+/// Ordered swaps that start and end in the same token, or, with an
+/// `l1_order`, pool swaps hedged by an order on the L1 book.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct Route {
+    pub legs: Vec<RouteLeg>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub l1_order: Option<L1Order>,
+}
+
+impl fmt::Display for Route {
+    /// `PRJX->HyperSwap` style summary, as used by the dashboard and strategy
+    /// filters. An L1 buy comes before the pool legs, an L1 sell after them:
+    /// `HL-perp->PRJX`, `PRJX->HL-spot`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut venues: Vec<String> = self.legs.iter().map(|l| l.dex.to_string()).collect();
+        if let Some(order) = &self.l1_order {
+            let venue = format!("HL-{}", order.market);
+            match order.side {
+                Side::Buy => venues.insert(0, venue),
+                Side::Sell => venues.push(venue),
+            }
+        }
+        write!(f, "{}", venues.join("->"))
+    }
+}
+
+/// What an opportunity earns from.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum OpportunityKind {
+    /// A price difference captured as soon as the route fills.
+    #[default]
+    Arbitrage,
+    /// Funding collected over a holding period; see [`Carry`].
+    Carry,
+}
+
+/// Terms of a funding carry: spot bought on the pool and the perp shorted on
+/// L1, held for `horizon_hours`, then both unwound.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Carry {
+    pub horizon_hours: f64,
+    /// Hourly funding the short receives, as a fraction of notional.
+    pub funding_rate: f64,
+    /// Funding collected over the horizon at the oracle price.
+    pub funding_usd: f64,
+    /// Slippage and fees of entering and exiting both legs.
+    pub round_trip_usd: f64,
+    /// Net profit over the horizon as a yearly rate on `size_usd`.
+    pub apr: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Opportunity {
+    #[serde(default)]
+    pub kind: OpportunityKind,
+    pub pair: TokenPair,
+    /// Mid-price spread between the legs, net of every leg's pool fee.
+    pub spread_bps: f64,
+    /// Same as `costs.gas_usd`.
+    pub est_gas_usd: f64,
+    /// Output minus input at `amount_in`, after pool fees and price impact
+    /// but before gas.
+    pub est_profit_usd: f64,
+    /// Smallest in-range liquidity across the legs.
+    pub liquidity_usd: f64,
+    /// 0..1, from how fresh the pool states behind the quote are.
+    pub confidence: f64,
+    /// Value of `amount_in`.
+    pub size_usd: f64,
+    /// Profit-maximising input to the first pool leg, raw units of its
+    /// `token_in`.
+    pub amount_in: U256,
+    /// Quoted output of the last pool leg for `amount_in`.
+    pub expected_out: U256,
+    /// Route output per unit of input at the margin after the trade.
+    pub marginal_price: f64,
+    /// Execution price versus mid at `amount_in`, LP fees excluded.
+    pub slip_bps: f64,
+    /// Gross edge, every execution cost and the resulting net profit.
+    pub costs: CostBreakdown,
+    /// Clears the profit gate of at least one enabled strategy.
+    pub viable: bool,
+    /// Why the closest strategy rejected it; empty when viable.
+    pub rejections: Vec<String>,
+    /// Risk-adjusted expected value per second of latency, from the EV model.
+    pub ev_per_sec: f64,
+    /// Probability both legs fill.
+    pub p_success: f64,
+    /// Size the EV model's search settled on; may differ from `size_usd`,
+    /// which maximises the exact quote alone.
+    pub ev_size_usd: f64,
+    /// Simulated VaR/CVaR of one attempt at `ev_size_usd`.
+    pub tail_risk: Vec<TailRisk>,
+    pub route: Route,
+    /// Set for [`OpportunityKind::Carry`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub carry: Option<Carry>,
+}
@@ -0,0 +1,317 @@
+//! USD prices for tracked tokens, derived from a graph of pools anchored on
+//! stablecoins.
+//!
+//! Stablecoins are $1: the tokens the tokens file flags
+//! [`stable`](crate::state::Token::stable) by address, never one that only
+//! reports a stablecoin's symbol. Each further hop prices the tokens paired
+//! with ones priced at the previous hop, averaging every such pool weighted
+//! by its USD depth. A pool's mid may sit up to its fee away from the market before
+//! arbitrage pulls it back, so every hop widens the price's interval by the
+//! pool fee on top of the disagreement between the pools averaged. Pools
+//! shallower than [`PricingConfig::min_depth_usd`] are not used at all: a
+//! token reachable only through thin pools stays unpriced.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::state::TrackedPool;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PricingConfig {
+    /// USD depth below which a pool is too thin to price through.
+    pub min_depth_usd: f64,
+    /// Most pools between a stablecoin and a priced token.
+    pub max_hops: usize,
+}
+
+impl Default for PricingConfig {
+    fn default() -> Self {
+        Self {
+            min_depth_usd: 10_000.0,
+            max_hops: 3,
+        }
+    }
+}
+
+/// USD price of one whole token and the interval it is known to.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PriceQuote {
+    pub usd: f64,
+    pub low: f64,
+    pub high: f64,
+    /// Pools between the token and a stablecoin; 0 for the anchors.
+    pub hops: usize,
+    /// Summed USD depth of the paths priced through; `None` for prices not
+    /// derived from pools.
+    pub depth_usd: Option<f64>,
+}
+
+impl PriceQuote {
+    pub fn exact(usd: f64) -> Self {
+        Self {
+            usd,
+            low: usd,
+            high: usd,
+            hops: 0,
+            depth_usd: None,
+        }
+    }
+
+    /// Half the interval as a fraction of the price.
+    pub fn rel_error(&self) -> f64 {
+        if self.usd > 0.0 {
+            (self.high - self.low) / 2.0 / self.usd
+        } else {
+            f64::INFINITY
+        }
+    }
+}
+
+/// One pool's view of a token's price, through an already priced token.
+struct Estimate {
+    usd: f64,
+    rel_error: f64,
+    depth_usd: f64,
+    path_depth_usd: f64,
+}
+
+/// USD prices per whole token, keyed by lowercase address.
+#[derive(Debug, Clone, Default)]
+pub struct UsdPrices {
+    prices: HashMap<String, PriceQuote>,
+}
+
+impl UsdPrices {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(&mut self, address: &str, usd: f64) {
+        self.prices
+            .insert(address.to_lowercase(), PriceQuote::exact(usd));
+    }
+
+    pub fn get(&self, address: &str) -> Option<f64> {
+        self.quote(address).map(|q| q.usd)
+    }
+
+    pub fn quote(&self, address: &str) -> Option<&PriceQuote> {
+        self.prices.get(address)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &PriceQuote)> {
+        self.prices.iter().map(|(a, q)| (a.as_str(), q))
+    }
+
+    pub fn len(&self) -> usize {
+        self.prices.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.prices.is_empty()
+    }
+
+    /// [`Self::from_pools_with`] under the default [`PricingConfig`].
+    pub fn from_pools<'a>(pools: impl IntoIterator<Item = &'a TrackedPool>) -> Self {
+        Self::from_pools_with(pools, &PricingConfig::default())
+    }
+
+    /// Stablecoins at $1 and every token within `config.max_hops` deep
+    /// enough pools of one.
+    pub fn from_pools_with<'a>(
+        pools: impl IntoIterator<Item = &'a TrackedPool>,
+        config: &PricingConfig,
+    ) -> Self {
+        let pools: Vec<&TrackedPool> = pools.into_iter().filter(|p| p.mid_price() > 0.0).collect();
+        let mut out = Self::new();
+        for pool in &pools {
+            for token in [&pool.token0, &pool.token1] {
+                if token.stable {
+                    out.set(&token.address, 1.0);
+                }
+            }
+        }
+
+        for hops in 1..=config.max_hops {
+            let mut estimates: HashMap<&str, Vec<Estimate>> = HashMap::new();
+            for pool in &pools {
+                let (reserve0, reserve1) = pool.virtual_reserves();
+                let mid = pool.mid_price();
+                for (known, reserve, other, rate) in [
+                    (&pool.token1, reserve1, &pool.token0, mid),
+                    (&pool.token0, reserve0, &pool.token1, 1.0 / mid),
+                ] {
+                    if out.prices.contains_key(&other.address) {
+                        continue;
+                    }
+                    let Some(q) = out.prices.get(&known.address) else {
+                        continue;
+                    };
+                    // Both sides of a pool hold the same value at its mid.
+                    let depth_usd = 2.0 * reserve * q.usd;
+                    if depth_usd < config.min_depth_usd {
+                        continue;
+                    }
+                    estimates.entry(&other.address).or_default().push(Estimate {
+                        usd: q.usd * rate,
+                        rel_error: q.rel_error() + pool.fee_fraction(),
+                        depth_usd,
+                        path_depth_usd: q.depth_usd.map_or(depth_usd, |d| d.min(depth_usd)),
+                    });
+                }
+            }
+            if estimates.is_empty() {
+                break;
+            }
+            for (address, estimates) in estimates {
+                out.prices
+                    .insert(address.to_string(), combine(&estimates, hops));
+            }
+        }
+        out
+    }
+}
+
+/// Depth-weighted mean of `estimates`, its interval the weighted spread of
+/// the estimates plus their weighted inherited error.
+fn combine(estimates: &[Estimate], hops: usize) -> PriceQuote {
+    let total: f64 = estimates.iter().map(|e| e.depth_usd).sum();
+    let mean = |f: &dyn Fn(&Estimate) -> f64| {
+        estimates.iter().map(|e| e.depth_usd * f(e)).sum::<f64>() / total
+    };
+    let usd = mean(&|e| e.usd);
+    let spread = mean(&|e| (e.usd - usd).powi(2)).sqrt();
+    let half = spread + usd * mean(&|e| e.rel_error);
+    PriceQuote {
+        usd,
+        low: (usd - half).max(0.0),
+        high: usd + half,
+        hops,
+        depth_usd: Some(estimates.iter().map(|e| e.path_depth_usd).sum()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{Dex, Token};
+    use crate::testing::{tick_for_price, token, v3_pool};
+
+    /// `base`/`quote` pool near `price` with `liquidity`.
+    fn pool(address: &str, base: &str, quote: &str, price: f64, liquidity: u128) -> TrackedPool {
+        let (t0, t1) = (token(base), token(quote));
+        let tick = tick_for_price(price, &t0, &t1);
+        v3_pool(address, Dex::Prjx, t0, t1, 500, tick, liquidity)
+    }
+
+    fn close(a: f64, b: f64) -> bool {
+        (a / b - 1.0).abs() < 1e-3
+    }
+
+    #[test]
+    fn walks_pools_back_to_stablecoins() {
+        let pools = [
+            pool("0xa", "WHYPE", "USDC", 40.0, 10u128.pow(17)),
+            // Two hops out: KHYPE trades only against WHYPE.
+            pool("0xb", "KHYPE", "WHYPE", 1.02, 10u128.pow(22)),
+            // Three hops out, beyond a two-hop limit.
+            pool("0xc", "PURR", "KHYPE", 0.005, 10u128.pow(22)),
+        ];
+        let prices = UsdPrices::from_pools(&pools);
+        let usdc = prices.quote(&token("USDC").address).unwrap();
+        assert_eq!(usdc, &PriceQuote::exact(1.0));
+
+        let whype = prices.quote(&token("WHYPE").address).unwrap();
+        assert!(close(whype.usd, 40.0), "{whype:?}");
+        assert_eq!(whype.hops, 1);
+        assert!(close(whype.rel_error(), 0.0005), "{whype:?}");
+
+        let khype = prices.quote(&token("KHYPE").address).unwrap();
+        assert!(close(khype.usd, 40.8), "{khype:?}");
+        assert_eq!(khype.hops, 2);
+        // Errors add up along the path; depth is the thinnest pool's.
+        assert!(close(khype.rel_error(), 0.001), "{khype:?}");
+        assert!(khype.depth_usd.unwrap() <= whype.depth_usd.unwrap());
+        assert!(prices.get(&token("PURR").address).is_some());
+
+        let short = UsdPrices::from_pools_with(
+            &pools,
+            &PricingConfig {
+                max_hops: 2,
+                ..PricingConfig::default()
+            },
+        );
+        assert_eq!(short.len(), 3);
+        assert!(short.get(&token("PURR").address).is_none());
+    }
+
+    #[test]
+    fn weights_pools_by_depth_and_widens_on_disagreement() {
+        let deep = pool("0xa", "WHYPE", "USDC", 40.0, 10u128.pow(17));
+        let shallow = pool("0xb", "WHYPE", "USDC", 41.0, 10u128.pow(16));
+        let prices = UsdPrices::from_pools([&deep, &shallow]);
+        let q = prices.quote(&token("WHYPE").address).unwrap();
+        // Ten times the depth, ten times the weight.
+        assert!(close(q.usd, (40.0 * 10.0 + 41.0) / 11.0), "{q:?}");
+        // The pools' spread (~0.29) dominates the 5 bps fee band.
+        assert!(q.low < 40.0 && q.high - q.usd > 0.25, "{q:?}");
+
+        let alone = UsdPrices::from_pools([&deep]);
+        assert!(q.rel_error() > alone.quote(&token("WHYPE").address).unwrap().rel_error());
+    }
+
+    #[test]
+    fn rejects_thin_paths() {
+        // About $2.5k of depth.
+        let thin = pool("0xa", "WHYPE", "USDC", 40.0, 10u128.pow(14) * 2);
+        let beyond = pool("0xb", "KHYPE", "WHYPE", 1.02, 10u128.pow(22));
+        let prices = UsdPrices::from_pools([&thin, &beyond]);
+        assert!(prices.get(&token("WHYPE").address).is_none());
+        assert!(prices.get(&token("KHYPE").address).is_none());
+
+        let lax = UsdPrices::from_pools_with(
+            [&thin, &beyond],
+            &PricingConfig {
+                min_depth_usd: 1_000.0,
+                ..PricingConfig::default()
+            },
+        );
+        assert!(close(lax.get(&token("KHYPE").address).unwrap(), 40.8));
+    }
+
+    #[test]
+    fn a_look_alike_stablecoin_is_not_an_anchor() {
+        // Anyone's "USDC", far deeper than the real one and quoting WHYPE
+        // at $1000.
+        let fake = Token {
+            address: "0x00000000000000000000000000000000000000cc".into(),
+            stable: false,
+            ..token("USDC")
+        };
+        let (whype, real) = (token("WHYPE"), token("USDC"));
+        let tick = tick_for_price(1000.0, &whype, &fake);
+        let deep_fake = v3_pool(
+            "0xf",
+            Dex::Prjx,
+            whype,
+            fake.clone(),
+            500,
+            tick,
+            10u128.pow(22),
+        );
+
+        let alone = UsdPrices::from_pools([&deep_fake]);
+        assert!(alone.is_empty());
+        assert!(alone.get(&fake.address).is_none());
+
+        let real_pool = pool("0xa", "WHYPE", "USDC", 40.0, 10u128.pow(17));
+        let prices = UsdPrices::from_pools([&deep_fake, &real_pool]);
+        assert_eq!(prices.get(&real.address), Some(1.0));
+        assert!(close(prices.get(&token("WHYPE").address).unwrap(), 40.0));
+        // The fake is only worth what WHYPE says it is.
+        let q = prices.quote(&fake.address).unwrap();
+        assert_eq!(q.hops, 2);
+        assert!(close(q.usd, 0.04), "{q:?}");
+    }
+}
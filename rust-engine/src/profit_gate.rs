@@ -0,0 +1,224 @@
+//! Profit gate: itemised execution costs and the go/no-go decision.
+//!
+//! Port of mvp_py `arbitrage/profit_gate.py` (`Quote`, `Costs` and the net
+//! profit), extended with the flash-loan, referral and executor costs of
+//! `src/eval/model.ts`. LP fees come from the exact pool quotes; every other
+//! bps fee is charged on notional.
+
+use serde::{Deserialize, Serialize};
+
+use crate::opportunity::Opportunity;
+
+/// Execution cost settings.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Costs {
+    pub gas_limit: u64,
+    pub gas_price_gwei: f64,
+    pub gas_multiplier: f64,
+    pub router_fee_bps: f64,
+    /// Trades are funded by a flash loan (HyperLend) rather than inventory.
+    pub flash_enabled: bool,
+    pub flash_fee_bps: f64,
+    pub flash_fixed_usd: f64,
+    pub referral_bps: f64,
+    /// Charged per flash-loaned trade, with the flash fees.
+    pub executor_fee_usd: f64,
+    /// Priority tip paid to land the bundle.
+    pub mev_tip_usd: f64,
+}
+
+impl Default for Costs {
+    fn default() -> Self {
+        Self {
+            gas_limit: 250_000,
+            gas_price_gwei: 1.0,
+            gas_multiplier: 1.0,
+            router_fee_bps: 0.0,
+            flash_enabled: true,
+            flash_fee_bps: 0.0,
+            flash_fixed_usd: 0.0,
+            referral_bps: 0.0,
+            executor_fee_usd: 0.0,
+            mev_tip_usd: 0.0,
+        }
+    }
+}
+
+impl Costs {
+    pub fn gas_usd(&self, native_usd: f64) -> f64 {
+        self.gas_limit as f64 * self.gas_price_gwei * 1e-9 * self.gas_multiplier * native_usd
+    }
+}
+
+/// What the route is expected to yield at its chosen size.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quote {
+    pub size_usd: f64,
+    /// Output minus input before LP fees, price impact included.
+    pub gross_usd: f64,
+    /// LP fees across every leg.
+    pub lp_fees_usd: f64,
+    /// Execution price versus mid, fees excluded.
+    pub slip_bps: f64,
+}
+
+/// Every cost between gross edge and net profit, in USD.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CostBreakdown {
+    pub gross_usd: f64,
+    pub lp_fees_usd: f64,
+    pub router_fee_usd: f64,
+    pub gas_usd: f64,
+    /// Flash-loan premium plus its fixed overhead.
+    pub flash_fee_usd: f64,
+    pub referral_usd: f64,
+    pub executor_fee_usd: f64,
+    pub mev_tip_usd: f64,
+    pub total_costs_usd: f64,
+    pub net_usd: f64,
+}
+
+pub fn cost_breakdown(quote: &Quote, costs: &Costs, native_usd: f64) -> CostBreakdown {
+    let on_notional = |bps: f64| quote.size_usd * bps / 1e4;
+    let (flash_fee_usd, referral_usd, executor_fee_usd) = if costs.flash_enabled {
+        (
+            on_notional(costs.flash_fee_bps) + costs.flash_fixed_usd,
+            on_notional(costs.referral_bps),
+            costs.executor_fee_usd,
+        )
+    } else {
+        (0.0, 0.0, 0.0)
+    };
+    let mut b = CostBreakdown {
+        gross_usd: quote.gross_usd,
+        lp_fees_usd: quote.lp_fees_usd,
+        router_fee_usd: on_notional(costs.router_fee_bps),
+        gas_usd: costs.gas_usd(native_usd),
+        flash_fee_usd,
+        referral_usd,
+        executor_fee_usd,
+        mev_tip_usd: costs.mev_tip_usd,
+        total_costs_usd: 0.0,
+        net_usd: 0.0,
+    };
+    b.total_costs_usd = b.lp_fees_usd
+        + b.router_fee_usd
+        + b.gas_usd
+        + b.flash_fee_usd
+        + b.referral_usd
+        + b.executor_fee_usd
+        + b.mev_tip_usd;
+    b.net_usd = b.gross_usd - b.total_costs_usd;
+    b
+}
+
+/// Limits an opportunity has to clear.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Thresholds {
+    pub min_profit_usd: f64,
+    pub max_slip_bps: f64,
+    pub min_spread_bps: f64,
+    pub min_liquidity_usd: f64,
+    /// Factor on the opportunity's gas cost: the gas multiplier to charge
+    /// over the one the cost was estimated with.
+    pub gas_scale: f64,
+    /// Fees charged on notional on top of the itemised costs.
+    pub fees_bps: f64,
+}
+
+/// Why `opp` fails `limits`, one line per failed check; empty when viable.
+pub fn rejections(opp: &Opportunity, limits: &Thresholds) -> Vec<String> {
+    let mut out = Vec::new();
+    if opp.slip_bps > limits.max_slip_bps {
+        out.push(format!(
+            "slippage {:.1} bps > max {:.1} bps",
+            opp.slip_bps, limits.max_slip_bps
+        ));
+    }
+    let extra_usd =
+        opp.costs.gas_usd * (limits.gas_scale - 1.0) + opp.size_usd * limits.fees_bps / 1e4;
+    let net_usd = opp.costs.net_usd - extra_usd;
+    if net_usd < limits.min_profit_usd {
+        out.push(format!(
+            "net ${:.2} < min ${:.2} (gross ${:.2}, costs ${:.2})",
+            net_usd,
+            limits.min_profit_usd,
+            opp.costs.gross_usd,
+            opp.costs.total_costs_usd + extra_usd
+        ));
+    }
+    if opp.spread_bps < limits.min_spread_bps {
+        out.push(format!(
+            "spread {:.1} bps < min {:.1} bps",
+            opp.spread_bps, limits.min_spread_bps
+        ));
+    }
+    if opp.liquidity_usd < limits.min_liquidity_usd {
+        out.push(format!(
+            "liquidity ${:.0} < min ${:.0}",
+            opp.liquidity_usd, limits.min_liquidity_usd
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quote() -> Quote {
+        Quote {
+            size_usd: 10_000.0,
+            gross_usd: 60.0,
+            lp_fees_usd: 8.0,
+            slip_bps: 12.0,
+        }
+    }
+
+    fn costs() -> Costs {
+        Costs {
+            gas_limit: 300_000,
+            gas_price_gwei: 2.0,
+            gas_multiplier: 1.5,
+            router_fee_bps: 1.0,
+            flash_enabled: true,
+            flash_fee_bps: 5.0,
+            flash_fixed_usd: 0.25,
+            referral_bps: 0.5,
+            executor_fee_usd: 0.1,
+            mev_tip_usd: 0.4,
+        }
+    }
+
+    #[test]
+    fn itemises_every_cost() {
+        let b = cost_breakdown(&quote(), &costs(), 40.0);
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-9;
+        assert!(close(b.lp_fees_usd, 8.0));
+        assert!(close(b.router_fee_usd, 1.0));
+        // 300k gas * 2 gwei * 1.5 = 0.0009 HYPE at $40.
+        assert!(close(b.gas_usd, 0.036));
+        assert!(close(b.flash_fee_usd, 5.25));
+        assert!(close(b.referral_usd, 0.5));
+        assert!(close(
+            b.total_costs_usd,
+            8.0 + 1.0 + 0.036 + 5.25 + 0.5 + 0.1 + 0.4
+        ));
+        assert!(close(b.net_usd, 60.0 - b.total_costs_usd));
+    }
+
+    #[test]
+    fn flash_costs_only_when_borrowing() {
+        let own_funds = Costs {
+            flash_enabled: false,
+            ..costs()
+        };
+        let b = cost_breakdown(&quote(), &own_funds, 40.0);
+        // As `flashCostUsd` in the TS model, the executor fee goes with them.
+        assert_eq!(
+            (b.flash_fee_usd, b.referral_usd, b.executor_fee_usd),
+            (0.0, 0.0, 0.0)
+        );
+        assert!((b.total_costs_usd - (8.0 + 1.0 + 0.036 + 0.4)).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,436 @@
+//! Config hot reload, triggered by file changes or a Redis control command.
+//!
+//! Consumers hold a `watch::Receiver<Arc<EngineConfig>>` and read the latest
+//! value each cycle, so a reload swaps the whole config at once. Edits that
+//! fail to load or validate are rejected and the previous config stays live.
+//! The `engine` section (feeds, endpoints, detector wiring) is only read at
+//! startup: a reload that changes it keeps the running values and warns that
+//! a restart is needed.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use serde_json::Value;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::config::{ConfigError, EngineConfig, RUNTIME_CONFIG_FILE, STRATEGIES_FILE};
+use crate::status::SharedStatusReporter;
+
+type EnvFn = dyn Fn(&str) -> Option<String> + Send + Sync;
+
+/// Owner of the live config.
+pub struct ConfigHandle {
+    dir: PathBuf,
+    env: Box<EnvFn>,
+    tx: watch::Sender<Arc<EngineConfig>>,
+    /// Held for a whole reload so file and control triggers cannot
+    /// interleave between reading the live config and replacing it.
+    reloading: Mutex<()>,
+}
+
+impl ConfigHandle {
+    /// `config` must have been loaded from `dir` with the same `env`.
+    pub fn new(
+        config: EngineConfig,
+        dir: impl Into<PathBuf>,
+        env: impl Fn(&str) -> Option<String> + Send + Sync + 'static,
+    ) -> Arc<Self> {
+        let (tx, _) = watch::channel(Arc::new(config));
+        Arc::new(Self {
+            dir: dir.into(),
+            env: Box::new(env),
+            tx,
+            reloading: Mutex::new(()),
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn current(&self) -> Arc<EngineConfig> {
+        self.tx.borrow().clone()
+    }
+
+    pub fn subscribe(&self) -> watch::Receiver<Arc<EngineConfig>> {
+        self.tx.subscribe()
+    }
+
+    /// Re-reads the config and swaps it in if it is valid and differs.
+    /// Returns the changes applied; on error the live config is untouched.
+    /// Engine settings keep their startup values.
+    pub fn reload(&self, trigger: &str) -> Result<Vec<String>, ConfigError> {
+        let _guard = self.reloading.lock().unwrap_or_else(|e| e.into_inner());
+        let mut new = match EngineConfig::load(&self.dir, &self.env) {
+            Ok(new) => new,
+            Err(e) => {
+                warn!(trigger, error = %e, "config reload rejected; keeping current config");
+                return Err(e);
+            }
+        };
+        let current = self.current();
+        let mut restart = Vec::new();
+        diff_object(
+            "engine",
+            &to_value(&current.engine),
+            &to_value(&new.engine),
+            &mut restart,
+        );
+        if !restart.is_empty() {
+            warn!(trigger, changes = %restart.join("; "), "engine settings changed; restart required to apply them");
+            new.engine = current.engine.clone();
+        }
+        let changes = diff(&current, &new);
+        if changes.is_empty() {
+            info!(trigger, "config reload: no changes");
+        } else {
+            self.tx.send_replace(Arc::new(new));
+            info!(trigger, changes = %changes.join("; "), "config reloaded");
+        }
+        Ok(changes)
+    }
+}
+
+/// Human-readable `path: old -> new` lines, one per changed field.
+pub fn diff(old: &EngineConfig, new: &EngineConfig) -> Vec<String> {
+    let mut out = Vec::new();
+    diff_object(
+        "runtime",
+        &to_value(&old.runtime),
+        &to_value(&new.runtime),
+        &mut out,
+    );
+
+    let by_name = |c: &EngineConfig| -> BTreeMap<String, Value> {
+        c.strategies
+            .iter()
+            .map(|s| (s.name.clone(), to_value(s)))
+            .collect()
+    };
+    let (old_s, new_s) = (by_name(old), by_name(new));
+    for (name, before) in &old_s {
+        match new_s.get(name) {
+            Some(after) => diff_object(&format!("strategies.{name}"), before, after, &mut out),
+            None => out.push(format!("strategies.{name}: removed")),
+        }
+    }
+    for name in new_s.keys().filter(|n| !old_s.contains_key(*n)) {
+        out.push(format!("strategies.{name}: added"));
+    }
+
+    diff_object(
+        "engine",
+        &to_value(&old.engine),
+        &to_value(&new.engine),
+        &mut out,
+    );
+    diff_object(
+        "costs",
+        &to_value(&old.costs),
+        &to_value(&new.costs),
+        &mut out,
+    );
+    diff_object("ev", &to_value(&old.ev), &to_value(&new.ev), &mut out);
+    diff_object(
+        "monte_carlo",
+        &to_value(&old.monte_carlo),
+        &to_value(&new.monte_carlo),
+        &mut out,
+    );
+    out
+}
+
+fn to_value<T: serde::Serialize>(v: &T) -> Value {
+    serde_json::to_value(v).unwrap_or(Value::Null)
+}
+
+fn diff_object(prefix: &str, old: &Value, new: &Value, out: &mut Vec<String>) {
+    let empty = serde_json::Map::new();
+    let old = old.as_object().unwrap_or(&empty);
+    let new = new.as_object().unwrap_or(&empty);
+    let mut keys: Vec<&String> = old.keys().chain(new.keys()).collect();
+    keys.sort();
+    keys.dedup();
+    for key in keys {
+        let (a, b) = (
+            old.get(key).unwrap_or(&Value::Null),
+            new.get(key).unwrap_or(&Value::Null),
+        );
+        if a != b {
+            out.push(format!("{prefix}.{key}: {a} -> {b}"));
+        }
+    }
+}
+
+/// Polls the config files every `every` and reloads when either changes.
+pub fn spawn_file_watch(handle: Arc<ConfigHandle>, every: Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let fingerprint = |dir: &Path| -> Vec<Option<(SystemTime, u64)>> {
+            [RUNTIME_CONFIG_FILE, STRATEGIES_FILE]
+                .iter()
+                .map(|f| {
+                    let meta = std::fs::metadata(dir.join(f)).ok()?;
+                    Some((meta.modified().ok()?, meta.len()))
+                })
+                .collect()
+        };
+        let mut last = fingerprint(handle.dir());
+        let mut interval = tokio::time::interval(every);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        loop {
+            interval.tick().await;
+            let now = fingerprint(handle.dir());
+            if now != last {
+                last = now;
+                let _ = handle.reload("file");
+            }
+        }
+    })
+}
+
+/// Commands accepted on the control channel, as a bare word or `{"cmd": ...}`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControlCommand {
+    Reload,
+    Pause,
+    Resume,
+}
+
+impl FromStr for ControlCommand {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let word = match serde_json::from_str::<Value>(s) {
+            Ok(Value::Object(obj)) => obj
+                .get("cmd")
+                .and_then(Value::as_str)
+                .ok_or_else(|| format!("missing \"cmd\" in {s}"))?
+                .to_string(),
+            _ => s.to_string(),
+        };
+        match word.to_ascii_lowercase().as_str() {
+            "reload" => Ok(ControlCommand::Reload),
+            "pause" => Ok(ControlCommand::Pause),
+            "resume" => Ok(ControlCommand::Resume),
+            _ => Err(format!("unknown command {word:?}")),
+        }
+    }
+}
+
+pub async fn handle_command(
+    command: ControlCommand,
+    handle: &ConfigHandle,
+    reporter: &SharedStatusReporter,
+) {
+    match command {
+        ControlCommand::Reload => {
+            let _ = handle.reload("control");
+        }
+        ControlCommand::Pause | ControlCommand::Resume => {
+            let paused = command == ControlCommand::Pause;
+            reporter.write().await.paused = paused;
+            info!(paused, "engine pause state changed");
+        }
+    }
+}
+
+/// Subscribes to `channel` and executes commands; resubscribes after errors.
+pub fn spawn_control_listener(
+    client: redis::Client,
+    channel: String,
+    handle: Arc<ConfigHandle>,
+    reporter: SharedStatusReporter,
+) -> JoinHandle<()> {
+    use futures_util::StreamExt;
+
+    tokio::spawn(async move {
+        loop {
+            match client.get_async_pubsub().await {
+                Ok(mut pubsub) => match pubsub.subscribe(&channel).await {
+                    Ok(()) => {
+                        info!(%channel, "listening for control commands");
+                        let mut messages = std::pin::pin!(pubsub.on_message());
+                        while let Some(msg) = messages.next().await {
+                            let payload: String = match msg.get_payload() {
+                                Ok(p) => p,
+                                Err(e) => {
+                                    warn!(error = %e, "unreadable control message");
+                                    continue;
+                                }
+                            };
+                            match payload.parse() {
+                                Ok(cmd) => handle_command(cmd, &handle, &reporter).await,
+                                Err(e) => warn!(error = %e, "ignoring control message"),
+                            }
+                        }
+                        warn!("control subscription closed");
+                    }
+                    Err(e) => warn!(error = %e, "control subscribe failed"),
+                },
+                Err(e) => warn!(error = %e, "control connection failed"),
+            }
+            tokio::time::sleep(Duration::from_secs(5)).await;
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::DEFAULT_CONFIG_DIR;
+    use crate::status::{StatusConfig, StatusReporter};
+    use crate::testing::TempDir;
+
+    fn no_env(_: &str) -> Option<String> {
+        None
+    }
+
+    /// Copy of the repo config in a scratch dir, plus its handle.
+    fn setup() -> (TempDir, Arc<ConfigHandle>) {
+        let dir = TempDir::new("reload");
+        for f in [RUNTIME_CONFIG_FILE, STRATEGIES_FILE] {
+            std::fs::copy(Path::new(DEFAULT_CONFIG_DIR).join(f), dir.path().join(f)).unwrap();
+        }
+        let config = EngineConfig::load(dir.path(), no_env).unwrap();
+        let handle = ConfigHandle::new(config, dir.path(), no_env);
+        (dir, handle)
+    }
+
+    fn edit(dir: &TempDir, file: &str, f: impl FnOnce(&mut Value)) {
+        let path = dir.path().join(file);
+        let mut v: Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        f(&mut v);
+        std::fs::write(&path, serde_json::to_string_pretty(&v).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn reload_swaps_valid_config_and_reports_diff() {
+        let (dir, handle) = setup();
+        let mut rx = handle.subscribe();
+        assert_eq!(handle.reload("test").unwrap(), Vec::<String>::new());
+        assert!(!rx.has_changed().unwrap());
+
+        edit(&dir, RUNTIME_CONFIG_FILE, |v| {
+            v["min_profit_usd"] = 7.0.into()
+        });
+        edit(&dir, STRATEGIES_FILE, |v| {
+            v["HYPE_to_KHYPE"]["enabled"] = false.into();
+            v.as_object_mut().unwrap().remove("auto_high_spread");
+        });
+        let changes = handle.reload("test").unwrap();
+        assert_eq!(
+            changes,
+            vec![
+                "runtime.min_profit_usd: 5.0 -> 7.0",
+                "strategies.HYPE_to_KHYPE.enabled: true -> false",
+                "strategies.auto_high_spread: removed",
+            ]
+        );
+        assert!(rx.has_changed().unwrap());
+        let live = rx.borrow_and_update().clone();
+        assert_eq!(live.runtime.min_profit_usd, 7.0);
+        assert_eq!(live.strategies.len(), 4);
+    }
+
+    #[test]
+    fn invalid_edit_keeps_old_config() {
+        let (dir, handle) = setup();
+        edit(&dir, RUNTIME_CONFIG_FILE, |v| {
+            v["slippage_bps"] = (-5.0).into()
+        });
+        assert!(matches!(
+            handle.reload("test"),
+            Err(ConfigError::Invalid(_))
+        ));
+        std::fs::write(dir.path().join(STRATEGIES_FILE), "{ truncated").unwrap();
+        assert!(matches!(
+            handle.reload("test"),
+            Err(ConfigError::Parse { .. })
+        ));
+        assert_eq!(handle.current().runtime.slippage_bps, 30.0);
+    }
+
+    #[test]
+    fn engine_changes_wait_for_a_restart() {
+        let dir = TempDir::new("reload");
+        for f in [RUNTIME_CONFIG_FILE, STRATEGIES_FILE] {
+            std::fs::copy(Path::new(DEFAULT_CONFIG_DIR).join(f), dir.path().join(f)).unwrap();
+        }
+        let top_n = Arc::new(Mutex::new("5".to_string()));
+        let env = {
+            let top_n = top_n.clone();
+            move |k: &str| (k == "STATUS_TOP_N").then(|| top_n.lock().unwrap().clone())
+        };
+        let config = EngineConfig::load(dir.path(), &env).unwrap();
+        let handle = ConfigHandle::new(config, dir.path(), env);
+
+        *top_n.lock().unwrap() = "9".to_string();
+        edit(&dir, RUNTIME_CONFIG_FILE, |v| {
+            v["min_profit_usd"] = 7.0.into()
+        });
+        let changes = handle.reload("test").unwrap();
+        assert_eq!(changes, vec!["runtime.min_profit_usd: 5.0 -> 7.0"]);
+        let live = handle.current();
+        assert_eq!(live.runtime.min_profit_usd, 7.0);
+        assert_eq!(live.engine.status_top_n, 5);
+    }
+
+    #[test]
+    fn concurrent_reloads_apply_a_change_once() {
+        let (dir, handle) = setup();
+        edit(&dir, RUNTIME_CONFIG_FILE, |v| {
+            v["min_profit_usd"] = 7.0.into()
+        });
+        let applied: usize = std::thread::scope(|scope| {
+            let reloads: Vec<_> = (0..8)
+                .map(|_| scope.spawn(|| handle.reload("test").unwrap().len()))
+                .collect();
+            reloads.into_iter().map(|r| r.join().unwrap()).sum()
+        });
+        assert_eq!(applied, 1);
+        assert_eq!(handle.current().runtime.min_profit_usd, 7.0);
+    }
+
+    #[tokio::test]
+    async fn file_watch_picks_up_edits() {
+        let (dir, handle) = setup();
+        let mut rx = handle.subscribe();
+        let task = spawn_file_watch(handle.clone(), Duration::from_millis(10));
+        tokio::time::sleep(Duration::from_millis(30)).await;
+
+        edit(&dir, RUNTIME_CONFIG_FILE, |v| {
+            v["min_spread_bps"] = 25.0.into()
+        });
+        tokio::time::timeout(Duration::from_secs(2), rx.changed())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(rx.borrow().runtime.min_spread_bps, 25.0);
+        task.abort();
+    }
+
+    #[test]
+    fn parses_control_commands() {
+        assert_eq!("reload".parse(), Ok(ControlCommand::Reload));
+        assert_eq!(" PAUSE\n".parse(), Ok(ControlCommand::Pause));
+        assert_eq!(r#"{"cmd": "resume"}"#.parse(), Ok(ControlCommand::Resume));
+        assert!("restart".parse::<ControlCommand>().is_err());
+        assert!(r#"{"command": "reload"}"#.parse::<ControlCommand>().is_err());
+    }
+
+    #[tokio::test]
+    async fn pause_and_resume_toggle_status() {
+        let (_dir, handle) = setup();
+        let reporter = StatusReporter::shared(StatusConfig::default());
+        handle_command(ControlCommand::Pause, &handle, &reporter).await;
+        assert!(reporter.read().await.paused);
+        handle_command(ControlCommand::Resume, &handle, &reporter).await;
+        assert!(!reporter.read().await.paused);
+    }
+}
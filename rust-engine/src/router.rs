@@ -0,0 +1,270 @@
+//! Swap router calldata for the pool legs of an opportunity.
+//!
+//! A leg becomes one SwapRouter02 `exactInputSingle`, or a `multicall` with
+//! one per entry when it is split across fee tiers. The router is called by
+//! `ArbitrageExecutor`, which checks the route's profit as a whole, so each
+//! swap goes out with no minimum output and no price limit.
+//!
+//! A leg's input is only known on chain when it is the output of the leg
+//! before. [`LegCalldata`] therefore carries the byte offset of every
+//! `amountIn` word and each swap's share of the leg; the executor rewrites
+//! those words from the balance it actually holds before calling the router.
+
+use anyhow::{bail, Result};
+
+use crate::opportunity::RouteLeg;
+use crate::rpc::abi::{self, Selector, Word};
+use crate::univ3::full_math::mul_div;
+use crate::univ3::U256;
+
+/// `exactInputSingle((address,address,uint24,address,uint256,uint256,uint160))`.
+pub const EXACT_INPUT_SINGLE: Selector = [0x04, 0xe4, 0x5a, 0xaf];
+/// `multicall(bytes[])`.
+pub const MULTICALL: Selector = [0xac, 0x96, 0x50, 0xd8];
+
+/// Scale of [`LegCalldata::shares`]: a share of `SHARE_ONE` is the whole leg.
+pub const SHARE_ONE: u128 = 10u128.pow(18);
+
+/// `amountIn` is the fifth word of `exactInputSingle`'s parameters.
+const AMOUNT_IN_WORD: usize = 4;
+/// Selector plus seven static words.
+const SWAP_LEN: usize = 4 + 7 * 32;
+
+/// Router calldata for one leg.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LegCalldata {
+    pub data: Vec<u8>,
+    /// Byte offset in `data` of each swap's `amountIn` word, in split order.
+    pub amount_offsets: Vec<usize>,
+    /// Each swap's share of the leg's input in units of [`SHARE_ONE`];
+    /// they sum to `SHARE_ONE`.
+    pub shares: Vec<U256>,
+}
+
+impl LegCalldata {
+    /// `data` with the swaps resized to share `amount_in`, as the executor
+    /// does on chain: each takes its share rounded down and the last one
+    /// what is left, so the swaps spend exactly `amount_in`.
+    pub fn sized(&self, amount_in: U256) -> Result<Vec<u8>> {
+        let mut data = self.data.clone();
+        let mut left = amount_in;
+        for (i, (&at, &share)) in self.amount_offsets.iter().zip(&self.shares).enumerate() {
+            let amount = if i + 1 == self.shares.len() {
+                left
+            } else {
+                mul_div(amount_in, share, U256::from(SHARE_ONE))?
+            };
+            left = left - amount;
+            data[at..at + 32].copy_from_slice(&amount.to_be_bytes());
+        }
+        Ok(data)
+    }
+}
+
+/// Calldata swapping `amount_in` of the leg's `token_in` for its
+/// `token_out`, paid to `recipient`. A split leg divides `amount_in` in
+/// proportion to its entries' quoted inputs, so at the quoted size every
+/// swap gets exactly its entry's `amount_in`.
+pub fn leg_calldata(leg: &RouteLeg, amount_in: U256, recipient: &str) -> Result<LegCalldata> {
+    let (fees, weights): (Vec<u32>, Vec<U256>) = if leg.split.is_empty() {
+        (vec![leg.fee], vec![amount_in])
+    } else {
+        leg.split.iter().map(|f| (f.fee, f.amount_in)).unzip()
+    };
+    let weight = weights.iter().fold(U256::ZERO, |a, &w| a + w);
+    if weight.is_zero() {
+        bail!("leg {} -> {} has no input", leg.token_in, leg.token_out);
+    }
+    let shares = share_out(U256::from(SHARE_ONE), &weights)?;
+    let amounts = share_out(amount_in, &weights)?;
+
+    let swaps = fees
+        .iter()
+        .zip(&amounts)
+        .map(|(&fee, &amount)| exact_input_single(leg, fee, recipient, amount))
+        .collect::<Result<Vec<_>>>()?;
+    if let [swap] = &swaps[..] {
+        return Ok(LegCalldata {
+            data: swap.clone(),
+            amount_offsets: vec![4 + 32 * AMOUNT_IN_WORD],
+            shares,
+        });
+    }
+
+    // `multicall(bytes[])`: the array offset and length, one offset per
+    // element, then each element's length and its swap padded to words.
+    let padded = SWAP_LEN.next_multiple_of(32);
+    let elements = 4 + 64;
+    let mut data = abi::encode_call(
+        MULTICALL,
+        &[abi::uint_word(32), abi::uint_word(swaps.len() as u128)],
+    );
+    let mut amount_offsets = Vec::with_capacity(swaps.len());
+    for i in 0..swaps.len() {
+        let offset = 32 * swaps.len() + i * (32 + padded);
+        data.extend_from_slice(&abi::uint_word(offset as u128));
+        amount_offsets.push(elements + offset + 32 + 4 + 32 * AMOUNT_IN_WORD);
+    }
+    for swap in swaps {
+        data.extend_from_slice(&abi::uint_word(swap.len() as u128));
+        data.extend_from_slice(&swap);
+        data.resize(data.len() + padded - swap.len(), 0);
+    }
+    Ok(LegCalldata {
+        data,
+        amount_offsets,
+        shares,
+    })
+}
+
+fn exact_input_single(leg: &RouteLeg, fee: u32, recipient: &str, amount: U256) -> Result<Vec<u8>> {
+    let args: [Word; 7] = [
+        abi::address_word(&leg.token_in)?,
+        abi::address_word(&leg.token_out)?,
+        abi::uint_word(fee.into()),
+        abi::address_word(recipient)?,
+        amount.to_be_bytes(),
+        abi::uint_word(0),
+        abi::uint_word(0),
+    ];
+    Ok(abi::encode_call(EXACT_INPUT_SINGLE, &args))
+}
+
+/// `total` divided in proportion to `weights`, rounded down with the last
+/// taking the remainder.
+fn share_out(total: U256, weights: &[U256]) -> Result<Vec<U256>> {
+    let weight = weights.iter().fold(U256::ZERO, |a, &w| a + w);
+    let mut left = total;
+    let mut out = Vec::with_capacity(weights.len());
+    for (i, &w) in weights.iter().enumerate() {
+        let part = if i + 1 == weights.len() {
+            left
+        } else {
+            mul_div(total, w, weight)?
+        };
+        left = left - part;
+        out.push(part);
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::opportunity::SplitFill;
+    use crate::state::Dex;
+
+    const KHYPE: &str = "0xfd739d4e423301ce9385c1fb8850539d657c296d";
+    const USDC: &str = "0xb88339cb7199b77e23db6e890353e22632ba630f";
+    const EXECUTOR: &str = "0x00000000000000000000000000000000000e8ec0";
+
+    fn leg(split: &[(u32, u128)]) -> RouteLeg {
+        RouteLeg {
+            dex: Dex::Prjx,
+            pool: "0x0000000000000000000000000000000000000001".into(),
+            token_in: USDC.into(),
+            token_out: KHYPE.into(),
+            fee: split.first().map_or(500, |s| s.0),
+            fee_usd: 0.0,
+            split: split
+                .iter()
+                .map(|&(fee, amount_in)| SplitFill {
+                    dex: Dex::Prjx,
+                    pool: format!("0x{fee:040x}"),
+                    fee,
+                    amount_in: U256::from(amount_in),
+                    amount_out: U256::ZERO,
+                })
+                .collect(),
+        }
+    }
+
+    /// `(tokenIn, tokenOut, fee, recipient, amountIn)` of every swap.
+    fn decode(data: &[u8]) -> Vec<(String, String, u32, String, U256)> {
+        let swap = |call: &[u8]| {
+            assert_eq!(call[..4], EXACT_INPUT_SINGLE);
+            let w = abi::expect_words(&call[4..], 7).unwrap();
+            (
+                abi::as_address(&w[0]).unwrap(),
+                abi::as_address(&w[1]).unwrap(),
+                abi::as_u128(&w[2]).unwrap() as u32,
+                abi::as_address(&w[3]).unwrap(),
+                abi::as_u256(&w[4]),
+            )
+        };
+        if data[..4] == EXACT_INPUT_SINGLE {
+            return vec![swap(data)];
+        }
+        assert_eq!(data[..4], MULTICALL);
+        let args = &data[4..];
+        let usize_at =
+            |at: usize| abi::as_u128(&args[at..at + 32].try_into().unwrap()).unwrap() as usize;
+        let array = usize_at(0);
+        let elements = array + 32;
+        (0..usize_at(array))
+            .map(|i| {
+                let bytes = elements + usize_at(elements + 32 * i);
+                swap(&args[bytes + 32..bytes + 32 + usize_at(bytes)])
+            })
+            .collect()
+    }
+
+    #[test]
+    fn split_leg_is_one_swap_per_entry() {
+        let split = [(500, 7_000_000_000u128), (3000, 2_500_000_001)];
+        let leg = leg(&split);
+        let call = leg_calldata(&leg, U256::from(9_500_000_001u128), EXECUTOR).unwrap();
+        let swaps = decode(&call.data);
+        assert_eq!(swaps.len(), 2);
+        for ((token_in, token_out, fee, recipient, amount), (want_fee, want_in)) in
+            swaps.into_iter().zip(split)
+        {
+            assert_eq!((token_in.as_str(), token_out.as_str()), (USDC, KHYPE));
+            assert_eq!(recipient, EXECUTOR);
+            assert_eq!(fee, want_fee);
+            assert_eq!(amount, U256::from(want_in));
+        }
+        let share_sum = call.shares.iter().fold(U256::ZERO, |a, &s| a + s);
+        assert_eq!(share_sum, U256::from(SHARE_ONE));
+    }
+
+    #[test]
+    fn unsplit_leg_is_a_single_swap() {
+        let call = leg_calldata(&leg(&[]), U256::from(42u64), EXECUTOR).unwrap();
+        assert_eq!(call.data.len(), SWAP_LEN);
+        assert_eq!(call.amount_offsets, [4 + 32 * AMOUNT_IN_WORD]);
+        assert_eq!(call.shares, [U256::from(SHARE_ONE)]);
+        let swaps = decode(&call.data);
+        assert_eq!((swaps[0].2, swaps[0].4), (500, U256::from(42u64)));
+    }
+
+    #[test]
+    fn sized_spends_the_actual_input() {
+        // The buy leg delivered less than quoted: the split keeps its
+        // proportions and spends every wei that arrived.
+        let leg = leg(&[
+            (500, 6 * 10u128.pow(17)),
+            (3000, 3 * 10u128.pow(17)),
+            (10000, 10u128.pow(17)),
+        ]);
+        let call = leg_calldata(&leg, U256::from(10u128.pow(18)), EXECUTOR).unwrap();
+        let actual = 987_654_321_987_654_323u128;
+        let swaps = decode(&call.sized(U256::from(actual)).unwrap());
+        let amounts: Vec<u128> = swaps.iter().map(|s| s.4.to_u128().unwrap()).collect();
+        assert_eq!(amounts.iter().sum::<u128>(), actual);
+        for (amount, weight) in amounts.iter().zip([6, 3, 1]) {
+            assert!(amount.abs_diff(actual / 10 * weight) <= 10, "{amounts:?}");
+        }
+        // Everything but the amounts is left as built.
+        assert_eq!(
+            decode(&call.sized(U256::from(10u128.pow(18))).unwrap()),
+            decode(&call.data)
+        );
+    }
+
+    #[test]
+    fn rejects_bad_addresses_and_empty_legs() {
+        assert!(leg_calldata(&leg(&[]), U256::ZERO, EXECUTOR).is_err());
+        assert!(leg_calldata(&leg(&[]), U256::ONE, "0x1234").is_err());
+    }
+}
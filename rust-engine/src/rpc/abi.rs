@@ -0,0 +1,205 @@
+//! Just enough Solidity ABI for the pool and token getters: 32-byte words,
+//! static ints, addresses and strings. The dynamic `aggregate3` encoding is
+//! in [`super::multicall`].
+
+use anyhow::{anyhow, bail, Result};
+
+use crate::univ3::U256;
+
+pub type Selector = [u8; 4];
+pub type Word = [u8; 32];
+
+// `bytes4(keccak256(signature))` of the getters the engine calls.
+pub const SLOT0: Selector = [0x38, 0x50, 0xc7, 0xbd];
+pub const LIQUIDITY: Selector = [0x1a, 0x68, 0x65, 0x02];
+pub const TICK_BITMAP: Selector = [0x53, 0x39, 0xc2, 0x96];
+pub const TICKS: Selector = [0xf3, 0x0d, 0xba, 0x93];
+pub const FEE: Selector = [0xdd, 0xca, 0x3f, 0x43];
+pub const TICK_SPACING: Selector = [0xd0, 0xc9, 0x3a, 0x7c];
+pub const TOKEN0: Selector = [0x0d, 0xfe, 0x16, 0x81];
+pub const TOKEN1: Selector = [0xd2, 0x12, 0x20, 0xa7];
+pub const DECIMALS: Selector = [0x31, 0x3c, 0xe5, 0x67];
+pub const SYMBOL: Selector = [0x95, 0xd8, 0x9b, 0x41];
+pub const NAME: Selector = [0x06, 0xfd, 0xde, 0x03];
+/// Multicall3 `aggregate3((address,bool,bytes)[])`.
+pub const AGGREGATE3: Selector = [0x82, 0xad, 0x56, 0xcb];
+
+/// Calldata for `selector` with static arguments.
+pub fn encode_call(selector: Selector, args: &[Word]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(4 + 32 * args.len());
+    out.extend_from_slice(&selector);
+    for arg in args {
+        out.extend_from_slice(arg);
+    }
+    out
+}
+
+pub fn uint_word(v: u128) -> Word {
+    U256::from_u128(v).to_be_bytes()
+}
+
+/// Sign-extended two's-complement word, for `int16`/`int24` arguments.
+pub fn int_word(v: i64) -> Word {
+    let mut w = if v < 0 { [0xff; 32] } else { [0; 32] };
+    w[24..].copy_from_slice(&v.to_be_bytes());
+    w
+}
+
+pub fn address_word(address: &str) -> Result<Word> {
+    let bytes = from_hex(address)?;
+    if bytes.len() != 20 {
+        bail!("address {address} is not 20 bytes");
+    }
+    let mut w = [0u8; 32];
+    w[12..].copy_from_slice(&bytes);
+    Ok(w)
+}
+
+/// Splits return data into words; fails on a ragged tail.
+pub fn words(data: &[u8]) -> Result<Vec<Word>> {
+    if !data.len().is_multiple_of(32) {
+        bail!("return data of {} bytes is not word-aligned", data.len());
+    }
+    Ok(data
+        .chunks_exact(32)
+        .map(|c| c.try_into().unwrap())
+        .collect())
+}
+
+/// The first `n` words of `data`, for getters returning a static tuple.
+pub fn expect_words(data: &[u8], n: usize) -> Result<Vec<Word>> {
+    let w = words(data)?;
+    if w.len() < n {
+        bail!("expected {n} return words, got {}", w.len());
+    }
+    Ok(w)
+}
+
+pub fn as_u256(w: &Word) -> U256 {
+    U256::from_be_bytes(*w)
+}
+
+/// `uintN` for N <= 128.
+pub fn as_u128(w: &Word) -> Result<u128> {
+    as_u256(w)
+        .to_u128()
+        .ok_or_else(|| anyhow!("word does not fit in uint128"))
+}
+
+/// `intN` for N <= 128, checking the sign extension.
+pub fn as_i128(w: &Word) -> Result<i128> {
+    let fill = if w[16] & 0x80 != 0 { 0xff } else { 0 };
+    if w[..16].iter().any(|&b| b != fill) {
+        bail!("word does not fit in int128");
+    }
+    Ok(i128::from_be_bytes(w[16..].try_into().unwrap()))
+}
+
+/// `int24` and other ints that fit in 32 bits.
+pub fn as_i32(w: &Word) -> Result<i32> {
+    i32::try_from(as_i128(w)?).map_err(|_| anyhow!("word does not fit in int32"))
+}
+
+pub fn as_bool(w: &Word) -> Result<bool> {
+    match as_u256(w) {
+        v if v.is_zero() => Ok(false),
+        v if v == U256::ONE => Ok(true),
+        v => bail!("invalid bool word {v}"),
+    }
+}
+
+/// Lowercase hex address.
+pub fn as_address(w: &Word) -> Result<String> {
+    if w[..12].iter().any(|&b| b != 0) {
+        bail!("word is not an address");
+    }
+    Ok(to_hex(&w[12..]))
+}
+
+/// A `string` return value, or the `bytes32` some older tokens return.
+pub fn decode_string(data: &[u8]) -> Result<String> {
+    if data.len() == 32 {
+        let end = data.iter().position(|&b| b == 0).unwrap_or(32);
+        return Ok(String::from_utf8_lossy(&data[..end]).into_owned());
+    }
+    let w = expect_words(data, 2)?;
+    let offset = usize::try_from(as_u128(&w[0])?)?;
+    let len_word: &Word = data
+        .get(offset..offset + 32)
+        .and_then(|s| s.try_into().ok())
+        .ok_or_else(|| anyhow!("string offset {offset} out of bounds"))?;
+    let len = usize::try_from(as_u128(len_word)?)?;
+    let bytes = data
+        .get(offset + 32..offset + 32 + len)
+        .ok_or_else(|| anyhow!("string of {len} bytes out of bounds"))?;
+    Ok(String::from_utf8_lossy(bytes).into_owned())
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    let mut s = String::with_capacity(2 + 2 * bytes.len());
+    s.push_str("0x");
+    for b in bytes {
+        s.push_str(&format!("{b:02x}"));
+    }
+    s
+}
+
+pub fn from_hex(s: &str) -> Result<Vec<u8>> {
+    let digits = s.strip_prefix("0x").unwrap_or(s);
+    if !digits.len().is_multiple_of(2) {
+        bail!("odd-length hex {s:?}");
+    }
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&digits[i..i + 2], 16).map_err(|_| anyhow!("invalid hex {s:?}"))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signed_words_round_trip() {
+        for v in [0i64, 1, -1, 887272, -887272, i64::from(i16::MIN)] {
+            assert_eq!(as_i32(&int_word(v)).unwrap() as i64, v);
+        }
+        assert_eq!(int_word(-2)[0], 0xff);
+        let mut bad = int_word(-1);
+        bad[0] = 0;
+        assert!(as_i128(&bad).is_err());
+        assert_eq!(as_i128(&int_word(-5)).unwrap(), -5);
+    }
+
+    #[test]
+    fn addresses_and_calldata() {
+        let addr = "0x5555555555555555555555555555555555555555";
+        let w = address_word(addr).unwrap();
+        assert_eq!(as_address(&w).unwrap(), addr);
+        assert!(address_word("0x1234").is_err());
+        let data = encode_call(TICKS, &[int_word(-60)]);
+        assert_eq!(data.len(), 36);
+        assert_eq!(to_hex(&data[..4]), "0xf30dba93");
+        assert_eq!(from_hex(&to_hex(&data)).unwrap(), data);
+    }
+
+    #[test]
+    fn decodes_dynamic_and_fixed_strings() {
+        let mut data = Vec::new();
+        data.extend_from_slice(&int_word(32));
+        data.extend_from_slice(&int_word(5));
+        let mut tail = [0u8; 32];
+        tail[..5].copy_from_slice(b"WHYPE");
+        data.extend_from_slice(&tail);
+        assert_eq!(decode_string(&data).unwrap(), "WHYPE");
+
+        let mut fixed = [0u8; 32];
+        fixed[..3].copy_from_slice(b"MKR");
+        assert_eq!(decode_string(&fixed).unwrap(), "MKR");
+
+        data.truncate(64);
+        assert!(decode_string(&data).is_err());
+    }
+}
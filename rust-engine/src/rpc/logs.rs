@@ -0,0 +1,366 @@
+//! V3 pool events and their effect on [`PoolState`].
+//!
+//! `Swap` carries the post-swap price, tick and active liquidity, so it
+//! overwrites them. `Mint` and `Burn` move liquidity between the position's
+//! bounds, and into the active liquidity when the range covers the current
+//! tick, exactly as `UniswapV3Pool._modifyPosition` does.
+//!
+//! The factory's `PoolCreated` is decoded here too, for pool discovery.
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use super::abi::{self, Word};
+use crate::univ3::liquidity_math::add_delta;
+use crate::univ3::{PoolState, U256};
+
+/// `Swap(address,address,int256,int256,uint160,uint128,int24)`
+pub const SWAP_TOPIC: &str = "0xc42079f94a6350d7e6235f29174924f928cc2ac818eb64fed8004e115fbcca67";
+/// `Mint(address,address,int24,int24,uint128,uint256,uint256)`
+pub const MINT_TOPIC: &str = "0x7a53080ba414158be7ec69b987b5fb7d07dee101fe85488f0853ae16239d0bde";
+/// `Burn(address,int24,int24,uint128,uint256,uint256)`
+pub const BURN_TOPIC: &str = "0x0c396cd989a39f4459b5fa1aed6a9a8dcdbc45908acfd67e028cd568da98982c";
+
+/// `PoolCreated(address,address,uint24,int24,address)`
+pub const POOL_CREATED_TOPIC: &str =
+    "0x783cca1c0412dd0d695e784568c96da2e9c22ff989357a2e8b1d9b2b4e6b7118";
+
+/// A log as `eth_subscribe("logs")` and `eth_getLogs` return it.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Log {
+    pub address: String,
+    pub topics: Vec<String>,
+    pub data: String,
+    #[serde(deserialize_with = "super::deserialize_quantity")]
+    pub block_number: u64,
+    pub block_hash: String,
+    #[serde(deserialize_with = "super::deserialize_quantity")]
+    pub log_index: u64,
+    /// Set when a reorg dropped the block this log was in.
+    #[serde(default)]
+    pub removed: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PoolEvent {
+    Swap {
+        sqrt_price_x96: U256,
+        liquidity: u128,
+        tick: i32,
+    },
+    Mint {
+        tick_lower: i32,
+        tick_upper: i32,
+        amount: u128,
+    },
+    Burn {
+        tick_lower: i32,
+        tick_upper: i32,
+        amount: u128,
+    },
+}
+
+impl PoolEvent {
+    /// Decodes a pool log; `None` for events that do not move swap state.
+    pub fn decode(log: &Log) -> Result<Option<Self>> {
+        let Some(topic0) = log.topics.first() else {
+            return Ok(None);
+        };
+        let data = abi::from_hex(&log.data).context("log data")?;
+        let event = match topic0.to_ascii_lowercase().as_str() {
+            SWAP_TOPIC => {
+                let w = abi::expect_words(&data, 5).context("Swap data")?;
+                PoolEvent::Swap {
+                    sqrt_price_x96: abi::as_u256(&w[2]),
+                    liquidity: abi::as_u128(&w[3]).context("Swap liquidity")?,
+                    tick: abi::as_i32(&w[4]).context("Swap tick")?,
+                }
+            }
+            MINT_TOPIC => {
+                let (tick_lower, tick_upper) = range(log)?;
+                let w = abi::expect_words(&data, 4).context("Mint data")?;
+                PoolEvent::Mint {
+                    tick_lower,
+                    tick_upper,
+                    amount: abi::as_u128(&w[1]).context("Mint amount")?,
+                }
+            }
+            BURN_TOPIC => {
+                let (tick_lower, tick_upper) = range(log)?;
+                let w = abi::expect_words(&data, 3).context("Burn data")?;
+                PoolEvent::Burn {
+                    tick_lower,
+                    tick_upper,
+                    amount: abi::as_u128(&w[0]).context("Burn amount")?,
+                }
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(event))
+    }
+
+    pub fn apply(&self, state: &mut PoolState) -> Result<()> {
+        match *self {
+            PoolEvent::Swap {
+                sqrt_price_x96,
+                liquidity,
+                tick,
+            } => {
+                state.sqrt_price_x96 = sqrt_price_x96;
+                state.liquidity = liquidity;
+                state.tick = tick;
+                Ok(())
+            }
+            PoolEvent::Mint {
+                tick_lower,
+                tick_upper,
+                amount,
+            } => modify_position(state, tick_lower, tick_upper, signed(amount)?),
+            PoolEvent::Burn {
+                tick_lower,
+                tick_upper,
+                amount,
+            } => modify_position(state, tick_lower, tick_upper, -signed(amount)?),
+        }
+    }
+}
+
+/// A factory's `PoolCreated`: tokens and fee are indexed, the tick spacing
+/// and pool address are the data.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PoolCreated {
+    /// Lowercase hex addresses.
+    pub pool: String,
+    pub token0: String,
+    pub token1: String,
+    pub fee: u32,
+    pub tick_spacing: i32,
+}
+
+impl PoolCreated {
+    /// `None` for any other event.
+    pub fn decode(log: &Log) -> Result<Option<Self>> {
+        if log
+            .topics
+            .first()
+            .is_none_or(|t| !t.eq_ignore_ascii_case(POOL_CREATED_TOPIC))
+        {
+            return Ok(None);
+        }
+        let data = abi::from_hex(&log.data).context("log data")?;
+        let w = abi::expect_words(&data, 2).context("PoolCreated data")?;
+        let fee = abi::as_u128(&topic_word(log, 3)?)?;
+        Ok(Some(Self {
+            pool: abi::as_address(&w[1]).context("PoolCreated pool")?,
+            token0: abi::as_address(&topic_word(log, 1)?).context("PoolCreated token0")?,
+            token1: abi::as_address(&topic_word(log, 2)?).context("PoolCreated token1")?,
+            fee: u32::try_from(fee).context("PoolCreated fee")?,
+            tick_spacing: abi::as_i32(&w[0]).context("PoolCreated tickSpacing")?,
+        }))
+    }
+}
+
+/// Indexed topic `i` as an ABI word.
+fn topic_word(log: &Log, i: usize) -> Result<Word> {
+    let topic = log
+        .topics
+        .get(i)
+        .ok_or_else(|| anyhow!("log has {} topics", log.topics.len()))?;
+    abi::from_hex(topic)?
+        .try_into()
+        .map_err(|_| anyhow!("topic {topic} is not a word"))
+}
+
+/// `tickLower` and `tickUpper`, the indexed third and fourth topics.
+fn range(log: &Log) -> Result<(i32, i32)> {
+    let tick = |i: usize| abi::as_i32(&topic_word(log, i)?);
+    let (lower, upper) = (tick(2)?, tick(3)?);
+    if lower >= upper {
+        bail!("empty position range {lower}..{upper}");
+    }
+    Ok((lower, upper))
+}
+
+fn signed(amount: u128) -> Result<i128> {
+    i128::try_from(amount).map_err(|_| anyhow!("liquidity delta {amount} overflows int128"))
+}
+
+fn modify_position(state: &mut PoolState, lower: i32, upper: i32, delta: i128) -> Result<()> {
+    for (tick, d) in [(lower, delta), (upper, -delta)] {
+        let net = state
+            .liquidity_net(tick)
+            .checked_add(d)
+            .ok_or_else(|| anyhow!("liquidityNet overflow at tick {tick}"))?;
+        state.set_liquidity_net(tick, net)?;
+    }
+    if (lower..upper).contains(&state.tick) {
+        state.liquidity = add_delta(state.liquidity, delta)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{int128_word, uint_word};
+    use crate::univ3::tick_math::get_sqrt_ratio_at_tick;
+
+    fn log(topics: Vec<String>, words: &[Word]) -> Log {
+        Log {
+            address: "0x00000000000000000000000000000000000000aa".to_string(),
+            topics,
+            data: abi::to_hex(&words.concat()),
+            block_number: 10,
+            block_hash: format!("0x{:064x}", 10),
+            log_index: 0,
+            removed: false,
+        }
+    }
+
+    fn position_topics(topic0: &str, lower: i64, upper: i64) -> Vec<String> {
+        vec![
+            topic0.to_string(),
+            abi::to_hex(&uint_word(0xbeef)),
+            abi::to_hex(&abi::int_word(lower)),
+            abi::to_hex(&abi::int_word(upper)),
+        ]
+    }
+
+    fn state() -> PoolState {
+        PoolState::new(3000, 60, get_sqrt_ratio_at_tick(-30).unwrap(), -30, 1_000).unwrap()
+    }
+
+    #[test]
+    fn decodes_log_json() {
+        let json = r#"{
+            "address": "0x00000000000000000000000000000000000000aa",
+            "topics": ["0x0c396cd989a39f4459b5fa1aed6a9a8dcdbc45908acfd67e028cd568da98982c"],
+            "data": "0x",
+            "blockNumber": "0x1b4",
+            "blockHash": "0x01",
+            "transactionHash": "0x02",
+            "logIndex": "0x3",
+            "removed": true
+        }"#;
+        let log: Log = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            (log.block_number, log.log_index, log.removed),
+            (436, 3, true)
+        );
+        // A Burn without its indexed ticks is malformed.
+        assert!(PoolEvent::decode(&log).is_err());
+    }
+
+    #[test]
+    fn decodes_pool_created() {
+        let address = |a: &str| abi::address_word(a).unwrap();
+        let created = log(
+            vec![
+                POOL_CREATED_TOPIC.to_string(),
+                abi::to_hex(&address("0x5555555555555555555555555555555555555555")),
+                abi::to_hex(&address("0xb88339cb7199b77e23db6e890353e22632ba630f")),
+                abi::to_hex(&uint_word(3000)),
+            ],
+            &[
+                abi::int_word(60),
+                address("0x00000000000000000000000000000000000000bb"),
+            ],
+        );
+        assert_eq!(
+            PoolCreated::decode(&created).unwrap(),
+            Some(PoolCreated {
+                pool: "0x00000000000000000000000000000000000000bb".into(),
+                token0: "0x5555555555555555555555555555555555555555".into(),
+                token1: "0xb88339cb7199b77e23db6e890353e22632ba630f".into(),
+                fee: 3000,
+                tick_spacing: 60,
+            })
+        );
+        assert_eq!(
+            PoolCreated::decode(&log(vec![SWAP_TOPIC.into()], &[])).unwrap(),
+            None
+        );
+        let mut truncated = created;
+        truncated.topics.pop();
+        assert!(PoolCreated::decode(&truncated).is_err());
+    }
+
+    #[test]
+    fn swap_overwrites_price_tick_and_liquidity() {
+        let sqrt = get_sqrt_ratio_at_tick(125).unwrap();
+        let swap = log(
+            vec![SWAP_TOPIC.to_string(), "0x01".into(), "0x02".into()],
+            &[
+                int128_word(-5),
+                uint_word(7),
+                sqrt.to_be_bytes(),
+                uint_word(42),
+                abi::int_word(125),
+            ],
+        );
+        let event = PoolEvent::decode(&swap).unwrap().unwrap();
+        let mut s = state();
+        event.apply(&mut s).unwrap();
+        assert_eq!((s.sqrt_price_x96, s.tick, s.liquidity), (sqrt, 125, 42));
+
+        let other = log(vec![format!("0x{:064x}", 1)], &[]);
+        assert_eq!(PoolEvent::decode(&other).unwrap(), None);
+    }
+
+    #[test]
+    fn mint_and_burn_move_range_liquidity() {
+        let mint = |lower, upper, amount| {
+            log(
+                position_topics(MINT_TOPIC, lower, upper),
+                &[
+                    uint_word(0xbeef),
+                    uint_word(amount),
+                    uint_word(1),
+                    uint_word(1),
+                ],
+            )
+        };
+        let burn = |lower, upper, amount| {
+            log(
+                position_topics(BURN_TOPIC, lower, upper),
+                &[uint_word(amount), uint_word(1), uint_word(1)],
+            )
+        };
+        let mut s = state();
+        for l in [mint(-60, 60, 500), mint(60, 120, 300), mint(-120, 60, 200)] {
+            PoolEvent::decode(&l)
+                .unwrap()
+                .unwrap()
+                .apply(&mut s)
+                .unwrap();
+        }
+        // Only ranges around tick -30 add active liquidity.
+        assert_eq!(s.liquidity, 1_700);
+        assert_eq!(
+            s.initialized_ticks().collect::<Vec<_>>(),
+            vec![(-120, 200), (-60, 500), (60, -400), (120, -300)]
+        );
+
+        for l in [burn(-60, 60, 500), burn(60, 120, 300)] {
+            PoolEvent::decode(&l)
+                .unwrap()
+                .unwrap()
+                .apply(&mut s)
+                .unwrap();
+        }
+        assert_eq!(s.liquidity, 1_200);
+        assert_eq!(
+            s.initialized_ticks().collect::<Vec<_>>(),
+            vec![(-120, 200), (60, -200)]
+        );
+
+        // Burning more than the active liquidity is inconsistent state.
+        let e = PoolEvent::decode(&burn(-60, 60, 5_000))
+            .unwrap()
+            .unwrap()
+            .apply(&mut s.clone())
+            .unwrap_err();
+        assert!(e.to_string().contains("underflow"), "{e}");
+    }
+}
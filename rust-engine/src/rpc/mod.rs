@@ -0,0 +1,359 @@
+//! Minimal Ethereum JSON-RPC client for HyperEVM, on the shared `reqwest::Client`.
+//!
+//! Only what the engine reads: the head block and `eth_call`, always pinned
+//! to an explicit block so that several reads describe the same state and
+//! batched through Multicall3 by [`multicall`], `eth_getLogs` over block
+//! ranges, plus the WebSocket log subscription in [`stream`].
+//!
+//! A client can sit on several providers. Reads fail over from the
+//! healthiest one (see [`provider`]), the head block can require a quorum,
+//! and raw transactions go to all of them.
+
+pub mod abi;
+pub mod logs;
+pub mod multicall;
+pub mod pool_reader;
+pub mod provider;
+pub mod reorg;
+pub mod stream;
+
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail, Context, Result};
+use futures_util::future::join_all;
+use reqwest::Client;
+use serde::de::{DeserializeOwned, Deserializer};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tracing::warn;
+
+pub use pool_reader::PoolReader;
+pub use provider::ProviderHealth;
+
+use provider::Providers;
+
+/// Per-request limit before a provider counts as failed.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Error object of a JSON-RPC response, e.g. a reverted `eth_call`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+    #[serde(default)]
+    pub data: Option<Value>,
+}
+
+impl fmt::Display for RpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "JSON-RPC error {}: {}", self.code, self.message)
+    }
+}
+
+impl std::error::Error for RpcError {}
+
+impl RpcError {
+    /// The call itself failed, e.g. reverted; any node would say the same.
+    pub fn is_execution_error(&self) -> bool {
+        self.code == 3 || self.message.to_ascii_lowercase().contains("revert")
+    }
+}
+
+/// Whether `e` says something about the node rather than the request.
+fn is_provider_fault(e: &anyhow::Error) -> bool {
+    e.downcast_ref::<RpcError>()
+        .is_none_or(|err| !err.is_execution_error())
+}
+
+#[derive(Debug, Deserialize)]
+struct Response {
+    result: Option<Value>,
+    error: Option<RpcError>,
+}
+
+/// Client for one or more JSON-RPC endpoints serving the same chain. Cheap
+/// to clone; clones share the id counter and provider health.
+#[derive(Debug, Clone)]
+pub struct RpcClient {
+    http: Client,
+    providers: Arc<Providers>,
+    head_quorum: usize,
+    timeout: Duration,
+    next_id: Arc<AtomicU64>,
+}
+
+impl RpcClient {
+    pub fn new(http: Client, url: impl Into<String>) -> Self {
+        Self::with_providers(http, vec![url.into()])
+    }
+
+    /// Several endpoints; the first is preferred until scores say otherwise.
+    /// Panics if `urls` is empty.
+    pub fn with_providers(http: Client, urls: Vec<String>) -> Self {
+        Self {
+            http,
+            providers: Arc::new(Providers::new(urls)),
+            head_quorum: 1,
+            timeout: DEFAULT_TIMEOUT,
+            next_id: Arc::new(AtomicU64::new(1)),
+        }
+    }
+
+    /// [`RpcClient::block_number`] asks every provider and returns the
+    /// highest block at least `n` of them have reached.
+    pub fn with_head_quorum(mut self, n: usize) -> Self {
+        self.head_quorum = n.max(1);
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// The first configured provider.
+    pub fn url(&self) -> &str {
+        self.providers.url(0)
+    }
+
+    pub fn health(&self) -> Vec<ProviderHealth> {
+        self.providers.health()
+    }
+
+    /// Sends one request, failing over between providers. A JSON-RPC error
+    /// comes back as an [`RpcError`] inside the `anyhow::Error`; one that
+    /// blames the call rather than the node is returned without failover.
+    pub async fn request<T: DeserializeOwned>(&self, method: &str, params: Value) -> Result<T> {
+        self.failover(method, &params).await.map(|(_, v)| v)
+    }
+
+    /// Like [`RpcClient::request`], also returning which provider answered.
+    async fn failover<T: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: &Value,
+    ) -> Result<(usize, T)> {
+        let mut last = None;
+        for i in self.providers.ranked() {
+            match self.request_on(i, method, params).await {
+                Ok(v) => return Ok((i, v)),
+                Err(e) if !is_provider_fault(&e) => return Err(e),
+                Err(e) => {
+                    if self.providers.len() > 1 {
+                        warn!(url = self.providers.url(i), method, error = %e, "RPC provider failed");
+                    }
+                    last = Some(e);
+                }
+            }
+        }
+        let e = last.expect("at least one provider");
+        match self.providers.len() {
+            1 => Err(e),
+            n => Err(e.context(format!("{method}: all {n} providers failed"))),
+        }
+    }
+
+    /// Sends one request to provider `i` and scores the outcome.
+    async fn request_on<T: DeserializeOwned>(
+        &self,
+        i: usize,
+        method: &str,
+        params: &Value,
+    ) -> Result<T> {
+        let started = Instant::now();
+        let result = self.send(self.providers.url(i), method, params).await;
+        match &result {
+            Err(e) if is_provider_fault(e) => self.providers.record_failure(i),
+            _ => self.providers.record_success(i, started.elapsed()),
+        }
+        result
+    }
+
+    async fn send<T: DeserializeOwned>(
+        &self,
+        url: &str,
+        method: &str,
+        params: &Value,
+    ) -> Result<T> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let body = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+        let resp = self
+            .http
+            .post(url)
+            .timeout(self.timeout)
+            .json(&body)
+            .send()
+            .await
+            .with_context(|| format!("POST {url}"))?;
+        let status = resp.status();
+        if !status.is_success() {
+            bail!("{method}: RPC returned HTTP {status}");
+        }
+        let parsed: Response = resp
+            .json()
+            .await
+            .with_context(|| format!("{method}: decode JSON-RPC response"))?;
+        if let Some(err) = parsed.error {
+            return Err(anyhow::Error::new(err).context(method.to_string()));
+        }
+        let result = parsed
+            .result
+            .ok_or_else(|| anyhow!("{method}: response without result"))?;
+        serde_json::from_value(result).with_context(|| format!("{method}: unexpected result"))
+    }
+
+    /// Head block: from the healthiest provider, or under a quorum the
+    /// highest block enough providers agree has been reached.
+    pub async fn block_number(&self) -> Result<u64> {
+        if self.head_quorum <= 1 {
+            let (i, hex) = self
+                .failover::<String>("eth_blockNumber", &json!([]))
+                .await?;
+            let head = parse_quantity(&hex)?;
+            self.providers.record_head(i, head);
+            return Ok(head);
+        }
+        let mut heads: Vec<u64> = self.heads().await.into_iter().flatten().collect();
+        heads.sort_unstable_by(|a, b| b.cmp(a));
+        heads.get(self.head_quorum - 1).copied().ok_or_else(|| {
+            anyhow!(
+                "eth_blockNumber: {} of {} providers answered, quorum is {}",
+                heads.len(),
+                self.providers.len(),
+                self.head_quorum
+            )
+        })
+    }
+
+    /// Every provider's head, in configured order.
+    pub async fn heads(&self) -> Vec<Result<u64>> {
+        join_all((0..self.providers.len()).map(|i| async move {
+            let hex: String = self.request_on(i, "eth_blockNumber", &json!([])).await?;
+            let head = parse_quantity(&hex)?;
+            self.providers.record_head(i, head);
+            Ok(head)
+        }))
+        .await
+    }
+
+    /// Header of the canonical block at `number`.
+    pub async fn block_header(&self, number: u64) -> Result<reorg::BlockHeader> {
+        let header: Option<reorg::BlockHeader> = self
+            .request("eth_getBlockByNumber", json!([quantity(number), false]))
+            .await?;
+        header.ok_or_else(|| anyhow!("eth_getBlockByNumber: no block {number}"))
+    }
+
+    /// Logs `address` emitted in `from..=to` whose first topic is `topic0`.
+    pub async fn get_logs(
+        &self,
+        address: &str,
+        topic0: &str,
+        from: u64,
+        to: u64,
+    ) -> Result<Vec<logs::Log>> {
+        self.request(
+            "eth_getLogs",
+            json!([{
+                "address": address,
+                "topics": [topic0],
+                "fromBlock": quantity(from),
+                "toBlock": quantity(to),
+            }]),
+        )
+        .await
+    }
+
+    /// Submits a signed transaction to every provider at once; succeeds
+    /// with the hash if any of them accepts it.
+    pub async fn send_raw_transaction(&self, raw: &[u8]) -> Result<String> {
+        let params = json!([abi::to_hex(raw)]);
+        let results = join_all(
+            (0..self.providers.len())
+                .map(|i| self.request_on::<String>(i, "eth_sendRawTransaction", &params)),
+        )
+        .await;
+        let mut first_err = None;
+        for result in results {
+            match result {
+                Ok(hash) => return Ok(hash),
+                Err(e) => {
+                    first_err.get_or_insert(e);
+                }
+            }
+        }
+        let e = first_err.expect("at least one provider");
+        Err(e.context(format!(
+            "eth_sendRawTransaction: rejected by all {} providers",
+            self.providers.len()
+        )))
+    }
+
+    /// `eth_call` of `data` against `to` at `block`; returns the raw return data.
+    pub async fn eth_call(&self, to: &str, data: &[u8], block: u64) -> Result<Vec<u8>> {
+        let hex: String = self
+            .request(
+                "eth_call",
+                json!([{ "to": to, "data": abi::to_hex(data) }, quantity(block)]),
+            )
+            .await?;
+        abi::from_hex(&hex)
+    }
+}
+
+/// Hex-encoded quantity, as JSON-RPC expects block numbers.
+pub fn quantity(v: u64) -> String {
+    format!("{v:#x}")
+}
+
+pub fn parse_quantity(s: &str) -> Result<u64> {
+    let digits = s
+        .strip_prefix("0x")
+        .ok_or_else(|| anyhow!("quantity {s:?} lacks 0x prefix"))?;
+    u64::from_str_radix(digits, 16).with_context(|| format!("invalid quantity {s:?}"))
+}
+
+/// Serde adapter for quantity fields such as `blockNumber`.
+pub fn deserialize_quantity<'de, D: Deserializer<'de>>(d: D) -> Result<u64, D::Error> {
+    let s = String::deserialize(d)?;
+    parse_quantity(&s).map_err(serde::de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockJsonServer;
+
+    #[test]
+    fn quantities_round_trip() {
+        assert_eq!(quantity(0), "0x0");
+        assert_eq!(quantity(4_660), "0x1234");
+        assert_eq!(parse_quantity("0x1234").unwrap(), 4_660);
+        assert!(parse_quantity("1234").is_err());
+    }
+
+    #[tokio::test]
+    async fn surfaces_rpc_errors() {
+        let server = MockJsonServer::start(|req| {
+            json!({
+                "jsonrpc": "2.0",
+                "id": req["id"],
+                "error": { "code": 3, "message": "execution reverted", "data": "0x" }
+            })
+        })
+        .await;
+        let rpc = RpcClient::new(Client::new(), &server.url);
+        let e = rpc.eth_call("0x00", &[1, 2, 3, 4], 7).await.unwrap_err();
+        let rpc_err = e.downcast_ref::<RpcError>().unwrap();
+        assert_eq!(rpc_err.code, 3);
+        assert!(e.to_string().contains("eth_call"), "{e}");
+
+        let req = &server.requests()[0];
+        assert_eq!(req["method"], "eth_call");
+        assert_eq!(req["params"][0]["data"], "0x01020304");
+        assert_eq!(req["params"][1], "0x7");
+    }
+}
@@ -0,0 +1,358 @@
+//! Multicall3 batching: many view calls in one `eth_call` to `aggregate3`.
+//!
+//! Callers add calls to a [`Batch`], each with its own decoder, and get a
+//! typed [`Handle`] back. [`Multicall::execute`] splits the batch so no
+//! `eth_call` exceeds the gas or calldata limit, sends the pieces
+//! concurrently at one block number, and [`Results::get`] decodes each
+//! handle's return data. Calls are sent with `allowFailure`, so a revert only
+//! fails its own handle.
+
+use std::ops::Range;
+
+use anyhow::{anyhow, bail, Context, Result};
+use futures_util::future::try_join_all;
+
+use super::abi::{self, Selector, Word};
+use super::RpcClient;
+
+/// Multicall3, at the same address on every chain it is deployed on.
+pub const MULTICALL3: &str = "0xca11bde05977b3631167028862be2a173976ca11";
+
+/// Gas assumed for a call that names none: a handful of cold storage reads.
+pub const DEFAULT_CALL_GAS: u64 = 30_000;
+/// Kept well under the `eth_call` gas cap of public nodes.
+pub const DEFAULT_MAX_BATCH_GAS: u64 = 10_000_000;
+pub const DEFAULT_MAX_BATCH_BYTES: usize = 32 * 1024;
+
+/// One view call.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Call {
+    pub target: String,
+    pub data: Vec<u8>,
+    /// Estimated gas, counted against the batch limit.
+    pub gas: u64,
+}
+
+impl Call {
+    pub fn new(target: &str, selector: Selector, args: &[Word]) -> Self {
+        Self {
+            target: target.to_string(),
+            data: abi::encode_call(selector, args),
+            gas: DEFAULT_CALL_GAS,
+        }
+    }
+
+    pub fn with_gas(mut self, gas: u64) -> Self {
+        self.gas = gas;
+        self
+    }
+
+    /// Bytes this call adds to `aggregate3` calldata: its offset, the
+    /// `(target, allowFailure, data offset)` head, the data length and the
+    /// padded data.
+    fn encoded_len(&self) -> usize {
+        32 * 5 + self.data.len().next_multiple_of(32)
+    }
+}
+
+/// A call's place in its batch and how to decode its return data.
+pub struct Handle<T> {
+    index: usize,
+    decode: fn(&[u8]) -> Result<T>,
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Handle<T> {}
+
+/// Calls to send together, at one block.
+#[derive(Debug, Clone, Default)]
+pub struct Batch {
+    calls: Vec<Call>,
+}
+
+impl Batch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add<T>(&mut self, call: Call, decode: fn(&[u8]) -> Result<T>) -> Handle<T> {
+        self.calls.push(call);
+        Handle {
+            index: self.calls.len() - 1,
+            decode,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.calls.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.calls.is_empty()
+    }
+}
+
+/// Return data of an executed [`Batch`]; `None` where the call reverted.
+#[derive(Debug, Clone)]
+pub struct Results {
+    calls: Vec<Call>,
+    returns: Vec<Option<Vec<u8>>>,
+}
+
+impl Results {
+    pub fn get<T>(&self, handle: Handle<T>) -> Result<T> {
+        let call = &self.calls[handle.index];
+        let selector = abi::to_hex(&call.data[..call.data.len().min(4)]);
+        match &self.returns[handle.index] {
+            Some(data) => {
+                (handle.decode)(data).with_context(|| format!("{}: decode {selector}", call.target))
+            }
+            None => Err(anyhow!("{}: call {selector} reverted", call.target)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Multicall {
+    rpc: RpcClient,
+    address: String,
+    max_gas: u64,
+    max_bytes: usize,
+}
+
+impl Multicall {
+    pub fn new(rpc: RpcClient) -> Self {
+        Self {
+            rpc,
+            address: MULTICALL3.to_string(),
+            max_gas: DEFAULT_MAX_BATCH_GAS,
+            max_bytes: DEFAULT_MAX_BATCH_BYTES,
+        }
+    }
+
+    /// For chains where Multicall3 lives elsewhere.
+    pub fn with_address(mut self, address: impl Into<String>) -> Self {
+        self.address = address.into();
+        self
+    }
+
+    /// Per-`eth_call` budget of summed [`Call::gas`] and calldata bytes. A
+    /// call over either limit on its own still goes out, alone.
+    pub fn with_limits(mut self, max_gas: u64, max_bytes: usize) -> Self {
+        self.max_gas = max_gas;
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    pub fn rpc(&self) -> &RpcClient {
+        &self.rpc
+    }
+
+    /// Runs every call in `batch` at `block`. Fails only if a whole
+    /// `eth_call` does; reverts surface per handle.
+    pub async fn execute(&self, batch: Batch, block: u64) -> Result<Results> {
+        let all = &batch.calls;
+        let returns = try_join_all(self.chunks(all).into_iter().map(|range| async move {
+            let calls = &all[range];
+            let data = self
+                .rpc
+                .eth_call(&self.address, &encode_aggregate3(calls), block)
+                .await
+                .with_context(|| format!("aggregate3 of {} calls", calls.len()))?;
+            let returns = decode_aggregate3(&data)?;
+            if returns.len() != calls.len() {
+                bail!(
+                    "aggregate3 returned {} results for {} calls",
+                    returns.len(),
+                    calls.len()
+                );
+            }
+            Ok(returns)
+        }))
+        .await?;
+        Ok(Results {
+            calls: batch.calls,
+            returns: returns.into_iter().flatten().collect(),
+        })
+    }
+
+    /// Consecutive runs of `calls` within the gas and size limits.
+    fn chunks(&self, calls: &[Call]) -> Vec<Range<usize>> {
+        // Selector, array offset and length.
+        const BASE: usize = 4 + 32 * 2;
+        let mut chunks = Vec::new();
+        let (mut start, mut gas, mut bytes) = (0, 0, BASE);
+        for (i, call) in calls.iter().enumerate() {
+            let len = call.encoded_len();
+            if i > start && (gas + call.gas > self.max_gas || bytes + len > self.max_bytes) {
+                chunks.push(start..i);
+                (start, gas, bytes) = (i, 0, BASE);
+            }
+            gas += call.gas;
+            bytes += len;
+        }
+        if start < calls.len() {
+            chunks.push(start..calls.len());
+        }
+        chunks
+    }
+}
+
+/// Calldata of `aggregate3((address,bool,bytes)[])`, every call allowed to fail.
+pub fn encode_aggregate3(calls: &[Call]) -> Vec<u8> {
+    let mut heads = Vec::with_capacity(calls.len());
+    let mut tails = Vec::new();
+    let mut offset = 32 * calls.len();
+    for call in calls {
+        heads.push(abi::uint_word(offset as u128));
+        let mut tail = Vec::with_capacity(call.encoded_len() - 32);
+        // An unparsable target goes out as the zero address, whose empty
+        // return then fails that call's decoder alone.
+        tail.extend_from_slice(&abi::address_word(&call.target).unwrap_or([0; 32]));
+        tail.extend_from_slice(&abi::uint_word(1));
+        tail.extend_from_slice(&abi::uint_word(0x60));
+        tail.extend_from_slice(&abi::uint_word(call.data.len() as u128));
+        tail.extend_from_slice(&call.data);
+        tail.resize(tail.len().next_multiple_of(32), 0);
+        offset += tail.len();
+        tails.push(tail);
+    }
+    let mut out = abi::encode_call(
+        abi::AGGREGATE3,
+        &[abi::uint_word(32), abi::uint_word(calls.len() as u128)],
+    );
+    out.extend(heads.concat());
+    out.extend(tails.concat());
+    out
+}
+
+/// Return data of `aggregate3`: `(bool success, bytes returnData)[]`.
+pub fn decode_aggregate3(data: &[u8]) -> Result<Vec<Option<Vec<u8>>>> {
+    let word_at = |at: usize| -> Result<Word> {
+        data.get(at..at + 32)
+            .and_then(|w| w.try_into().ok())
+            .ok_or_else(|| anyhow!("aggregate3 result truncated at byte {at}"))
+    };
+    let usize_at =
+        |at: usize| -> Result<usize> { Ok(usize::try_from(abi::as_u128(&word_at(at)?)?)?) };
+    let array = usize_at(0)?;
+    let len = usize_at(array)?;
+    let elements = array + 32;
+    (0..len)
+        .map(|i| {
+            let tuple = elements + usize_at(elements + 32 * i)?;
+            let success = abi::as_bool(&word_at(tuple)?)?;
+            let bytes = tuple + usize_at(tuple + 32)?;
+            let n = usize_at(bytes)?;
+            let ret = data
+                .get(bytes + 32..bytes + 32 + n)
+                .ok_or_else(|| anyhow!("aggregate3 return data {i} out of bounds"))?;
+            Ok(success.then(|| ret.to_vec()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::Client;
+
+    use super::*;
+    use crate::state::Dex;
+    use crate::testing::{encode_aggregate3_results, token, v3_pool, MockChain};
+
+    const POOL: &str = "0x00000000000000000000000000000000000000aa";
+
+    fn word(data: &[u8]) -> Result<Word> {
+        Ok(abi::expect_words(data, 1)?[0])
+    }
+
+    #[test]
+    fn encodes_aggregate3_calldata() {
+        let data = encode_aggregate3(&[Call::new(POOL, abi::SLOT0, &[])]);
+        let expected = [
+            "82ad56cb",
+            &format!("{:064x}", 0x20),
+            &format!("{:064x}", 1),
+            &format!("{:064x}", 0x20),
+            &format!("{:0>64}", &POOL[2..]),
+            &format!("{:064x}", 1),
+            &format!("{:064x}", 0x60),
+            &format!("{:064x}", 4),
+            &format!("{:0<64}", "3850c7bd"),
+        ]
+        .concat();
+        assert_eq!(abi::to_hex(&data), format!("0x{expected}"));
+
+        let ret = encode_aggregate3_results(&[Some(vec![7; 40]), None, Some(vec![])]);
+        assert_eq!(
+            decode_aggregate3(&ret).unwrap(),
+            vec![Some(vec![7; 40]), None, Some(vec![])]
+        );
+        assert!(decode_aggregate3(&ret[..ret.len() - 32]).is_err());
+    }
+
+    #[test]
+    fn splits_under_gas_and_size_limits() {
+        let rpc = RpcClient::new(Client::new(), "http://127.0.0.1:1");
+        let calls: Vec<Call> = (0..5).map(|_| Call::new(POOL, abi::FEE, &[])).collect();
+        let by_gas = Multicall::new(rpc.clone()).with_limits(2 * DEFAULT_CALL_GAS, usize::MAX);
+        assert_eq!(by_gas.chunks(&calls), vec![0..2, 2..4, 4..5]);
+
+        // 68 bytes of framing plus 192 per call leaves room for three.
+        let by_size = Multicall::new(rpc.clone()).with_limits(u64::MAX, 68 + 3 * 192);
+        assert_eq!(by_size.chunks(&calls), vec![0..3, 3..5]);
+
+        let huge = vec![Call::new(POOL, abi::FEE, &[]).with_gas(u64::MAX / 2)];
+        assert_eq!(by_gas.chunks(&huge), vec![0..1]);
+        assert!(by_gas.chunks(&[]).is_empty());
+    }
+
+    #[tokio::test]
+    async fn decodes_results_per_caller_at_one_block() {
+        let pool = v3_pool(
+            POOL,
+            Dex::Prjx,
+            token("WHYPE"),
+            token("USDC"),
+            3000,
+            -239_434,
+            7,
+        );
+        let server = MockChain::new(vec![pool], 100).serve().await;
+        let rpc = RpcClient::new(Client::new(), &server.url);
+        let multicall = Multicall::new(rpc).with_limits(3 * DEFAULT_CALL_GAS, usize::MAX);
+
+        let mut batch = Batch::new();
+        let fee = batch.add(Call::new(POOL, abi::FEE, &[]), |d| abi::as_u128(&word(d)?));
+        let missing = batch.add(
+            Call::new("0x00000000000000000000000000000000000000bb", abi::FEE, &[]),
+            word,
+        );
+        let token0 = batch.add(Call::new(POOL, abi::TOKEN0, &[]), |d| {
+            abi::as_address(&word(d)?)
+        });
+        let symbol = batch.add(
+            Call::new(&token("USDC").address, abi::SYMBOL, &[]),
+            abi::decode_string,
+        );
+        let results = multicall.execute(batch, 0x4d).await.unwrap();
+
+        assert_eq!(results.get(fee).unwrap(), 3000);
+        assert_eq!(results.get(token0).unwrap(), token("WHYPE").address);
+        assert_eq!(results.get(symbol).unwrap(), "USDC");
+        let e = results.get(missing).unwrap_err();
+        assert!(e.to_string().contains("reverted"), "{e}");
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        for r in &requests {
+            assert_eq!(r["params"][0]["to"], MULTICALL3);
+            assert_eq!(r["params"][1], "0x4d");
+        }
+    }
+}
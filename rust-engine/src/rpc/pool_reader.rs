@@ -0,0 +1,491 @@
+//! V3 pool state read straight from the pool contract over JSON-RPC.
+//!
+//! `slot0`, `liquidity`, the `tickBitmap` words around the current tick and
+//! `ticks` of every initialized tick in them, all at one block. Ticks outside
+//! the word window are not read: a quote that walks past it sees no liquidity
+//! change there, so keep the window wider than any size the detector tries.
+//!
+//! Reads go through Multicall3, three rounds for any number of pools: the
+//! slot getters, then bitmap words, then ticks.
+
+use std::ops::RangeInclusive;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::Utc;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+use super::abi::{self, Word};
+use super::multicall::{Batch, Call, Multicall};
+use super::RpcClient;
+use crate::state::{Dex, SharedPoolStore, Token, TrackedPool};
+use crate::univ3::tick_bitmap::position;
+use crate::univ3::tick_math::{MAX_TICK, MIN_TICK};
+use crate::univ3::{PoolState, U256};
+
+/// Bitmap words read on each side of the current tick's word. One word
+/// spans 256 tick spacings, e.g. +-15% of price at spacing 60.
+pub const DEFAULT_WORD_RADIUS: u8 = 2;
+
+#[derive(Debug, Clone)]
+pub struct PoolReader {
+    multicall: Multicall,
+    word_radius: u8,
+}
+
+impl PoolReader {
+    pub fn new(rpc: RpcClient) -> Self {
+        Self {
+            multicall: Multicall::new(rpc),
+            word_radius: DEFAULT_WORD_RADIUS,
+        }
+    }
+
+    pub fn with_word_radius(mut self, words: u8) -> Self {
+        self.word_radius = words;
+        self
+    }
+
+    pub fn with_multicall(mut self, multicall: Multicall) -> Self {
+        self.multicall = multicall;
+        self
+    }
+
+    pub fn rpc(&self) -> &RpcClient {
+        self.multicall.rpc()
+    }
+
+    /// Swap state of `pool` at `block`.
+    pub async fn read_state(&self, pool: &str, block: u64) -> Result<PoolState> {
+        let mut states = self.read_states(&[pool.to_string()], block).await?;
+        states.remove(0)
+    }
+
+    /// Swap state of each of `pools` at `block`, in order. The outer error
+    /// is a failed request; a pool whose calls revert or do not decode
+    /// fails alone.
+    pub async fn read_states(
+        &self,
+        pools: &[String],
+        block: u64,
+    ) -> Result<Vec<Result<PoolState>>> {
+        let mut batch = Batch::new();
+        let slots: Vec<_> = pools
+            .iter()
+            .map(|p| {
+                (
+                    batch.add(Call::new(p, abi::FEE, &[]), decode_fee),
+                    batch.add(Call::new(p, abi::TICK_SPACING, &[]), decode_tick_spacing),
+                    batch.add(Call::new(p, abi::SLOT0, &[]), decode_slot0),
+                    batch.add(Call::new(p, abi::LIQUIDITY, &[]), decode_liquidity),
+                )
+            })
+            .collect();
+        let results = self.multicall.execute(batch, block).await?;
+        let mut states: Vec<Result<PoolState>> = pools
+            .iter()
+            .zip(slots)
+            .map(|(pool, (fee, spacing, slot0, liquidity))| {
+                let (sqrt_price_x96, tick) = results.get(slot0)?;
+                if sqrt_price_x96.is_zero() {
+                    bail!("pool {pool} not initialized");
+                }
+                Ok(PoolState::new(
+                    results.get(fee)?,
+                    results.get(spacing)?,
+                    sqrt_price_x96,
+                    tick,
+                    results.get(liquidity)?,
+                )?)
+            })
+            .collect();
+
+        let mut batch = Batch::new();
+        let mut words = Vec::new();
+        for (i, state) in states.iter().enumerate() {
+            let Ok(state) = state else { continue };
+            for w in self.word_range(state) {
+                let call = Call::new(&pools[i], abi::TICK_BITMAP, &[abi::int_word(w.into())]);
+                words.push((i, w, batch.add(call, decode_u256)));
+            }
+        }
+        let results = self.multicall.execute(batch, block).await?;
+
+        let mut batch = Batch::new();
+        let mut ticks = Vec::new();
+        for (i, w, handle) in words {
+            let Ok(state) = &states[i] else { continue };
+            match results.get(handle) {
+                Ok(bits) => {
+                    for t in initialized_ticks(w, bits, state.tick_spacing) {
+                        let call = Call::new(&pools[i], abi::TICKS, &[abi::int_word(t.into())]);
+                        ticks.push((i, t, batch.add(call, decode_liquidity_net)));
+                    }
+                }
+                Err(e) => states[i] = Err(e.context(format!("tickBitmap({w})"))),
+            }
+        }
+        let results = self.multicall.execute(batch, block).await?;
+
+        for (i, t, handle) in ticks {
+            let Ok(state) = &mut states[i] else { continue };
+            let set = results
+                .get(handle)
+                .and_then(|net| Ok(state.set_liquidity_net(t, net)?));
+            if let Err(e) = set {
+                states[i] = Err(e.context(format!("ticks({t})")));
+            }
+        }
+        Ok(states)
+    }
+
+    /// Bitmap words within `word_radius` of the current tick, clamped to the
+    /// words valid ticks fall in.
+    fn word_range(&self, state: &PoolState) -> RangeInclusive<i16> {
+        let spacing = state.tick_spacing;
+        let (center, _) = position(state.tick.div_euclid(spacing));
+        let (min_word, _) = position(MIN_TICK / spacing);
+        let (max_word, _) = position(MAX_TICK / spacing);
+        let radius = i16::from(self.word_radius);
+        center.saturating_sub(radius).max(min_word)..=center.saturating_add(radius).min(max_word)
+    }
+
+    pub async fn read_token(&self, address: &str, block: u64) -> Result<Token> {
+        let mut tokens = self.read_tokens(&[address.to_string()], block).await?;
+        tokens.remove(0)
+    }
+
+    /// Decimals, symbol and name of each of `tokens` at `block`, in one
+    /// round. A token without a working `name` gets an empty one.
+    pub async fn read_tokens(&self, tokens: &[String], block: u64) -> Result<Vec<Result<Token>>> {
+        let mut batch = Batch::new();
+        let handles: Vec<_> = tokens
+            .iter()
+            .map(|t| {
+                (
+                    batch.add(Call::new(t, abi::DECIMALS, &[]), decode_decimals),
+                    batch.add(Call::new(t, abi::SYMBOL, &[]), abi::decode_string),
+                    batch.add(Call::new(t, abi::NAME, &[]), abi::decode_string),
+                )
+            })
+            .collect();
+        let results = self.multicall.execute(batch, block).await?;
+        Ok(tokens
+            .iter()
+            .zip(handles)
+            .map(|(address, (decimals, symbol, name))| {
+                Ok(Token {
+                    address: address.to_lowercase(),
+                    symbol: results.get(symbol)?,
+                    name: results.get(name).unwrap_or_default(),
+                    decimals: results.get(decimals)?,
+                    ..Token::default()
+                })
+            })
+            .collect())
+    }
+
+    /// Pool metadata, both tokens and the swap state at `block`.
+    pub async fn read_pool(&self, address: &str, dex: Dex, block: u64) -> Result<TrackedPool> {
+        let mut batch = Batch::new();
+        let token0 = batch.add(Call::new(address, abi::TOKEN0, &[]), decode_address);
+        let token1 = batch.add(Call::new(address, abi::TOKEN1, &[]), decode_address);
+        let (results, state) = tokio::try_join!(
+            self.multicall.execute(batch, block),
+            self.read_state(address, block),
+        )?;
+        let (token0, token1) = (results.get(token0)?, results.get(token1)?);
+        let mut tokens = self.read_tokens(&[token0, token1], block).await?;
+        let token1 = tokens.pop().ok_or_else(|| anyhow!("token1 missing"))??;
+        let token0 = tokens.pop().ok_or_else(|| anyhow!("token0 missing"))??;
+        Ok(TrackedPool {
+            address: address.to_lowercase(),
+            dex,
+            token0,
+            token1,
+            state: state.into(),
+            tvl_usd: None,
+            block: Some(block),
+            updated_at: Utc::now(),
+        })
+    }
+}
+
+/// Ticks whose bit is set in bitmap word `word`.
+fn initialized_ticks(word: i16, bits: U256, spacing: i32) -> impl Iterator<Item = i32> {
+    (0..256u32)
+        .filter(move |&b| !(bits & (U256::ONE << b)).is_zero())
+        .map(move |b| (i32::from(word) * 256 + b as i32) * spacing)
+}
+
+fn first_word(data: &[u8]) -> Result<Word> {
+    Ok(abi::expect_words(data, 1)?[0])
+}
+
+fn decode_u256(data: &[u8]) -> Result<U256> {
+    Ok(abi::as_u256(&first_word(data)?))
+}
+
+fn decode_fee(data: &[u8]) -> Result<u32> {
+    u32::try_from(abi::as_u128(&first_word(data)?)?).context("fee")
+}
+
+fn decode_tick_spacing(data: &[u8]) -> Result<i32> {
+    abi::as_i32(&first_word(data)?).context("tickSpacing")
+}
+
+fn decode_slot0(data: &[u8]) -> Result<(U256, i32)> {
+    let w = abi::expect_words(data, 2).context("slot0")?;
+    Ok((
+        abi::as_u256(&w[0]),
+        abi::as_i32(&w[1]).context("slot0.tick")?,
+    ))
+}
+
+fn decode_liquidity(data: &[u8]) -> Result<u128> {
+    abi::as_u128(&first_word(data)?).context("liquidity")
+}
+
+/// `ticks(tick).liquidityNet`, the second field.
+fn decode_liquidity_net(data: &[u8]) -> Result<i128> {
+    let info = abi::expect_words(data, 2)?;
+    abi::as_i128(&info[1]).context("liquidityNet")
+}
+
+fn decode_decimals(data: &[u8]) -> Result<u8> {
+    u8::try_from(abi::as_u128(&first_word(data)?)?).context("decimals")
+}
+
+fn decode_address(data: &[u8]) -> Result<String> {
+    abi::as_address(&first_word(data)?)
+}
+
+/// Re-reads every tracked pool at the head block every `every`, starting
+/// immediately. Pools come from the other sources; this only keeps their
+/// swap state current.
+pub fn spawn_refresh(
+    reader: PoolReader,
+    store: SharedPoolStore,
+    every: Duration,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(every);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            let block = match reader.rpc().block_number().await {
+                Ok(block) => block,
+                Err(e) => {
+                    warn!(error = %e, "RPC head block failed");
+                    continue;
+                }
+            };
+            let addresses: Vec<String> = store
+                .read()
+                .await
+                .iter()
+                .map(|p| p.address.clone())
+                .collect();
+            let states = match reader.read_states(&addresses, block).await {
+                Ok(states) => states,
+                Err(e) => {
+                    warn!(block, error = %e, "RPC pool read failed");
+                    continue;
+                }
+            };
+            let mut store = store.write().await;
+            let mut updated = 0;
+            for (address, state) in addresses.iter().zip(states) {
+                match state {
+                    Ok(state) => updated += usize::from(store.set_state(address, state, block)),
+                    Err(e) => debug!(pool = %address, error = %e, "RPC pool read failed"),
+                }
+            }
+            info!(block, pools = updated, "refreshed pool state from RPC");
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::Client;
+
+    use super::*;
+    use crate::rpc::multicall::MULTICALL3;
+    use crate::state::PoolStore;
+    use crate::testing::{token, v3_pool, MockChain, MockJsonServer};
+    use crate::univ3::tick_math::get_sqrt_ratio_at_tick;
+
+    const POOL: &str = "0x00000000000000000000000000000000000000aa";
+    const HEAD: u64 = 0x1234;
+    const TICKS: [(i32, i128); 5] = [
+        (-300_000, 7),
+        (-240_000, 4_000_000_000_000_000 - 7),
+        (-239_460, 1_000_000_000_000_000),
+        (-238_800, -5_000_000_000_000_000 + 7),
+        (-230_400, -7),
+    ];
+
+    /// Ticks on both sides of the current one, plus one far outside the
+    /// default word window.
+    fn fixture() -> TrackedPool {
+        let mut pool = v3_pool(
+            POOL,
+            Dex::Prjx,
+            token("WHYPE"),
+            token("USDC"),
+            3000,
+            -239_434,
+            1,
+        );
+        let mut state = PoolState::new(
+            3000,
+            60,
+            get_sqrt_ratio_at_tick(-239_434).unwrap(),
+            -239_434,
+            5_000_000_000_000_000,
+        )
+        .unwrap();
+        for (tick, net) in TICKS {
+            state.set_liquidity_net(tick, net).unwrap();
+        }
+        pool.state = state.into();
+        pool
+    }
+
+    async fn mock_node() -> MockJsonServer {
+        MockChain::new(vec![fixture()], HEAD).serve().await
+    }
+
+    #[tokio::test]
+    async fn decodes_pool_state_at_one_block() {
+        let server = mock_node().await;
+        let reader = PoolReader::new(RpcClient::new(Client::new(), &server.url));
+        let pool = reader.read_pool(POOL, Dex::HyperSwap, 77).await.unwrap();
+
+        let expected = fixture();
+        assert_eq!(pool.block, Some(77));
+        // Only the tokens file vouches for a stablecoin, not the chain.
+        let unflagged = |t: Token| Token { stable: false, ..t };
+        assert_eq!(pool.token0, unflagged(expected.token0));
+        assert_eq!(pool.token1, unflagged(expected.token1));
+        assert_eq!(
+            pool.state.as_v3().unwrap().sqrt_price_x96,
+            expected.state.as_v3().unwrap().sqrt_price_x96
+        );
+        assert_eq!(
+            (
+                pool.state.as_v3().unwrap().tick,
+                pool.state.as_v3().unwrap().fee,
+                pool.state.as_v3().unwrap().tick_spacing
+            ),
+            (-239_434, 3000, 60)
+        );
+        assert_eq!(
+            pool.state.as_v3().unwrap().liquidity,
+            expected.state.as_v3().unwrap().liquidity
+        );
+        // The current word is -16; +-2 words cover ticks -276480..=-199740.
+        assert_eq!(
+            pool.state
+                .as_v3()
+                .unwrap()
+                .initialized_ticks()
+                .collect::<Vec<_>>(),
+            TICKS[1..]
+        );
+
+        // Same swap as the full fixture while it stays inside the window.
+        let amount = U256::from_u128(10 * 10u128.pow(18));
+        let quote = pool
+            .state
+            .as_v3()
+            .unwrap()
+            .quote_exact_input(true, amount, None)
+            .unwrap();
+        assert!(quote.ticks_crossed > 0);
+        assert_eq!(
+            quote,
+            expected
+                .state
+                .as_v3()
+                .unwrap()
+                .quote_exact_input(true, amount, None)
+                .unwrap()
+        );
+
+        // Slot getters, bitmap words and ticks for the state; token
+        // addresses alongside; then both tokens. All through Multicall3.
+        let requests = server.requests();
+        assert_eq!(requests.len(), 5);
+        for r in &requests {
+            assert_eq!(r["params"][0]["to"], MULTICALL3);
+            assert_eq!(r["params"][1], "0x4d");
+        }
+        let ticks_calls = requests
+            .iter()
+            .map(|r| r["params"][0]["data"].as_str().unwrap())
+            .map(|data| data.matches("f30dba93").count())
+            .sum::<usize>();
+        assert_eq!(ticks_calls, 4);
+    }
+
+    #[tokio::test]
+    async fn reverted_call_fails_the_read() {
+        let server = mock_node().await;
+        let reader = PoolReader::new(RpcClient::new(Client::new(), &server.url));
+        let missing = "0x00000000000000000000000000000000000000bb".to_string();
+        let states = reader
+            .read_states(&[missing, POOL.to_string()], 1)
+            .await
+            .unwrap();
+        let e = states[0].as_ref().unwrap_err();
+        assert!(e.to_string().contains("reverted"), "{e}");
+        // The other pool in the batch is unaffected.
+        assert_eq!(states[1].as_ref().unwrap().tick, -239_434);
+
+        let down = PoolReader::new(RpcClient::new(Client::new(), "http://127.0.0.1:1"));
+        assert!(down.read_state(POOL, 1).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn refresh_task_pins_store_to_head() {
+        let server = mock_node().await;
+        let reader = PoolReader::new(RpcClient::new(Client::new(), &server.url));
+        let store = PoolStore::shared();
+        let mut stale = fixture();
+        stale.state = PoolState::new(3000, 60, get_sqrt_ratio_at_tick(0).unwrap(), 0, 1)
+            .unwrap()
+            .into();
+        store.write().await.upsert(stale);
+
+        let handle = spawn_refresh(reader, store.clone(), Duration::from_secs(60));
+        for _ in 0..100 {
+            if store.read().await.get(POOL).unwrap().block.is_some() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        handle.abort();
+        let mut store = store.write().await;
+        let pool = store.get(POOL).unwrap();
+        assert_eq!(pool.block, Some(HEAD));
+        assert_eq!(pool.state.as_v3().unwrap().tick, -239_434);
+
+        // A later indexer snapshot does not roll the chain read back.
+        let mut snapshot = fixture();
+        snapshot.state = PoolState::new(3000, 60, get_sqrt_ratio_at_tick(0).unwrap(), 0, 1)
+            .unwrap()
+            .into();
+        snapshot.tvl_usd = Some(1.0);
+        store.replace_dex(Dex::Prjx, vec![snapshot]);
+        let pool = store.get(POOL).unwrap().clone();
+        assert_eq!(
+            (pool.block, pool.state.as_v3().unwrap().tick),
+            (Some(HEAD), -239_434)
+        );
+        assert_eq!(pool.tvl_usd, Some(1.0));
+        assert!(!store.set_state(POOL, pool.state, HEAD - 1));
+    }
+}
@@ -0,0 +1,295 @@
+//! Health of the JSON-RPC endpoints behind one [`RpcClient`].
+//!
+//! Each provider keeps a smoothed latency, a smoothed error rate and the
+//! last head it reported. Its score is the latency plus a penalty per unit
+//! of error rate and per block its head is off the median head of all
+//! providers, ahead or behind, so one node reporting a runaway head ranks
+//! last rather than making every honest node look behind. Reads try
+//! providers from the lowest score up.
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::Serialize;
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+use super::RpcClient;
+
+/// Weight of the newest sample in the moving averages.
+const ALPHA: f64 = 0.2;
+/// Score cost of an error rate of 1, in milliseconds.
+pub const ERROR_PENALTY_MS: f64 = 2_000.0;
+/// Score cost of each block of head lag, in milliseconds.
+pub const LAG_PENALTY_MS: f64 = 250.0;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ProviderHealth {
+    pub url: String,
+    /// Smoothed round trip of answered requests; `None` before the first.
+    pub latency_ms: Option<f64>,
+    /// Smoothed share of failed requests, 0..=1.
+    pub error_rate: f64,
+    pub head: Option<u64>,
+    /// Blocks between this provider's head and the median head, either way.
+    pub head_lag: u64,
+    /// Lower is better.
+    pub score: f64,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Stats {
+    latency_ms: Option<f64>,
+    error_rate: f64,
+    head: Option<u64>,
+}
+
+fn ewma(prev: f64, sample: f64) -> f64 {
+    prev + ALPHA * (sample - prev)
+}
+
+#[derive(Debug)]
+pub(super) struct Providers {
+    urls: Vec<String>,
+    stats: Mutex<Vec<Stats>>,
+}
+
+impl Providers {
+    pub(super) fn new(urls: Vec<String>) -> Self {
+        assert!(!urls.is_empty(), "RPC client needs at least one provider");
+        Self {
+            stats: Mutex::new(vec![Stats::default(); urls.len()]),
+            urls,
+        }
+    }
+
+    pub(super) fn len(&self) -> usize {
+        self.urls.len()
+    }
+
+    pub(super) fn url(&self, i: usize) -> &str {
+        &self.urls[i]
+    }
+
+    pub(super) fn record_success(&self, i: usize, latency: Duration) {
+        let ms = latency.as_secs_f64() * 1_000.0;
+        let s = &mut self.stats.lock().unwrap()[i];
+        s.latency_ms = Some(s.latency_ms.map_or(ms, |prev| ewma(prev, ms)));
+        s.error_rate = ewma(s.error_rate, 0.0);
+    }
+
+    pub(super) fn record_failure(&self, i: usize) {
+        let s = &mut self.stats.lock().unwrap()[i];
+        s.error_rate = ewma(s.error_rate, 1.0);
+    }
+
+    pub(super) fn record_head(&self, i: usize, head: u64) {
+        let s = &mut self.stats.lock().unwrap()[i];
+        s.head = Some(head);
+    }
+
+    pub(super) fn health(&self) -> Vec<ProviderHealth> {
+        let stats = self.stats.lock().unwrap();
+        let median = median_head(stats.iter().filter_map(|s| s.head).collect());
+        self.urls
+            .iter()
+            .zip(stats.iter())
+            .map(|(url, s)| {
+                let head_lag = match (median, s.head) {
+                    (Some(median), Some(head)) => median.abs_diff(head),
+                    _ => 0,
+                };
+                ProviderHealth {
+                    url: url.clone(),
+                    latency_ms: s.latency_ms,
+                    error_rate: s.error_rate,
+                    head: s.head,
+                    head_lag,
+                    score: s.latency_ms.unwrap_or(0.0)
+                        + s.error_rate * ERROR_PENALTY_MS
+                        + head_lag as f64 * LAG_PENALTY_MS,
+                }
+            })
+            .collect()
+    }
+
+    /// Provider indices, best score first. Providers never heard from go
+    /// last; ties keep the configured order.
+    pub(super) fn ranked(&self) -> Vec<usize> {
+        let health = self.health();
+        let mut order: Vec<usize> = (0..self.urls.len()).collect();
+        order.sort_by(|&a, &b| {
+            let (a, b) = (&health[a], &health[b]);
+            (a.latency_ms.is_none().cmp(&b.latency_ms.is_none())).then(a.score.total_cmp(&b.score))
+        });
+        order
+    }
+}
+
+/// Middle of the reported heads; between the middle two, rounded down,
+/// when there is an even number of them.
+fn median_head(mut heads: Vec<u64>) -> Option<u64> {
+    if heads.is_empty() {
+        return None;
+    }
+    heads.sort_unstable();
+    let n = heads.len();
+    let (lo, hi) = (heads[(n - 1) / 2], heads[n / 2]);
+    Some(lo + (hi - lo) / 2)
+}
+
+/// Polls every provider's head every `every` so lag counts in the scores
+/// even when reads only ever reach the best provider.
+pub fn spawn_health_probe(rpc: RpcClient, every: Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(every);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            for (health, head) in rpc.health().iter().zip(rpc.heads().await) {
+                if let Err(e) = head {
+                    warn!(url = %health.url, error = %e, "RPC provider probe failed");
+                }
+            }
+            for h in rpc.health() {
+                debug!(
+                    url = %h.url,
+                    score = h.score,
+                    latency_ms = ?h.latency_ms,
+                    error_rate = h.error_rate,
+                    head_lag = h.head_lag,
+                    "RPC provider health"
+                );
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::Client;
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::rpc::RpcError;
+    use crate::testing::{Fault, MockJsonServer};
+
+    /// A node at `head` that answers `eth_call` with its own head.
+    async fn node(head: u64) -> MockJsonServer {
+        MockJsonServer::start(move |req| {
+            let result = match req["method"].as_str().unwrap() {
+                "eth_sendRawTransaction" => json!(format!("0x{:064x}", 0xabc)),
+                "eth_call" if req["params"][0]["to"] == "0xdead" => {
+                    return json!({
+                        "jsonrpc": "2.0", "id": req["id"],
+                        "error": { "code": 3, "message": "execution reverted" }
+                    });
+                }
+                _ => json!(format!("{head:#x}")),
+            };
+            json!({ "jsonrpc": "2.0", "id": req["id"], "result": result })
+        })
+        .await
+    }
+
+    fn client(nodes: &[&MockJsonServer]) -> RpcClient {
+        let urls = nodes.iter().map(|n| n.url.clone()).collect();
+        RpcClient::with_providers(Client::new(), urls).with_timeout(Duration::from_millis(200))
+    }
+
+    fn count(node: &MockJsonServer, method: &str) -> usize {
+        node.requests()
+            .iter()
+            .filter(|r| r["method"] == method)
+            .count()
+    }
+
+    #[tokio::test]
+    async fn fails_over_and_prefers_healthy_providers() {
+        let (a, b) = (node(100).await, node(200).await);
+        for fault in [
+            Fault::Status(502),
+            Fault::Garbage,
+            Fault::Hangup,
+            Fault::Delay(Duration::from_secs(2)),
+        ] {
+            a.set_fault(fault);
+            let rpc = client(&[&a, &b]);
+            let head: String = rpc.request("eth_chainId", json!([])).await.unwrap();
+            assert_eq!(head, "0xc8", "{fault:?}");
+            let health = rpc.health();
+            assert!(health[0].score > health[1].score, "{health:?}");
+
+            // Behind on errors, `a` is no longer asked first once it recovers.
+            a.set_fault(Fault::None);
+            let before = count(&a, "eth_chainId");
+            let _: String = rpc.request("eth_chainId", json!([])).await.unwrap();
+            assert_eq!(count(&a, "eth_chainId"), before, "{fault:?}");
+        }
+
+        let rpc = client(&[&a, &b]);
+        // A revert is the call's fault: no failover, no penalty.
+        let e = rpc.eth_call("0xdead", &[0; 4], 1).await.unwrap_err();
+        assert_eq!(e.downcast_ref::<RpcError>().unwrap().code, 3);
+        assert_eq!(count(&a, "eth_call") + count(&b, "eth_call"), 1);
+
+        let down = RpcClient::with_providers(
+            Client::new(),
+            vec!["http://127.0.0.1:1".into(), "http://127.0.0.1:2".into()],
+        );
+        let e = down.block_number().await.unwrap_err();
+        assert!(format!("{e:#}").contains("all 2 providers failed"), "{e:#}");
+    }
+
+    #[tokio::test]
+    async fn head_quorum_ignores_outliers_and_scores_lag() {
+        let (a, b, c) = (node(100).await, node(104).await, node(1_000_000).await);
+        let rpc = client(&[&a, &b, &c]).with_head_quorum(2);
+        // The runaway node alone cannot move the head.
+        assert_eq!(rpc.block_number().await.unwrap(), 104);
+        // Nor make the honest nodes look behind: lag is off the median
+        // head, and the runaway ranks last.
+        let health = rpc.health();
+        let lags: Vec<u64> = health.iter().map(|h| h.head_lag).collect();
+        assert_eq!(lags, [4, 0, 1_000_000 - 104]);
+        assert!(health[2].score > health[0].score);
+        assert_eq!(rpc.providers.ranked().last(), Some(&2));
+
+        c.set_fault(Fault::Status(500));
+        b.set_fault(Fault::Hangup);
+        let rpc = rpc.with_head_quorum(3);
+        let e = rpc.block_number().await.unwrap_err();
+        assert!(e.to_string().contains("1 of 3 providers answered"), "{e}");
+    }
+
+    #[test]
+    fn lag_is_symmetric_about_the_median_head() {
+        let providers = Providers::new(vec!["a".into(), "b".into(), "c".into()]);
+        let lags = |p: &Providers| p.health().iter().map(|h| h.head_lag).collect::<Vec<_>>();
+        assert_eq!(lags(&providers), [0, 0, 0]);
+        // Two heads and no way to tell which is right: both are off by half.
+        providers.record_head(0, 100);
+        providers.record_head(1, 104);
+        assert_eq!(lags(&providers), [2, 2, 0]);
+        providers.record_head(2, 90);
+        assert_eq!(lags(&providers), [0, 4, 10]);
+    }
+
+    #[tokio::test]
+    async fn broadcasts_raw_transactions() {
+        let (a, b) = (node(1).await, node(1).await);
+        a.set_fault(Fault::Status(503));
+        let rpc = client(&[&a, &b]);
+        let hash = rpc.send_raw_transaction(&[0x02, 0xf8]).await.unwrap();
+        assert_eq!(hash, format!("0x{:064x}", 0xabc));
+        for n in [&a, &b] {
+            let sent: Vec<Value> = n.requests();
+            assert_eq!(sent[0]["method"], "eth_sendRawTransaction");
+            assert_eq!(sent[0]["params"], json!(["0x02f8"]));
+        }
+
+        b.set_fault(Fault::Hangup);
+        let e = rpc.send_raw_transaction(&[0x02]).await.unwrap_err();
+        assert!(format!("{e:#}").contains("rejected by all 2"), "{e:#}");
+    }
+}
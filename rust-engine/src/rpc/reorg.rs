@@ -0,0 +1,279 @@
+//! Reorg bookkeeping for the log stream.
+//!
+//! [`BlockWindow`] keeps the hashes of the last few canonical blocks so a
+//! head whose parent hash disagrees gives the reorg away. [`PoolJournal`]
+//! keeps each pool's state at a checkpoint plus the events applied since, so
+//! a reorg rolls the pool back to the fork point by replaying the surviving
+//! events instead of re-reading it.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::Result;
+use serde::Deserialize;
+
+use super::logs::PoolEvent;
+use crate::univ3::PoolState;
+
+/// Blocks of history kept for reorg detection and rollback.
+pub const DEFAULT_REORG_WINDOW: u64 = 64;
+
+/// The header fields `newHeads` and `eth_getBlockByNumber` share.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockHeader {
+    #[serde(deserialize_with = "super::deserialize_quantity")]
+    pub number: u64,
+    pub hash: String,
+    pub parent_hash: String,
+}
+
+/// How a new head relates to the recorded chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Link {
+    /// Next block on the recorded tip, or the first one seen.
+    Extends,
+    /// Already recorded with this hash.
+    Known,
+    /// Older than anything recorded.
+    Stale,
+    /// Blocks between the tip and this head were never seen.
+    Gap,
+    /// Replaces a recorded block, or its parent is not the recorded one.
+    Reorg,
+}
+
+/// Canonical hashes of the last `depth` blocks, and of the blocks reorgs
+/// replaced within that range.
+#[derive(Debug, Clone)]
+pub struct BlockWindow {
+    blocks: BTreeMap<u64, BlockHeader>,
+    orphaned: BTreeSet<(u64, String)>,
+    depth: u64,
+}
+
+impl BlockWindow {
+    pub fn new(depth: u64) -> Self {
+        Self {
+            blocks: BTreeMap::new(),
+            orphaned: BTreeSet::new(),
+            depth: depth.max(1),
+        }
+    }
+
+    pub fn depth(&self) -> u64 {
+        self.depth
+    }
+
+    pub fn tip(&self) -> Option<&BlockHeader> {
+        self.blocks.values().next_back()
+    }
+
+    pub fn hash(&self, number: u64) -> Option<&str> {
+        self.blocks.get(&number).map(|b| b.hash.as_str())
+    }
+
+    /// Whether a reorg already replaced block `number` with hash `hash`.
+    pub fn is_orphaned(&self, number: u64, hash: &str) -> bool {
+        self.orphaned.contains(&(number, hash.to_string()))
+    }
+
+    pub fn link(&self, head: &BlockHeader) -> Link {
+        let (Some(first), Some(tip)) = (self.blocks.keys().next(), self.tip()) else {
+            return Link::Extends;
+        };
+        if let Some(known) = self.blocks.get(&head.number) {
+            return if known.hash == head.hash {
+                Link::Known
+            } else {
+                Link::Reorg
+            };
+        }
+        if head.number < *first {
+            Link::Stale
+        } else if head.number > tip.number + 1 {
+            Link::Gap
+        } else if head.parent_hash == tip.hash {
+            Link::Extends
+        } else {
+            Link::Reorg
+        }
+    }
+
+    /// Records `head` as canonical, dropping any recorded block at or above
+    /// its height and anything that fell out of the window.
+    pub fn insert(&mut self, head: BlockHeader) {
+        self.truncate_from(head.number);
+        let floor = head.number.saturating_sub(self.depth - 1);
+        self.blocks.insert(head.number, head);
+        self.blocks = self.blocks.split_off(&floor);
+        self.orphaned = self.orphaned.split_off(&(floor, String::new()));
+    }
+
+    /// Marks every block at or above `number` as orphaned.
+    pub fn truncate_from(&mut self, number: u64) {
+        let dropped = self.blocks.split_off(&number);
+        self.orphaned
+            .extend(dropped.into_values().map(|b| (b.number, b.hash)));
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Entry {
+    block: u64,
+    log_index: u64,
+    event: PoolEvent,
+}
+
+/// A pool's state at a checkpoint block and the events applied after it.
+#[derive(Debug, Clone)]
+pub struct PoolJournal {
+    checkpoint_block: u64,
+    checkpoint: PoolState,
+    entries: Vec<Entry>,
+}
+
+impl PoolJournal {
+    /// Starts from `state` as read at `block`.
+    pub fn new(block: u64, state: PoolState) -> Self {
+        Self {
+            checkpoint_block: block,
+            checkpoint: state,
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn checkpoint_block(&self) -> u64 {
+        self.checkpoint_block
+    }
+
+    /// Whether the checkpoint already includes `block`'s logs.
+    pub fn covers(&self, block: u64) -> bool {
+        block <= self.checkpoint_block
+    }
+
+    pub fn record(&mut self, block: u64, log_index: u64, event: PoolEvent) {
+        self.entries.push(Entry {
+            block,
+            log_index,
+            event,
+        });
+    }
+
+    /// Folds events up to `block` into the checkpoint; no reorg is expected
+    /// to reach that far back any more.
+    pub fn advance(&mut self, block: u64) -> Result<()> {
+        let keep = self.entries.partition_point(|e| e.block <= block);
+        if block <= self.checkpoint_block {
+            return Ok(());
+        }
+        for entry in self.entries.drain(..keep) {
+            entry.event.apply(&mut self.checkpoint)?;
+        }
+        self.checkpoint_block = block;
+        Ok(())
+    }
+
+    /// Drops events from `fork` on and returns the state replayed up to the
+    /// block before it, with the block it describes. `None` when the
+    /// checkpoint itself is at or past `fork` and cannot be trusted.
+    pub fn rollback(&mut self, fork: u64) -> Option<Result<(PoolState, u64)>> {
+        if self.checkpoint_block >= fork {
+            return None;
+        }
+        self.entries.retain(|e| e.block < fork);
+        let mut state = self.checkpoint.clone();
+        for entry in &self.entries {
+            if let Err(e) = entry.event.apply(&mut state) {
+                return Some(Err(e));
+            }
+        }
+        let block = self
+            .entries
+            .last()
+            .map_or(self.checkpoint_block, |e| e.block);
+        Some(Ok((state, block)))
+    }
+
+    /// Events since the checkpoint, as `(block, log_index)`.
+    pub fn positions(&self) -> impl Iterator<Item = (u64, u64)> + '_ {
+        self.entries.iter().map(|e| (e.block, e.log_index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::univ3::tick_math::get_sqrt_ratio_at_tick;
+
+    fn header(number: u64, fork: char) -> BlockHeader {
+        let hash = |n: u64| format!("0x{fork}{n}");
+        BlockHeader {
+            number,
+            hash: hash(number),
+            parent_hash: if number > 0 {
+                format!("0xa{}", number - 1)
+            } else {
+                "0x0".into()
+            },
+        }
+    }
+
+    fn swap(tick: i32) -> PoolEvent {
+        PoolEvent::Swap {
+            sqrt_price_x96: get_sqrt_ratio_at_tick(tick).unwrap(),
+            liquidity: 1_000,
+            tick,
+        }
+    }
+
+    #[test]
+    fn window_classifies_heads() {
+        let mut w = BlockWindow::new(3);
+        assert_eq!(w.link(&header(10, 'a')), Link::Extends);
+        for n in 10..=13 {
+            w.insert(header(n, 'a'));
+        }
+        // Only the last three blocks stay.
+        assert_eq!(w.hash(10), None);
+        assert_eq!(w.hash(11), Some("0xa11"));
+        assert_eq!(w.link(&header(14, 'a')), Link::Extends);
+        assert_eq!(w.link(&header(13, 'a')), Link::Known);
+        assert_eq!(w.link(&header(10, 'a')), Link::Stale);
+        assert_eq!(w.link(&header(16, 'a')), Link::Gap);
+        // Same height, other hash; and a parent we never saw.
+        assert_eq!(w.link(&header(13, 'b')), Link::Reorg);
+        let mut orphan = header(14, 'b');
+        orphan.parent_hash = "0xb13".into();
+        assert_eq!(w.link(&orphan), Link::Reorg);
+
+        w.insert(header(12, 'b'));
+        assert_eq!(w.tip().unwrap().hash, "0xb12");
+        assert_eq!(w.hash(13), None);
+        assert!(w.is_orphaned(12, "0xa12") && w.is_orphaned(13, "0xa13"));
+        assert!(!w.is_orphaned(12, "0xb12"));
+    }
+
+    #[test]
+    fn journal_rolls_back_and_replays() {
+        let base = PoolState::new(3000, 60, get_sqrt_ratio_at_tick(0).unwrap(), 0, 1_000).unwrap();
+        let mut j = PoolJournal::new(100, base);
+        j.record(101, 0, swap(10));
+        j.record(101, 1, swap(20));
+        j.record(102, 0, swap(30));
+        j.record(103, 0, swap(40));
+
+        let (state, block) = j.rollback(102).unwrap().unwrap();
+        assert_eq!((state.tick, block), (20, 101));
+        assert_eq!(j.positions().collect::<Vec<_>>(), vec![(101, 0), (101, 1)]);
+
+        j.record(102, 0, swap(35));
+        j.advance(101).unwrap();
+        assert_eq!(j.checkpoint_block(), 101);
+        assert!(j.covers(101) && !j.covers(102));
+        let (state, block) = j.rollback(102).unwrap().unwrap();
+        assert_eq!((state.tick, block), (20, 101));
+
+        // Deeper than the checkpoint: only a fresh read will do.
+        assert!(j.rollback(101).is_none());
+    }
+}
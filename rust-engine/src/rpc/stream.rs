@@ -0,0 +1,639 @@
+//! Live pool state from `eth_subscribe` over WebSocket.
+//!
+//! One connection carries a `newHeads` subscription and a `logs`
+//! subscription for the Swap, Mint and Burn events of the tracked pools.
+//! Logs are applied to the store as they arrive. Every pool is re-read over
+//! HTTP at the head block once subscribed, and again whenever head numbers
+//! skip a block or an event does not apply cleanly. A change in the tracked
+//! pool set resubscribes.
+//!
+//! Reorgs show up as a head whose parent hash is not the recorded one, or as
+//! a log marked removed. Pools then roll back to their [`PoolJournal`]
+//! checkpoint and replay the events from before the fork; the logs of the
+//! new branch follow on the subscription. A pool whose checkpoint is itself
+//! past the fork is re-read.
+
+use std::collections::{BTreeSet, HashMap};
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::Utc;
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::Message;
+use tracing::{debug, info, warn};
+
+use super::logs::{Log, PoolEvent, BURN_TOPIC, MINT_TOPIC, SWAP_TOPIC};
+use super::reorg::{BlockHeader, BlockWindow, Link, PoolJournal, DEFAULT_REORG_WINDOW};
+use super::PoolReader;
+use crate::state::SharedPoolStore;
+use crate::status::SharedStatusReporter;
+
+const RECONNECT_MIN: Duration = Duration::from_millis(500);
+const RECONNECT_MAX: Duration = Duration::from_secs(30);
+
+const HEADS_REQUEST_ID: u64 = 1;
+const LOGS_REQUEST_ID: u64 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Subscription {
+    Heads,
+    Logs,
+}
+
+pub struct LogStream {
+    url: String,
+    reader: PoolReader,
+    store: SharedPoolStore,
+    /// Where head and reorg counts are reported, if anywhere.
+    reporter: Option<SharedStatusReporter>,
+    window: BlockWindow,
+    /// Per pool: state at the last re-read or folded block, and the events since.
+    journals: HashMap<String, PoolJournal>,
+    head: Option<u64>,
+    backoff: Duration,
+}
+
+impl LogStream {
+    /// Streams from the WebSocket endpoint `url`, resyncing through `reader`.
+    pub fn new(url: impl Into<String>, reader: PoolReader, store: SharedPoolStore) -> Self {
+        Self {
+            url: url.into(),
+            reader,
+            store,
+            reporter: None,
+            window: BlockWindow::new(DEFAULT_REORG_WINDOW),
+            journals: HashMap::new(),
+            head: None,
+            backoff: RECONNECT_MIN,
+        }
+    }
+
+    /// Blocks of history kept for rollback; deeper reorgs re-read every pool.
+    pub fn with_reorg_window(mut self, blocks: u64) -> Self {
+        self.window = BlockWindow::new(blocks);
+        self
+    }
+
+    /// Reports the head block and reorgs to `reporter`'s chain section.
+    pub fn with_status(mut self, reporter: SharedStatusReporter) -> Self {
+        self.reporter = Some(reporter);
+        self
+    }
+
+    async fn tracked(&self) -> BTreeSet<String> {
+        self.store
+            .read()
+            .await
+            .iter()
+            .map(|p| p.address.clone())
+            .collect()
+    }
+
+    /// One connection, until it fails or the tracked pools change.
+    async fn session(&mut self) -> Result<()> {
+        let (ws, _) = tokio_tungstenite::connect_async(self.url.as_str())
+            .await
+            .with_context(|| format!("connect {}", self.url))?;
+        let (mut sink, mut source) = ws.split();
+
+        let pools = self.tracked().await;
+        let mut requests = vec![json!({
+            "jsonrpc": "2.0", "id": HEADS_REQUEST_ID,
+            "method": "eth_subscribe", "params": ["newHeads"]
+        })];
+        // An empty address filter would match every contract.
+        if !pools.is_empty() {
+            requests.push(json!({
+                "jsonrpc": "2.0", "id": LOGS_REQUEST_ID,
+                "method": "eth_subscribe",
+                "params": ["logs", {
+                    "address": pools,
+                    "topics": [[SWAP_TOPIC, MINT_TOPIC, BURN_TOPIC]],
+                }]
+            }));
+        }
+        let mut pending = requests.len();
+        for request in requests {
+            sink.send(Message::Text(request.to_string())).await?;
+        }
+
+        let mut subscriptions = HashMap::new();
+        while let Some(msg) = source.next().await {
+            let text = match msg? {
+                Message::Text(text) => text,
+                Message::Ping(payload) => {
+                    sink.send(Message::Pong(payload)).await?;
+                    continue;
+                }
+                Message::Close(_) => bail!("closed by server"),
+                _ => continue,
+            };
+            let msg: Value = serde_json::from_str(&text).context("decode WebSocket message")?;
+
+            if let Some(id) = msg.get("id").and_then(Value::as_u64) {
+                if let Some(err) = msg.get("error") {
+                    bail!("eth_subscribe failed: {err}");
+                }
+                let sub = msg["result"]
+                    .as_str()
+                    .ok_or_else(|| anyhow!("eth_subscribe returned {}", msg["result"]))?;
+                let kind = match id {
+                    HEADS_REQUEST_ID => Subscription::Heads,
+                    _ => Subscription::Logs,
+                };
+                subscriptions.insert(sub.to_string(), kind);
+                pending -= 1;
+                if pending == 0 {
+                    info!(url = %self.url, pools = pools.len(), "subscribed to pool logs");
+                    self.backoff = RECONNECT_MIN;
+                    let head = self.reader.rpc().block_number().await?;
+                    self.resync(head).await;
+                }
+                continue;
+            }
+
+            let params = &msg["params"];
+            match subscriptions.get(params["subscription"].as_str().unwrap_or_default()) {
+                Some(Subscription::Heads) => {
+                    let header: BlockHeader = serde_json::from_value(params["result"].clone())
+                        .context("decode head notification")?;
+                    self.on_head(header).await;
+                    if self.tracked().await != pools {
+                        info!("tracked pools changed; resubscribing");
+                        return Ok(());
+                    }
+                }
+                Some(Subscription::Logs) => {
+                    let log: Log = serde_json::from_value(params["result"].clone())
+                        .context("decode log notification")?;
+                    self.on_log(log).await;
+                }
+                None => debug!(%text, "unexpected WebSocket message"),
+            }
+        }
+        bail!("connection closed")
+    }
+
+    async fn on_head(&mut self, header: BlockHeader) {
+        let number = header.number;
+        let link = match self.window.link(&header) {
+            // Nothing recorded yet, but the subscribe-time resync was older.
+            Link::Extends if self.head.is_some_and(|last| number > last + 1) => Link::Gap,
+            link => link,
+        };
+        match link {
+            Link::Known | Link::Stale => return,
+            Link::Extends => self.window.insert(header),
+            Link::Gap => {
+                warn!(last = ?self.head, number, "missed blocks; resyncing pools");
+                self.window.insert(header);
+                self.resync(number).await;
+            }
+            Link::Reorg => {
+                let tip = self.window.tip().map_or(number, |t| t.number);
+                match self.fork_point(&header).await {
+                    Ok((fork, branch)) => {
+                        let deep = fork == 0 || self.window.hash(fork - 1).is_none();
+                        for h in branch.into_iter().rev() {
+                            self.window.insert(h);
+                        }
+                        self.window.insert(header);
+                        if deep {
+                            warn!(
+                                number,
+                                "reorg deeper than the block window; resyncing pools"
+                            );
+                            self.record_reorg(tip + 1 - fork).await;
+                            self.resync(number).await;
+                        } else {
+                            self.rollback(fork, tip + 1 - fork, number).await;
+                        }
+                    }
+                    Err(e) => {
+                        warn!(number, error = %e, "could not locate reorg fork; resyncing pools");
+                        self.window.insert(header);
+                        self.resync(number).await;
+                    }
+                }
+            }
+        }
+        self.head = Some(number);
+        self.fold(number);
+        if let Some(reporter) = &self.reporter {
+            reporter.write().await.chain.head_block = Some(number);
+        }
+    }
+
+    /// First block of the branch `head` is on, with the canonical headers
+    /// fetched on the way there, newest first. Walks parent hashes back over
+    /// RPC until one matches the window; if none does, the oldest recorded
+    /// block is the best bound.
+    async fn fork_point(&self, head: &BlockHeader) -> Result<(u64, Vec<BlockHeader>)> {
+        let mut parent = head.parent_hash.clone();
+        let mut number = head.number;
+        let mut branch = Vec::new();
+        while number > 0 {
+            match self.window.hash(number - 1) {
+                Some(hash) if hash == parent => break,
+                Some(_) => {
+                    let header = self.reader.rpc().block_header(number - 1).await?;
+                    parent = header.parent_hash.clone();
+                    branch.push(header);
+                    number -= 1;
+                }
+                None => break,
+            }
+        }
+        Ok((number, branch))
+    }
+
+    /// Folds journal events out of reach of a reorg into the checkpoints.
+    fn fold(&mut self, head: u64) {
+        let Some(block) = head.checked_sub(self.window.depth()) else {
+            return;
+        };
+        self.journals
+            .retain(|address, journal| match journal.advance(block) {
+                Ok(()) => true,
+                Err(e) => {
+                    warn!(pool = %address, block, error = %e, "pool journal did not fold");
+                    false
+                }
+            });
+    }
+
+    async fn on_log(&mut self, log: Log) {
+        let address = log.address.to_ascii_lowercase();
+        let block = log.block_number;
+        if log.removed {
+            // Only the first removed log of a reorg finds its block still
+            // canonical; for the rest a rollback already orphaned it.
+            let current = match self.window.hash(block) {
+                Some(hash) => hash == log.block_hash,
+                None => {
+                    !self.window.is_orphaned(block, &log.block_hash)
+                        && self.window.tip().is_none_or(|t| block <= t.number)
+                }
+            };
+            if current {
+                let tip = self.window.tip().map_or(block, |t| t.number.max(block));
+                self.window.truncate_from(block);
+                let head = match self.reader.rpc().block_number().await {
+                    Ok(head) => head,
+                    Err(_) => self.head.unwrap_or(block),
+                };
+                self.head = Some(block.saturating_sub(1));
+                self.rollback(block, tip + 1 - block, head).await;
+            }
+            return;
+        }
+        if self.journals.get(&address).is_some_and(|j| j.covers(block)) {
+            return;
+        }
+        let applied = match PoolEvent::decode(&log) {
+            Ok(None) => return,
+            Ok(Some(event)) => {
+                let mut store = self.store.write().await;
+                let Some(pool) = store.get_mut(&address) else {
+                    return;
+                };
+                // Only V3 pools emit the events decoded here.
+                let Some(mut state) = pool.state.as_v3().cloned() else {
+                    return;
+                };
+                event.apply(&mut state).map(|()| {
+                    pool.state = state.into();
+                    pool.block = pool.block.max(Some(block));
+                    pool.updated_at = Utc::now();
+                    if let Some(journal) = self.journals.get_mut(&address) {
+                        journal.record(block, log.log_index, event);
+                    }
+                })
+            }
+            Err(e) => Err(e),
+        };
+        if let Err(e) = applied {
+            warn!(pool = %address, block, error = %e, "pool log did not apply; resyncing pool");
+            let head = self.head.unwrap_or(block).max(block);
+            self.resync_pools(vec![address], head).await;
+        }
+    }
+
+    /// Puts every pool back to its state before block `fork` by replaying
+    /// its journal; pools that cannot be replayed are re-read at `head`.
+    async fn rollback(&mut self, fork: u64, depth: u64, head: u64) {
+        warn!(fork, depth, "chain reorg; rolling pool state back");
+        self.record_reorg(depth).await;
+        let mut reread = Vec::new();
+        {
+            let mut store = self.store.write().await;
+            let addresses: Vec<String> = store.iter().map(|p| p.address.clone()).collect();
+            for address in addresses {
+                let replayed = self
+                    .journals
+                    .get_mut(&address)
+                    .and_then(|j| j.rollback(fork));
+                match replayed {
+                    Some(Ok((state, block))) => {
+                        if let Some(pool) = store.get_mut(&address) {
+                            pool.state = state.into();
+                            pool.block = Some(block);
+                            pool.updated_at = Utc::now();
+                        }
+                    }
+                    Some(Err(e)) => {
+                        warn!(pool = %address, fork, error = %e, "pool journal did not replay");
+                        reread.push(address);
+                    }
+                    None => reread.push(address),
+                }
+            }
+        }
+        if !reread.is_empty() {
+            self.resync_pools(reread, head).await;
+        }
+    }
+
+    async fn record_reorg(&self, depth: u64) {
+        if let Some(reporter) = &self.reporter {
+            reporter.write().await.chain.record_reorg(depth, Utc::now());
+        }
+    }
+
+    async fn resync(&mut self, block: u64) {
+        let addresses = self.tracked().await.into_iter().collect();
+        self.resync_pools(addresses, block).await;
+        self.head = self.head.max(Some(block));
+    }
+
+    async fn resync_pools(&mut self, addresses: Vec<String>, block: u64) {
+        let states = match self.reader.read_states(&addresses, block).await {
+            Ok(states) => states,
+            Err(e) => {
+                warn!(block, pools = addresses.len(), error = %e, "pool resync failed");
+                return;
+            }
+        };
+        let mut store = self.store.write().await;
+        let mut synced = 0;
+        for (address, state) in addresses.into_iter().zip(states) {
+            match state {
+                Ok(state) => {
+                    // Unconditional: after a reorg to a shorter branch the
+                    // head is below blocks the pool already saw.
+                    if let Some(pool) = store.get_mut(&address) {
+                        pool.state = state.clone().into();
+                        pool.block = Some(block);
+                        pool.updated_at = Utc::now();
+                        self.journals
+                            .insert(address, PoolJournal::new(block, state));
+                        synced += 1;
+                    }
+                }
+                Err(e) => warn!(pool = %address, block, error = %e, "pool resync failed"),
+            }
+        }
+        info!(block, pools = synced, "resynced pool state from RPC");
+    }
+}
+
+/// Runs `stream` forever, reconnecting with exponential backoff.
+pub fn spawn_log_stream(mut stream: LogStream) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            match stream.session().await {
+                Ok(()) => continue,
+                Err(e) => warn!(url = %stream.url, error = %e, "pool log stream failed"),
+            }
+            tokio::time::sleep(stream.backoff).await;
+            stream.backoff = (stream.backoff * 2).min(RECONNECT_MAX);
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::Client;
+
+    use super::*;
+    use crate::rpc::abi;
+    use crate::rpc::RpcClient;
+    use crate::state::{Dex, PoolStore, TrackedPool};
+    use crate::status::{StatusConfig, StatusReporter};
+    use crate::testing::{token, uint_word, v3_pool, MockChain, MockWsServer};
+    use crate::univ3::tick_math::get_sqrt_ratio_at_tick;
+
+    const POOL: &str = "0x00000000000000000000000000000000000000aa";
+
+    fn pool() -> TrackedPool {
+        v3_pool(
+            POOL,
+            Dex::Prjx,
+            token("WHYPE"),
+            token("USDC"),
+            3000,
+            -239_434,
+            5_000_000_000_000_000,
+        )
+    }
+
+    /// Hash of `block` on `branch`; branch 0 is what logs carry by default.
+    fn hash(block: u64, branch: u8) -> String {
+        format!("0x{branch:02x}{block:062x}")
+    }
+
+    fn head(block: u64, branch: u8, parent_branch: u8) -> Value {
+        json!({
+            "number": format!("{block:#x}"),
+            "hash": hash(block, branch),
+            "parentHash": hash(block - 1, parent_branch),
+        })
+    }
+
+    fn log_json(block: u64, topics: Vec<String>, words: &[[u8; 32]]) -> Value {
+        json!({
+            "address": POOL,
+            "topics": topics,
+            "data": abi::to_hex(&words.concat()),
+            "blockNumber": format!("{block:#x}"),
+            "blockHash": hash(block, 0),
+            "transactionHash": format!("0x{:064x}", block + 1),
+            "logIndex": "0x0",
+            "removed": false,
+        })
+    }
+
+    fn swap(block: u64, tick: i32, liquidity: u128) -> Value {
+        let sqrt = get_sqrt_ratio_at_tick(tick).unwrap();
+        log_json(
+            block,
+            vec![
+                SWAP_TOPIC.into(),
+                abi::to_hex(&uint_word(1)),
+                abi::to_hex(&uint_word(2)),
+            ],
+            &[
+                uint_word(1),
+                uint_word(1),
+                sqrt.to_be_bytes(),
+                uint_word(liquidity),
+                abi::int_word(tick.into()),
+            ],
+        )
+    }
+
+    fn mint(block: u64, lower: i64, upper: i64, amount: u128) -> Value {
+        log_json(
+            block,
+            vec![
+                MINT_TOPIC.into(),
+                abi::to_hex(&uint_word(1)),
+                abi::to_hex(&abi::int_word(lower)),
+                abi::to_hex(&abi::int_word(upper)),
+            ],
+            &[uint_word(1), uint_word(amount), uint_word(1), uint_word(1)],
+        )
+    }
+
+    async fn wait_for(store: &SharedPoolStore, done: impl Fn(&TrackedPool) -> bool) {
+        for _ in 0..200 {
+            if store.read().await.get(POOL).is_some_and(&done) {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("timed out: {:?}", store.read().await.get(POOL));
+    }
+
+    fn subscriptions() -> impl Fn(&Value) -> Value + Send + Sync + 'static {
+        |req| {
+            let sub = match req["params"][0].as_str() {
+                Some("newHeads") => "0xheads",
+                _ => "0xlogs",
+            };
+            json!({ "jsonrpc": "2.0", "id": req["id"], "result": sub })
+        }
+    }
+
+    #[tokio::test]
+    async fn applies_logs_and_resyncs_on_gap() {
+        let chain = MockChain::new(vec![pool()], 100);
+        let rpc = chain.serve().await;
+        let ws = MockWsServer::start(subscriptions()).await;
+        let store = PoolStore::shared();
+        store.write().await.upsert(pool());
+
+        let reader = PoolReader::new(RpcClient::new(Client::new(), &rpc.url));
+        let handle = spawn_log_stream(LogStream::new(&ws.url, reader, store.clone()));
+        wait_for(&store, |p| p.block == Some(100)).await;
+
+        let subscribe = ws.requests();
+        assert_eq!(subscribe[0]["params"], json!(["newHeads"]));
+        assert_eq!(subscribe[1]["params"][1]["address"], json!([POOL]));
+        assert_eq!(
+            subscribe[1]["params"][1]["topics"],
+            json!([[SWAP_TOPIC, MINT_TOPIC, BURN_TOPIC]])
+        );
+
+        ws.notify("0xheads", head(101, 0, 0));
+        // Already part of the block-100 read, so skipped.
+        ws.notify("0xlogs", swap(100, -200_000, 1));
+        ws.notify("0xlogs", swap(101, -239_500, 4_000_000_000_000_000));
+        ws.notify("0xlogs", mint(101, -239_520, -239_400, 1_000));
+        wait_for(&store, |p| {
+            p.state.as_v3().unwrap().liquidity_net(-239_520) == 1_000
+        })
+        .await;
+        {
+            let store = store.read().await;
+            let p = store.get(POOL).unwrap();
+            assert_eq!(p.block, Some(101));
+            assert_eq!(p.state.as_v3().unwrap().tick, -239_500);
+            assert_eq!(
+                p.state.as_v3().unwrap().liquidity,
+                4_000_000_000_000_000 + 1_000
+            );
+            assert_eq!(p.state.as_v3().unwrap().liquidity_net(-239_400), -1_000);
+        }
+
+        // 102..=104 never arrived: the pool is read again at 105.
+        ws.notify("0xheads", head(105, 0, 0));
+        wait_for(&store, |p| p.block == Some(105)).await;
+        handle.abort();
+        let store = store.read().await;
+        let p = store.get(POOL).unwrap();
+        assert_eq!(p.state.as_v3().unwrap().tick, -239_434);
+        assert_eq!(p.state.as_v3().unwrap().liquidity_net(-239_520), 0);
+        assert!(rpc
+            .requests()
+            .iter()
+            .any(|r| r["method"] == "eth_call" && r["params"][1] == "0x69"));
+    }
+
+    #[tokio::test]
+    async fn rolls_back_to_the_fork_on_reorg() {
+        let chain = MockChain::new(vec![pool()], 100);
+        let rpc = chain.serve().await;
+        let ws = MockWsServer::start(subscriptions()).await;
+        let store = PoolStore::shared();
+        store.write().await.upsert(pool());
+        let reporter = StatusReporter::shared(StatusConfig::default());
+
+        let reader = PoolReader::new(RpcClient::new(Client::new(), &rpc.url));
+        let stream = LogStream::new(&ws.url, reader, store.clone()).with_status(reporter.clone());
+        let handle = spawn_log_stream(stream);
+        wait_for(&store, |p| p.block == Some(100)).await;
+
+        ws.notify("0xheads", head(101, 0, 0));
+        ws.notify("0xlogs", swap(101, -239_500, 4_000_000_000_000_000));
+        ws.notify("0xheads", head(102, 0, 0));
+        ws.notify("0xlogs", mint(102, -239_520, -239_400, 1_000));
+        wait_for(&store, |p| p.block == Some(102)).await;
+
+        // 103 builds on another 102: only the mint is undone.
+        chain.set_block(102, &hash(102, 1), &hash(101, 0));
+        ws.notify("0xheads", head(103, 1, 1));
+        wait_for(&store, |p| p.block == Some(101)).await;
+        {
+            let store = store.read().await;
+            let p = store.get(POOL).unwrap();
+            assert_eq!(p.state.as_v3().unwrap().tick, -239_500);
+            assert_eq!(p.state.as_v3().unwrap().liquidity, 4_000_000_000_000_000);
+            assert_eq!(p.state.as_v3().unwrap().liquidity_net(-239_520), 0);
+        }
+        let chain_status = reporter.read().await.chain.clone();
+        assert_eq!(chain_status.head_block, Some(103));
+        assert_eq!((chain_status.reorgs, chain_status.last_reorg_depth), (1, 1));
+
+        // The new branch's logs apply on top of the replayed state.
+        ws.notify("0xlogs", swap(103, -239_460, 4_500_000_000_000_000));
+        wait_for(&store, |p| p.block == Some(103)).await;
+
+        // 101 itself goes: back to the checkpoint read at 100. The second
+        // removed log is part of the same reorg.
+        let mut removed = swap(101, -239_500, 4_000_000_000_000_000);
+        removed["removed"] = json!(true);
+        ws.notify("0xlogs", removed.clone());
+        ws.notify("0xlogs", removed);
+        ws.notify("0xlogs", swap(101, -239_480, 4_200_000_000_000_000));
+        wait_for(&store, |p| p.state.as_v3().unwrap().tick == -239_480).await;
+        handle.abort();
+
+        let chain_status = reporter.read().await.chain.clone();
+        assert_eq!(chain_status.reorgs, 2);
+        assert_eq!(
+            (chain_status.last_reorg_depth, chain_status.max_reorg_depth),
+            (3, 3)
+        );
+        // Rolled back without re-reading the pool.
+        let pinned: Vec<_> = rpc
+            .requests()
+            .iter()
+            .filter(|r| r["method"] == "eth_call")
+            .map(|r| r["params"][1].clone())
+            .collect();
+        assert!(pinned.iter().all(|b| b == "0x64"), "{pinned:?}");
+    }
+}
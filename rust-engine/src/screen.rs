@@ -0,0 +1,428 @@
+//! Negative-cycle screen over log prices, run ahead of the exact cycle sizer.
+//!
+//! Each pool is an edge each way weighted `-ln(rate × (1 - fee))`, so a cycle
+//! whose net rates multiply to more than one has negative total weight.
+//! The graph persists across blocks: only pools whose state moved are
+//! reweighted, and only pools added, dropped or moved to other tokens touch
+//! the adjacency lists. [`CycleScreen::negative_cycles`] runs SPFA
+//! (queue-based Bellman-Ford) from a virtual source joined to every token and
+//! closes a cycle whenever a relaxation reaches back into its own predecessor
+//! chain.
+//!
+//! Screening is not enumeration: it finds a cycle among each group of tokens
+//! that has one, not every cycle, and which one depends on relaxation order.
+//! Sizing stays with [`crate::sizing::optimal_route`].
+
+use std::collections::{HashMap, VecDeque};
+
+use chrono::{DateTime, Utc};
+
+use crate::amm::Pool;
+use crate::cycles::MIN_HOPS;
+use crate::state::TrackedPool;
+
+/// Slack on relaxations so rounding cannot walk a zero-weight cycle forever.
+const EPSILON: f64 = 1e-12;
+
+/// Paths stop being extended past this many swaps per cycle hop. Deeper ones
+/// are mostly laps of negative cycles too long to report, and chasing them
+/// to a swap per token, as plain SPFA does, makes screening superlinear in
+/// the pool count (see `benches/screening.rs`).
+const MAX_DEPTH_PER_HOP: usize = 16;
+
+/// A swap in a screened cycle.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScreenedHop {
+    pub pool: String,
+    pub zero_for_one: bool,
+}
+
+/// A cycle with a positive edge and how large it is at mid prices.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScreenedCycle {
+    pub hops: Vec<ScreenedHop>,
+    /// `-Σ weight`: the log of the product of the hop rates net of fees.
+    pub log_edge: f64,
+}
+
+#[derive(Debug, Clone)]
+struct PoolEdges {
+    address: String,
+    /// Nodes of token0 and token1.
+    nodes: [usize; 2],
+    /// Weights token0->token1 and token1->token0; infinite when unpriced.
+    weights: [f64; 2],
+    /// `(block, updated_at)` the weights were computed at.
+    stamp: (Option<u64>, DateTime<Utc>),
+    /// Last [`CycleScreen::update`] the pool was given in.
+    seen: u64,
+}
+
+/// An edge out of a node: the pool's slot and the side it swaps from, 0 for
+/// token0 in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct EdgeRef {
+    slot: usize,
+    side: usize,
+}
+
+/// Log-price graph over the tracked pools, kept up to date between blocks.
+#[derive(Debug, Clone, Default)]
+pub struct CycleScreen {
+    /// Node index of each token address.
+    nodes: HashMap<String, usize>,
+    /// Pool edges by slot; a dropped pool's slot is `None` until reused.
+    slots: Vec<Option<PoolEdges>>,
+    free: Vec<usize>,
+    /// Slot of each pool address.
+    by_address: HashMap<String, usize>,
+    /// Out-edges of each node.
+    adjacency: Vec<Vec<EdgeRef>>,
+    /// Count of [`Self::update`] calls.
+    generation: u64,
+}
+
+impl CycleScreen {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pools with edges in the graph.
+    pub fn pool_count(&self) -> usize {
+        self.by_address.len()
+    }
+
+    /// Brings the graph in line with `pools`: new pools are added, pools
+    /// whose block or update time moved are reweighted and pools no longer
+    /// given are dropped. Returns how many pools changed.
+    pub fn update<'a>(&mut self, pools: impl IntoIterator<Item = &'a TrackedPool>) -> usize {
+        self.generation += 1;
+        let mut changed = 0;
+        for pool in pools {
+            let stamp = (pool.block, pool.updated_at);
+            let slot = self.by_address.get(&pool.address).copied();
+            if let Some(edges) = slot.and_then(|s| self.slots[s].as_mut()) {
+                if edges.stamp == stamp {
+                    edges.seen = self.generation;
+                    continue;
+                }
+            }
+            changed += 1;
+            let nodes = [&pool.token0, &pool.token1].map(|t| self.node(&t.address));
+            let edges = PoolEdges {
+                address: pool.address.clone(),
+                nodes,
+                weights: weights(pool),
+                stamp,
+                seen: self.generation,
+            };
+            match slot {
+                Some(slot) => {
+                    let old = self.slots[slot].replace(edges).map(|e| e.nodes);
+                    if old != Some(nodes) {
+                        if let Some(old) = old {
+                            self.unlink(slot, old);
+                        }
+                        self.link(slot, nodes);
+                    }
+                }
+                None => {
+                    let slot = match self.free.pop() {
+                        Some(slot) => {
+                            self.slots[slot] = Some(edges);
+                            slot
+                        }
+                        None => {
+                            self.slots.push(Some(edges));
+                            self.slots.len() - 1
+                        }
+                    };
+                    self.by_address.insert(pool.address.clone(), slot);
+                    self.link(slot, nodes);
+                }
+            }
+        }
+        for slot in 0..self.slots.len() {
+            let stale = self.slots[slot]
+                .as_ref()
+                .is_some_and(|e| e.seen != self.generation);
+            if let Some(edges) = stale.then(|| self.slots[slot].take()).flatten() {
+                self.unlink(slot, edges.nodes);
+                self.by_address.remove(&edges.address);
+                self.free.push(slot);
+                changed += 1;
+            }
+        }
+        changed
+    }
+
+    /// Index of `token`'s node, added if new.
+    fn node(&mut self, token: &str) -> usize {
+        let next = self.nodes.len();
+        let node = *self.nodes.entry(token.to_string()).or_insert(next);
+        if node == self.adjacency.len() {
+            self.adjacency.push(Vec::new());
+        }
+        node
+    }
+
+    fn link(&mut self, slot: usize, nodes: [usize; 2]) {
+        for (side, node) in nodes.into_iter().enumerate() {
+            self.adjacency[node].push(EdgeRef { slot, side });
+        }
+    }
+
+    fn unlink(&mut self, slot: usize, nodes: [usize; 2]) {
+        for (side, node) in nodes.into_iter().enumerate() {
+            let edges = &mut self.adjacency[node];
+            if let Some(i) = edges.iter().position(|e| *e == EdgeRef { slot, side }) {
+                edges.swap_remove(i);
+            }
+        }
+    }
+
+    /// Target node, weight, pool and direction of `u`'s `i`th out-edge.
+    fn edge(&self, u: usize, i: usize) -> (usize, f64, &PoolEdges, bool) {
+        let EdgeRef { slot, side } = self.adjacency[u][i];
+        let edges = self.slots[slot].as_ref().expect("linked slot is live");
+        (edges.nodes[1 - side], edges.weights[side], edges, side == 0)
+    }
+
+    /// Cycles of `MIN_HOPS..=max_hops` swaps through at least one of the
+    /// `through` tokens, or any token when it is empty, whose rates net of
+    /// fees multiply to more than `e^min_log_edge`, no two sharing a token.
+    /// Cycles that miss `through` are passed over without claiming their
+    /// tokens, so they cannot hide one that overlaps them.
+    pub fn negative_cycles(
+        &self,
+        max_hops: usize,
+        min_log_edge: f64,
+        through: &[String],
+    ) -> Vec<ScreenedCycle> {
+        let n = self.nodes.len();
+        let max_depth = (MAX_DEPTH_PER_HOP * max_hops).min(n);
+        let mut wanted = vec![through.is_empty(); n];
+        for token in through {
+            if let Some(&node) = self.nodes.get(token) {
+                wanted[node] = true;
+            }
+        }
+
+        // Every node starts at distance 0, as if one zero edge led to each.
+        let mut dist = vec![0.0; n];
+        let mut pred: Vec<Option<(usize, usize)>> = vec![None; n];
+        let mut depth = vec![0usize; n];
+        let mut queued = vec![true; n];
+        let mut blocked = vec![false; n];
+        let mut queue: VecDeque<usize> = (0..n).collect();
+        let mut out = Vec::new();
+
+        while let Some(u) = queue.pop_front() {
+            queued[u] = false;
+            for i in 0..self.adjacency[u].len() {
+                let (v, weight, _, _) = self.edge(u, i);
+                if blocked[u] || blocked[v] || dist[u] + weight >= dist[v] - EPSILON {
+                    continue;
+                }
+                // Relaxing around a short cycle again finds nothing new,
+                // so a closed one is reported if it clears the bar and then
+                // never walked.
+                if let Some(mut cycle) = closed_cycle(&pred, u, v, max_hops) {
+                    cycle.push((u, i));
+                    let total: f64 = cycle.iter().map(|&(a, j)| self.edge(a, j).1).sum();
+                    if cycle.len() >= MIN_HOPS
+                        && -total > min_log_edge.max(EPSILON)
+                        && cycle.iter().any(|&(a, _)| wanted[a])
+                    {
+                        let hops = cycle
+                            .iter()
+                            .map(|&(a, j)| {
+                                let (_, _, edges, zero_for_one) = self.edge(a, j);
+                                ScreenedHop {
+                                    pool: edges.address.clone(),
+                                    zero_for_one,
+                                }
+                            })
+                            .collect();
+                        for &(a, _) in &cycle {
+                            blocked[a] = true;
+                        }
+                        out.push(ScreenedCycle {
+                            hops,
+                            log_edge: -total,
+                        });
+                    }
+                    continue;
+                }
+                dist[v] = dist[u] + weight;
+                pred[v] = Some((u, i));
+                depth[v] = depth[u] + 1;
+                if depth[v] < max_depth && !queued[v] {
+                    queued[v] = true;
+                    queue.push_back(v);
+                }
+            }
+        }
+        out
+    }
+}
+
+/// The predecessor edges leading from `v` to `u`, first hop first, when `v`
+/// is at most `max_hops - 1` steps up `u`'s predecessor chain.
+fn closed_cycle(
+    pred: &[Option<(usize, usize)>],
+    u: usize,
+    v: usize,
+    max_hops: usize,
+) -> Option<Vec<(usize, usize)>> {
+    let mut chain = Vec::new();
+    let mut at = u;
+    while at != v {
+        if chain.len() + 1 >= max_hops {
+            return None;
+        }
+        let (from, i) = pred[at]?;
+        chain.push((from, i));
+        at = from;
+    }
+    chain.reverse();
+    Some(chain)
+}
+
+/// `-ln` of each direction's raw rate net of the fee.
+fn weights(pool: &TrackedPool) -> [f64; 2] {
+    let price = pool.state.spot_price();
+    let keep = (1.0 - pool.fee_fraction()).ln();
+    if !(price.is_finite() && price > 0.0) {
+        return [f64::INFINITY; 2];
+    }
+    [-(price.ln() + keep), -(keep - price.ln())]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::Dex;
+    use crate::testing::{priced_pool, token, triangle};
+
+    #[test]
+    fn flags_the_triangle_with_an_edge() {
+        let mut screen = CycleScreen::new();
+        screen.update(&triangle(40.8));
+        let cycles = screen.negative_cycles(3, 0.0, &[]);
+        assert_eq!(cycles.len(), 1);
+        let c = &cycles[0];
+        let mut pools: Vec<&str> = c.hops.iter().map(|h| h.pool.as_str()).collect();
+        pools.sort();
+        assert_eq!(pools, vec!["0xa", "0xb", "0xc"]);
+        // ~2% less three 5 bps fees.
+        assert!(
+            (c.log_edge - 0.0185f64.ln_1p()).abs() < 3e-4,
+            "{}",
+            c.log_edge
+        );
+        assert!(screen.negative_cycles(3, 0.02f64.ln_1p(), &[]).is_empty());
+
+        screen.update(&triangle(40.0));
+        assert!(screen.negative_cycles(3, 0.0, &[]).is_empty());
+    }
+
+    #[test]
+    fn reweights_only_pools_that_moved() {
+        let mut pools = triangle(40.0);
+        let mut screen = CycleScreen::new();
+        assert_eq!(screen.update(&pools), 3);
+        assert_eq!(screen.update(&pools), 0);
+
+        pools[2] = triangle(40.8).remove(2);
+        pools[2].block = Some(7);
+        assert_eq!(screen.update(&pools), 1);
+        assert_eq!(screen.negative_cycles(3, 0.0, &[]).len(), 1);
+
+        assert_eq!(screen.update(&pools[..2]), 1);
+        assert_eq!(screen.pool_count(), 2);
+        assert!(screen.negative_cycles(3, 0.0, &[]).is_empty());
+
+        // Back in the freed slot, and again under another pair.
+        assert_eq!(screen.update(&pools), 1);
+        assert_eq!(screen.negative_cycles(3, 0.0, &[]).len(), 1);
+        let mut moved = pools.clone();
+        moved[2] = priced_pool("0xc", Dex::Prjx, ("KHYPE", "USDT0"), 40.8, 10u128.pow(17));
+        assert_eq!(screen.update(&moved), 1);
+        assert_eq!(screen.pool_count(), 3);
+        assert!(screen.negative_cycles(3, 0.0, &[]).is_empty());
+    }
+
+    #[test]
+    fn leaves_two_pool_routes_and_long_cycles_alone() {
+        let two = [
+            priced_pool("0xa", Dex::Prjx, ("WHYPE", "USDC"), 40.0, 10u128.pow(17)),
+            priced_pool(
+                "0xb",
+                Dex::HyperSwap,
+                ("WHYPE", "USDC"),
+                41.0,
+                10u128.pow(17),
+            ),
+        ];
+        let mut screen = CycleScreen::new();
+        screen.update(&two);
+        assert!(screen.negative_cycles(3, 0.0, &[]).is_empty());
+
+        // USDC -> WHYPE -> KHYPE -> USDT0 -> USDC, 2% up on the last pool.
+        let mut square = triangle(40.0);
+        square.pop();
+        square.push(priced_pool(
+            "0xe",
+            Dex::Prjx,
+            ("KHYPE", "USDT0"),
+            40.0,
+            10u128.pow(17),
+        ));
+        square.push(priced_pool(
+            "0xf",
+            Dex::Prjx,
+            ("USDT0", "USDC"),
+            1.02,
+            10u128.pow(17),
+        ));
+        screen.update(&square);
+        assert!(screen.negative_cycles(3, 0.0, &[]).is_empty());
+        let cycles = screen.negative_cycles(4, 0.0, &[]);
+        assert_eq!(cycles.len(), 1);
+        assert_eq!(cycles[0].hops.len(), 4);
+    }
+
+    #[test]
+    fn a_cycle_off_the_base_tokens_does_not_hide_one_on_them() {
+        // KHYPE -> FOO -> BAR -> KHYPE, 10% up, shares KHYPE with the
+        // WHYPE triangle.
+        let mut pools = triangle(40.8);
+        for (address, pair, price) in [
+            ("0xd", ("KHYPE", "FOO"), 1.0),
+            ("0xe", ("FOO", "BAR"), 1.0),
+            ("0xf", ("BAR", "KHYPE"), 1.1),
+        ] {
+            pools.push(priced_pool(address, Dex::Prjx, pair, price, 10u128.pow(17)));
+        }
+        let mut screen = CycleScreen::new();
+        screen.update(&pools);
+        let pools_of = |c: &ScreenedCycle| {
+            let mut pools: Vec<String> = c.hops.iter().map(|h| h.pool.clone()).collect();
+            pools.sort();
+            pools
+        };
+        // Unrestricted, the wider off-base cycle claims KHYPE first.
+        let any: Vec<_> = screen
+            .negative_cycles(3, 0.0, &[])
+            .iter()
+            .map(pools_of)
+            .collect();
+        assert_eq!(any, [["0xd", "0xe", "0xf"]]);
+
+        let whype = vec![token("WHYPE").address];
+        let cycles = screen.negative_cycles(3, 0.0, &whype);
+        assert_eq!(cycles.len(), 1);
+        assert_eq!(pools_of(&cycles[0]), ["0xa", "0xb", "0xc"]);
+    }
+}
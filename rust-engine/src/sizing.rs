@@ -0,0 +1,703 @@
+//! Optimal input size for a buy-then-sell arbitrage across two pools, or a
+//! cycle of swaps through several, optionally splitting each swap across
+//! parallel pools of its pair.
+//!
+//! Replaces the xyk-only `solve_best_dx` of the Python backend. Both legs are
+//! quoted with the pools' exact swap math, whatever the venue, so the profit
+//! curve is the one the pools would actually produce; it is concave in the
+//! input (each pool's marginal rate only gets worse as it trades), which lets
+//! a ternary search find the maximum.
+
+use crate::amm::{Pool, Result, SwapQuote};
+use crate::univ3::U256;
+
+/// Bisection steps on the common marginal rate of a split hop; each halves
+/// the log of the bracket, which starts within a few hundred bps.
+const SPLIT_ITERATIONS: usize = 40;
+
+/// One side of the trade: the pool and the direction it is swapped in.
+#[derive(Debug, Clone, Copy)]
+pub struct Leg<'a> {
+    pub pool: &'a dyn Pool,
+    pub zero_for_one: bool,
+}
+
+impl Leg<'_> {
+    /// Price limit that keeps this pool's price within `bps` of where it is now.
+    pub(crate) fn price_limit(&self, bps: f64) -> f64 {
+        let spot = self.pool.spot_price();
+        if self.zero_for_one {
+            spot * (1.0 - bps / 1e4).max(0.0)
+        } else {
+            spot * (1.0 + bps / 1e4)
+        }
+    }
+
+    pub(crate) fn quote(&self, amount_in: U256, limit: f64) -> Result<SwapQuote> {
+        self.pool
+            .quote_exact_in(self.zero_for_one, amount_in, Some(limit))
+    }
+
+    /// Pool price at which [`Self::marginal_rate`] is `rate`.
+    fn price_at_rate(&self, rate: f64) -> f64 {
+        let keep = 1.0 - self.pool.fee_fraction();
+        if self.zero_for_one {
+            rate / keep
+        } else {
+            keep / rate
+        }
+    }
+
+    /// Output per unit of input for the next wei traded at pool price `price`.
+    pub(crate) fn marginal_rate(&self, price: f64) -> f64 {
+        let keep = 1.0 - self.pool.fee_fraction();
+        if self.zero_for_one {
+            price * keep
+        } else {
+            keep / price
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SizingLimits {
+    /// Largest input, in raw units of the input token.
+    pub max_amount_in: U256,
+    /// How far either pool's price may be pushed, in bps.
+    pub max_slippage_bps: f64,
+}
+
+/// Best trade found by [`optimal_two_pool`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sizing {
+    /// Input to the buy leg, raw units.
+    pub amount_in: U256,
+    /// Buy leg output, fed to the sell leg.
+    pub amount_mid: U256,
+    /// Expected sell leg output, in the input token.
+    pub amount_out: U256,
+    /// LP fee taken by the buy leg, in the input token.
+    pub fee_in: U256,
+    /// LP fee taken by the sell leg, in the intermediate token.
+    pub fee_mid: U256,
+    /// Route output per unit of input at the margin after the trade, fees
+    /// included. 1.0 at an unconstrained optimum; above 1.0 when a cap binds.
+    pub marginal_price: f64,
+}
+
+impl Sizing {
+    pub fn profit(&self) -> U256 {
+        self.amount_out.saturating_sub(self.amount_in)
+    }
+}
+
+/// What one pool of a hop takes in and gives out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Fill {
+    pub amount_in: U256,
+    pub amount_out: U256,
+}
+
+/// Best trade found by [`optimal_route`] or [`optimal_split_route`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct RouteSizing {
+    /// Input to the first hop, raw units.
+    pub amount_in: U256,
+    /// Output of each hop, fed to the next; the last is the route's output,
+    /// in the input token.
+    pub amounts_out: Vec<U256>,
+    /// LP fee taken by each hop, in that hop's input token.
+    pub fees: Vec<U256>,
+    /// How each hop's input was split, one fill per leg given for the hop.
+    pub fills: Vec<Vec<Fill>>,
+    /// As [`Sizing::marginal_price`].
+    pub marginal_price: f64,
+}
+
+impl RouteSizing {
+    pub fn amount_out(&self) -> U256 {
+        self.amounts_out.last().copied().unwrap_or_default()
+    }
+
+    pub fn profit(&self) -> U256 {
+        self.amount_out().saturating_sub(self.amount_in)
+    }
+}
+
+/// Input amount that maximises `sell(buy(x)) - x` within `limits`, or `None`
+/// when no positive size is profitable.
+pub fn optimal_two_pool(buy: Leg, sell: Leg, limits: &SizingLimits) -> Result<Option<Sizing>> {
+    Ok(optimal_route(&[buy, sell], limits)?.map(|r| Sizing {
+        amount_in: r.amount_in,
+        amount_mid: r.amounts_out[0],
+        amount_out: r.amounts_out[1],
+        fee_in: r.fees[0],
+        fee_mid: r.fees[1],
+        marginal_price: r.marginal_price,
+    }))
+}
+
+/// Input amount that maximises the output of `legs` swapped in sequence,
+/// less the input, within `limits`; `None` when no positive size is
+/// profitable. The route must end in the token it starts with.
+pub fn optimal_route(legs: &[Leg], limits: &SizingLimits) -> Result<Option<RouteSizing>> {
+    let hops: Vec<&[Leg]> = legs.iter().map(std::slice::from_ref).collect();
+    optimal_split_route(&hops, limits)
+}
+
+/// As [`optimal_route`], with each hop's input split across parallel pools
+/// of the same pair swapped in the same direction. At any size the split is
+/// the one that leaves every pool that trades at the same marginal rate,
+/// which maximises the hop's output; pools that cannot match the others'
+/// rate get nothing.
+pub fn optimal_split_route(hops: &[&[Leg]], limits: &SizingLimits) -> Result<Option<RouteSizing>> {
+    if hops.is_empty()
+        || hops.iter().any(|h| h.is_empty())
+        || limits.max_slippage_bps <= 0.0
+        || limits.max_amount_in.is_zero()
+    {
+        return Ok(None);
+    }
+    let price_limits: Vec<Vec<f64>> = hops
+        .iter()
+        .map(|h| {
+            h.iter()
+                .map(|l| l.price_limit(limits.max_slippage_bps))
+                .collect()
+        })
+        .collect();
+
+    // Largest input every hop can absorb without crossing its price limits,
+    // carried back from the last hop through each one before it where the
+    // hop is a single pool.
+    let unbounded = U256::from(u128::MAX);
+    let mut cap: Option<U256> = None;
+    for (hop, limits) in hops.iter().zip(&price_limits).rev() {
+        let mut max_in = U256::ZERO;
+        let mut max_out = U256::ZERO;
+        for (leg, &limit) in hop.iter().zip(limits) {
+            let own = leg.quote(unbounded, limit)?;
+            max_in = max_in + own.amount_in;
+            max_out = max_out + own.amount_out;
+        }
+        if let [leg] = hop {
+            if let Some(next) = cap.filter(|&next| next < max_out) {
+                let via_next = leg
+                    .pool
+                    .quote_exact_out(leg.zero_for_one, next, Some(limits[0]))?;
+                max_in = max_in.min(via_next.amount_in);
+            }
+        }
+        cap = Some(max_in);
+    }
+    let max_in = limits
+        .max_amount_in
+        .min(cap.unwrap_or_default())
+        .to_u128()
+        .unwrap_or(u128::MAX);
+
+    let run = |x: U256| -> Result<Option<Vec<Vec<SwapQuote>>>> {
+        let mut quotes = Vec::with_capacity(hops.len());
+        let mut amount = x;
+        for (hop, limits) in hops.iter().zip(&price_limits) {
+            let Some(split) = split_exact_in(hop, limits, amount)? else {
+                return Ok(None);
+            };
+            amount = split
+                .iter()
+                .map(|q| q.amount_out)
+                .fold(U256::ZERO, |a, b| a + b);
+            quotes.push(split);
+        }
+        Ok(Some(quotes))
+    };
+    let total_out = |hop: &[SwapQuote]| hop.iter().fold(U256::ZERO, |a, q| a + q.amount_out);
+    let eval = |x: u128| -> Option<(U256, U256)> {
+        let quotes = run(U256::from(x)).ok()??;
+        Some((U256::from(x), total_out(quotes.last()?)))
+    };
+    // out_a - a < out_b - b without signed arithmetic.
+    let worse = |a: Option<(U256, U256)>, b: Option<(U256, U256)>| match (a, b) {
+        (Some((in_a, out_a)), Some((in_b, out_b))) => {
+            let sum = |x: U256, y: U256| x.checked_add(y).unwrap_or(U256::MAX);
+            sum(out_a, in_b) < sum(out_b, in_a)
+        }
+        (None, Some(_)) => true,
+        _ => false,
+    };
+
+    let tolerance = (max_in >> 20).max(2);
+    let (mut lo, mut hi) = (0u128, max_in);
+    while hi - lo > tolerance {
+        let third = (hi - lo) / 3;
+        let (m1, m2) = (lo + third, hi - third);
+        if worse(eval(m1), eval(m2)) {
+            lo = m1;
+        } else {
+            hi = m2;
+        }
+    }
+    let best = [lo, lo + (hi - lo) / 2, hi]
+        .into_iter()
+        .max_by(|&a, &b| {
+            if worse(eval(a), eval(b)) {
+                std::cmp::Ordering::Less
+            } else {
+                std::cmp::Ordering::Greater
+            }
+        })
+        .unwrap_or(0);
+    if best == 0 {
+        return Ok(None);
+    }
+
+    let amount_in = U256::from(best);
+    let Some(quotes) = run(amount_in)? else {
+        return Ok(None);
+    };
+    let amount_out = quotes.last().map(|h| total_out(h)).unwrap_or_default();
+    if amount_out <= amount_in {
+        return Ok(None);
+    }
+    Ok(Some(RouteSizing {
+        amount_in,
+        amounts_out: quotes.iter().map(|h| total_out(h)).collect(),
+        fees: quotes
+            .iter()
+            .map(|h| h.iter().fold(U256::ZERO, |a, q| a + q.fee_amount))
+            .collect(),
+        fills: quotes
+            .iter()
+            .map(|h| {
+                h.iter()
+                    .map(|q| Fill {
+                        amount_in: q.amount_in,
+                        amount_out: q.amount_out,
+                    })
+                    .collect()
+            })
+            .collect(),
+        // Every pool of a hop that trades ends at the same rate; those left
+        // out start below it.
+        marginal_price: hops
+            .iter()
+            .zip(&quotes)
+            .map(|(hop, h)| {
+                hop.iter()
+                    .zip(h)
+                    .map(|(leg, q)| leg.marginal_rate(q.price_after))
+                    .fold(0.0, f64::max)
+            })
+            .product(),
+    }))
+}
+
+/// Quotes `amount_in` split across the parallel `legs` of one hop so that
+/// each pool that trades ends at the same marginal rate, none past its
+/// entry in `price_limits`; `None` when they cannot absorb it all.
+fn split_exact_in(
+    legs: &[Leg],
+    price_limits: &[f64],
+    amount_in: U256,
+) -> Result<Option<Vec<SwapQuote>>> {
+    if let ([leg], [limit]) = (legs, price_limits) {
+        let q = leg.quote(amount_in, *limit)?;
+        return Ok((!q.is_partial()).then(|| vec![q]));
+    }
+    if amount_in.is_zero() {
+        return Ok(None);
+    }
+
+    // Price each leg ends at once its marginal rate falls to `rate`; `None`
+    // when it does not trade at that rate.
+    let end_price = |leg: &Leg, limit: f64, rate: f64| -> Option<f64> {
+        if leg.marginal_rate(leg.pool.spot_price()) <= rate {
+            return None;
+        }
+        let price = leg.price_at_rate(rate);
+        Some(if leg.zero_for_one {
+            price.max(limit)
+        } else {
+            price.min(limit)
+        })
+    };
+    // Input each leg takes before its marginal rate falls to `rate`.
+    let unbounded = U256::from(u128::MAX);
+    let takes = |rate: f64| -> Result<Vec<U256>> {
+        legs.iter()
+            .zip(price_limits)
+            .map(|(leg, &limit)| match end_price(leg, limit, rate) {
+                Some(price) => Ok(leg.quote(unbounded, price)?.amount_in),
+                None => Ok(U256::ZERO),
+            })
+            .collect()
+    };
+    let total = |takes: &[U256]| takes.iter().fold(U256::ZERO, |a, &t| a + t);
+
+    let rates = |price_of: &dyn Fn(&Leg, f64) -> f64| -> Vec<f64> {
+        legs.iter()
+            .zip(price_limits)
+            .map(|(leg, &limit)| leg.marginal_rate(price_of(leg, limit)))
+            .collect()
+    };
+    // At the best pool's spot rate nothing trades; at the lowest rate any
+    // pool may reach, every pool is at its limit.
+    let mut hi = rates(&|leg, _| leg.pool.spot_price())
+        .into_iter()
+        .fold(0.0, f64::max);
+    let mut lo = rates(&|_, limit| limit).into_iter().fold(hi, f64::min);
+    if total(&takes(lo)?) < amount_in {
+        return Ok(None);
+    }
+    // The higher bound always takes no more than `amount_in`.
+    for _ in 0..SPLIT_ITERATIONS {
+        let mid = (lo * hi).sqrt();
+        if !(mid > lo && mid < hi) {
+            break;
+        }
+        if total(&takes(mid)?) > amount_in {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+    let mut amounts = takes(hi)?;
+    // What is left over after rounding goes to the pool whose next wei pays
+    // the most at the final rate: one still trading below its limit, or the
+    // best pool when none trades yet. The first wins a tie.
+    let left = amount_in - total(&amounts);
+    let (best, _) = legs.iter().zip(price_limits).enumerate().fold(
+        (0, f64::MIN),
+        |(best, best_rate), (i, (leg, &limit))| {
+            let price = end_price(leg, limit, hi).unwrap_or_else(|| leg.pool.spot_price());
+            let rate = leg.marginal_rate(price);
+            if rate > best_rate {
+                (i, rate)
+            } else {
+                (best, best_rate)
+            }
+        },
+    );
+    amounts[best] = amounts[best] + left;
+    let mut quotes = Vec::with_capacity(legs.len());
+    for ((leg, &limit), amount) in legs.iter().zip(price_limits).zip(amounts) {
+        let q = if amount.is_zero() {
+            SwapQuote {
+                amount_in: U256::ZERO,
+                amount_out: U256::ZERO,
+                fee_amount: U256::ZERO,
+                amount_remaining: U256::ZERO,
+                price_after: leg.pool.spot_price(),
+            }
+        } else {
+            leg.quote(amount, limit)?
+        };
+        if q.is_partial() {
+            return Ok(None);
+        }
+        quotes.push(q);
+    }
+    Ok(Some(quotes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amm::{StablePool, V2Pool};
+    use crate::univ3::tick_math::get_sqrt_ratio_at_tick;
+    use crate::univ3::PoolState;
+
+    const L: u128 = 10u128.pow(21);
+
+    /// Full-range-ish pool at `tick` with constant liquidity `L`.
+    fn pool(fee: u32, tick: i32) -> PoolState {
+        let spacing = crate::univ3::tick_spacing_for_fee(fee).unwrap();
+        let mut p =
+            PoolState::new(fee, spacing, get_sqrt_ratio_at_tick(tick).unwrap(), tick, L).unwrap();
+        let base = tick.div_euclid(spacing) * spacing;
+        p.set_liquidity_net(base - 2000 * spacing, L as i128)
+            .unwrap();
+        p.set_liquidity_net(base + 2000 * spacing, -(L as i128))
+            .unwrap();
+        p
+    }
+
+    fn profit_at(buy: Leg, sell: Leg, x: u128) -> U256 {
+        let b = buy
+            .pool
+            .quote_exact_in(buy.zero_for_one, U256::from(x), None)
+            .unwrap();
+        let s = sell
+            .pool
+            .quote_exact_in(sell.zero_for_one, b.amount_out, None)
+            .unwrap();
+        s.amount_out.saturating_sub(U256::from(x))
+    }
+
+    fn limits() -> SizingLimits {
+        SizingLimits {
+            max_amount_in: U256::from(u128::MAX),
+            max_slippage_bps: 500.0,
+        }
+    }
+
+    #[test]
+    fn finds_interior_optimum() {
+        // token0 is ~1% cheaper (in token1) on `cheap`: buy it there with token1.
+        let (cheap, rich) = (pool(500, 0), pool(500, 100));
+        let buy = Leg {
+            pool: &cheap,
+            zero_for_one: false,
+        };
+        let sell = Leg {
+            pool: &rich,
+            zero_for_one: true,
+        };
+        let s = optimal_two_pool(buy, sell, &limits()).unwrap().unwrap();
+
+        let x = s.amount_in.to_u128().unwrap();
+        let best = profit_at(buy, sell, x);
+        assert_eq!(s.profit(), best);
+        for scale in [0.9, 0.99, 1.01, 1.1] {
+            let other = profit_at(buy, sell, (x as f64 * scale) as u128);
+            assert!(best >= other, "{scale}: {best} < {other}");
+        }
+        // Prices have met, net of fees.
+        assert!(
+            (s.marginal_price - 1.0).abs() < 1e-4,
+            "{}",
+            s.marginal_price
+        );
+        assert!(s.amount_mid > U256::ZERO && s.amount_out > s.amount_in);
+    }
+
+    #[test]
+    fn notional_cap_binds() {
+        let (cheap, rich) = (pool(500, 0), pool(500, 100));
+        let buy = Leg {
+            pool: &cheap,
+            zero_for_one: false,
+        };
+        let sell = Leg {
+            pool: &rich,
+            zero_for_one: true,
+        };
+        let cap = U256::from(10u128.pow(15));
+        let s = optimal_two_pool(
+            buy,
+            sell,
+            &SizingLimits {
+                max_amount_in: cap,
+                ..limits()
+            },
+        )
+        .unwrap()
+        .unwrap();
+        assert!(s.amount_in <= cap && s.amount_in >= cap - U256::from(10u128.pow(9)));
+        assert!(s.marginal_price > 1.005);
+    }
+
+    #[test]
+    fn slippage_cap_limits_price_impact() {
+        let (cheap, rich) = (pool(500, 0), pool(500, 100));
+        let buy = Leg {
+            pool: &cheap,
+            zero_for_one: false,
+        };
+        let sell = Leg {
+            pool: &rich,
+            zero_for_one: true,
+        };
+        let unconstrained = optimal_two_pool(buy, sell, &limits()).unwrap().unwrap();
+        let tight = optimal_two_pool(
+            buy,
+            sell,
+            &SizingLimits {
+                max_slippage_bps: 5.0,
+                ..limits()
+            },
+        )
+        .unwrap()
+        .unwrap();
+        assert!(tight.amount_in < unconstrained.amount_in);
+
+        let after = cheap
+            .quote_exact_input(false, tight.amount_in, None)
+            .unwrap()
+            .sqrt_price_x96
+            .to_f64();
+        let moved_bps = ((after / cheap.sqrt_price_x96.to_f64()).powi(2) - 1.0) * 1e4;
+        assert!(moved_bps <= 5.0 + 1e-6, "{moved_bps}");
+    }
+
+    #[test]
+    fn no_size_without_spread() {
+        let (a, b) = (pool(3000, 0), pool(3000, 30));
+        let buy = Leg {
+            pool: &a,
+            zero_for_one: false,
+        };
+        let sell = Leg {
+            pool: &b,
+            zero_for_one: true,
+        };
+        assert_eq!(optimal_two_pool(buy, sell, &limits()).unwrap(), None);
+    }
+
+    #[test]
+    fn sizes_across_venues() {
+        // A depegged stableswap pool against an xyk pool still at par.
+        let e18 = 10u128.pow(18);
+        let stable = StablePool::new(
+            [U256::from(1_200_000 * e18), U256::from(800_000 * e18)],
+            [18, 18],
+            20,
+            400,
+        )
+        .unwrap();
+        let xyk = V2Pool::new(
+            U256::from(5_000_000 * e18),
+            U256::from(5_000_000 * e18),
+            3000,
+        )
+        .unwrap();
+        // token0 is cheap on the stableswap pool: buy it there with token1.
+        assert!(stable.spot_price() < 0.99);
+        let buy = Leg {
+            pool: &stable,
+            zero_for_one: false,
+        };
+        let sell = Leg {
+            pool: &xyk,
+            zero_for_one: true,
+        };
+        let s = optimal_two_pool(buy, sell, &limits()).unwrap().unwrap();
+        let x = s.amount_in.to_u128().unwrap();
+        let best = profit_at(buy, sell, x);
+        assert_eq!(s.profit(), best);
+        for scale in [0.9, 0.99, 1.01, 1.1] {
+            let other = profit_at(buy, sell, (x as f64 * scale) as u128);
+            assert!(best >= other, "{scale}: {best} < {other}");
+        }
+        assert!(
+            (s.marginal_price - 1.0).abs() < 1e-4,
+            "{}",
+            s.marginal_price
+        );
+    }
+
+    #[test]
+    fn split_equalizes_marginal_rates() {
+        // Two fee tiers of one pair, the cheaper tier priced a little worse.
+        let (low_fee, high_fee) = (pool(500, 20), pool(3000, 0));
+        let legs = [
+            Leg {
+                pool: &low_fee,
+                zero_for_one: false,
+            },
+            Leg {
+                pool: &high_fee,
+                zero_for_one: false,
+            },
+        ];
+        let price_limits: Vec<f64> = legs.iter().map(|l| l.price_limit(500.0)).collect();
+        let amount = 3 * 10u128.pow(19);
+        let split = split_exact_in(&legs, &price_limits, U256::from(amount))
+            .unwrap()
+            .unwrap();
+        let (a, b) = (split[0].amount_in, split[1].amount_in);
+        assert_eq!(a + b, U256::from(amount));
+        assert!(a > b && !b.is_zero(), "{a} {b}");
+        let rates: Vec<f64> = legs
+            .iter()
+            .zip(&split)
+            .map(|(l, q)| l.marginal_rate(q.price_after))
+            .collect();
+        assert!((rates[0] / rates[1] - 1.0).abs() < 1e-6, "{rates:?}");
+
+        // Moving input from either pool to the other only loses output.
+        let out = |x: u128| {
+            let q = |leg: &Leg, x: u128| {
+                leg.pool
+                    .quote_exact_in(false, U256::from(x), None)
+                    .unwrap()
+                    .amount_out
+            };
+            q(&legs[0], x) + q(&legs[1], amount - x)
+        };
+        let best = split[0].amount_out + split[1].amount_out;
+        let a = a.to_u128().unwrap();
+        for shifted in [a - a / 20, a + (amount - a) / 20] {
+            assert!(out(shifted) <= best);
+        }
+
+        // Small enough, and only the better pool trades.
+        let small = split_exact_in(&legs, &price_limits, U256::from(10u128.pow(15)))
+            .unwrap()
+            .unwrap();
+        assert!(small[1].amount_in.is_zero() && !small[0].amount_in.is_zero());
+    }
+
+    #[test]
+    fn split_remainder_goes_to_the_best_pool() {
+        // Too little for either pool to move: all of it goes to the better
+        // pool, whichever side of the list it is on.
+        let (worse, better) = (pool(3000, 0), pool(500, 20));
+        for order in [[&worse, &better], [&better, &worse]] {
+            let legs = order.map(|p| Leg {
+                pool: p,
+                zero_for_one: false,
+            });
+            let price_limits: Vec<f64> = legs.iter().map(|l| l.price_limit(500.0)).collect();
+            let split = split_exact_in(&legs, &price_limits, U256::from(1000u64))
+                .unwrap()
+                .unwrap();
+            let on_better = if std::ptr::eq(order[0], &better) {
+                0
+            } else {
+                1
+            };
+            assert_eq!(split[on_better].amount_in, U256::from(1000u64));
+            assert!(split[1 - on_better].amount_in.is_zero());
+        }
+    }
+
+    #[test]
+    fn split_route_beats_the_best_single_pool() {
+        // token0 is cheap on two fee tiers and rich on a third pool.
+        let (cheap, also_cheap, rich) = (pool(500, 0), pool(500, 10), pool(500, 100));
+        let buys = [
+            Leg {
+                pool: &cheap,
+                zero_for_one: false,
+            },
+            Leg {
+                pool: &also_cheap,
+                zero_for_one: false,
+            },
+        ];
+        let sell = [Leg {
+            pool: &rich,
+            zero_for_one: true,
+        }];
+        let single = optimal_route(&[buys[0], sell[0]], &limits())
+            .unwrap()
+            .unwrap();
+        let split = optimal_split_route(&[&buys, &sell], &limits())
+            .unwrap()
+            .unwrap();
+        assert!(split.profit() > single.profit());
+        assert_eq!(split.fills[0].len(), 2);
+        assert!(split.fills[0].iter().all(|f| !f.amount_in.is_zero()));
+        let into_sell: U256 = split.fills[0]
+            .iter()
+            .fold(U256::ZERO, |a, f| a + f.amount_out);
+        assert_eq!(into_sell, split.fills[1][0].amount_in);
+        assert_eq!(split.amounts_out[0], into_sell);
+        assert!(
+            (split.marginal_price - 1.0).abs() < 1e-4,
+            "{}",
+            split.marginal_price
+        );
+    }
+}
@@ -0,0 +1,222 @@
+//! In-memory pool state shared between the ingestion tasks and the detector.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::amm::{Amm, Pool};
+use crate::tokens::TokenRegistry;
+use crate::univ3::U256;
+
+/// Venue a pool belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Dex {
+    #[serde(rename = "PRJX")]
+    Prjx,
+    #[serde(rename = "HyperSwap")]
+    HyperSwap,
+}
+
+impl fmt::Display for Dex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Dex::Prjx => write!(f, "PRJX"),
+            Dex::HyperSwap => write!(f, "HyperSwap"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Token {
+    /// Lowercase hex address.
+    pub address: String,
+    pub symbol: String,
+    /// ERC-20 `name`; empty when the source did not give one.
+    #[serde(default)]
+    pub name: String,
+    pub decimals: u8,
+    /// Transfers deliver less than the amount sent.
+    #[serde(default)]
+    pub fee_on_transfer: bool,
+    /// Balances change without transfers.
+    #[serde(default)]
+    pub rebasing: bool,
+    /// A USD stablecoin, priced at $1. Only the tokens file sets it, by
+    /// address: anyone can deploy a token whose `symbol()` says USDC.
+    #[serde(default)]
+    pub stable: bool,
+}
+
+impl Token {
+    /// Whether a swap moves exactly the amounts a pool quotes.
+    pub fn is_standard(&self) -> bool {
+        !self.fee_on_transfer && !self.rebasing
+    }
+
+    /// Raw units in whole tokens.
+    pub fn to_units(&self, raw: U256) -> f64 {
+        raw.to_f64() / self.scale()
+    }
+
+    /// Whole tokens in raw units, rounded down.
+    pub fn from_units(&self, amount: f64) -> U256 {
+        U256::from((amount.max(0.0) * self.scale()) as u128)
+    }
+
+    fn scale(&self) -> f64 {
+        10f64.powi(self.decimals.into())
+    }
+}
+
+/// A pool with its metadata and the latest known swap state.
+#[derive(Debug, Clone)]
+pub struct TrackedPool {
+    /// Lowercase hex address.
+    pub address: String,
+    pub dex: Dex,
+    pub token0: Token,
+    pub token1: Token,
+    pub state: Amm,
+    /// TVL as reported by the source, if any.
+    pub tvl_usd: Option<f64>,
+    /// Block `state` was read at on chain; `None` for indexer snapshots.
+    pub block: Option<u64>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl TrackedPool {
+    /// Mid price of token0 in units of token1, decimals applied.
+    pub fn mid_price(&self) -> f64 {
+        self.state.spot_price() * self.token0.scale() / self.token1.scale()
+    }
+
+    /// [`Pool::virtual_reserves`], decimals applied.
+    pub fn virtual_reserves(&self) -> (f64, f64) {
+        let (reserve0, reserve1) = self.state.virtual_reserves();
+        (
+            reserve0 / self.token0.scale(),
+            reserve1 / self.token1.scale(),
+        )
+    }
+
+    pub fn fee_fraction(&self) -> f64 {
+        self.state.fee_fraction()
+    }
+}
+
+/// All tracked pools keyed by address, plus per-venue refresh times and the
+/// token registry every pool's tokens are resolved through.
+#[derive(Debug, Default)]
+pub struct PoolStore {
+    pools: HashMap<String, TrackedPool>,
+    refreshed: HashMap<Dex, DateTime<Utc>>,
+    tokens: TokenRegistry,
+}
+
+pub type SharedPoolStore = Arc<RwLock<PoolStore>>;
+
+impl PoolStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn shared() -> SharedPoolStore {
+        Self::new().into_shared()
+    }
+
+    pub fn with_tokens(mut self, tokens: TokenRegistry) -> Self {
+        self.tokens = tokens;
+        self
+    }
+
+    pub fn into_shared(self) -> SharedPoolStore {
+        Arc::new(RwLock::new(self))
+    }
+
+    pub fn tokens(&self) -> &TokenRegistry {
+        &self.tokens
+    }
+
+    pub fn tokens_mut(&mut self) -> &mut TokenRegistry {
+        &mut self.tokens
+    }
+
+    fn resolve_tokens(&mut self, pool: &mut TrackedPool) {
+        self.tokens.resolve(&mut pool.token0);
+        self.tokens.resolve(&mut pool.token1);
+    }
+
+    /// Replaces every pool of `dex` with a fresh snapshot. Swap state read
+    /// on chain is kept when the snapshot's is older, so a lagging indexer
+    /// never rolls a pool back.
+    pub fn replace_dex(&mut self, dex: Dex, pools: Vec<TrackedPool>) {
+        let (mut previous, rest): (HashMap<_, _>, HashMap<_, _>) = std::mem::take(&mut self.pools)
+            .into_iter()
+            .partition(|(_, p)| p.dex == dex);
+        self.pools = rest;
+        for mut pool in pools {
+            self.resolve_tokens(&mut pool);
+            if let Some(prev) = previous.remove(&pool.address) {
+                if prev.block > pool.block {
+                    pool.state = prev.state;
+                    pool.block = prev.block;
+                    pool.updated_at = prev.updated_at;
+                }
+            }
+            self.pools.insert(pool.address.clone(), pool);
+        }
+        self.refreshed.insert(dex, Utc::now());
+    }
+
+    /// Swap state of a tracked pool read at `block`. Ignored, returning
+    /// `false`, for unknown pools and reads older than the current state.
+    pub fn set_state(&mut self, address: &str, state: impl Into<Amm>, block: u64) -> bool {
+        match self.pools.get_mut(address) {
+            Some(pool) if pool.block.is_none_or(|b| b <= block) => {
+                pool.state = state.into();
+                pool.block = Some(block);
+                pool.updated_at = Utc::now();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn upsert(&mut self, mut pool: TrackedPool) {
+        self.resolve_tokens(&mut pool);
+        self.pools.insert(pool.address.clone(), pool);
+    }
+
+    pub fn get(&self, address: &str) -> Option<&TrackedPool> {
+        self.pools.get(address)
+    }
+
+    pub fn get_mut(&mut self, address: &str) -> Option<&mut TrackedPool> {
+        self.pools.get_mut(address)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &TrackedPool> {
+        self.pools.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.pools.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pools.is_empty()
+    }
+
+    pub fn last_refresh(&self, dex: Dex) -> Option<DateTime<Utc>> {
+        self.refreshed.get(&dex).copied()
+    }
+
+    #[cfg(test)]
+    pub(crate) fn set_last_refresh(&mut self, dex: Dex, at: DateTime<Utc>) {
+        self.refreshed.insert(dex, at);
+    }
+}
@@ -0,0 +1,464 @@
+//! Engine status snapshot published on `REDIS_CHANNEL`.
+//!
+//! The payload layout is versioned by [`SCHEMA_VERSION`] and described by
+//! `schema/status.v3.json`; bump both together on breaking changes.
+
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{watch, RwLock};
+use tokio::task::JoinHandle;
+use tracing::warn;
+
+use crate::opportunity::Opportunity;
+use crate::pricing::{PriceQuote, PricingConfig, UsdPrices};
+use crate::state::{Dex, PoolStore, SharedPoolStore};
+
+pub const SCHEMA_VERSION: u32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EngineStatus {
+    /// No venue has delivered a pool snapshot yet.
+    Starting,
+    /// Some venues have synced, others not yet.
+    Syncing,
+    Running,
+    /// A venue's pool state is older than the allowed lag.
+    Degraded,
+    /// Detection continues but nothing should be acted on.
+    Paused,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EngineSnapshot {
+    pub status: EngineStatus,
+    pub uptime_secs: f64,
+}
+
+/// Freshness of one venue's pool state.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PoolLag {
+    pub dex: Dex,
+    pub tracked: usize,
+    pub last_refresh: Option<DateTime<Utc>>,
+    pub lag_secs: Option<f64>,
+}
+
+/// Head block and reorgs seen by the log stream. All zero/null when pool
+/// state does not come from the chain.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChainSnapshot {
+    pub head_block: Option<u64>,
+    /// Reorgs rolled back since start.
+    pub reorgs: u64,
+    /// Blocks the latest reorg replaced.
+    pub last_reorg_depth: u64,
+    pub max_reorg_depth: u64,
+    pub last_reorg_at: Option<DateTime<Utc>>,
+}
+
+impl ChainSnapshot {
+    pub fn record_reorg(&mut self, depth: u64, at: DateTime<Utc>) {
+        self.reorgs += 1;
+        self.last_reorg_depth = depth;
+        self.max_reorg_depth = self.max_reorg_depth.max(depth);
+        self.last_reorg_at = Some(at);
+    }
+}
+
+/// A token's derived USD price.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TokenPrice {
+    pub address: String,
+    pub symbol: String,
+    #[serde(flatten)]
+    pub quote: PriceQuote,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PnlSnapshot {
+    pub realized_usd: f64,
+    pub unrealized_usd: f64,
+    pub total_usd: f64,
+}
+
+/// One message on `REDIS_CHANNEL`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusSnapshot {
+    pub schema_version: u32,
+    pub ts: DateTime<Utc>,
+    pub engine: EngineSnapshot,
+    /// `None`, sent as `null`, while nothing reports fills: the engine
+    /// detects opportunities but does not trade, and a zero would read as
+    /// a flat book.
+    pub pnl: Option<PnlSnapshot>,
+    pub pools: Vec<PoolLag>,
+    pub chain: ChainSnapshot,
+    /// Every priced token, by symbol.
+    pub prices: Vec<TokenPrice>,
+    /// Best first, at most `top_n`.
+    pub opportunities: Vec<Opportunity>,
+}
+
+#[derive(Debug, Clone)]
+pub struct StatusConfig {
+    pub top_n: usize,
+    /// Venues expected to report; the engine is syncing until all have.
+    pub venues: Vec<Dex>,
+    /// A venue whose last refresh is older than this degrades the engine.
+    pub max_refresh_lag: Duration,
+    pub pricing: PricingConfig,
+}
+
+impl Default for StatusConfig {
+    fn default() -> Self {
+        Self {
+            top_n: 20,
+            venues: vec![Dex::Prjx],
+            max_refresh_lag: Duration::from_secs(120),
+            pricing: PricingConfig::default(),
+        }
+    }
+}
+
+/// Long-lived engine state that feeds the snapshot.
+#[derive(Debug)]
+pub struct StatusReporter {
+    pub config: StatusConfig,
+    pub paused: bool,
+    /// Kept current by the log stream.
+    pub chain: ChainSnapshot,
+    started_at: DateTime<Utc>,
+}
+
+pub type SharedStatusReporter = Arc<RwLock<StatusReporter>>;
+
+impl StatusReporter {
+    pub fn new(config: StatusConfig) -> Self {
+        Self {
+            config,
+            paused: false,
+            chain: ChainSnapshot::default(),
+            started_at: Utc::now(),
+        }
+    }
+
+    pub fn shared(config: StatusConfig) -> SharedStatusReporter {
+        Arc::new(RwLock::new(Self::new(config)))
+    }
+
+    fn pool_lag(&self, store: &PoolStore, now: DateTime<Utc>) -> Vec<PoolLag> {
+        self.config
+            .venues
+            .iter()
+            .map(|&dex| {
+                let last_refresh = store.last_refresh(dex);
+                PoolLag {
+                    dex,
+                    tracked: store.iter().filter(|p| p.dex == dex).count(),
+                    last_refresh,
+                    lag_secs: last_refresh
+                        .map(|t| (now - t).to_std().unwrap_or_default().as_secs_f64()),
+                }
+            })
+            .collect()
+    }
+
+    fn status(&self, lags: &[PoolLag]) -> EngineStatus {
+        let synced = lags.iter().filter(|l| l.last_refresh.is_some()).count();
+        let max_lag = self.config.max_refresh_lag.as_secs_f64();
+        if self.paused {
+            EngineStatus::Paused
+        } else if synced == 0 {
+            EngineStatus::Starting
+        } else if lags.iter().any(|l| l.lag_secs.is_some_and(|s| s > max_lag)) {
+            EngineStatus::Degraded
+        } else if synced < lags.len() {
+            EngineStatus::Syncing
+        } else {
+            EngineStatus::Running
+        }
+    }
+
+    /// Snapshot of the engine at `now`. `opportunities` must be best first.
+    pub fn snapshot(
+        &self,
+        store: &PoolStore,
+        opportunities: &[Opportunity],
+        now: DateTime<Utc>,
+    ) -> StatusSnapshot {
+        let pools = self.pool_lag(store, now);
+        let prices = UsdPrices::from_pools_with(store.iter(), &self.config.pricing);
+        StatusSnapshot {
+            schema_version: SCHEMA_VERSION,
+            ts: now,
+            engine: EngineSnapshot {
+                status: self.status(&pools),
+                uptime_secs: (now - self.started_at)
+                    .to_std()
+                    .unwrap_or_default()
+                    .as_secs_f64(),
+            },
+            pnl: None,
+            pools,
+            chain: self.chain.clone(),
+            prices: token_prices(store, &prices),
+            opportunities: opportunities
+                .iter()
+                .take(self.config.top_n)
+                .cloned()
+                .collect(),
+        }
+    }
+}
+
+fn token_prices(store: &PoolStore, prices: &UsdPrices) -> Vec<TokenPrice> {
+    let mut out: Vec<TokenPrice> = prices
+        .iter()
+        .map(|(address, quote)| TokenPrice {
+            address: address.to_string(),
+            symbol: store
+                .tokens()
+                .get(address)
+                .map(|t| t.symbol.clone())
+                .unwrap_or_default(),
+            quote: quote.clone(),
+        })
+        .collect();
+    out.sort_by(|a, b| a.symbol.cmp(&b.symbol).then(a.address.cmp(&b.address)));
+    out
+}
+
+/// Where snapshots go: Redis pub/sub in production, memory in tests.
+pub trait StatusSink: Send {
+    fn publish(
+        &mut self,
+        channel: &str,
+        payload: String,
+    ) -> impl Future<Output = Result<()>> + Send;
+}
+
+impl StatusSink for redis::aio::ConnectionManager {
+    async fn publish(&mut self, channel: &str, payload: String) -> Result<()> {
+        redis::AsyncCommands::publish::<_, _, ()>(self, channel, payload).await?;
+        Ok(())
+    }
+}
+
+/// Publishes a fresh snapshot on `channel` every `every`.
+pub fn spawn_publisher<S: StatusSink + 'static>(
+    mut sink: S,
+    channel: String,
+    reporter: SharedStatusReporter,
+    store: SharedPoolStore,
+    opportunities: watch::Receiver<Vec<Opportunity>>,
+    every: Duration,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(every);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        loop {
+            interval.tick().await;
+            let payload = {
+                let reporter = reporter.read().await;
+                let store = store.read().await;
+                let snapshot = reporter.snapshot(&store, &opportunities.borrow(), Utc::now());
+                match serde_json::to_string(&snapshot) {
+                    Ok(payload) => payload,
+                    Err(e) => {
+                        warn!(error = %e, "failed to encode status snapshot");
+                        continue;
+                    }
+                }
+            };
+            if let Err(e) = sink.publish(&channel, payload).await {
+                warn!(error = %e, "status publish failed");
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::detector::Detector;
+    use crate::testing::{stable_tokens, tick_for_price, token, v3_pool, MemorySink};
+    use serde_json::Value;
+
+    fn store_with(venues: &[(Dex, DateTime<Utc>)]) -> PoolStore {
+        let mut store = PoolStore::new().with_tokens(stable_tokens());
+        for (i, &(dex, at)) in venues.iter().enumerate() {
+            let (t0, t1) = (token("WHYPE"), token("USDC"));
+            let tick = tick_for_price(40.0 + i as f64, &t0, &t1);
+            let pool = v3_pool(&format!("0x{i}"), dex, t0, t1, 500, tick, 10u128.pow(17));
+            store.replace_dex(dex, vec![pool]);
+            store.set_last_refresh(dex, at);
+        }
+        store
+    }
+
+    fn reporter() -> StatusReporter {
+        StatusReporter::new(StatusConfig {
+            venues: vec![Dex::Prjx, Dex::HyperSwap],
+            ..StatusConfig::default()
+        })
+    }
+
+    #[test]
+    fn status_follows_venue_sync_and_lag() {
+        let now = Utc::now();
+        let mut r = reporter();
+        let status =
+            |r: &StatusReporter, store: &PoolStore| r.snapshot(store, &[], now).engine.status;
+
+        assert_eq!(status(&r, &PoolStore::new()), EngineStatus::Starting);
+        assert_eq!(
+            status(&r, &store_with(&[(Dex::Prjx, now)])),
+            EngineStatus::Syncing
+        );
+        let both = store_with(&[(Dex::Prjx, now), (Dex::HyperSwap, now)]);
+        assert_eq!(status(&r, &both), EngineStatus::Running);
+        let stale = store_with(&[
+            (Dex::Prjx, now),
+            (Dex::HyperSwap, now - chrono::Duration::seconds(600)),
+        ]);
+        assert_eq!(status(&r, &stale), EngineStatus::Degraded);
+        r.paused = true;
+        assert_eq!(status(&r, &both), EngineStatus::Paused);
+
+        let lag = &r.snapshot(&stale, &[], now).pools[1];
+        assert_eq!((lag.dex, lag.tracked), (Dex::HyperSwap, 1));
+        assert!((lag.lag_secs.unwrap() - 600.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn snapshot_keeps_top_n() {
+        let now = Utc::now();
+        let store = store_with(&[(Dex::Prjx, now), (Dex::HyperSwap, now)]);
+        let prices = UsdPrices::from_pools(store.iter());
+        let opp = Detector::default()
+            .detect(store.iter(), &prices, now)
+            .remove(0);
+        let opps = vec![opp; 5];
+        let mut r = reporter();
+        r.config.top_n = 3;
+        assert_eq!(r.snapshot(&store, &opps, now).opportunities.len(), 3);
+    }
+
+    #[test]
+    fn payload_matches_schema() {
+        let schema: Value = serde_json::from_str(include_str!("../schema/status.v3.json")).unwrap();
+        let now = Utc::now();
+        let store = store_with(&[(Dex::Prjx, now)]);
+        let payload = serde_json::to_value(reporter().snapshot(&store, &[], now)).unwrap();
+
+        assert_eq!(
+            schema["properties"]["schema_version"]["const"],
+            SCHEMA_VERSION
+        );
+        for key in schema["required"].as_array().unwrap() {
+            assert!(
+                payload.get(key.as_str().unwrap()).is_some(),
+                "missing {key}"
+            );
+        }
+        let statuses = &schema["properties"]["engine"]["properties"]["status"]["enum"];
+        for s in [
+            EngineStatus::Starting,
+            EngineStatus::Syncing,
+            EngineStatus::Running,
+            EngineStatus::Degraded,
+            EngineStatus::Paused,
+        ] {
+            let s = serde_json::to_value(s).unwrap();
+            assert!(statuses.as_array().unwrap().contains(&s), "{s}");
+        }
+        assert_eq!(payload["engine"]["status"], "syncing");
+        for key in schema["properties"]["chain"]["required"]
+            .as_array()
+            .unwrap()
+        {
+            assert!(
+                payload["chain"].get(key.as_str().unwrap()).is_some(),
+                "missing chain.{key}"
+            );
+        }
+
+        let mut chain = ChainSnapshot::default();
+        chain.record_reorg(3, now);
+        chain.record_reorg(1, now);
+        assert_eq!(
+            (chain.reorgs, chain.last_reorg_depth, chain.max_reorg_depth),
+            (2, 1, 3)
+        );
+
+        let both = store_with(&[(Dex::Prjx, now), (Dex::HyperSwap, now)]);
+        let prices = UsdPrices::from_pools(both.iter());
+        let opps = Detector::default().detect(both.iter(), &prices, now);
+        let payload = serde_json::to_value(reporter().snapshot(&both, &opps, now)).unwrap();
+        let opp = &payload["opportunities"][0];
+        for key in schema["$defs"]["opportunity"]["required"]
+            .as_array()
+            .unwrap()
+        {
+            assert!(opp.get(key.as_str().unwrap()).is_some(), "missing {key}");
+        }
+        assert!(opp["amount_in"].is_string());
+        let prices = payload["prices"].as_array().unwrap();
+        let symbols: Vec<&str> = prices
+            .iter()
+            .map(|p| p["symbol"].as_str().unwrap())
+            .collect();
+        assert_eq!(symbols, ["USDC", "WHYPE"]);
+        for key in schema["properties"]["prices"]["items"]["required"]
+            .as_array()
+            .unwrap()
+        {
+            assert!(
+                prices[1].get(key.as_str().unwrap()).is_some(),
+                "missing prices.{key}"
+            );
+        }
+        assert!(prices[0]["depth_usd"].is_null());
+        assert!(prices[1]["low"].as_f64() < prices[1]["high"].as_f64());
+        for side in ["base", "quote"] {
+            for key in schema["$defs"]["token"]["required"].as_array().unwrap() {
+                assert!(
+                    opp["pair"][side].get(key.as_str().unwrap()).is_some(),
+                    "missing pair.{side}.{key}"
+                );
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn publisher_sends_snapshots() {
+        let sink = MemorySink::default();
+        let store = PoolStore::shared();
+        let (_tx, rx) = watch::channel(Vec::new());
+        let handle = spawn_publisher(
+            sink.clone(),
+            "arb:realtime".into(),
+            StatusReporter::shared(StatusConfig::default()),
+            store,
+            rx,
+            Duration::from_millis(10),
+        );
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        handle.abort();
+
+        let sent = sink.messages();
+        assert!(sent.len() >= 2);
+        let (channel, payload) = &sent[0];
+        assert_eq!(channel, "arb:realtime");
+        let v: Value = serde_json::from_str(payload).unwrap();
+        assert_eq!(v["schema_version"], SCHEMA_VERSION);
+        assert_eq!(v["engine"]["status"], "starting");
+        assert!(v["pnl"].is_null() && v.get("pnl").is_some());
+    }
+}
@@ -0,0 +1,389 @@
+//! Pool-state ingestion from a Uniswap V3 style subgraph (PRJX on Goldsky).
+
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::Utc;
+use reqwest::Client;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+use crate::state::{Dex, SharedPoolStore, Token, TrackedPool};
+use crate::univ3::{tick_spacing_for_fee, PoolState, U256};
+
+/// The Graph caps `first` at 1000.
+pub const DEFAULT_PAGE_SIZE: usize = 1000;
+
+const POOLS_QUERY: &str = r#"query Pools($first: Int!, $lastId: String!) {
+  pools(first: $first, orderBy: id, orderDirection: asc, where: { id_gt: $lastId }) {
+    id feeTier liquidity sqrtPrice tick totalValueLockedUSD
+    token0 { id symbol name decimals }
+    token1 { id symbol name decimals }
+    ticks(first: $first, orderBy: tickIdx, orderDirection: asc, where: { liquidityNet_not: "0" }) { tickIdx liquidityNet }
+  }
+}"#;
+
+const TICKS_QUERY: &str = r#"query PoolTicks($pool: String!, $first: Int!, $lastTick: BigInt!) {
+  ticks(first: $first, orderBy: tickIdx, orderDirection: asc, where: { pool: $pool, tickIdx_gt: $lastTick, liquidityNet_not: "0" }) { tickIdx liquidityNet }
+}"#;
+
+#[derive(Debug, Deserialize)]
+struct GraphQlResponse<T> {
+    data: Option<T>,
+    #[serde(default)]
+    errors: Vec<GraphQlError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GraphQlError {
+    message: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct PoolsData {
+    pools: Vec<RawPool>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TicksData {
+    ticks: Vec<RawTick>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawPool {
+    id: String,
+    fee_tier: String,
+    liquidity: String,
+    sqrt_price: String,
+    tick: Option<String>,
+    #[serde(rename = "totalValueLockedUSD")]
+    total_value_locked_usd: Option<String>,
+    token0: RawToken,
+    token1: RawToken,
+    #[serde(default)]
+    ticks: Vec<RawTick>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawToken {
+    id: String,
+    symbol: String,
+    #[serde(default)]
+    name: String,
+    decimals: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawTick {
+    tick_idx: String,
+    liquidity_net: String,
+}
+
+impl RawToken {
+    fn into_token(self) -> Result<Token> {
+        Ok(Token {
+            address: self.id.to_lowercase(),
+            decimals: self
+                .decimals
+                .parse()
+                .with_context(|| format!("token {} decimals {:?}", self.id, self.decimals))?,
+            symbol: self.symbol,
+            name: self.name,
+            ..Token::default()
+        })
+    }
+}
+
+impl RawPool {
+    fn into_tracked(self, dex: Dex) -> Result<TrackedPool> {
+        let fee: u32 = self.fee_tier.parse().context("feeTier")?;
+        let tick_spacing =
+            tick_spacing_for_fee(fee).ok_or_else(|| anyhow!("unsupported fee tier {fee}"))?;
+        let sqrt_price_x96 = U256::from_dec_str(&self.sqrt_price).context("sqrtPrice")?;
+        if sqrt_price_x96.is_zero() {
+            bail!("pool not initialized");
+        }
+        let tick: i32 = self
+            .tick
+            .as_deref()
+            .ok_or_else(|| anyhow!("pool has no tick"))?
+            .parse()
+            .context("tick")?;
+        let liquidity: u128 = self.liquidity.parse().context("liquidity")?;
+
+        let mut state = PoolState::new(fee, tick_spacing, sqrt_price_x96, tick, liquidity)?;
+        for t in &self.ticks {
+            let idx: i32 = t.tick_idx.parse().context("tickIdx")?;
+            let net: i128 = t.liquidity_net.parse().context("liquidityNet")?;
+            state.set_liquidity_net(idx, net)?;
+        }
+
+        Ok(TrackedPool {
+            address: self.id.to_lowercase(),
+            dex,
+            token0: self.token0.into_token()?,
+            token1: self.token1.into_token()?,
+            state: state.into(),
+            tvl_usd: self.total_value_locked_usd.and_then(|v| v.parse().ok()),
+            block: None,
+            updated_at: Utc::now(),
+        })
+    }
+}
+
+/// Typed client for one subgraph endpoint.
+#[derive(Debug, Clone)]
+pub struct SubgraphClient {
+    http: Client,
+    url: String,
+    dex: Dex,
+    page_size: usize,
+}
+
+impl SubgraphClient {
+    pub fn new(http: Client, url: impl Into<String>, dex: Dex) -> Self {
+        Self {
+            http,
+            url: url.into(),
+            dex,
+            page_size: DEFAULT_PAGE_SIZE,
+        }
+    }
+
+    pub fn with_page_size(mut self, page_size: usize) -> Self {
+        self.page_size = page_size.clamp(1, DEFAULT_PAGE_SIZE);
+        self
+    }
+
+    pub fn dex(&self) -> Dex {
+        self.dex
+    }
+
+    async fn query<T: DeserializeOwned>(&self, query: &str, variables: Value) -> Result<T> {
+        let body = json!({ "query": query, "variables": variables });
+        let resp = self
+            .http
+            .post(&self.url)
+            .json(&body)
+            .send()
+            .await
+            .with_context(|| format!("POST {}", self.url))?;
+        let status = resp.status();
+        if !status.is_success() {
+            bail!("subgraph returned HTTP {status}");
+        }
+        let parsed: GraphQlResponse<T> = resp.json().await.context("decode GraphQL response")?;
+        if !parsed.errors.is_empty() {
+            let messages: Vec<_> = parsed.errors.into_iter().map(|e| e.message).collect();
+            bail!("GraphQL errors: {}", messages.join("; "));
+        }
+        parsed
+            .data
+            .ok_or_else(|| anyhow!("GraphQL response without data"))
+    }
+
+    /// Pages through every pool (and every initialized tick of each pool).
+    /// Pools that cannot be priced, e.g. uninitialized ones, are skipped.
+    pub async fn fetch_pools(&self) -> Result<Vec<TrackedPool>> {
+        let mut out = Vec::new();
+        let mut last_id = String::new();
+        loop {
+            let page: PoolsData = self
+                .query(
+                    POOLS_QUERY,
+                    json!({ "first": self.page_size, "lastId": last_id }),
+                )
+                .await?;
+            let count = page.pools.len();
+            for mut raw in page.pools {
+                last_id.clone_from(&raw.id);
+                if raw.ticks.len() >= self.page_size {
+                    self.fetch_remaining_ticks(&mut raw).await?;
+                }
+                let id = raw.id.clone();
+                match raw.into_tracked(self.dex) {
+                    Ok(pool) => out.push(pool),
+                    Err(e) => debug!(dex = %self.dex, pool = %id, error = %e, "skipping pool"),
+                }
+            }
+            if count < self.page_size {
+                break;
+            }
+        }
+        Ok(out)
+    }
+
+    async fn fetch_remaining_ticks(&self, raw: &mut RawPool) -> Result<()> {
+        loop {
+            let last_tick = match raw.ticks.last() {
+                Some(t) => t.tick_idx.clone(),
+                None => return Ok(()),
+            };
+            let page: TicksData = self
+                .query(
+                    TICKS_QUERY,
+                    json!({ "pool": raw.id, "first": self.page_size, "lastTick": last_tick }),
+                )
+                .await
+                .with_context(|| format!("ticks for pool {}", raw.id))?;
+            let count = page.ticks.len();
+            raw.ticks.extend(page.ticks);
+            if count < self.page_size {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Refreshes `store` from `client` every `every`, starting immediately.
+pub fn spawn_refresh(
+    client: SubgraphClient,
+    store: SharedPoolStore,
+    every: Duration,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(every);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            match client.fetch_pools().await {
+                Ok(pools) => {
+                    let count = pools.len();
+                    store.write().await.replace_dex(client.dex(), pools);
+                    info!(dex = %client.dex(), pools = count, "refreshed pool state");
+                }
+                Err(e) => warn!(dex = %client.dex(), error = %e, "pool refresh failed"),
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::PoolStore;
+    use crate::testing::MockJsonServer;
+
+    fn fixture(name: &str) -> Value {
+        let path = format!(
+            "{}/tests/fixtures/subgraph/{name}",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()
+    }
+
+    async fn mock_subgraph() -> MockJsonServer {
+        let page1 = fixture("pools_page1.json");
+        let page2 = fixture("pools_page2.json");
+        let ticks = fixture("ticks_3a36.json");
+        MockJsonServer::start(move |req| {
+            let query = req["query"].as_str().unwrap_or_default();
+            let vars = &req["variables"];
+            if query.contains("PoolTicks") {
+                if vars["pool"] == "0x3a36b04bcc1d5e2e303981ef643d2668e00b43e7" {
+                    ticks.clone()
+                } else {
+                    json!({ "data": { "ticks": [] } })
+                }
+            } else if vars["lastId"] == "" {
+                page1.clone()
+            } else {
+                page2.clone()
+            }
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn pages_through_pools_and_ticks() {
+        let server = mock_subgraph().await;
+        let client = SubgraphClient::new(Client::new(), &server.url, Dex::Prjx).with_page_size(2);
+        let mut pools = client.fetch_pools().await.unwrap();
+        pools.sort_by(|a, b| a.address.cmp(&b.address));
+
+        // The uninitialized pool on page two is dropped.
+        assert_eq!(pools.len(), 2);
+        let hype_usdc = &pools[0];
+        assert_eq!(hype_usdc.dex, Dex::Prjx);
+        assert_eq!(
+            hype_usdc.token1.address,
+            "0xb88339cb7199b77e23db6e890353e22632ba630f"
+        );
+        assert_eq!(hype_usdc.token1.decimals, 6);
+        assert_eq!(hype_usdc.state.as_v3().unwrap().fee, 3000);
+        assert_eq!(hype_usdc.state.as_v3().unwrap().tick_spacing, 60);
+        assert_eq!(hype_usdc.state.as_v3().unwrap().tick, -239434);
+        assert_eq!(
+            hype_usdc.state.as_v3().unwrap().liquidity,
+            5_000_000_000_000_000
+        );
+        assert_eq!(
+            hype_usdc
+                .state
+                .as_v3()
+                .unwrap()
+                .initialized_ticks()
+                .collect::<Vec<_>>(),
+            vec![
+                (-240000, 4_000_000_000_000_000),
+                (-239460, 1_000_000_000_000_000),
+                (-238800, -5_000_000_000_000_000),
+            ]
+        );
+        assert_eq!(hype_usdc.tvl_usd, Some(412345.67));
+        assert_eq!(pools[1].state.as_v3().unwrap().tick_spacing, 10);
+
+        let requests = server.requests();
+        let pool_pages: Vec<_> = requests
+            .iter()
+            .filter(|r| r["query"].as_str().unwrap().contains("query Pools"))
+            .map(|r| r["variables"]["lastId"].clone())
+            .collect();
+        assert_eq!(
+            pool_pages,
+            vec![
+                json!(""),
+                json!("0x8e1fb7bb1f2d3e3e4e4b8b5c1a2c2d4f1a2b3c4d")
+            ]
+        );
+        let tick_pages = requests
+            .iter()
+            .filter(|r| r["query"].as_str().unwrap().contains("PoolTicks"))
+            .count();
+        assert_eq!(tick_pages, 2);
+    }
+
+    #[tokio::test]
+    async fn surfaces_graphql_errors() {
+        let err = fixture("graphql_error.json");
+        let server = MockJsonServer::start(move |_| err.clone()).await;
+        let client = SubgraphClient::new(Client::new(), &server.url, Dex::Prjx);
+        let e = client.fetch_pools().await.unwrap_err();
+        assert!(e.to_string().contains("has no field `poolz`"), "{e}");
+    }
+
+    #[tokio::test]
+    async fn refresh_task_fills_store() {
+        let server = mock_subgraph().await;
+        let client =
+            SubgraphClient::new(Client::new(), &server.url, Dex::HyperSwap).with_page_size(2);
+        let store = PoolStore::shared();
+        let handle = spawn_refresh(client, store.clone(), Duration::from_secs(60));
+        for _ in 0..100 {
+            if !store.read().await.is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        handle.abort();
+        let store = store.read().await;
+        assert_eq!(store.len(), 2);
+        assert!(store.last_refresh(Dex::HyperSwap).is_some());
+        assert!(store.iter().all(|p| p.dex == Dex::HyperSwap));
+    }
+}
@@ -0,0 +1,591 @@
+//! Test helpers: tiny HTTP/1.1 and WebSocket JSON servers, a mock chain and
+//! in-memory pool fixtures.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::Utc;
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast;
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::rpc::abi::{self, Selector, Word};
+use crate::rpc::logs::Log;
+use crate::rpc::multicall::MULTICALL3;
+use crate::state::{Dex, Token, TrackedPool};
+use crate::status::StatusSink;
+use crate::tokens::{TokenOverride, TokenRegistry};
+use crate::univ3::tick_math::get_sqrt_ratio_at_tick;
+use crate::univ3::{tick_spacing_for_fee, PoolState};
+
+type Handler = dyn Fn(&Value) -> Value + Send + Sync;
+
+/// Ways a [`MockJsonServer`] can misbehave after recording a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    None,
+    /// Answers, but only after this long.
+    Delay(Duration),
+    /// Answers with this HTTP status and no body.
+    Status(u16),
+    /// Answers 200 with a body that is not JSON.
+    Garbage,
+    /// Closes the connection without answering.
+    Hangup,
+}
+
+/// Serves every request body through `handler` and records what it saw.
+pub struct MockJsonServer {
+    pub url: String,
+    requests: Arc<Mutex<Vec<Value>>>,
+    fault: Arc<Mutex<Fault>>,
+}
+
+impl MockJsonServer {
+    pub async fn start<F>(handler: F) -> Self
+    where
+        F: Fn(&Value) -> Value + Send + Sync + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let fault = Arc::new(Mutex::new(Fault::None));
+        let handler: Arc<Handler> = Arc::new(handler);
+        let (seen, faults) = (requests.clone(), fault.clone());
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let handler = handler.clone();
+                let seen = seen.clone();
+                let fault = *faults.lock().unwrap();
+                tokio::spawn(async move {
+                    let _ = serve(stream, handler, seen, fault).await;
+                });
+            }
+        });
+        Self {
+            url,
+            requests,
+            fault,
+        }
+    }
+
+    pub fn requests(&self) -> Vec<Value> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Applies to connections accepted from now on.
+    pub fn set_fault(&self, fault: Fault) {
+        *self.fault.lock().unwrap() = fault;
+    }
+}
+
+async fn serve(
+    mut stream: TcpStream,
+    handler: Arc<Handler>,
+    seen: Arc<Mutex<Vec<Value>>>,
+    fault: Fault,
+) -> std::io::Result<()> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 4096];
+    let header_end = loop {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Ok(());
+        }
+        buf.extend_from_slice(&chunk[..n]);
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+    };
+    let headers = String::from_utf8_lossy(&buf[..header_end]).to_ascii_lowercase();
+    let content_length = headers
+        .lines()
+        .find_map(|l| l.strip_prefix("content-length:"))
+        .and_then(|v| v.trim().parse::<usize>().ok())
+        .unwrap_or(0);
+    while buf.len() < header_end + content_length {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            break;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+
+    let request: Value = serde_json::from_slice(&buf[header_end..]).unwrap_or(Value::Null);
+    let response = handler(&request);
+    seen.lock().unwrap().push(request);
+
+    let (status, body) = match fault {
+        Fault::None => ("200 OK".to_string(), response.to_string()),
+        Fault::Delay(delay) => {
+            tokio::time::sleep(delay).await;
+            ("200 OK".to_string(), response.to_string())
+        }
+        Fault::Status(code) => (format!("{code} Oops"), String::new()),
+        Fault::Garbage => ("200 OK".to_string(), "<html>bad gateway</html>".to_string()),
+        Fault::Hangup => return Ok(()),
+    };
+    let head = format!(
+        "HTTP/1.1 {status}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n",
+        body.len()
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(body.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+/// WebSocket JSON-RPC server: answers requests through `handler` and pushes
+/// [`MockWsServer::push`]ed messages to every open connection.
+pub struct MockWsServer {
+    pub url: String,
+    requests: Arc<Mutex<Vec<Value>>>,
+    push: broadcast::Sender<Value>,
+}
+
+impl MockWsServer {
+    pub async fn start<F>(handler: F) -> Self
+    where
+        F: Fn(&Value) -> Value + Send + Sync + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let (push, _) = broadcast::channel(256);
+        let handler: Arc<Handler> = Arc::new(handler);
+        let (seen, pushes) = (requests.clone(), push.clone());
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let (handler, seen) = (handler.clone(), seen.clone());
+                let mut pushed = pushes.subscribe();
+                tokio::spawn(async move {
+                    let Ok(ws) = tokio_tungstenite::accept_async(stream).await else {
+                        return;
+                    };
+                    let (mut tx, mut rx) = ws.split();
+                    loop {
+                        let out = tokio::select! {
+                            msg = rx.next() => match msg {
+                                Some(Ok(Message::Text(text))) => {
+                                    let request: Value =
+                                        serde_json::from_str(&text).unwrap_or(Value::Null);
+                                    let response = handler(&request);
+                                    seen.lock().unwrap().push(request);
+                                    response
+                                }
+                                Some(Ok(_)) => continue,
+                                _ => return,
+                            },
+                            msg = pushed.recv() => match msg {
+                                Ok(msg) => msg,
+                                Err(_) => return,
+                            },
+                        };
+                        if tx.send(Message::Text(out.to_string())).await.is_err() {
+                            return;
+                        }
+                    }
+                });
+            }
+        });
+        Self {
+            url,
+            requests,
+            push,
+        }
+    }
+
+    pub fn push(&self, msg: Value) {
+        let _ = self.push.send(msg);
+    }
+
+    /// `eth_subscription` notification for `subscription`.
+    pub fn notify(&self, subscription: &str, result: Value) {
+        self.push(json!({
+            "jsonrpc": "2.0",
+            "method": "eth_subscription",
+            "params": { "subscription": subscription, "result": result }
+        }));
+    }
+
+    pub fn requests(&self) -> Vec<Value> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+/// JSON-RPC node answering `eth_blockNumber`, `eth_getBlockByNumber` for
+/// blocks added with [`MockChain::set_block`], `eth_getLogs` over logs added
+/// with [`MockChain::push_log`], and the pool and token getters of `pools`,
+/// directly or through Multicall3.
+#[derive(Clone)]
+pub struct MockChain {
+    pools: Arc<Mutex<HashMap<String, TrackedPool>>>,
+    /// Canonical `(hash, parentHash)` by block number.
+    blocks: Arc<Mutex<HashMap<u64, (String, String)>>>,
+    logs: Arc<Mutex<Vec<Log>>>,
+    head: Arc<AtomicU64>,
+}
+
+impl MockChain {
+    pub fn new(pools: Vec<TrackedPool>, head: u64) -> Self {
+        Self {
+            pools: Arc::new(Mutex::new(
+                pools.into_iter().map(|p| (p.address.clone(), p)).collect(),
+            )),
+            blocks: Arc::default(),
+            logs: Arc::default(),
+            head: Arc::new(AtomicU64::new(head)),
+        }
+    }
+
+    pub fn set_head(&self, head: u64) {
+        self.head.store(head, Ordering::Relaxed);
+    }
+
+    pub fn add_pool(&self, pool: TrackedPool) {
+        self.pools
+            .lock()
+            .unwrap()
+            .insert(pool.address.clone(), pool);
+    }
+
+    pub fn push_log(&self, log: Log) {
+        self.logs.lock().unwrap().push(log);
+    }
+
+    pub fn set_block(&self, number: u64, hash: &str, parent_hash: &str) {
+        self.blocks
+            .lock()
+            .unwrap()
+            .insert(number, (hash.to_string(), parent_hash.to_string()));
+    }
+
+    pub async fn serve(&self) -> MockJsonServer {
+        let chain = self.clone();
+        MockJsonServer::start(move |req| chain.respond(req)).await
+    }
+
+    fn respond(&self, req: &Value) -> Value {
+        let result = match req["method"].as_str().unwrap() {
+            "eth_blockNumber" => json!(crate::rpc::quantity(self.head.load(Ordering::Relaxed))),
+            "eth_getLogs" => {
+                let filter = &req["params"][0];
+                let block =
+                    |key: &str| crate::rpc::parse_quantity(filter[key].as_str().unwrap()).unwrap();
+                let (from, to) = (block("fromBlock"), block("toBlock"));
+                let logs: Vec<Value> = self
+                    .logs
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .filter(|l| {
+                        l.address == filter["address"]
+                            && l.topics.first().map(String::as_str) == filter["topics"][0].as_str()
+                            && (from..=to).contains(&l.block_number)
+                    })
+                    .map(|l| {
+                        json!({
+                            "address": l.address,
+                            "topics": l.topics,
+                            "data": l.data,
+                            "blockNumber": crate::rpc::quantity(l.block_number),
+                            "blockHash": l.block_hash,
+                            "logIndex": crate::rpc::quantity(l.log_index),
+                            "removed": l.removed,
+                        })
+                    })
+                    .collect();
+                json!(logs)
+            }
+            "eth_getBlockByNumber" => {
+                let number =
+                    crate::rpc::parse_quantity(req["params"][0].as_str().unwrap()).unwrap();
+                match self.blocks.lock().unwrap().get(&number) {
+                    Some((hash, parent)) => json!({
+                        "number": req["params"][0], "hash": hash, "parentHash": parent
+                    }),
+                    None => Value::Null,
+                }
+            }
+            "eth_call" => {
+                let to = req["params"][0]["to"].as_str().unwrap();
+                let data = abi::from_hex(req["params"][0]["data"].as_str().unwrap()).unwrap();
+                let ret = if to == MULTICALL3 && data[..4] == abi::AGGREGATE3 {
+                    let returns: Vec<_> = aggregate3_calls(&data[4..])
+                        .iter()
+                        .map(|(target, call)| self.dispatch(target, call))
+                        .collect();
+                    Some(encode_aggregate3_results(&returns))
+                } else {
+                    self.dispatch(to, &data)
+                };
+                let Some(ret) = ret else {
+                    return json!({
+                        "jsonrpc": "2.0", "id": req["id"],
+                        "error": { "code": 3, "message": "execution reverted" }
+                    });
+                };
+                json!(abi::to_hex(&ret))
+            }
+            other => panic!("unexpected method {other}"),
+        };
+        json!({ "jsonrpc": "2.0", "id": req["id"], "result": result })
+    }
+
+    fn dispatch(&self, to: &str, data: &[u8]) -> Option<Vec<u8>> {
+        let selector: Selector = data.get(..4)?.try_into().unwrap();
+        let arg = data
+            .get(4..36)
+            .map(|w| abi::as_i32(w.try_into().unwrap()).unwrap());
+        self.call(to, selector, arg)
+    }
+
+    fn call(&self, to: &str, selector: Selector, arg: Option<i32>) -> Option<Vec<u8>> {
+        let pools = self.pools.lock().unwrap();
+        if let Some(pool) = pools.get(to) {
+            let s = pool.state.as_v3()?;
+            let word = match selector {
+                abi::FEE => uint_word(s.fee.into()),
+                abi::TICK_SPACING => abi::int_word(s.tick_spacing.into()),
+                abi::LIQUIDITY => uint_word(s.liquidity),
+                abi::TOKEN0 => abi::address_word(&pool.token0.address).unwrap(),
+                abi::TOKEN1 => abi::address_word(&pool.token1.address).unwrap(),
+                abi::TICK_BITMAP => s.bitmap().word(arg? as i16).to_be_bytes(),
+                abi::SLOT0 => {
+                    let mut out =
+                        [s.sqrt_price_x96.to_be_bytes(), abi::int_word(s.tick.into())].concat();
+                    // observation fields, feeProtocol, unlocked
+                    for w in [0, 1, 1, 0, 1] {
+                        out.extend_from_slice(&uint_word(w));
+                    }
+                    return Some(out);
+                }
+                abi::TICKS => {
+                    let net = s.liquidity_net(arg?);
+                    let mut out = [uint_word(net.unsigned_abs()), int128_word(net)].concat();
+                    out.extend((0..6).flat_map(|_| uint_word(0)));
+                    return Some(out);
+                }
+                _ => return None,
+            };
+            return Some(word.to_vec());
+        }
+        let token = pools
+            .values()
+            .flat_map(|p| [&p.token0, &p.token1])
+            .find(|t| t.address == to)?;
+        match selector {
+            abi::DECIMALS => Some(uint_word(token.decimals.into()).to_vec()),
+            abi::SYMBOL => Some(string_return(&token.symbol)),
+            abi::NAME => Some(string_return(&token.name)),
+            _ => None,
+        }
+    }
+}
+
+pub use crate::rpc::abi::uint_word;
+
+/// `(target, callData)` of each call in `aggregate3` arguments.
+fn aggregate3_calls(args: &[u8]) -> Vec<(String, Vec<u8>)> {
+    let at = |i: usize| abi::as_u128(args[i..i + 32].try_into().unwrap()).unwrap() as usize;
+    let array = at(0);
+    (0..at(array))
+        .map(|i| {
+            let tuple = array + 32 + at(array + 32 + 32 * i);
+            let target = abi::as_address(args[tuple..tuple + 32].try_into().unwrap()).unwrap();
+            let bytes = tuple + at(tuple + 64);
+            (target, args[bytes + 32..bytes + 32 + at(bytes)].to_vec())
+        })
+        .collect()
+}
+
+/// `aggregate3` return data; `None` for a reverted call.
+pub fn encode_aggregate3_results(returns: &[Option<Vec<u8>>]) -> Vec<u8> {
+    let mut out = [uint_word(32), uint_word(returns.len() as u128)].concat();
+    let mut tails = Vec::new();
+    for ret in returns {
+        out.extend_from_slice(&uint_word((32 * returns.len() + tails.len()) as u128));
+        let data = ret.as_deref().unwrap_or_default();
+        tails.extend_from_slice(&uint_word(ret.is_some().into()));
+        tails.extend_from_slice(&uint_word(0x40));
+        tails.extend_from_slice(&uint_word(data.len() as u128));
+        tails.extend_from_slice(data);
+        tails.resize(tails.len().next_multiple_of(32), 0);
+    }
+    out.extend(tails);
+    out
+}
+
+pub fn int128_word(v: i128) -> Word {
+    let mut w = if v < 0 { [0xff; 32] } else { [0; 32] };
+    w[16..].copy_from_slice(&v.to_be_bytes());
+    w
+}
+
+fn string_return(s: &str) -> Vec<u8> {
+    let mut out = [uint_word(32), uint_word(s.len() as u128)].concat();
+    let mut tail = [0u8; 32];
+    tail[..s.len()].copy_from_slice(s.as_bytes());
+    out.extend_from_slice(&tail);
+    out
+}
+
+/// In-process stand-in for Redis pub/sub: keeps every published message.
+#[derive(Clone, Default)]
+pub struct MemorySink {
+    messages: Arc<Mutex<Vec<(String, String)>>>,
+}
+
+impl MemorySink {
+    pub fn messages(&self) -> Vec<(String, String)> {
+        self.messages.lock().unwrap().clone()
+    }
+}
+
+impl StatusSink for MemorySink {
+    async fn publish(&mut self, channel: &str, payload: String) -> anyhow::Result<()> {
+        self.messages
+            .lock()
+            .unwrap()
+            .push((channel.to_string(), payload));
+        Ok(())
+    }
+}
+
+/// Token with a stable, recognizable test address (real ones for WHYPE and USDC).
+pub fn token(symbol: &str) -> Token {
+    let address = match symbol {
+        "WHYPE" => crate::detector::WHYPE.to_string(),
+        "USDC" => "0xb88339cb7199b77e23db6e890353e22632ba630f".to_string(),
+        other => {
+            let hex: String = other.bytes().map(|b| format!("{b:02x}")).collect();
+            format!("0x{hex:0>40}")
+        }
+    };
+    Token {
+        address,
+        symbol: symbol.to_string(),
+        name: format!("Test {symbol}"),
+        decimals: if symbol.starts_with("USD") { 6 } else { 18 },
+        stable: symbol.starts_with("USD"),
+        ..Token::default()
+    }
+}
+
+/// A registry flagging the [`token`] dollars stable, as the tokens file
+/// does for the real ones.
+pub fn stable_tokens() -> TokenRegistry {
+    TokenRegistry::from_overrides(
+        ["USDC", "USDT0"]
+            .map(|s| TokenOverride {
+                address: token(s).address,
+                stable: true,
+                ..TokenOverride::default()
+            })
+            .to_vec(),
+    )
+}
+
+/// Tick at which token0 is worth `price` units of token1.
+pub fn tick_for_price(price: f64, token0: &Token, token1: &Token) -> i32 {
+    let raw = price * 10f64.powi(token1.decimals as i32 - token0.decimals as i32);
+    (raw.ln() / 1.0001f64.ln()).floor() as i32
+}
+
+/// V3 pool at `tick` with `liquidity` spread over +-1000 tick spacings.
+pub fn v3_pool(
+    address: &str,
+    dex: Dex,
+    token0: Token,
+    token1: Token,
+    fee: u32,
+    tick: i32,
+    liquidity: u128,
+) -> TrackedPool {
+    let spacing = tick_spacing_for_fee(fee).unwrap();
+    let sqrt_price = get_sqrt_ratio_at_tick(tick).unwrap();
+    let mut state = PoolState::new(fee, spacing, sqrt_price, tick, liquidity).unwrap();
+    let base = tick.div_euclid(spacing) * spacing;
+    state
+        .set_liquidity_net(base - 1000 * spacing, liquidity as i128)
+        .unwrap();
+    state
+        .set_liquidity_net(base + 1000 * spacing, -(liquidity as i128))
+        .unwrap();
+    TrackedPool {
+        address: address.to_string(),
+        dex,
+        token0,
+        token1,
+        state: state.into(),
+        tvl_usd: None,
+        block: None,
+        updated_at: Utc::now(),
+    }
+}
+
+/// 0.05% V3 pool where `pair.0` is worth `price` of `pair.1`.
+pub fn priced_pool(
+    address: &str,
+    dex: Dex,
+    pair: (&str, &str),
+    price: f64,
+    liquidity: u128,
+) -> TrackedPool {
+    let (t0, t1) = (token(pair.0), token(pair.1));
+    let tick = tick_for_price(price, &t0, &t1);
+    v3_pool(address, dex, t0, t1, 500, tick, liquidity)
+}
+
+/// WHYPE at $40 against USDC, KHYPE at par with WHYPE, and KHYPE at
+/// `khype_usdc` against USDC.
+pub fn triangle(khype_usdc: f64) -> Vec<TrackedPool> {
+    vec![
+        priced_pool("0xa", Dex::Prjx, ("WHYPE", "USDC"), 40.0, 10u128.pow(17)),
+        priced_pool(
+            "0xb",
+            Dex::HyperSwap,
+            ("KHYPE", "WHYPE"),
+            1.0,
+            10u128.pow(22),
+        ),
+        priced_pool(
+            "0xc",
+            Dex::Prjx,
+            ("KHYPE", "USDC"),
+            khype_usdc,
+            10u128.pow(17),
+        ),
+    ]
+}
+
+/// Directory under the system temp dir, removed on drop.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(prefix: &str) -> Self {
+        static N: AtomicU32 = AtomicU32::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "arb-engine-{prefix}-{}-{}",
+            std::process::id(),
+            N.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
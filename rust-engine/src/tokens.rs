@@ -0,0 +1,227 @@
+//! Token metadata keyed by address.
+//!
+//! Decimals, symbol and name come from the pool sources or the ERC-20
+//! getters over RPC. `config/tokens.json` overrides any of them and flags
+//! the fee-on-transfer and rebasing tokens no getter reveals, and the
+//! stablecoins pricing anchors on at $1; no other source can mark a token
+//! stable. The pool store
+//! resolves the tokens of every pool it takes through its registry, so an
+//! address scales by the same decimals and carries the same flags
+//! everywhere in the engine.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use crate::state::Token;
+
+pub const TOKENS_FILE: &str = "tokens.json";
+
+/// One entry of the tokens file. Unset fields are left to the sources.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct TokenOverride {
+    pub address: String,
+    pub symbol: Option<String>,
+    pub name: Option<String>,
+    pub decimals: Option<u8>,
+    pub fee_on_transfer: bool,
+    pub rebasing: bool,
+    pub stable: bool,
+}
+
+impl TokenOverride {
+    fn apply(&self, token: &mut Token) {
+        if let Some(symbol) = &self.symbol {
+            token.symbol.clone_from(symbol);
+        }
+        if let Some(name) = &self.name {
+            token.name.clone_from(name);
+        }
+        if let Some(decimals) = self.decimals {
+            token.decimals = decimals;
+        }
+        token.fee_on_transfer |= self.fee_on_transfer;
+        token.rebasing |= self.rebasing;
+        token.stable |= self.stable;
+    }
+
+    /// The whole token, when the entry names its symbol and decimals.
+    fn to_token(&self) -> Option<Token> {
+        let mut token = Token {
+            address: self.address.clone(),
+            symbol: self.symbol.clone()?,
+            decimals: self.decimals?,
+            ..Token::default()
+        };
+        self.apply(&mut token);
+        Some(token)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TokenRegistry {
+    tokens: HashMap<String, Token>,
+    overrides: HashMap<String, TokenOverride>,
+}
+
+impl TokenRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_overrides(overrides: Vec<TokenOverride>) -> Self {
+        let mut registry = Self::new();
+        for mut o in overrides {
+            o.address = o.address.to_lowercase();
+            if let Some(token) = o.to_token() {
+                registry.tokens.insert(token.address.clone(), token);
+            }
+            registry.overrides.insert(o.address.clone(), o);
+        }
+        registry
+    }
+
+    /// The tokens file at `path`, or an empty registry if there is none.
+    pub fn load(path: &Path) -> Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => Ok(Self::from_overrides(
+                serde_json::from_str(&text)
+                    .with_context(|| format!("parsing {}", path.display()))?,
+            )),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::new()),
+            Err(e) => Err(e).with_context(|| format!("reading {}", path.display())),
+        }
+    }
+
+    pub fn get(&self, address: &str) -> Option<&Token> {
+        self.tokens.get(address)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Token> {
+        self.tokens.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    /// Records `token` as a source reported it, the file's overrides on top.
+    /// A token already known keeps its metadata, gaining only a name.
+    pub fn insert(&mut self, mut token: Token) -> &Token {
+        token.address.make_ascii_lowercase();
+        token.stable = false;
+        if let Some(o) = self.overrides.get(&token.address) {
+            o.apply(&mut token);
+        }
+        let known = self
+            .tokens
+            .entry(token.address.clone())
+            .or_insert(token.clone());
+        if known.name.is_empty() {
+            known.name = token.name;
+        }
+        known
+    }
+
+    /// Replaces `token` with the registry's copy, recording it first if new.
+    pub fn resolve(&mut self, token: &mut Token) {
+        *token = self.insert(std::mem::take(token)).clone();
+    }
+
+    /// Lowercased, sorted and deduplicated addresses of `addresses` not
+    /// known yet.
+    pub fn missing<'a>(&self, addresses: impl IntoIterator<Item = &'a String>) -> Vec<String> {
+        let mut missing: Vec<String> = addresses
+            .into_iter()
+            .map(|a| a.to_lowercase())
+            .filter(|a| !self.tokens.contains_key(a))
+            .collect();
+        missing.sort();
+        missing.dedup();
+        missing
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{token, TempDir};
+
+    #[test]
+    fn file_overrides_sources() {
+        let dir = TempDir::new("tokens");
+        let path = dir.path().join(TOKENS_FILE);
+        assert!(TokenRegistry::load(&path).unwrap().is_empty());
+        std::fs::write(
+            &path,
+            r#"[
+                { "address": "0xB88339CB7199b77E23DB6E890353E22632Ba630f", "symbol": "USDC", "name": "USD Coin", "decimals": 6, "stable": true },
+                { "address": "0x0000000000000000000000000000000000000f07", "fee_on_transfer": true },
+                { "address": "0x00000000000000000000000000000000000000b5", "rebasing": true, "symbol": "stHYPE" }
+            ]"#,
+        )
+        .unwrap();
+        let mut registry = TokenRegistry::load(&path).unwrap();
+        // Only the complete entry is a token before any source reports one.
+        assert_eq!(registry.len(), 1);
+        let usdc = registry.get(&token("USDC").address).unwrap();
+        assert_eq!((usdc.name.as_str(), usdc.decimals), ("USD Coin", 6));
+        assert!(usdc.stable);
+
+        let mut fot = Token {
+            address: "0x0000000000000000000000000000000000000F07".into(),
+            symbol: "TAX".into(),
+            decimals: 9,
+            ..Token::default()
+        };
+        registry.resolve(&mut fot);
+        assert_eq!(fot.address, "0x0000000000000000000000000000000000000f07");
+        assert!(fot.fee_on_transfer && !fot.is_standard());
+
+        let mut rebasing = Token {
+            address: "0x00000000000000000000000000000000000000b5".into(),
+            symbol: "wrong".into(),
+            decimals: 18,
+            ..Token::default()
+        };
+        registry.resolve(&mut rebasing);
+        assert_eq!(rebasing.symbol, "stHYPE");
+        assert!(rebasing.rebasing);
+
+        // The first report stands; a later one only fills in the name.
+        let mut again = Token {
+            name: "Taxed".into(),
+            decimals: 18,
+            ..fot.clone()
+        };
+        registry.resolve(&mut again);
+        assert_eq!((again.decimals, again.name.as_str()), (9, "Taxed"));
+        assert!(again.fee_on_transfer);
+
+        // A source cannot vouch for a stablecoin, whatever it calls it.
+        let mut look_alike = Token {
+            address: "0x00000000000000000000000000000000000000cc".into(),
+            stable: true,
+            ..token("USDC")
+        };
+        registry.resolve(&mut look_alike);
+        assert!(!look_alike.stable);
+
+        let tax = "0x0000000000000000000000000000000000000F07".to_string();
+        let other = "0x00000000000000000000000000000000000000EE".to_string();
+        assert_eq!(
+            registry.missing([&other, &tax, &other.to_lowercase()]),
+            vec![other.to_lowercase()]
+        );
+
+        std::fs::write(&path, r#"[{ "address": "0x01", "decimals": "six" }]"#).unwrap();
+        assert!(TokenRegistry::load(&path).is_err());
+    }
+}
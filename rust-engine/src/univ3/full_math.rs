@@ -0,0 +1,96 @@
+//! 512-bit intermediate multiply/divide (`FullMath` / `UnsafeMath`).
+
+use super::u256::{div_rem_wide, U256};
+use super::{Error, Result};
+
+fn mul_div_rem(a: U256, b: U256, denominator: U256) -> Result<(U256, bool)> {
+    if denominator.is_zero() {
+        return Err(Error::DivisionByZero);
+    }
+    let (q, r) = div_rem_wide(a.widening_mul(b), denominator);
+    if q[4..].iter().any(|&l| l != 0) {
+        return Err(Error::Overflow);
+    }
+    Ok((U256::from_limbs([q[0], q[1], q[2], q[3]]), !r.is_zero()))
+}
+
+/// `floor(a * b / denominator)` with a full-precision intermediate.
+pub fn mul_div(a: U256, b: U256, denominator: U256) -> Result<U256> {
+    mul_div_rem(a, b, denominator).map(|(q, _)| q)
+}
+
+/// `ceil(a * b / denominator)` with a full-precision intermediate.
+pub fn mul_div_rounding_up(a: U256, b: U256, denominator: U256) -> Result<U256> {
+    let (q, inexact) = mul_div_rem(a, b, denominator)?;
+    if inexact {
+        q.checked_add(U256::ONE).ok_or(Error::Overflow)
+    } else {
+        Ok(q)
+    }
+}
+
+/// `ceil(x / y)`.
+pub fn div_rounding_up(x: U256, y: U256) -> Result<U256> {
+    if y.is_zero() {
+        return Err(Error::DivisionByZero);
+    }
+    let (q, r) = x.div_rem(y);
+    Ok(if r.is_zero() { q } else { q + U256::ONE })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn u(s: &str) -> U256 {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn mul_div_handles_phantom_overflow() {
+        // Cases from the v3-core FullMath spec.
+        let q128 = U256::ONE << 128;
+        assert_eq!(
+            mul_div(q128, q128 / U256::from(2u64), q128).unwrap(),
+            q128 / U256::from(2u64)
+        );
+        assert_eq!(
+            mul_div(
+                q128,
+                U256::from(35u64) * q128 / U256::from(100u64),
+                U256::from(8u64) * q128 / U256::from(1000u64)
+            )
+            .unwrap(),
+            u("14887353552791057776522639075139859254668")
+        );
+        assert_eq!(
+            mul_div(q128, U256::from(1000u64) * q128, U256::from(3000u64) * q128).unwrap(),
+            u("113427455640312821154458202477256070485")
+        );
+        assert_eq!(mul_div(U256::MAX, U256::MAX, U256::MAX).unwrap(), U256::MAX);
+        assert_eq!(mul_div(q128, q128, U256::ONE), Err(Error::Overflow));
+        assert_eq!(mul_div(q128, q128, U256::ZERO), Err(Error::DivisionByZero));
+    }
+
+    #[test]
+    fn rounding_up_only_when_inexact() {
+        let q128 = U256::ONE << 128;
+        assert_eq!(
+            mul_div_rounding_up(q128, U256::from(1000u64) * q128, U256::from(3000u64) * q128)
+                .unwrap(),
+            u("113427455640312821154458202477256070486")
+        );
+        assert_eq!(
+            mul_div_rounding_up(U256::from(6u64), U256::from(4u64), U256::from(8u64)).unwrap(),
+            U256::from(3u64)
+        );
+        assert_eq!(
+            mul_div_rounding_up(U256::MAX, U256::MAX, U256::MAX - U256::ONE),
+            Err(Error::Overflow)
+        );
+        assert_eq!(
+            div_rounding_up(U256::from(7u64), U256::from(2u64)).unwrap(),
+            U256::from(4u64)
+        );
+    }
+}
@@ -0,0 +1,13 @@
+//! `LiquidityMath.addDelta`.
+
+use super::{Error, Result};
+
+/// Applies a signed liquidity delta, failing on underflow or overflow.
+pub fn add_delta(x: u128, y: i128) -> Result<u128> {
+    if y < 0 {
+        x.checked_sub(y.unsigned_abs())
+            .ok_or(Error::LiquidityUnderflow)
+    } else {
+        x.checked_add(y as u128).ok_or(Error::Overflow)
+    }
+}
@@ -0,0 +1,73 @@
+//! Uniswap V3 concentrated-liquidity math.
+//!
+//! Straight ports of the v3-core libraries (`FullMath`, `TickMath`,
+//! `SqrtPriceMath`, `SwapMath`, `TickBitmap`) on a fixed-width [`U256`], so
+//! quotes match what the pool contract would return to the wei. [`PoolState`]
+//! runs the `UniswapV3Pool.swap` loop over an in-memory tick bitmap.
+
+pub mod full_math;
+pub mod liquidity_math;
+pub mod pool;
+pub mod sqrt_price_math;
+pub mod swap_math;
+pub mod tick_bitmap;
+pub mod tick_math;
+pub mod u256;
+
+use std::fmt;
+
+pub use pool::{PoolState, SwapResult};
+pub use u256::U256;
+
+/// 2^96, the fixed-point scale of `sqrtPriceX96`.
+pub const Q96: U256 = U256::from_limbs([0, 1 << 32, 0, 0]);
+
+/// Fee denominator: pool fees are expressed in hundredths of a bip.
+pub const FEE_PIPS_DENOMINATOR: u32 = 1_000_000;
+
+/// Errors mirror the `require`s and reverts of the Solidity libraries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    DivisionByZero,
+    Overflow,
+    TickOutOfRange(i32),
+    SqrtPriceOutOfRange,
+    ZeroLiquidity,
+    LiquidityUnderflow,
+    InvalidPriceLimit,
+    InvalidTickSpacing(i32),
+    TickNotSpaced(i32),
+    InvalidFee(u32),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::DivisionByZero => write!(f, "division by zero"),
+            Error::Overflow => write!(f, "arithmetic overflow"),
+            Error::TickOutOfRange(t) => write!(f, "tick {t} out of range"),
+            Error::SqrtPriceOutOfRange => write!(f, "sqrt price out of range"),
+            Error::ZeroLiquidity => write!(f, "liquidity must be non-zero"),
+            Error::LiquidityUnderflow => write!(f, "liquidity underflow"),
+            Error::InvalidPriceLimit => write!(f, "invalid price limit"),
+            Error::InvalidTickSpacing(s) => write!(f, "invalid tick spacing {s}"),
+            Error::TickNotSpaced(t) => write!(f, "tick {t} is not a multiple of the tick spacing"),
+            Error::InvalidFee(fee) => write!(f, "invalid fee {fee}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Tick spacing for the canonical V3 fee tiers (fee in pips).
+pub fn tick_spacing_for_fee(fee: u32) -> Option<i32> {
+    match fee {
+        100 => Some(1),
+        500 => Some(10),
+        3000 => Some(60),
+        10000 => Some(200),
+        _ => None,
+    }
+}
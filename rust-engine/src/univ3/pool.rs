@@ -0,0 +1,458 @@
+//! In-memory V3 pool state and the `UniswapV3Pool.swap` loop, for quoting.
+
+use std::collections::BTreeMap;
+
+use super::liquidity_math::add_delta;
+use super::swap_math::compute_swap_step;
+use super::tick_bitmap::TickBitmap;
+use super::tick_math::{
+    get_sqrt_ratio_at_tick, get_tick_at_sqrt_ratio, MAX_SQRT_RATIO, MAX_TICK, MIN_SQRT_RATIO,
+    MIN_TICK,
+};
+use super::u256::U256;
+use super::{Error, Result, FEE_PIPS_DENOMINATOR};
+
+/// Snapshot of the parts of a V3 pool that determine swap output.
+#[derive(Debug, Clone)]
+pub struct PoolState {
+    pub sqrt_price_x96: U256,
+    pub tick: i32,
+    pub liquidity: u128,
+    /// Swap fee in hundredths of a bip (`3000` = 0.3%).
+    pub fee: u32,
+    pub tick_spacing: i32,
+    ticks: BTreeMap<i32, i128>,
+    bitmap: TickBitmap,
+}
+
+/// Result of simulating a swap against a [`PoolState`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SwapResult {
+    /// Input taken by the pool, fees included.
+    pub amount_in: U256,
+    pub amount_out: U256,
+    pub fee_amount: U256,
+    /// Unfilled part of the specified amount when the price limit was reached.
+    pub amount_remaining: U256,
+    pub sqrt_price_x96: U256,
+    pub tick: i32,
+    pub liquidity: u128,
+    pub ticks_crossed: u32,
+}
+
+impl SwapResult {
+    pub fn is_partial(&self) -> bool {
+        !self.amount_remaining.is_zero()
+    }
+}
+
+impl PoolState {
+    pub fn new(
+        fee: u32,
+        tick_spacing: i32,
+        sqrt_price_x96: U256,
+        tick: i32,
+        liquidity: u128,
+    ) -> Result<Self> {
+        if fee >= FEE_PIPS_DENOMINATOR {
+            return Err(Error::InvalidFee(fee));
+        }
+        if tick_spacing <= 0 || tick_spacing > 16384 {
+            return Err(Error::InvalidTickSpacing(tick_spacing));
+        }
+        if sqrt_price_x96 < MIN_SQRT_RATIO || sqrt_price_x96 >= MAX_SQRT_RATIO {
+            return Err(Error::SqrtPriceOutOfRange);
+        }
+        if !(MIN_TICK..=MAX_TICK).contains(&tick) {
+            return Err(Error::TickOutOfRange(tick));
+        }
+        Ok(Self {
+            sqrt_price_x96,
+            tick,
+            liquidity,
+            fee,
+            tick_spacing,
+            ticks: BTreeMap::new(),
+            bitmap: TickBitmap::new(),
+        })
+    }
+
+    /// Sets the net liquidity at an initialized tick; zero uninitializes it.
+    pub fn set_liquidity_net(&mut self, tick: i32, liquidity_net: i128) -> Result<()> {
+        if !(MIN_TICK..=MAX_TICK).contains(&tick) {
+            return Err(Error::TickOutOfRange(tick));
+        }
+        if tick % self.tick_spacing != 0 {
+            return Err(Error::TickNotSpaced(tick));
+        }
+        let was_initialized = self.ticks.contains_key(&tick);
+        if liquidity_net == 0 {
+            if was_initialized {
+                self.ticks.remove(&tick);
+                self.bitmap.flip_tick(tick, self.tick_spacing);
+            }
+        } else {
+            self.ticks.insert(tick, liquidity_net);
+            if !was_initialized {
+                self.bitmap.flip_tick(tick, self.tick_spacing);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn liquidity_net(&self, tick: i32) -> i128 {
+        self.ticks.get(&tick).copied().unwrap_or(0)
+    }
+
+    /// Initialized ticks in ascending order with their net liquidity.
+    pub fn initialized_ticks(&self) -> impl Iterator<Item = (i32, i128)> + '_ {
+        self.ticks.iter().map(|(&t, &n)| (t, n))
+    }
+
+    pub fn bitmap(&self) -> &TickBitmap {
+        &self.bitmap
+    }
+
+    /// Simulates `exactInput` of `amount_in`, stopping at `sqrt_price_limit_x96`
+    /// (defaults to the edge of the price range).
+    pub fn quote_exact_input(
+        &self,
+        zero_for_one: bool,
+        amount_in: U256,
+        sqrt_price_limit_x96: Option<U256>,
+    ) -> Result<SwapResult> {
+        self.swap(zero_for_one, amount_in, true, sqrt_price_limit_x96)
+    }
+
+    /// Simulates `exactOutput` of `amount_out`, stopping at `sqrt_price_limit_x96`.
+    pub fn quote_exact_output(
+        &self,
+        zero_for_one: bool,
+        amount_out: U256,
+        sqrt_price_limit_x96: Option<U256>,
+    ) -> Result<SwapResult> {
+        self.swap(zero_for_one, amount_out, false, sqrt_price_limit_x96)
+    }
+
+    fn swap(
+        &self,
+        zero_for_one: bool,
+        amount_specified: U256,
+        exact_in: bool,
+        sqrt_price_limit_x96: Option<U256>,
+    ) -> Result<SwapResult> {
+        let limit = sqrt_price_limit_x96.unwrap_or(if zero_for_one {
+            MIN_SQRT_RATIO + U256::ONE
+        } else {
+            MAX_SQRT_RATIO - U256::ONE
+        });
+        let limit_ok = if zero_for_one {
+            limit < self.sqrt_price_x96 && limit > MIN_SQRT_RATIO
+        } else {
+            limit > self.sqrt_price_x96 && limit < MAX_SQRT_RATIO
+        };
+        if !limit_ok {
+            return Err(Error::InvalidPriceLimit);
+        }
+
+        let mut remaining = amount_specified;
+        let mut calculated = U256::ZERO;
+        let mut fee_total = U256::ZERO;
+        let mut sqrt_price = self.sqrt_price_x96;
+        let mut tick = self.tick;
+        let mut liquidity = self.liquidity;
+        let mut ticks_crossed = 0;
+
+        while !remaining.is_zero() && sqrt_price != limit {
+            let sqrt_price_start = sqrt_price;
+            let (tick_next, initialized) = self.next_tick(tick, zero_for_one, liquidity);
+            let tick_next = tick_next.clamp(MIN_TICK, MAX_TICK);
+            let sqrt_price_next = get_sqrt_ratio_at_tick(tick_next)?;
+
+            let target = if (zero_for_one && sqrt_price_next < limit)
+                || (!zero_for_one && sqrt_price_next > limit)
+            {
+                limit
+            } else {
+                sqrt_price_next
+            };
+            let step =
+                compute_swap_step(sqrt_price, target, liquidity, remaining, exact_in, self.fee)?;
+            sqrt_price = step.sqrt_ratio_next_x96;
+
+            let gross_in = step
+                .amount_in
+                .checked_add(step.fee_amount)
+                .ok_or(Error::Overflow)?;
+            if exact_in {
+                remaining = remaining.checked_sub(gross_in).ok_or(Error::Overflow)?;
+                calculated = calculated
+                    .checked_add(step.amount_out)
+                    .ok_or(Error::Overflow)?;
+            } else {
+                remaining = remaining
+                    .checked_sub(step.amount_out)
+                    .ok_or(Error::Overflow)?;
+                calculated = calculated.checked_add(gross_in).ok_or(Error::Overflow)?;
+            }
+            fee_total = fee_total
+                .checked_add(step.fee_amount)
+                .ok_or(Error::Overflow)?;
+
+            if sqrt_price == sqrt_price_next {
+                if initialized {
+                    let net = self.liquidity_net(tick_next);
+                    let net = if zero_for_one {
+                        net.checked_neg().ok_or(Error::Overflow)?
+                    } else {
+                        net
+                    };
+                    liquidity = add_delta(liquidity, net)?;
+                    ticks_crossed += 1;
+                }
+                tick = if zero_for_one {
+                    tick_next - 1
+                } else {
+                    tick_next
+                };
+            } else if sqrt_price != sqrt_price_start {
+                tick = get_tick_at_sqrt_ratio(sqrt_price)?;
+            }
+        }
+
+        let filled = amount_specified - remaining;
+        let (amount_in, amount_out) = if exact_in {
+            (filled, calculated)
+        } else {
+            (calculated, filled)
+        };
+        Ok(SwapResult {
+            amount_in,
+            amount_out,
+            fee_amount: fee_total,
+            amount_remaining: remaining,
+            sqrt_price_x96: sqrt_price,
+            tick,
+            liquidity,
+            ticks_crossed,
+        })
+    }
+
+    /// Next tick boundary for a swap step.
+    ///
+    /// With liquidity in range this is the bitmap's one-word search, which is
+    /// what keeps step boundaries (and therefore rounding) identical to the
+    /// contract. With zero liquidity every step is a no-op, so the walk jumps
+    /// straight to the next initialized tick instead of scanning empty words.
+    fn next_tick(&self, tick: i32, zero_for_one: bool, liquidity: u128) -> (i32, bool) {
+        if liquidity != 0 {
+            return self.bitmap.next_initialized_tick_within_one_word(
+                tick,
+                self.tick_spacing,
+                zero_for_one,
+            );
+        }
+        let next = if zero_for_one {
+            self.ticks.range(..=tick).next_back()
+        } else {
+            self.ticks.range(tick + 1..).next()
+        };
+        match next {
+            Some((&t, _)) => (t, true),
+            None if zero_for_one => (MIN_TICK, false),
+            None => (MAX_TICK, false),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::univ3::Q96;
+
+    fn u(s: &str) -> U256 {
+        s.parse().unwrap()
+    }
+
+    /// 0.3% pool at price 1 with three positions: [-600, 600] x 1e21,
+    /// [-120, 120] x 5e20 and [60, 1200] x 3e20 (the last one out of range).
+    fn pool() -> PoolState {
+        let mut p = PoolState::new(3000, 60, Q96, 0, 15 * 10u128.pow(20)).unwrap();
+        for (tick, net) in [
+            (-600, 10i128.pow(21)),
+            (600, -10i128.pow(21)),
+            (-120, 5 * 10i128.pow(20)),
+            (120, -5 * 10i128.pow(20)),
+            (60, 3 * 10i128.pow(20)),
+            (1200, -3 * 10i128.pow(20)),
+        ] {
+            p.set_liquidity_net(tick, net).unwrap();
+        }
+        p
+    }
+
+    /// Pool at price 1 with 2e18 liquidity over the full usable range and,
+    /// with `around`, another 2e18 on each side of `[-spacing, spacing]`:
+    /// the "1:1 price, 2e18 max range liquidity" and "additional liquidity
+    /// around current price" setups of v3-core's swap tests.
+    fn snapshot_pool(fee: u32, spacing: i32, around: bool) -> PoolState {
+        let max = MAX_TICK / spacing * spacing;
+        let full = 2 * 10i128.pow(18);
+        let mut p = PoolState::new(fee, spacing, Q96, 0, full as u128).unwrap();
+        if around {
+            p.set_liquidity_net(-max, 2 * full).unwrap();
+            p.set_liquidity_net(-spacing, -full).unwrap();
+            p.set_liquidity_net(spacing, full).unwrap();
+            p.set_liquidity_net(max, -2 * full).unwrap();
+        } else {
+            p.set_liquidity_net(-max, full).unwrap();
+            p.set_liquidity_net(max, -full).unwrap();
+        }
+        p
+    }
+
+    // Expected amounts are the token deltas in v3-core's
+    // `UniswapV3Pool.swaps.spec.ts.snap`, recorded against the deployed
+    // contract, for "swap exactly 1.0000 token0 for token1" and "swap token0
+    // for exactly 1.0000 token1".
+    #[test]
+    fn matches_v3_core_swap_snapshots() {
+        let one = u("1000000000000000000");
+        for (fee, spacing, around, out_for_one_in, in_for_one_out) in [
+            (500, 10, false, "666444407401233536", "2001000500250125077"),
+            (3000, 60, false, "665331998665331998", "2006018054162487463"),
+            (
+                10000,
+                200,
+                false,
+                "662207357859531772",
+                "2020202020202020203",
+            ),
+            (3000, 60, true, "795933705287758544", "1342022152495072924"),
+        ] {
+            let p = snapshot_pool(fee, spacing, around);
+            let r = p.quote_exact_input(true, one, None).unwrap();
+            assert_eq!(r.amount_in, one);
+            assert_eq!(r.amount_out.to_string(), out_for_one_in, "fee {fee}");
+            // The pool is symmetric at price 1, so token1 in pays the same.
+            let r = p.quote_exact_input(false, one, None).unwrap();
+            assert_eq!(r.amount_out.to_string(), out_for_one_in, "fee {fee}");
+
+            let r = p.quote_exact_output(true, one, None).unwrap();
+            assert_eq!(r.amount_out, one);
+            assert_eq!(r.amount_in.to_string(), in_for_one_out, "fee {fee}");
+            assert_eq!(r.ticks_crossed, u32::from(around));
+        }
+    }
+
+    #[test]
+    fn exact_input_crosses_ticks() {
+        let r = pool()
+            .quote_exact_input(true, u("100000000000000000"), None)
+            .unwrap();
+        assert_eq!(r.amount_in, u("100000000000000000"));
+        assert_eq!(r.tick, -2);
+        assert_eq!(r.ticks_crossed, 0);
+        assert!(!r.is_partial());
+
+        let r = pool()
+            .quote_exact_input(true, u("20000000000000000000"), None)
+            .unwrap();
+        assert_eq!(r.tick, get_tick_at_sqrt_ratio(r.sqrt_price_x96).unwrap());
+        assert!((-600..-120).contains(&r.tick));
+        assert_eq!(r.liquidity, 10u128.pow(21));
+        assert_eq!(r.ticks_crossed, 1);
+
+        // Up through 60, where the out-of-range position joins, and 120.
+        let r = pool()
+            .quote_exact_input(false, u("40000000000000000000"), None)
+            .unwrap();
+        assert!((120..600).contains(&r.tick));
+        assert_eq!(r.liquidity, 13 * 10u128.pow(20));
+        assert_eq!(r.ticks_crossed, 2);
+    }
+
+    #[test]
+    fn exact_output_round_trips_exact_input() {
+        for zero_for_one in [true, false] {
+            let out = pool()
+                .quote_exact_output(zero_for_one, u("5000000000000000000"), None)
+                .unwrap();
+            assert_eq!(out.amount_out, u("5000000000000000000"));
+            let back = pool()
+                .quote_exact_input(zero_for_one, out.amount_in, None)
+                .unwrap();
+            // Exact output rounds the input up, so it buys at least as much.
+            assert!(back.amount_out >= out.amount_out);
+            assert!(back.amount_out - out.amount_out <= U256::ONE);
+        }
+    }
+
+    #[test]
+    fn stops_at_price_limit() {
+        let limit = get_sqrt_ratio_at_tick(-300).unwrap();
+        let r = pool()
+            .quote_exact_input(true, u("100000000000000000000"), Some(limit))
+            .unwrap();
+        assert_eq!(r.sqrt_price_x96, limit);
+        assert_eq!(r.tick, -300);
+        assert!(r.is_partial());
+        assert_eq!(r.amount_in + r.amount_remaining, u("100000000000000000000"));
+    }
+
+    #[test]
+    fn drains_all_liquidity() {
+        let r = pool()
+            .quote_exact_input(true, u("100000000000000000000000"), None)
+            .unwrap();
+        assert!(r.is_partial());
+        assert_eq!(r.sqrt_price_x96, MIN_SQRT_RATIO + U256::ONE);
+        assert_eq!(r.tick, MIN_TICK);
+        assert_eq!(r.liquidity, 0);
+    }
+
+    #[test]
+    fn walks_through_empty_range() {
+        let start = get_sqrt_ratio_at_tick(300).unwrap() + U256::from(12345u64);
+        let mut p = PoolState::new(500, 10, start, 300, 2 * 10u128.pow(20)).unwrap();
+        for (tick, net) in [
+            (-6000, 10i128.pow(20)),
+            (-3000, -10i128.pow(20)),
+            (0, 2 * 10i128.pow(20)),
+            (600, -2 * 10i128.pow(20)),
+        ] {
+            p.set_liquidity_net(tick, net).unwrap();
+        }
+        let r = p
+            .quote_exact_input(true, u("10000000000000000000"), None)
+            .unwrap();
+        // Out of [0, 600], across the gap and into [-6000, -3000].
+        assert!((-6000..-3000).contains(&r.tick));
+        assert_eq!(r.liquidity, 10u128.pow(20));
+        assert_eq!(r.ticks_crossed, 2);
+        assert!(!r.is_partial());
+    }
+
+    #[test]
+    fn rejects_liquidity_net_that_cannot_be_negated() {
+        let mut p = pool();
+        p.set_liquidity_net(-60, i128::MIN).unwrap();
+        assert_eq!(
+            p.quote_exact_input(true, u("100000000000000000000"), None),
+            Err(Error::Overflow)
+        );
+    }
+
+    #[test]
+    fn rejects_bad_limits_and_ticks() {
+        let mut p = pool();
+        assert_eq!(
+            p.quote_exact_input(true, U256::ONE, Some(Q96 + U256::ONE)),
+            Err(Error::InvalidPriceLimit)
+        );
+        assert_eq!(p.set_liquidity_net(61, 1), Err(Error::TickNotSpaced(61)));
+        p.set_liquidity_net(60, 0).unwrap();
+        assert!(!p.bitmap().is_initialized(60, 60));
+        assert_eq!(p.initialized_ticks().count(), 5);
+    }
+}
@@ -0,0 +1,163 @@
+//! `SqrtPriceMath`: price movement and token deltas within a single tick range.
+
+use super::full_math::{div_rounding_up, mul_div, mul_div_rounding_up};
+use super::u256::U256;
+use super::{Error, Result, Q96};
+
+fn max_u160() -> U256 {
+    (U256::ONE << 160) - U256::ONE
+}
+
+fn to_u160(v: U256) -> Result<U256> {
+    if v > max_u160() {
+        Err(Error::Overflow)
+    } else {
+        Ok(v)
+    }
+}
+
+/// Next sqrt price after adding or removing `amount` of token0, rounding up.
+pub fn get_next_sqrt_price_from_amount0_rounding_up(
+    sqrt_px96: U256,
+    liquidity: u128,
+    amount: U256,
+    add: bool,
+) -> Result<U256> {
+    if amount.is_zero() {
+        return Ok(sqrt_px96);
+    }
+    let numerator1 = U256::from(liquidity) << 96;
+
+    if add {
+        if let Some(product) = amount.checked_mul(sqrt_px96) {
+            if let Some(denominator) = numerator1.checked_add(product) {
+                return mul_div_rounding_up(numerator1, sqrt_px96, denominator);
+            }
+        }
+        let denominator = (numerator1 / sqrt_px96)
+            .checked_add(amount)
+            .ok_or(Error::Overflow)?;
+        div_rounding_up(numerator1, denominator)
+    } else {
+        let product = amount.checked_mul(sqrt_px96).ok_or(Error::Overflow)?;
+        if numerator1 <= product {
+            return Err(Error::Overflow);
+        }
+        to_u160(mul_div_rounding_up(
+            numerator1,
+            sqrt_px96,
+            numerator1 - product,
+        )?)
+    }
+}
+
+/// Next sqrt price after adding or removing `amount` of token1, rounding down.
+pub fn get_next_sqrt_price_from_amount1_rounding_down(
+    sqrt_px96: U256,
+    liquidity: u128,
+    amount: U256,
+    add: bool,
+) -> Result<U256> {
+    let liquidity = U256::from(liquidity);
+    if add {
+        let quotient = if amount <= max_u160() {
+            (amount << 96) / liquidity
+        } else {
+            mul_div(amount, Q96, liquidity)?
+        };
+        to_u160(sqrt_px96.checked_add(quotient).ok_or(Error::Overflow)?)
+    } else {
+        let quotient = if amount <= max_u160() {
+            div_rounding_up(amount << 96, liquidity)?
+        } else {
+            mul_div_rounding_up(amount, Q96, liquidity)?
+        };
+        if sqrt_px96 <= quotient {
+            return Err(Error::SqrtPriceOutOfRange);
+        }
+        Ok(sqrt_px96 - quotient)
+    }
+}
+
+/// Next sqrt price given an input amount of token0 (`zero_for_one`) or token1.
+pub fn get_next_sqrt_price_from_input(
+    sqrt_px96: U256,
+    liquidity: u128,
+    amount_in: U256,
+    zero_for_one: bool,
+) -> Result<U256> {
+    if sqrt_px96.is_zero() {
+        return Err(Error::SqrtPriceOutOfRange);
+    }
+    if liquidity == 0 {
+        return Err(Error::ZeroLiquidity);
+    }
+    if zero_for_one {
+        get_next_sqrt_price_from_amount0_rounding_up(sqrt_px96, liquidity, amount_in, true)
+    } else {
+        get_next_sqrt_price_from_amount1_rounding_down(sqrt_px96, liquidity, amount_in, true)
+    }
+}
+
+/// Next sqrt price given an output amount of token1 (`zero_for_one`) or token0.
+pub fn get_next_sqrt_price_from_output(
+    sqrt_px96: U256,
+    liquidity: u128,
+    amount_out: U256,
+    zero_for_one: bool,
+) -> Result<U256> {
+    if sqrt_px96.is_zero() {
+        return Err(Error::SqrtPriceOutOfRange);
+    }
+    if liquidity == 0 {
+        return Err(Error::ZeroLiquidity);
+    }
+    if zero_for_one {
+        get_next_sqrt_price_from_amount1_rounding_down(sqrt_px96, liquidity, amount_out, false)
+    } else {
+        get_next_sqrt_price_from_amount0_rounding_up(sqrt_px96, liquidity, amount_out, false)
+    }
+}
+
+/// Token0 needed to move between two sqrt prices at constant liquidity.
+pub fn get_amount0_delta(
+    sqrt_ratio_a_x96: U256,
+    sqrt_ratio_b_x96: U256,
+    liquidity: u128,
+    round_up: bool,
+) -> Result<U256> {
+    let (a, b) = if sqrt_ratio_a_x96 > sqrt_ratio_b_x96 {
+        (sqrt_ratio_b_x96, sqrt_ratio_a_x96)
+    } else {
+        (sqrt_ratio_a_x96, sqrt_ratio_b_x96)
+    };
+    if a.is_zero() {
+        return Err(Error::SqrtPriceOutOfRange);
+    }
+    let numerator1 = U256::from(liquidity) << 96;
+    let numerator2 = b - a;
+    if round_up {
+        div_rounding_up(mul_div_rounding_up(numerator1, numerator2, b)?, a)
+    } else {
+        Ok(mul_div(numerator1, numerator2, b)? / a)
+    }
+}
+
+/// Token1 needed to move between two sqrt prices at constant liquidity.
+pub fn get_amount1_delta(
+    sqrt_ratio_a_x96: U256,
+    sqrt_ratio_b_x96: U256,
+    liquidity: u128,
+    round_up: bool,
+) -> Result<U256> {
+    let (a, b) = if sqrt_ratio_a_x96 > sqrt_ratio_b_x96 {
+        (sqrt_ratio_b_x96, sqrt_ratio_a_x96)
+    } else {
+        (sqrt_ratio_a_x96, sqrt_ratio_b_x96)
+    };
+    if round_up {
+        mul_div_rounding_up(U256::from(liquidity), b - a, Q96)
+    } else {
+        mul_div(U256::from(liquidity), b - a, Q96)
+    }
+}
@@ -0,0 +1,259 @@
+//! `SwapMath.computeSwapStep`.
+
+use super::full_math::{mul_div, mul_div_rounding_up};
+use super::sqrt_price_math::{
+    get_amount0_delta, get_amount1_delta, get_next_sqrt_price_from_input,
+    get_next_sqrt_price_from_output,
+};
+use super::u256::U256;
+use super::{Error, Result, FEE_PIPS_DENOMINATOR};
+
+/// Outcome of one swap step within a single tick range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SwapStep {
+    pub sqrt_ratio_next_x96: U256,
+    pub amount_in: U256,
+    pub amount_out: U256,
+    pub fee_amount: U256,
+}
+
+/// Swaps as far as possible towards `sqrt_ratio_target_x96`.
+///
+/// Solidity encodes exact-in as a positive `amountRemaining` and exact-out as
+/// a negative one; here the magnitude and direction are passed separately.
+pub fn compute_swap_step(
+    sqrt_ratio_current_x96: U256,
+    sqrt_ratio_target_x96: U256,
+    liquidity: u128,
+    amount_remaining: U256,
+    exact_in: bool,
+    fee_pips: u32,
+) -> Result<SwapStep> {
+    if fee_pips >= FEE_PIPS_DENOMINATOR {
+        return Err(Error::InvalidFee(fee_pips));
+    }
+    let zero_for_one = sqrt_ratio_current_x96 >= sqrt_ratio_target_x96;
+    let fee_denominator = U256::from(FEE_PIPS_DENOMINATOR);
+    let fee = U256::from(fee_pips);
+
+    let mut amount_in = U256::ZERO;
+    let mut amount_out = U256::ZERO;
+    let sqrt_ratio_next_x96;
+
+    if exact_in {
+        let amount_remaining_less_fee =
+            mul_div(amount_remaining, fee_denominator - fee, fee_denominator)?;
+        amount_in = if zero_for_one {
+            get_amount0_delta(
+                sqrt_ratio_target_x96,
+                sqrt_ratio_current_x96,
+                liquidity,
+                true,
+            )?
+        } else {
+            get_amount1_delta(
+                sqrt_ratio_current_x96,
+                sqrt_ratio_target_x96,
+                liquidity,
+                true,
+            )?
+        };
+        sqrt_ratio_next_x96 = if amount_remaining_less_fee >= amount_in {
+            sqrt_ratio_target_x96
+        } else {
+            get_next_sqrt_price_from_input(
+                sqrt_ratio_current_x96,
+                liquidity,
+                amount_remaining_less_fee,
+                zero_for_one,
+            )?
+        };
+    } else {
+        amount_out = if zero_for_one {
+            get_amount1_delta(
+                sqrt_ratio_target_x96,
+                sqrt_ratio_current_x96,
+                liquidity,
+                false,
+            )?
+        } else {
+            get_amount0_delta(
+                sqrt_ratio_current_x96,
+                sqrt_ratio_target_x96,
+                liquidity,
+                false,
+            )?
+        };
+        sqrt_ratio_next_x96 = if amount_remaining >= amount_out {
+            sqrt_ratio_target_x96
+        } else {
+            get_next_sqrt_price_from_output(
+                sqrt_ratio_current_x96,
+                liquidity,
+                amount_remaining,
+                zero_for_one,
+            )?
+        };
+    }
+
+    let max = sqrt_ratio_target_x96 == sqrt_ratio_next_x96;
+
+    if zero_for_one {
+        if !max || !exact_in {
+            amount_in =
+                get_amount0_delta(sqrt_ratio_next_x96, sqrt_ratio_current_x96, liquidity, true)?;
+        }
+        if !max || exact_in {
+            amount_out = get_amount1_delta(
+                sqrt_ratio_next_x96,
+                sqrt_ratio_current_x96,
+                liquidity,
+                false,
+            )?;
+        }
+    } else {
+        if !max || !exact_in {
+            amount_in =
+                get_amount1_delta(sqrt_ratio_current_x96, sqrt_ratio_next_x96, liquidity, true)?;
+        }
+        if !max || exact_in {
+            amount_out = get_amount0_delta(
+                sqrt_ratio_current_x96,
+                sqrt_ratio_next_x96,
+                liquidity,
+                false,
+            )?;
+        }
+    }
+
+    // Cap the output amount to not exceed the remaining output amount.
+    if !exact_in && amount_out > amount_remaining {
+        amount_out = amount_remaining;
+    }
+
+    let fee_amount = if exact_in && sqrt_ratio_next_x96 != sqrt_ratio_target_x96 {
+        // Didn't reach the target, so take the remainder of the input as fee.
+        amount_remaining - amount_in
+    } else {
+        mul_div_rounding_up(amount_in, fee, fee_denominator - fee)?
+    };
+
+    Ok(SwapStep {
+        sqrt_ratio_next_x96,
+        amount_in,
+        amount_out,
+        fee_amount,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn u(s: &str) -> U256 {
+        s.parse().unwrap()
+    }
+
+    fn e18(n: u64) -> U256 {
+        U256::from(n) * U256::pow10(18)
+    }
+
+    // encodePriceSqrt(1, 1) and encodePriceSqrt(101, 100) from the v3-core tests.
+    const PRICE_1_1: &str = "79228162514264337593543950336";
+    const PRICE_101_100: &str = "79623317895830914510639640423";
+
+    #[test]
+    fn exact_in_capped_at_target_one_for_zero() {
+        let step = compute_swap_step(
+            u(PRICE_1_1),
+            u(PRICE_101_100),
+            2 * 10u128.pow(18),
+            e18(1),
+            true,
+            600,
+        )
+        .unwrap();
+        assert_eq!(step.amount_in.to_string(), "9975124224178055");
+        assert_eq!(step.fee_amount.to_string(), "5988667735148");
+        assert_eq!(step.amount_out.to_string(), "9925619580021728");
+        assert_eq!(step.sqrt_ratio_next_x96, u(PRICE_101_100));
+    }
+
+    #[test]
+    fn exact_out_capped_at_target_one_for_zero() {
+        let step = compute_swap_step(
+            u(PRICE_1_1),
+            u(PRICE_101_100),
+            2 * 10u128.pow(18),
+            e18(1),
+            false,
+            600,
+        )
+        .unwrap();
+        assert_eq!(step.amount_in.to_string(), "9975124224178055");
+        assert_eq!(step.fee_amount.to_string(), "5988667735148");
+        assert_eq!(step.amount_out.to_string(), "9925619580021728");
+        assert_eq!(step.sqrt_ratio_next_x96, u(PRICE_101_100));
+    }
+
+    #[test]
+    fn exact_in_fully_spent_one_for_zero() {
+        // encodePriceSqrt(1000, 100) as the target so the input runs out first.
+        let target = u("250541448375047931186413801569");
+        let step =
+            compute_swap_step(u(PRICE_1_1), target, 2 * 10u128.pow(18), e18(1), true, 600).unwrap();
+        assert_eq!(step.amount_in.to_string(), "999400000000000000");
+        assert_eq!(step.fee_amount.to_string(), "600000000000000");
+        assert_eq!(step.amount_out.to_string(), "666399946655997866");
+        assert!(step.sqrt_ratio_next_x96 < target);
+        assert_eq!(step.amount_in + step.fee_amount, e18(1));
+    }
+
+    #[test]
+    fn exact_out_fully_received_one_for_zero() {
+        let target = u("792281625142643375935439503360");
+        let step = compute_swap_step(u(PRICE_1_1), target, 2 * 10u128.pow(18), e18(1), false, 600)
+            .unwrap();
+        assert_eq!(step.amount_in.to_string(), "2000000000000000000");
+        assert_eq!(step.fee_amount.to_string(), "1200720432259356");
+        assert_eq!(step.amount_out, e18(1));
+        assert!(step.sqrt_ratio_next_x96 < target);
+    }
+
+    #[test]
+    fn amount_out_capped_at_desired() {
+        let step = compute_swap_step(
+            u("417332158212080721273783715441582"),
+            u("1452870262520218020823638996"),
+            159344665391607089467575320103,
+            U256::ONE,
+            false,
+            1,
+        )
+        .unwrap();
+        assert_eq!(step.amount_in, U256::ONE);
+        assert_eq!(step.fee_amount, U256::ONE);
+        assert_eq!(step.amount_out, U256::ONE);
+        assert_eq!(
+            step.sqrt_ratio_next_x96.to_string(),
+            "417332158212080721273783715441581"
+        );
+    }
+
+    #[test]
+    fn entire_input_taken_as_fee() {
+        let step = compute_swap_step(
+            U256::from(2413u64),
+            u("79887613182836312"),
+            1985041575832132834610021537970,
+            U256::from(10u64),
+            true,
+            1872,
+        )
+        .unwrap();
+        assert_eq!(step.amount_in, U256::ZERO);
+        assert_eq!(step.fee_amount, U256::from(10u64));
+        assert_eq!(step.amount_out, U256::ZERO);
+        assert_eq!(step.sqrt_ratio_next_x96, U256::from(2413u64));
+    }
+}
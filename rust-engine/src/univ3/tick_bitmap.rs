@@ -0,0 +1,210 @@
+//! `TickBitmap`: packed initialized-tick flags, 256 compressed ticks per word.
+
+use std::collections::HashMap;
+
+use super::u256::U256;
+
+/// Word and bit position of a compressed tick (`TickBitmap.position`).
+pub fn position(compressed: i32) -> (i16, u8) {
+    ((compressed >> 8) as i16, (compressed & 0xff) as u8)
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TickBitmap {
+    words: HashMap<i16, U256>,
+}
+
+impl TickBitmap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Raw bitmap word, as returned by the pool's `tickBitmap(int16)` getter.
+    pub fn word(&self, word_pos: i16) -> U256 {
+        self.words.get(&word_pos).copied().unwrap_or_default()
+    }
+
+    pub fn set_word(&mut self, word_pos: i16, word: U256) {
+        if word.is_zero() {
+            self.words.remove(&word_pos);
+        } else {
+            self.words.insert(word_pos, word);
+        }
+    }
+
+    /// Flips the initialized state of `tick`, which must be a multiple of `tick_spacing`.
+    pub fn flip_tick(&mut self, tick: i32, tick_spacing: i32) {
+        debug_assert_eq!(tick % tick_spacing, 0);
+        let (word_pos, bit_pos) = position(tick / tick_spacing);
+        let mask = U256::ONE << bit_pos as u32;
+        let word = self.word(word_pos);
+        let flipped = if (word & mask).is_zero() {
+            word | mask
+        } else {
+            word & !mask
+        };
+        self.set_word(word_pos, flipped);
+    }
+
+    pub fn is_initialized(&self, tick: i32, tick_spacing: i32) -> bool {
+        if tick % tick_spacing != 0 {
+            return false;
+        }
+        let (word_pos, bit_pos) = position(tick / tick_spacing);
+        !(self.word(word_pos) & (U256::ONE << bit_pos as u32)).is_zero()
+    }
+
+    /// Next initialized tick in the same word as `tick`, to the left (`lte`) or
+    /// right. Returns the word boundary and `false` when there is none.
+    pub fn next_initialized_tick_within_one_word(
+        &self,
+        tick: i32,
+        tick_spacing: i32,
+        lte: bool,
+    ) -> (i32, bool) {
+        let mut compressed = tick / tick_spacing;
+        if tick < 0 && tick % tick_spacing != 0 {
+            compressed -= 1; // round towards negative infinity
+        }
+
+        if lte {
+            let (word_pos, bit_pos) = position(compressed);
+            // All the 1s at or to the right of the current bit position.
+            let mask = (U256::ONE << bit_pos as u32) - U256::ONE + (U256::ONE << bit_pos as u32);
+            let masked = self.word(word_pos) & mask;
+            if masked.is_zero() {
+                ((compressed - bit_pos as i32) * tick_spacing, false)
+            } else {
+                let msb = masked.bits() as i32 - 1;
+                ((compressed - (bit_pos as i32 - msb)) * tick_spacing, true)
+            }
+        } else {
+            // Start from the word of the next tick, since the current tick state doesn't matter.
+            let (word_pos, bit_pos) = position(compressed + 1);
+            // All the 1s at or to the left of the bit position.
+            let mask = !((U256::ONE << bit_pos as u32) - U256::ONE);
+            let masked = self.word(word_pos) & mask;
+            if masked.is_zero() {
+                (
+                    (compressed + 1 + (255 - bit_pos as i32)) * tick_spacing,
+                    false,
+                )
+            } else {
+                let lsb = masked.trailing_zeros() as i32;
+                (
+                    (compressed + 1 + (lsb - bit_pos as i32)) * tick_spacing,
+                    true,
+                )
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Same initialized ticks as the v3-core TickBitmap spec.
+    fn bitmap() -> TickBitmap {
+        let mut b = TickBitmap::new();
+        for tick in [-200, -55, -4, 70, 78, 84, 139, 240, 535] {
+            b.flip_tick(tick, 1);
+        }
+        b
+    }
+
+    #[test]
+    fn flip_toggles() {
+        let mut b = TickBitmap::new();
+        b.flip_tick(-230, 1);
+        assert!(b.is_initialized(-230, 1));
+        assert!(!b.is_initialized(-229, 1));
+        b.flip_tick(-230, 1);
+        assert!(!b.is_initialized(-230, 1));
+        assert!(b.word(-1).is_zero());
+    }
+
+    #[test]
+    fn next_to_the_right() {
+        let b = bitmap();
+        assert_eq!(
+            b.next_initialized_tick_within_one_word(78, 1, false),
+            (84, true)
+        );
+        assert_eq!(
+            b.next_initialized_tick_within_one_word(-55, 1, false),
+            (-4, true)
+        );
+        assert_eq!(
+            b.next_initialized_tick_within_one_word(77, 1, false),
+            (78, true)
+        );
+        assert_eq!(
+            b.next_initialized_tick_within_one_word(-56, 1, false),
+            (-55, true)
+        );
+        assert_eq!(
+            b.next_initialized_tick_within_one_word(255, 1, false),
+            (511, false)
+        );
+        assert_eq!(
+            b.next_initialized_tick_within_one_word(-257, 1, false),
+            (-200, true)
+        );
+        assert_eq!(
+            b.next_initialized_tick_within_one_word(328, 1, false),
+            (511, false)
+        );
+    }
+
+    #[test]
+    fn next_to_the_left() {
+        let b = bitmap();
+        assert_eq!(
+            b.next_initialized_tick_within_one_word(78, 1, true),
+            (78, true)
+        );
+        assert_eq!(
+            b.next_initialized_tick_within_one_word(79, 1, true),
+            (78, true)
+        );
+        assert_eq!(
+            b.next_initialized_tick_within_one_word(258, 1, true),
+            (256, false)
+        );
+        assert_eq!(
+            b.next_initialized_tick_within_one_word(256, 1, true),
+            (256, false)
+        );
+        assert_eq!(
+            b.next_initialized_tick_within_one_word(72, 1, true),
+            (70, true)
+        );
+        assert_eq!(
+            b.next_initialized_tick_within_one_word(-257, 1, true),
+            (-512, false)
+        );
+        assert_eq!(
+            b.next_initialized_tick_within_one_word(1023, 1, true),
+            (768, false)
+        );
+        assert_eq!(
+            b.next_initialized_tick_within_one_word(900, 1, true),
+            (768, false)
+        );
+    }
+
+    #[test]
+    fn compresses_negative_ticks_towards_negative_infinity() {
+        let mut b = TickBitmap::new();
+        b.flip_tick(-120, 60);
+        assert_eq!(
+            b.next_initialized_tick_within_one_word(-61, 60, true),
+            (-120, true)
+        );
+        assert_eq!(
+            b.next_initialized_tick_within_one_word(-121, 60, false),
+            (-120, true)
+        );
+    }
+}
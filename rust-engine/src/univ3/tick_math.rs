@@ -0,0 +1,198 @@
+//! `TickMath`: conversions between ticks and Q64.96 sqrt prices.
+
+use super::u256::U256;
+use super::{Error, Result};
+
+pub const MIN_TICK: i32 = -887272;
+pub const MAX_TICK: i32 = -MIN_TICK;
+
+/// `getSqrtRatioAtTick(MIN_TICK)`.
+pub const MIN_SQRT_RATIO: U256 = U256::from_limbs([4295128739, 0, 0, 0]);
+/// `getSqrtRatioAtTick(MAX_TICK)`.
+pub const MAX_SQRT_RATIO: U256 =
+    U256::from_limbs([0x5d951d5263988d26, 0xefd1fc6a50648849, 0xfffd8963, 0]);
+
+/// `sqrt(1.0001^-(2^i))` in Q128.128 for bit `i + 1` of the absolute tick.
+const TICK_MULTIPLIERS: [u128; 19] = [
+    0xfff97272373d413259a46990580e213a,
+    0xfff2e50f5f656932ef12357cf3c7fdcc,
+    0xffe5caca7e10e4e61c3624eaa0941cd0,
+    0xffcb9843d60f6159c9db58835c926644,
+    0xff973b41fa98c081472e6896dfb254c0,
+    0xff2ea16466c96a3843ec78b326b52861,
+    0xfe5dee046a99a2a811c461f1969c3053,
+    0xfcbe86c7900a88aedcffc83b479aa3a4,
+    0xf987a7253ac413176f2b074cf7815e54,
+    0xf3392b0822b70005940c7a398e4b70f3,
+    0xe7159475a2c29b7443b29c7fa6e889d9,
+    0xd097f3bdfd2022b8845ad8f792aa5825,
+    0xa9f746462d870fdf8a65dc1f90e061e5,
+    0x70d869a156d2a1b890bb3df62baf32f7,
+    0x31be135f97d08fd981231505542fcfa6,
+    0x9aa508b5b7a84e1c677de54f3e99bc9,
+    0x5d6af8dedb81196699c329225ee604,
+    0x2216e584f5fa1ea926041bedfe98,
+    0x48a170391f7dc42444e8fa2,
+];
+
+/// `sqrt(1.0001^tick) * 2^96`, rounded up exactly as the contract does.
+pub fn get_sqrt_ratio_at_tick(tick: i32) -> Result<U256> {
+    let abs_tick = tick.unsigned_abs();
+    if abs_tick > MAX_TICK as u32 {
+        return Err(Error::TickOutOfRange(tick));
+    }
+
+    let mut ratio = if abs_tick & 0x1 != 0 {
+        U256::from_u128(0xfffcb933bd6fad37aa2d162d1a594001)
+    } else {
+        U256::ONE << 128
+    };
+    for (i, m) in TICK_MULTIPLIERS.iter().enumerate() {
+        if abs_tick & (0x2 << i) != 0 {
+            ratio = ratio.wrapping_mul(U256::from_u128(*m)) >> 128;
+        }
+    }
+    if tick > 0 {
+        ratio = U256::MAX / ratio;
+    }
+
+    // Q128.128 -> Q128.96, rounding up so the result is never below the true price.
+    let low_mask = U256::from(u32::MAX as u64);
+    let round = if (ratio & low_mask).is_zero() {
+        U256::ZERO
+    } else {
+        U256::ONE
+    };
+    Ok((ratio >> 32) + round)
+}
+
+/// Greatest tick whose sqrt ratio is `<= sqrt_price_x96`.
+pub fn get_tick_at_sqrt_ratio(sqrt_price_x96: U256) -> Result<i32> {
+    if sqrt_price_x96 < MIN_SQRT_RATIO || sqrt_price_x96 >= MAX_SQRT_RATIO {
+        return Err(Error::SqrtPriceOutOfRange);
+    }
+    let ratio = sqrt_price_x96 << 32;
+    let msb = ratio.bits() - 1;
+    let mut r = if msb >= 128 {
+        ratio >> (msb - 127)
+    } else {
+        ratio << (127 - msb)
+    };
+
+    // Signed Q64.64 log2, kept in two's complement like the int256 in Solidity.
+    let mut log_2 = from_i128((msb as i128 - 128) << 64);
+    for i in 0..14 {
+        r = r.wrapping_mul(r) >> 127;
+        let f = r >> 128;
+        log_2 = log_2 | (f << (63 - i));
+        r = r >> f.low_u64() as u32;
+    }
+
+    let log_sqrt10001 = log_2.wrapping_mul(U256::from_u128(255738958999603826347141));
+    let tick_low = to_i32(sar(
+        log_sqrt10001.wrapping_sub(U256::from_u128(3402992956809132418596140100660247210)),
+        128,
+    ));
+    let tick_hi = to_i32(sar(
+        log_sqrt10001.wrapping_add(U256::from_u128(291339464771989622907027621153398088495)),
+        128,
+    ));
+
+    if tick_low == tick_hi || get_sqrt_ratio_at_tick(tick_hi)? > sqrt_price_x96 {
+        Ok(tick_low)
+    } else {
+        Ok(tick_hi)
+    }
+}
+
+fn from_i128(v: i128) -> U256 {
+    if v < 0 {
+        U256::ZERO.wrapping_sub(U256::from_u128(v.unsigned_abs()))
+    } else {
+        U256::from_u128(v as u128)
+    }
+}
+
+/// Arithmetic shift right of a two's-complement value.
+fn sar(v: U256, shift: u32) -> U256 {
+    let shifted = v >> shift;
+    if v.bits() == 256 {
+        shifted | !(U256::MAX >> shift)
+    } else {
+        shifted
+    }
+}
+
+fn to_i32(v: U256) -> i32 {
+    v.low_u64() as i64 as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bounds_and_identity() {
+        assert_eq!(get_sqrt_ratio_at_tick(MIN_TICK).unwrap(), MIN_SQRT_RATIO);
+        assert_eq!(get_sqrt_ratio_at_tick(MAX_TICK).unwrap(), MAX_SQRT_RATIO);
+        assert_eq!(get_sqrt_ratio_at_tick(0).unwrap(), super::super::Q96);
+        assert_eq!(
+            get_sqrt_ratio_at_tick(MIN_TICK - 1),
+            Err(Error::TickOutOfRange(MIN_TICK - 1))
+        );
+        assert_eq!(
+            get_sqrt_ratio_at_tick(MAX_TICK + 1),
+            Err(Error::TickOutOfRange(MAX_TICK + 1))
+        );
+        assert_eq!(
+            MAX_SQRT_RATIO.to_string(),
+            "1461446703485210103287273052203988822378723970342"
+        );
+    }
+
+    #[test]
+    fn sqrt_ratio_golden_vectors() {
+        // Generated from the v3-core TickMath implementation.
+        let cases = [
+            (-887271, "4295343490"),
+            (-500000, "1101692437043807371"),
+            (-50, "79030349367926598376800521322"),
+            (-1, "79224201403219477170569942574"),
+            (1, "79232123823359799118286999568"),
+            (50, "79426470787362580746886972461"),
+            (150000, "143194173941309278083010301478497"),
+            (887271, "1461373636630004318706518188784493106690254656249"),
+        ];
+        for (tick, expected) in cases {
+            assert_eq!(
+                get_sqrt_ratio_at_tick(tick).unwrap().to_string(),
+                expected,
+                "tick {tick}"
+            );
+        }
+    }
+
+    #[test]
+    fn tick_at_sqrt_ratio_inverts() {
+        assert_eq!(get_tick_at_sqrt_ratio(MIN_SQRT_RATIO).unwrap(), MIN_TICK);
+        assert_eq!(
+            get_tick_at_sqrt_ratio(MAX_SQRT_RATIO - U256::ONE).unwrap(),
+            MAX_TICK - 1
+        );
+        assert_eq!(
+            get_tick_at_sqrt_ratio(MAX_SQRT_RATIO),
+            Err(Error::SqrtPriceOutOfRange)
+        );
+        assert_eq!(
+            get_tick_at_sqrt_ratio(MIN_SQRT_RATIO - U256::ONE),
+            Err(Error::SqrtPriceOutOfRange)
+        );
+
+        for tick in [-887000, -200000, -60, -1, 0, 1, 59, 12345, 500000, 887000] {
+            let at = get_sqrt_ratio_at_tick(tick).unwrap();
+            assert_eq!(get_tick_at_sqrt_ratio(at).unwrap(), tick);
+            assert_eq!(get_tick_at_sqrt_ratio(at - U256::ONE).unwrap(), tick - 1);
+            assert_eq!(get_tick_at_sqrt_ratio(at + U256::ONE).unwrap(), tick);
+        }
+    }
+}
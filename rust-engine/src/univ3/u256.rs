@@ -0,0 +1,592 @@
+//! Minimal fixed-width 256-bit unsigned integer.
+//!
+//! Only the operations the V3 math needs are implemented. Arithmetic operators
+//! panic on overflow (like Solidity 0.8 checked math); use the `checked_*` and
+//! `wrapping_*` methods where the EVM semantics call for something else.
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::{Add, BitAnd, BitOr, Div, Mul, Not, Rem, Shl, Shr, Sub};
+use std::str::FromStr;
+
+/// 256-bit unsigned integer stored as four little-endian `u64` limbs.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct U256([u64; 4]);
+
+impl U256 {
+    pub const ZERO: U256 = U256([0; 4]);
+    pub const ONE: U256 = U256([1, 0, 0, 0]);
+    pub const MAX: U256 = U256([u64::MAX; 4]);
+
+    pub const fn from_limbs(limbs: [u64; 4]) -> Self {
+        U256(limbs)
+    }
+
+    pub const fn from_u128(v: u128) -> Self {
+        U256([v as u64, (v >> 64) as u64, 0, 0])
+    }
+
+    pub const fn limbs(&self) -> [u64; 4] {
+        self.0
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.0 == [0; 4]
+    }
+
+    /// Number of significant bits (0 for zero).
+    pub fn bits(&self) -> u32 {
+        for i in (0..4).rev() {
+            if self.0[i] != 0 {
+                return 64 * i as u32 + (64 - self.0[i].leading_zeros());
+            }
+        }
+        0
+    }
+
+    pub fn trailing_zeros(&self) -> u32 {
+        for i in 0..4 {
+            if self.0[i] != 0 {
+                return 64 * i as u32 + self.0[i].trailing_zeros();
+            }
+        }
+        256
+    }
+
+    pub fn low_u64(&self) -> u64 {
+        self.0[0]
+    }
+
+    pub fn low_u128(&self) -> u128 {
+        (self.0[0] as u128) | ((self.0[1] as u128) << 64)
+    }
+
+    pub fn to_u128(&self) -> Option<u128> {
+        if self.0[2] == 0 && self.0[3] == 0 {
+            Some(self.low_u128())
+        } else {
+            None
+        }
+    }
+
+    /// Lossy conversion, for reporting only.
+    pub fn to_f64(&self) -> f64 {
+        self.0
+            .iter()
+            .rev()
+            .fold(0.0, |acc, &limb| acc * 18446744073709551616.0 + limb as f64)
+    }
+
+    pub fn overflowing_add(self, rhs: U256) -> (U256, bool) {
+        let mut out = [0u64; 4];
+        let mut carry = false;
+        for (i, limb) in out.iter_mut().enumerate() {
+            let (s1, c1) = self.0[i].overflowing_add(rhs.0[i]);
+            let (s2, c2) = s1.overflowing_add(carry as u64);
+            *limb = s2;
+            carry = c1 || c2;
+        }
+        (U256(out), carry)
+    }
+
+    pub fn overflowing_sub(self, rhs: U256) -> (U256, bool) {
+        let mut out = [0u64; 4];
+        let mut borrow = false;
+        for (i, limb) in out.iter_mut().enumerate() {
+            let (d1, b1) = self.0[i].overflowing_sub(rhs.0[i]);
+            let (d2, b2) = d1.overflowing_sub(borrow as u64);
+            *limb = d2;
+            borrow = b1 || b2;
+        }
+        (U256(out), borrow)
+    }
+
+    pub fn checked_add(self, rhs: U256) -> Option<U256> {
+        match self.overflowing_add(rhs) {
+            (v, false) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn checked_sub(self, rhs: U256) -> Option<U256> {
+        match self.overflowing_sub(rhs) {
+            (v, false) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn saturating_sub(self, rhs: U256) -> U256 {
+        self.checked_sub(rhs).unwrap_or(U256::ZERO)
+    }
+
+    pub fn wrapping_add(self, rhs: U256) -> U256 {
+        self.overflowing_add(rhs).0
+    }
+
+    pub fn wrapping_sub(self, rhs: U256) -> U256 {
+        self.overflowing_sub(rhs).0
+    }
+
+    /// Full 512-bit product as eight little-endian limbs.
+    pub fn widening_mul(self, rhs: U256) -> [u64; 8] {
+        let mut out = [0u64; 8];
+        for i in 0..4 {
+            let mut carry: u128 = 0;
+            for j in 0..4 {
+                let t = self.0[i] as u128 * rhs.0[j] as u128 + out[i + j] as u128 + carry;
+                out[i + j] = t as u64;
+                carry = t >> 64;
+            }
+            out[i + 4] = carry as u64;
+        }
+        out
+    }
+
+    pub fn wrapping_mul(self, rhs: U256) -> U256 {
+        let wide = self.widening_mul(rhs);
+        U256([wide[0], wide[1], wide[2], wide[3]])
+    }
+
+    pub fn checked_mul(self, rhs: U256) -> Option<U256> {
+        let wide = self.widening_mul(rhs);
+        if wide[4..].iter().any(|&l| l != 0) {
+            None
+        } else {
+            Some(U256([wide[0], wide[1], wide[2], wide[3]]))
+        }
+    }
+
+    /// Quotient and remainder. Panics on division by zero.
+    pub fn div_rem(self, rhs: U256) -> (U256, U256) {
+        let mut num = [0u64; 8];
+        num[..4].copy_from_slice(&self.0);
+        let (q, r) = div_rem_wide(num, rhs);
+        (U256([q[0], q[1], q[2], q[3]]), r)
+    }
+
+    /// From a 32-byte big-endian word, as in EVM calldata and storage.
+    pub fn from_be_bytes(bytes: [u8; 32]) -> Self {
+        let mut limbs = [0u64; 4];
+        for (i, chunk) in bytes.chunks_exact(8).enumerate() {
+            limbs[3 - i] = u64::from_be_bytes(chunk.try_into().unwrap());
+        }
+        U256(limbs)
+    }
+
+    pub fn to_be_bytes(&self) -> [u8; 32] {
+        let mut out = [0u8; 32];
+        for (i, limb) in self.0.iter().rev().enumerate() {
+            out[8 * i..8 * i + 8].copy_from_slice(&limb.to_be_bytes());
+        }
+        out
+    }
+
+    pub fn pow10(exp: u32) -> U256 {
+        (0..exp).fold(U256::ONE, |acc, _| acc * U256::from(10u64))
+    }
+
+    pub fn from_dec_str(s: &str) -> Result<U256, ParseU256Error> {
+        if s.is_empty() {
+            return Err(ParseU256Error::Empty);
+        }
+        let ten = U256::from(10u64);
+        let mut acc = U256::ZERO;
+        for c in s.chars() {
+            let d = c.to_digit(10).ok_or(ParseU256Error::InvalidDigit)?;
+            acc = acc
+                .checked_mul(ten)
+                .and_then(|v| v.checked_add(U256::from(d as u64)))
+                .ok_or(ParseU256Error::Overflow)?;
+        }
+        Ok(acc)
+    }
+}
+
+/// Divides a 512-bit numerator by a 256-bit denominator (Knuth, TAOCP 4.3.1
+/// algorithm D). Returns the 512-bit quotient and the remainder.
+pub(crate) fn div_rem_wide(num: [u64; 8], den: U256) -> ([u64; 8], U256) {
+    let v = den.0;
+    let n = v.iter().rposition(|&l| l != 0).expect("division by zero") + 1;
+    let m = match num.iter().rposition(|&l| l != 0) {
+        Some(i) => i + 1,
+        None => return ([0; 8], U256::ZERO),
+    };
+    let mut q = [0u64; 8];
+
+    if m < n {
+        return (q, U256([num[0], num[1], num[2], num[3]]));
+    }
+
+    if n == 1 {
+        let d = v[0] as u128;
+        let mut rem: u128 = 0;
+        for i in (0..m).rev() {
+            let cur = (rem << 64) | num[i] as u128;
+            q[i] = (cur / d) as u64;
+            rem = cur % d;
+        }
+        return (q, U256::from_u128(rem));
+    }
+
+    // Normalize so the top limb of the divisor has its high bit set.
+    let s = v[n - 1].leading_zeros();
+    let mut vn = [0u64; 4];
+    let mut un = [0u64; 9];
+    if s == 0 {
+        vn[..n].copy_from_slice(&v[..n]);
+        un[..m].copy_from_slice(&num[..m]);
+    } else {
+        for i in (1..n).rev() {
+            vn[i] = (v[i] << s) | (v[i - 1] >> (64 - s));
+        }
+        vn[0] = v[0] << s;
+        un[m] = num[m - 1] >> (64 - s);
+        for i in (1..m).rev() {
+            un[i] = (num[i] << s) | (num[i - 1] >> (64 - s));
+        }
+        un[0] = num[0] << s;
+    }
+
+    const B: u128 = 1 << 64;
+    for j in (0..=m - n).rev() {
+        let top = ((un[j + n] as u128) << 64) | un[j + n - 1] as u128;
+        let mut qhat = top / vn[n - 1] as u128;
+        let mut rhat = top % vn[n - 1] as u128;
+        while qhat >= B || qhat * vn[n - 2] as u128 > ((rhat << 64) | un[j + n - 2] as u128) {
+            qhat -= 1;
+            rhat += vn[n - 1] as u128;
+            if rhat >= B {
+                break;
+            }
+        }
+
+        // Multiply and subtract qhat * vn from un[j..=j+n].
+        let mut carry: u128 = 0;
+        let mut borrow = 0u64;
+        for i in 0..n {
+            let p = qhat * vn[i] as u128 + carry;
+            carry = p >> 64;
+            let (d1, b1) = un[i + j].overflowing_sub(p as u64);
+            let (d2, b2) = d1.overflowing_sub(borrow);
+            un[i + j] = d2;
+            borrow = b1 as u64 + b2 as u64;
+        }
+        let (d1, b1) = un[j + n].overflowing_sub(carry as u64);
+        let (d2, b2) = d1.overflowing_sub(borrow);
+        un[j + n] = d2;
+
+        if b1 || b2 {
+            // qhat was one too large; add the divisor back.
+            qhat -= 1;
+            let mut c: u128 = 0;
+            for i in 0..n {
+                let t = un[i + j] as u128 + vn[i] as u128 + c;
+                un[i + j] = t as u64;
+                c = t >> 64;
+            }
+            un[j + n] = un[j + n].wrapping_add(c as u64);
+        }
+        q[j] = qhat as u64;
+    }
+
+    let mut r = [0u64; 4];
+    if s == 0 {
+        r[..n].copy_from_slice(&un[..n]);
+    } else {
+        for i in 0..n {
+            r[i] = (un[i] >> s) | (un[i + 1] << (64 - s));
+        }
+    }
+    (q, U256(r))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseU256Error {
+    Empty,
+    InvalidDigit,
+    Overflow,
+}
+
+impl fmt::Display for ParseU256Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseU256Error::Empty => write!(f, "empty string"),
+            ParseU256Error::InvalidDigit => write!(f, "invalid digit"),
+            ParseU256Error::Overflow => write!(f, "number does not fit in 256 bits"),
+        }
+    }
+}
+
+impl std::error::Error for ParseU256Error {}
+
+impl FromStr for U256 {
+    type Err = ParseU256Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        U256::from_dec_str(s)
+    }
+}
+
+impl From<u32> for U256 {
+    fn from(v: u32) -> Self {
+        U256([v as u64, 0, 0, 0])
+    }
+}
+
+impl From<u64> for U256 {
+    fn from(v: u64) -> Self {
+        U256([v, 0, 0, 0])
+    }
+}
+
+impl From<u128> for U256 {
+    fn from(v: u128) -> Self {
+        U256::from_u128(v)
+    }
+}
+
+impl Ord for U256 {
+    fn cmp(&self, other: &Self) -> Ordering {
+        for i in (0..4).rev() {
+            match self.0[i].cmp(&other.0[i]) {
+                Ordering::Equal => continue,
+                ord => return ord,
+            }
+        }
+        Ordering::Equal
+    }
+}
+
+impl PartialOrd for U256 {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Add for U256 {
+    type Output = U256;
+    fn add(self, rhs: U256) -> U256 {
+        self.checked_add(rhs).expect("U256 addition overflow")
+    }
+}
+
+impl Sub for U256 {
+    type Output = U256;
+    fn sub(self, rhs: U256) -> U256 {
+        self.checked_sub(rhs).expect("U256 subtraction underflow")
+    }
+}
+
+impl Mul for U256 {
+    type Output = U256;
+    fn mul(self, rhs: U256) -> U256 {
+        self.checked_mul(rhs).expect("U256 multiplication overflow")
+    }
+}
+
+impl Div for U256 {
+    type Output = U256;
+    fn div(self, rhs: U256) -> U256 {
+        self.div_rem(rhs).0
+    }
+}
+
+impl Rem for U256 {
+    type Output = U256;
+    fn rem(self, rhs: U256) -> U256 {
+        self.div_rem(rhs).1
+    }
+}
+
+impl Shl<u32> for U256 {
+    type Output = U256;
+    fn shl(self, shift: u32) -> U256 {
+        if shift >= 256 {
+            return U256::ZERO;
+        }
+        let limbs = (shift / 64) as usize;
+        let bits = shift % 64;
+        let mut out = [0u64; 4];
+        for i in (limbs..4).rev() {
+            out[i] = self.0[i - limbs] << bits;
+            if bits > 0 && i > limbs {
+                out[i] |= self.0[i - limbs - 1] >> (64 - bits);
+            }
+        }
+        U256(out)
+    }
+}
+
+impl Shr<u32> for U256 {
+    type Output = U256;
+    fn shr(self, shift: u32) -> U256 {
+        if shift >= 256 {
+            return U256::ZERO;
+        }
+        let limbs = (shift / 64) as usize;
+        let bits = shift % 64;
+        let mut out = [0u64; 4];
+        for (i, limb) in out.iter_mut().enumerate().take(4 - limbs) {
+            *limb = self.0[i + limbs] >> bits;
+            if bits > 0 && i + limbs + 1 < 4 {
+                *limb |= self.0[i + limbs + 1] << (64 - bits);
+            }
+        }
+        U256(out)
+    }
+}
+
+impl BitAnd for U256 {
+    type Output = U256;
+    fn bitand(self, rhs: U256) -> U256 {
+        U256(std::array::from_fn(|i| self.0[i] & rhs.0[i]))
+    }
+}
+
+impl BitOr for U256 {
+    type Output = U256;
+    fn bitor(self, rhs: U256) -> U256 {
+        U256(std::array::from_fn(|i| self.0[i] | rhs.0[i]))
+    }
+}
+
+impl Not for U256 {
+    type Output = U256;
+    fn not(self) -> U256 {
+        U256(self.0.map(|l| !l))
+    }
+}
+
+impl fmt::Display for U256 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_zero() {
+            return f.pad("0");
+        }
+        // Peel off 19 decimal digits at a time.
+        let chunk = U256::from(10_000_000_000_000_000_000u64);
+        let mut parts = Vec::new();
+        let mut v = *self;
+        while !v.is_zero() {
+            let (q, r) = v.div_rem(chunk);
+            parts.push(r.low_u64());
+            v = q;
+        }
+        let mut s = parts.pop().unwrap_or_default().to_string();
+        for p in parts.iter().rev() {
+            s.push_str(&format!("{p:019}"));
+        }
+        f.pad(&s)
+    }
+}
+
+impl fmt::Debug for U256 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl fmt::LowerHex for U256 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut s = String::new();
+        for limb in self.0.iter().rev() {
+            if s.is_empty() {
+                if *limb != 0 {
+                    s = format!("{limb:x}");
+                }
+            } else {
+                s.push_str(&format!("{limb:016x}"));
+            }
+        }
+        if s.is_empty() {
+            s.push('0');
+        }
+        if f.alternate() {
+            s.insert_str(0, "0x");
+        }
+        f.pad(&s)
+    }
+}
+
+impl serde::Serialize for U256 {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for U256 {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        U256::from_dec_str(&s).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn u(s: &str) -> U256 {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn decimal_round_trip() {
+        let max = "115792089237316195423570985008687907853269984665640564039457584007913129639935";
+        assert_eq!(u(max), U256::MAX);
+        assert_eq!(U256::MAX.to_string(), max);
+        assert_eq!(U256::ZERO.to_string(), "0");
+        assert_eq!(
+            u("10000000000000000000").to_string(),
+            "10000000000000000000"
+        );
+        assert!(matches!(
+            U256::from_dec_str(&format!("{max}0")),
+            Err(ParseU256Error::Overflow)
+        ));
+    }
+
+    #[test]
+    fn big_endian_words() {
+        let mut word = [0u8; 32];
+        word[31] = 1;
+        word[0] = 0x80;
+        assert_eq!(U256::from_be_bytes(word), (U256::ONE << 255) | U256::ONE);
+        let x = u("79228162514264337593543950336123");
+        assert_eq!(U256::from_be_bytes(x.to_be_bytes()), x);
+    }
+
+    #[test]
+    fn shifts_and_bits() {
+        let one = U256::ONE;
+        assert_eq!((one << 255).bits(), 256);
+        assert_eq!((one << 255) >> 255, one);
+        assert_eq!((one << 96).to_string(), "79228162514264337593543950336");
+        assert_eq!((U256::MAX >> 200).bits(), 56);
+        assert_eq!((one << 130).trailing_zeros(), 130);
+    }
+
+    #[test]
+    fn division_matches_reference() {
+        // Reference values computed with arbitrary-precision integers.
+        let a = u("115792089237316195423570985008687907853269984665640564039457584007913129639935");
+        let b = u("340282366920938463463374607431768211457");
+        let (q, r) = a.div_rem(b);
+        assert_eq!(q.to_string(), "340282366920938463463374607431768211455");
+        assert_eq!(r.to_string(), "0");
+
+        let c = u("98765432109876543210987654321098765432109876543210");
+        let d = u("1234567890123456789012345678901");
+        let (q, r) = c.div_rem(d);
+        assert_eq!(q * d + r, c);
+        assert!(r < d);
+        assert_eq!(q.to_string(), "80000000729000006633");
+        assert_eq!(r.to_string(), "1111185630129883994391988392877");
+    }
+
+    #[test]
+    fn checked_arithmetic() {
+        assert_eq!(U256::MAX.checked_add(U256::ONE), None);
+        assert_eq!(U256::ZERO.checked_sub(U256::ONE), None);
+        assert_eq!((U256::ONE << 128).checked_mul(U256::ONE << 128), None);
+        assert_eq!(U256::ZERO.wrapping_sub(U256::ONE), U256::MAX);
+    }
+}
@@ -11,6 +11,8 @@ type FlashParams = {
   sellRouter: string;
   sellSpender: string;
   sellCalldata: string; // 0x...
+  sellAmountOffsets: bigint[]; // amountIn word offsets in sellCalldata; [] sends it as built
+  sellShares: bigint[]; // share of leg 1 output per offset, 1e18 = all
   tokenBorrowed: string;
   tokenIntermediate: string;
   profitToken: string;
@@ -37,6 +39,8 @@ async function main() {
     sellRouter: "0xSELL_ROUTER",
     sellSpender: "0xSELL_SPENDER",
     sellCalldata: "0x",
+    sellAmountOffsets: [],
+    sellShares: [],
     tokenBorrowed: process.env.ASSET!,
     tokenIntermediate: "0xTOKEN_INTERMEDIATE",
     profitToken: process.env.ASSET!, // or a different token
@@ -45,7 +49,7 @@ async function main() {
 
   const encoded = ethers.AbiCoder.defaultAbiCoder().encode(
     [
-      "tuple(address,address,bytes,address,address,bytes,address,address,address,uint256)",
+      "tuple(address,address,bytes,address,address,bytes,uint256[],uint256[],address,address,address,uint256)",
     ],
     [[
       params.buyRouter,
@@ -54,6 +58,8 @@ async function main() {
       params.sellRouter,
       params.sellSpender,
       params.sellCalldata,
+      params.sellAmountOffsets,
+      params.sellShares,
       params.tokenBorrowed,
       params.tokenIntermediate,
       params.profitToken,
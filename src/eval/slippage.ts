@@ -28,12 +28,13 @@ export function effectiveSlipBps(model: SlippageModel, sizeUsd: number): number
     return estimateSlippageBpsEmpirical(sizeUsd, k, alpha, L);
   }
   if (model.kind === "univ3") {
-    const { sqrtPriceX96, liquidity, feeTierBps, usdPerTokenIn, zeroForOne } = model as any;
+    const { sqrtPriceX96, liquidity, feeTierBps, usdPerTokenIn, zeroForOne, decimalsIn } = model as any;
     if (sqrtPriceX96 && liquidity && usdPerTokenIn && Number(usdPerTokenIn) > 0) {
       const fee = Number.isFinite(feeTierBps) ? Number(feeTierBps) : (model.k ? Math.max(0, model.k) : 30);
       const tokenAmountIn = (Math.max(0, sizeUsd) / Number(usdPerTokenIn));
-      // assume 18 decimals for tokens by default; callers can pre-scale in the future
-      const amtInRaw = BigInt(Math.floor(tokenAmountIn * 1e18));
+      // scale by the input token's decimals (18 when the caller does not say)
+      const decimals = Number.isInteger(decimalsIn) ? Number(decimalsIn) : 18;
+      const amtInRaw = BigInt(Math.floor(tokenAmountIn * 10 ** decimals));
       if (Array.isArray((model as any).ticks) && (model as any).ticks.length > 0) {
         const uni = simulateUniV3WithTicksSlipBps({
           sqrtPriceX96: String(sqrtPriceX96),
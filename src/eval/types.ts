@@ -39,6 +39,7 @@ export type SlippageModel = {
   ticks?: Array<{ index: number; liquidityNet: string; sqrtPriceX96?: string }>; // subset of initialized ticks
   // Optional USD conversion hints for mapping sizeUsd to token amounts
   usdPerTokenIn?: number;            // price of input token in USD
+  decimalsIn?: number;               // input token decimals (default 18)
   zeroForOne?: boolean;              // swap direction: token0 -> token1 when true
 };
 
@@ -19,4 +19,19 @@ describe('slippage', () => {
     const s = effectiveSlipBps(model, 50_000);
     expect(s).toBe(0);
   });
+
+  it('univ3 slippage scales the input by its token decimals', () => {
+    const pool = {
+      kind: 'univ3' as const,
+      sqrtPriceX96: (2n ** 96n).toString(),
+      liquidity: (10n ** 21n).toString(),
+      feeTierBps: 5,
+      usdPerTokenIn: 1,
+    };
+    const implicit = effectiveSlipBps(pool, 100_000);
+    expect(effectiveSlipBps({ ...pool, decimalsIn: 18 }, 100_000)).toBe(implicit);
+    // The same dollars of a 6-decimal token are 1e12 fewer raw units.
+    const usdc = effectiveSlipBps({ ...pool, decimalsIn: 6 }, 100_000);
+    expect(usdc).toBeLessThan(implicit);
+  });
 });
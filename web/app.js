@@ -475,8 +475,15 @@ loadHistory();
 
 function updateDashboard(data) {
   const pnlEl = document.getElementById('daily-pnl');
-  const pnl = Number(data.pnl || 0);
-  pnlEl.innerHTML = `Daily P&L: <span class="${pnl > 0 ? 'status-green' : 'status-red'}">${pnl > 0 ? '+' : ''}$${pnl.toFixed(2)}</span>`;
+  // engine snapshots send pnl: null until something reports fills; hide the widget rather than show a fake $0
+  const pnlRaw = data.pnl && typeof data.pnl === 'object' ? data.pnl.total_usd : data.pnl;
+  const pnl = pnlRaw == null ? NaN : Number(pnlRaw);
+  if (Number.isFinite(pnl)) {
+    pnlEl.style.display = '';
+    pnlEl.innerHTML = `Daily P&L: <span class="${pnl > 0 ? 'status-green' : 'status-red'}">${pnl > 0 ? '+' : ''}$${pnl.toFixed(2)}</span>`;
+  } else {
+    pnlEl.style.display = 'none';
+  }
 
   // Engine + Config preview
   const eng = data.engine || {};
@@ -1308,8 +1315,15 @@ function showError(msg, details) {
 }
 
 // ================ Opportunities Table (static rows) ================
+// engine snapshots (schema_version >= 2) carry the pair as { base, quote } tokens
+function pairLabel(o){
+  const p = o.pair;
+  if (p && typeof p === 'object') return `${p.base?.symbol || '?'}/${p.quote?.symbol || '?'}`;
+  return p || '';
+}
+
 function makeOppKey(o){
-  const pair = (o.pair || o.route || '').toLowerCase();
+  const pair = (pairLabel(o) || o.route || '').toLowerCase();
   const route = (o.route || '').toLowerCase();
   const chain = (o.chain_name || '').toLowerCase();
   return `${chain}|${pair}|${route}`;
@@ -1335,7 +1349,7 @@ function upsertOppRow(o){
   const profitNet = Number(o.profit_net_usd || (o.est_profit_usd||0));
   const profitPerGas = Number(o.profit_per_gas || 0);
   const ts = o.ts || '';
-  const pair = o.pair || o.route || '';
+  const pair = pairLabel(o) || o.route || '';
   if (existing) {
     // update cells only
     const { cells, data } = existing;
@@ -1409,16 +1423,31 @@ function routeTypeFrom(o){
 }
 
 function assetFromPair(o){
-  const p = (o.pair||'').trim();
+  const p = pairLabel(o).trim();
   if (!p) return '';
   const parts = p.split('/');
   return (parts[0]||p).toLowerCase();
 }
 
+// same as the engine's Route Display: an L1 buy comes before the pool legs,
+// an L1 sell after them (HL-perp->PRJX, PRJX->HL-spot)
+function routeLabel(route){
+  const venues = (route.legs || []).map(l => l.dex);
+  const l1 = route.l1_order;
+  if (l1) {
+    const venue = `HL-${l1.market}`;
+    if (l1.side === 'buy') venues.unshift(venue); else venues.push(venue);
+  }
+  return venues.join('->');
+}
+
 async function processOpps(opps){
   const filters = readFilters();
   const gas = await fetchGasIfStale();
   for (const o of opps) {
+    // the rust engine sends structured routes and its own gas estimate
+    if (o.route && typeof o.route === 'object') o.route = routeLabel(o.route);
+    if (o.gas_usd == null && o.est_gas_usd != null) o.gas_usd = o.est_gas_usd;
     // derive metrics
     const gasUsd = o.gas_usd != null ? Number(o.gas_usd) : Number(gas.usd||0);
     const gross = Number(o.est_profit_usd || 0);